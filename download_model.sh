@@ -1,6 +1,11 @@
 #!/bin/bash
 
-MODEL_DIR="$HOME/.dictation/models"
+# Same folder the app uses; a legacy ~/.dictation is moved there on its next start
+APP_DIR="$HOME/Library/Application Support/Dictation"
+if [ -d "$HOME/.dictation" ] && [ ! -d "$APP_DIR" ]; then
+    APP_DIR="$HOME/.dictation"
+fi
+MODEL_DIR="$APP_DIR/models"
 MODEL_NAME="ggml-base.en.bin"
 MODEL_URL="https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin"
 
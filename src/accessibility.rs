@@ -0,0 +1,138 @@
+//! Text insertion through the Accessibility API (`output_mode = "accessibility"`),
+//! which leaves the clipboard alone and works in apps that take over Cmd+V.
+//!
+//! Manual test plan, with accessibility permission granted unless noted:
+//! - TextEdit and Notes: text lands at the caret, and replaces a selection
+//! - Safari address bar and a web page `<textarea>`: same
+//! - Terminal and an Electron app (e.g. VS Code): falls back, and the log says why
+//! - With the permission revoked: falls back with "accessibility permission not granted"
+//! - Nothing focused (click the desktop): falls back with "nothing has keyboard focus"
+//! - Afterwards the clipboard still holds what it held before dictating
+
+use core_foundation::base::{CFIndex, CFRange, CFType, CFTypeRef, TCFType};
+use core_foundation::string::{CFString, CFStringRef};
+use std::ffi::c_void;
+
+mod edit;
+
+pub use edit::InsertError;
+use edit::{Capabilities, Strategy};
+
+type AXUIElementRef = CFTypeRef;
+type AXValueRef = CFTypeRef;
+type AXError = i32;
+
+const AX_SUCCESS: AXError = 0;
+/// `kAXValueCFRangeType`
+const AX_VALUE_CF_RANGE: u32 = 4;
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+    fn AXUIElementCreateSystemWide() -> AXUIElementRef;
+    fn AXUIElementCopyAttributeValue(element: AXUIElementRef, attribute: CFStringRef, value: *mut CFTypeRef) -> AXError;
+    fn AXUIElementSetAttributeValue(element: AXUIElementRef, attribute: CFStringRef, value: CFTypeRef) -> AXError;
+    fn AXUIElementIsAttributeSettable(element: AXUIElementRef, attribute: CFStringRef, settable: *mut u8) -> AXError;
+    fn AXValueCreate(value_type: u32, value: *const c_void) -> AXValueRef;
+    fn AXValueGetValue(value: AXValueRef, value_type: u32, out: *mut c_void) -> bool;
+}
+
+/// An `AXUIElementRef`, released on drop
+struct Element(CFType);
+
+impl Element {
+    fn attribute(&self, name: &'static str) -> Option<CFType> {
+        let name = CFString::from_static_string(name);
+        let mut value: CFTypeRef = std::ptr::null();
+        let err = unsafe {
+            AXUIElementCopyAttributeValue(self.0.as_CFTypeRef(), name.as_concrete_TypeRef(), &mut value)
+        };
+        (err == AX_SUCCESS && !value.is_null()).then(|| unsafe { CFType::wrap_under_create_rule(value) })
+    }
+
+    fn settable(&self, name: &'static str) -> bool {
+        let name = CFString::from_static_string(name);
+        let mut settable = 0u8;
+        let err = unsafe {
+            AXUIElementIsAttributeSettable(self.0.as_CFTypeRef(), name.as_concrete_TypeRef(), &mut settable)
+        };
+        err == AX_SUCCESS && settable != 0
+    }
+
+    fn set(&self, name: &'static str, value: &CFType) -> Result<(), InsertError> {
+        let name = CFString::from_static_string(name);
+        let err = unsafe {
+            AXUIElementSetAttributeValue(self.0.as_CFTypeRef(), name.as_concrete_TypeRef(), value.as_CFTypeRef())
+        };
+        if err == AX_SUCCESS {
+            Ok(())
+        } else {
+            Err(InsertError::Rejected(err))
+        }
+    }
+
+    fn selected_range(&self) -> Option<CFRange> {
+        let value = self.attribute("AXSelectedTextRange")?;
+        let mut range = CFRange::init(0, 0);
+        let ok = unsafe {
+            AXValueGetValue(value.as_CFTypeRef(), AX_VALUE_CF_RANGE, &mut range as *mut CFRange as *mut c_void)
+        };
+        ok.then_some(range)
+    }
+}
+
+fn focused_element() -> Option<Element> {
+    let system = Element(unsafe { CFType::wrap_under_create_rule(AXUIElementCreateSystemWide()) });
+    system.attribute("AXFocusedUIElement").map(Element)
+}
+
+/// Insert `text` at the caret of the focused element in the frontmost app,
+/// replacing any selection
+pub fn insert_text(text: &str) -> Result<(), InsertError> {
+    let trusted = unsafe { AXIsProcessTrusted() };
+    let focused = if trusted { focused_element() } else { None };
+    let caps = match &focused {
+        Some(element) => Capabilities {
+            trusted,
+            focused: true,
+            selected_text_settable: element.settable("AXSelectedText"),
+            value_settable: element.settable("AXValue"),
+            has_selected_range: element.selected_range().is_some(),
+        },
+        None => Capabilities {
+            trusted,
+            ..Capabilities::default()
+        },
+    };
+    let strategy = edit::choose(caps)?;
+    let element = focused.ok_or(InsertError::NoFocusedElement)?;
+
+    match strategy {
+        Strategy::SelectedText => element.set("AXSelectedText", &CFString::new(text).as_CFType()),
+        Strategy::SpliceValue => {
+            let value = element
+                .attribute("AXValue")
+                .and_then(|value| value.downcast::<CFString>())
+                .ok_or(InsertError::NotEditable)?;
+            let range = element.selected_range().ok_or(InsertError::NotEditable)?;
+            let (new_value, caret) = edit::splice_utf16(
+                &value.to_string(),
+                range.location.max(0) as usize,
+                range.length.max(0) as usize,
+                text,
+            )
+            .ok_or(InsertError::NotEditable)?;
+            element.set("AXValue", &CFString::new(&new_value).as_CFType())?;
+
+            // Put the caret after the inserted text; not worth failing over
+            let caret = CFRange::init(caret as CFIndex, 0);
+            unsafe {
+                let value = AXValueCreate(AX_VALUE_CF_RANGE, &caret as *const CFRange as *const c_void);
+                if !value.is_null() {
+                    let _ = element.set("AXSelectedTextRange", &CFType::wrap_under_create_rule(value));
+                }
+            }
+            Ok(())
+        }
+    }
+}
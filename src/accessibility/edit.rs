@@ -0,0 +1,151 @@
+//! How to insert text into the focused element, kept apart from the FFI so
+//! the decisions can be tested
+
+use std::fmt;
+
+/// What the focused element turned out to support
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Capabilities {
+    /// The app has accessibility permission
+    pub trusted: bool,
+    /// Some element has keyboard focus
+    pub focused: bool,
+    /// `kAXSelectedTextAttribute` can be set, which replaces the selection
+    /// (or inserts at the caret)
+    pub selected_text_settable: bool,
+    /// `kAXValueAttribute` can be set
+    pub value_settable: bool,
+    /// `kAXSelectedTextRangeAttribute` can be read, giving the caret position
+    pub has_selected_range: bool,
+}
+
+/// Which attribute the text goes in through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    SelectedText,
+    /// Rewrite the whole value with the text spliced in at the selection
+    SpliceValue,
+}
+
+/// Why the text couldn't be inserted through accessibility
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    NotTrusted,
+    NoFocusedElement,
+    /// The focused element isn't an editable text element
+    NotEditable,
+    /// The app rejected a call with this `AXError`
+    Rejected(i32),
+}
+
+impl fmt::Display for InsertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotTrusted => write!(f, "accessibility permission not granted"),
+            Self::NoFocusedElement => write!(f, "nothing has keyboard focus"),
+            Self::NotEditable => write!(f, "the focused element doesn't accept text"),
+            Self::Rejected(code) => write!(f, "the app refused the change (AXError {})", code),
+        }
+    }
+}
+
+/// Prefer setting the selected text, which leaves undo and the caret to the
+/// app; rewriting the value only works when the caret position is known
+pub fn choose(caps: Capabilities) -> Result<Strategy, InsertError> {
+    if !caps.trusted {
+        Err(InsertError::NotTrusted)
+    } else if !caps.focused {
+        Err(InsertError::NoFocusedElement)
+    } else if caps.selected_text_settable {
+        Ok(Strategy::SelectedText)
+    } else if caps.value_settable && caps.has_selected_range {
+        Ok(Strategy::SpliceValue)
+    } else {
+        Err(InsertError::NotEditable)
+    }
+}
+
+/// Replace `length` UTF-16 units at `location` in `value` with `text`,
+/// returning the new value and the caret position (in UTF-16 units) just
+/// after the inserted text. `None` if the range is out of bounds or splits
+/// a character.
+pub fn splice_utf16(value: &str, location: usize, length: usize, text: &str) -> Option<(String, usize)> {
+    let units: Vec<u16> = value.encode_utf16().collect();
+    let end = location.checked_add(length)?;
+    if end > units.len() {
+        return None;
+    }
+    let before = String::from_utf16(&units[..location]).ok()?;
+    let after = String::from_utf16(&units[end..]).ok()?;
+    let caret = location + text.encode_utf16().count();
+    Some((before + text + &after, caret))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editable() -> Capabilities {
+        Capabilities {
+            trusted: true,
+            focused: true,
+            selected_text_settable: true,
+            value_settable: true,
+            has_selected_range: true,
+        }
+    }
+
+    #[test]
+    fn test_selected_text_is_preferred() {
+        assert_eq!(choose(editable()), Ok(Strategy::SelectedText));
+    }
+
+    /// Without a settable selection, the value is rewritten if the caret is known
+    #[test]
+    fn test_value_splice_needs_the_caret() {
+        let caps = Capabilities {
+            selected_text_settable: false,
+            ..editable()
+        };
+        assert_eq!(choose(caps), Ok(Strategy::SpliceValue));
+
+        let caps = Capabilities {
+            has_selected_range: false,
+            ..caps
+        };
+        assert_eq!(choose(caps), Err(InsertError::NotEditable));
+    }
+
+    /// Each reason to fall back is reported, permission first
+    #[test]
+    fn test_fallback_reasons() {
+        assert_eq!(choose(Capabilities::default()), Err(InsertError::NotTrusted));
+        let caps = Capabilities {
+            focused: false,
+            ..editable()
+        };
+        assert_eq!(choose(caps), Err(InsertError::NoFocusedElement));
+        let caps = Capabilities {
+            selected_text_settable: false,
+            value_settable: false,
+            ..editable()
+        };
+        assert_eq!(choose(caps), Err(InsertError::NotEditable));
+    }
+
+    #[test]
+    fn test_splice_at_caret_and_over_selection() {
+        assert_eq!(splice_utf16("Hello world", 5, 0, ","), Some(("Hello, world".to_string(), 6)));
+        assert_eq!(splice_utf16("Hello world", 6, 5, "there"), Some(("Hello there".to_string(), 11)));
+        assert_eq!(splice_utf16("", 0, 0, "Hi"), Some(("Hi".to_string(), 2)));
+    }
+
+    /// Offsets count UTF-16 units, as the Accessibility API does
+    #[test]
+    fn test_splice_counts_utf16_units() {
+        // The emoji is two units
+        assert_eq!(splice_utf16("a😀b", 3, 0, "é"), Some(("a😀éb".to_string(), 4)));
+        assert_eq!(splice_utf16("a😀b", 2, 0, "x"), None, "Splits the emoji");
+        assert_eq!(splice_utf16("abc", 2, 5, "x"), None, "Past the end");
+    }
+}
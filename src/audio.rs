@@ -1,136 +1,400 @@
-use anyhow::{anyhow, Result};
+mod error;
+pub mod filters;
+mod resample;
+mod ring_buffer;
+mod source;
+
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, SampleFormat, Stream, StreamConfig};
+use cpal::{
+    Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    SupportedStreamConfigRange,
+};
 use parking_lot::Mutex;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tao::event_loop::EventLoopProxy;
+
+use crate::config::Config;
+use crate::events::AppEvent;
+use crate::permissions::{self, PermissionStatus};
+use crate::power::{Activity, ActivityGuard};
+pub use error::AudioError;
+use resample::StreamResampler;
+use ring_buffer::RingBuffer;
+pub use source::AudioSource;
+
+type Result<T> = std::result::Result<T, AudioError>;
+
+/// Whisper's sample rate, which captured audio is resampled to on the fly
+pub const CAPTURE_RATE: u32 = 16000;
 
 pub struct AudioCapture {
     device: Device,
     config: StreamConfig,
+    /// Native sample format of the device; converted to f32 in the callback
+    sample_format: SampleFormat,
+    /// Configured device name, kept so recovery re-selects the same device
+    device_name: Option<String>,
+    /// Converts the device's rate to `CAPTURE_RATE`; `None` when the device
+    /// already runs at it, or when no resampler could be built for its rate
+    /// (then samples are kept at the device rate and resampled after stopping)
+    resampler: Arc<Mutex<Option<StreamResampler>>>,
+    /// Mono samples at `sample_rate()`
     buffer: Arc<Mutex<Vec<f32>>>,
+    /// Set while input goes into `buffer`, so clear while paused
     is_recording: Arc<AtomicBool>,
-    stream: Option<Stream>,
+    /// In a recording, but not buffering input until resumed
+    paused: bool,
+    /// Set by the stream error callback when the device goes away
+    stream_failed: Arc<AtomicBool>,
+    /// RMS of the most recent callback buffer, stored as f32 bits
+    level: Arc<AtomicU32>,
+    /// Recording length cap in seconds (0 = unlimited)
+    max_recording_secs: u64,
+    /// Set once the cap is hit so the limit event is only sent once per recording
+    limit_reached: Arc<AtomicBool>,
+    /// Audio captured by a warm stream while not recording, used to seed the
+    /// next recording so speech started just before the hotkey isn't clipped
+    preroll: Arc<Mutex<RingBuffer>>,
+    preroll_ms: u32,
+    /// Number of upcoming recorded samples to replace with silence
+    mute_samples: Arc<AtomicUsize>,
+    stream: Option<LiveStream>,
+    proxy: EventLoopProxy<AppEvent>,
 }
 
-impl AudioCapture {
-    pub fn new() -> Result<Self> {
-        let host = cpal::default_host();
-        let device = host
-            .default_input_device()
-            .ok_or_else(|| anyhow!("No input device available"))?;
+/// A running input stream and the power assertions that go with it, so
+/// dropping the stream, however that happens, releases them too
+struct LiveStream {
+    _stream: Stream,
+    _app_nap: ActivityGuard,
+    /// Set for as long as the stream is recording
+    recording: Option<ActivityGuard>,
+}
 
-        log::info!("Using input device: {}", device.name().unwrap_or_default());
+impl AudioCapture {
+    /// Open an input device. When `config.input_device` is set, the first device
+    /// whose name contains it (case-insensitive) is used; otherwise the system default.
+    pub fn new(app_config: &Config, proxy: EventLoopProxy<AppEvent>) -> Result<Self> {
+        log::info!("Available input devices: {:?}", input_device_names());
 
-        let supported_config = device
-            .supported_input_configs()?
-            .find(|c| c.sample_format() == SampleFormat::F32)
-            .ok_or_else(|| anyhow!("No F32 config available"))?
-            .with_max_sample_rate();
-
-        let config: StreamConfig = supported_config.into();
-        log::info!(
-            "Audio config: {} channels, {} Hz",
-            config.channels,
-            config.sample_rate.0
-        );
+        let device_name = app_config.input_device.clone();
+        let (device, config, sample_format) = open_device(device_name.as_deref())?;
+        let resampler = stream_resampler(config.sample_rate.0);
+        let preroll = RingBuffer::new(preroll_samples(
+            app_config.preroll_ms,
+            capture_rate(config.sample_rate.0, &resampler),
+        ));
 
         Ok(Self {
             device,
             config,
+            sample_format,
+            device_name,
+            resampler: Arc::new(Mutex::new(resampler)),
             buffer: Arc::new(Mutex::new(Vec::with_capacity(16000 * 30))),
             is_recording: Arc::new(AtomicBool::new(false)),
+            paused: false,
+            stream_failed: Arc::new(AtomicBool::new(false)),
+            level: Arc::new(AtomicU32::new(0)),
+            max_recording_secs: app_config.max_recording_secs,
+            limit_reached: Arc::new(AtomicBool::new(false)),
+            preroll: Arc::new(Mutex::new(preroll)),
+            preroll_ms: app_config.preroll_ms,
+            mute_samples: Arc::new(AtomicUsize::new(0)),
             stream: None,
+            proxy,
         })
     }
 
-    pub fn sample_rate(&self) -> u32 {
-        self.config.sample_rate.0
-    }
-
     pub fn channels(&self) -> u16 {
         self.config.channels
     }
 
-    pub fn start_recording(&mut self) -> Result<()> {
-        self.buffer.lock().clear();
-        self.is_recording.store(true, Ordering::SeqCst);
+    /// RMS level (0.0..=1.0) of the most recently captured buffer.
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Whether the stream reported an error (e.g. the device was unplugged)
+    /// and needs to be rebuilt with `recover()`.
+    pub fn has_failed(&self) -> bool {
+        self.stream_failed.load(Ordering::SeqCst)
+    }
+
+    /// Drop the current stream and re-open the input device, picking up a new
+    /// default device if the previous one disappeared. Sends
+    /// `AppEvent::AudioDeviceChanged` with the name of the device now in use.
+    pub fn recover(&mut self) -> Result<()> {
+        self.is_recording.store(false, Ordering::SeqCst);
+        self.paused = false;
+        let name = self.reopen()?;
+        log::info!("Audio input recovered on device: {}", name);
+        let _ = self.proxy.send_event(AppEvent::AudioDeviceChanged(name));
+        Ok(())
+    }
+
+    /// Drop the stream and re-open the input device, e.g. after system sleep
+    /// left it dead. Does nothing while recording.
+    pub fn reset(&mut self) -> Result<()> {
+        if self.is_recording() {
+            return Ok(());
+        }
+        let name = self.reopen()?;
+        log::info!("Audio input reset on device: {}", name);
+        Ok(())
+    }
+
+    /// Replace the stream's device and format with a freshly opened one,
+    /// returning the device name. The stream is left cold.
+    fn reopen(&mut self) -> Result<String> {
+        self.stream = None;
+
+        let (device, config, sample_format) = open_device(self.device_name.as_deref())?;
+        let name = device.name().unwrap_or_default();
+        if config.sample_rate != self.config.sample_rate {
+            log::info!(
+                "Sample rate changed: {} Hz -> {} Hz",
+                self.config.sample_rate.0,
+                config.sample_rate.0
+            );
+        }
+
+        let resampler = stream_resampler(config.sample_rate.0);
+        *self.preroll.lock() = RingBuffer::new(preroll_samples(
+            self.preroll_ms,
+            capture_rate(config.sample_rate.0, &resampler),
+        ));
+        *self.resampler.lock() = resampler;
+        self.device = device;
+        self.config = config;
+        self.sample_format = sample_format;
+        self.stream_failed.store(false, Ordering::SeqCst);
+        Ok(name)
+    }
+
+    /// Build and start an input stream in the device's native sample format.
+    fn build_stream(&self) -> Result<LiveStream> {
+        let stream = match self.sample_format {
+            SampleFormat::I16 => self.build_stream_as::<i16>(),
+            SampleFormat::U16 => self.build_stream_as::<u16>(),
+            _ => self.build_stream_as::<f32>(),
+        };
+        // Refused access shows up as a failure to build or start the stream
+        let stream = stream.map_err(|e| match permissions::microphone_status() {
+            PermissionStatus::Denied => AudioError::PermissionDenied,
+            _ => e,
+        })?;
+        Ok(LiveStream {
+            _stream: stream,
+            _app_nap: Activity::AudioStream.begin(),
+            recording: None,
+        })
+    }
 
+    /// Build and start an input stream that converts samples to f32 and
+    /// appends them (as mono, at `sample_rate()`) to the shared buffer while
+    /// `is_recording` is set, and to the pre-roll ring otherwise.
+    fn build_stream_as<T>(&self) -> Result<Stream>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
         let buffer = self.buffer.clone();
+        let resampler = self.resampler.clone();
+        let preroll = self.preroll.clone();
+        let use_preroll = self.preroll_ms > 0;
         let is_recording = self.is_recording.clone();
+        let stream_failed = self.stream_failed.clone();
+        let level = self.level.clone();
+        let limit_reached = self.limit_reached.clone();
+        let mute_samples = self.mute_samples.clone();
+        let proxy = self.proxy.clone();
         let channels = self.config.channels as usize;
+        let max_samples = self.sample_rate() as usize * self.max_recording_secs as usize;
+
+        // Conversion scratch space, reused across callbacks once grown
+        let mut converted: Vec<f32> = Vec::new();
+        let mut mono: Vec<f32> = Vec::new();
+        let mut resampled: Vec<f32> = Vec::new();
 
         let stream = self.device.build_input_stream(
             &self.config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                if is_recording.load(Ordering::SeqCst) {
-                    let mut buf = buffer.lock();
-                    if channels == 1 {
-                        buf.extend_from_slice(data);
-                    } else {
-                        for chunk in data.chunks(channels) {
-                            let mono = chunk.iter().sum::<f32>() / channels as f32;
-                            buf.push(mono);
+            move |raw: &[T], _: &cpal::InputCallbackInfo| {
+                let recording = is_recording.load(Ordering::SeqCst);
+                if !recording && !use_preroll {
+                    // Not recording, just discard the samples before they're
+                    // even converted; the next recording starts the resampler afresh
+                    if let Some(resampler) = resampler.lock().as_mut() {
+                        resampler.reset();
+                    }
+                    return;
+                }
+
+                converted.clear();
+                converted.extend(raw.iter().map(|&s| s.to_sample::<f32>()));
+                let data = converted.as_slice();
+
+                if !recording {
+                    let mut ring = preroll.lock();
+                    // Re-check under the lock: start_recording flips the flag while holding it
+                    if !is_recording.load(Ordering::SeqCst) {
+                        let samples = capture(data, channels, 0, &resampler, &mut mono, &mut resampled);
+                        for &s in samples {
+                            ring.push(s);
                         }
+                        return;
+                    }
+                }
+
+                level.store(rms(data).to_bits(), Ordering::Relaxed);
+
+                let mut buf = buffer.lock();
+                if max_samples > 0 && buf.len() >= max_samples {
+                    // Stop appending and let the main loop submit what we have
+                    if !limit_reached.swap(true, Ordering::SeqCst) {
+                        let _ = proxy.send_event(AppEvent::RecordingLimitReached);
                     }
+                    return;
+                }
+
+                let muted = mute_samples.load(Ordering::SeqCst);
+                let silent = (data.len() / channels).min(muted);
+                if silent > 0 {
+                    mute_samples.store(muted - silent, Ordering::SeqCst);
                 }
+                buf.extend_from_slice(capture(data, channels, silent, &resampler, &mut mono, &mut resampled));
+            },
+            move |err| {
+                log::error!("Audio stream error: {}", err);
+                stream_failed.store(true, Ordering::SeqCst);
             },
-            |err| log::error!("Audio stream error: {}", err),
             None,
         )?;
 
         stream.play()?;
-        self.stream = Some(stream);
+        Ok(stream)
+    }
+}
+
+impl AudioSource for AudioCapture {
+    /// Rate of the recorded samples: `CAPTURE_RATE` unless the device's
+    /// rate can't be resampled while capturing
+    fn sample_rate(&self) -> u32 {
+        capture_rate(self.config.sample_rate.0, &self.resampler.lock())
+    }
+
+    fn start_recording(&mut self) -> Result<()> {
+        if self.has_failed() {
+            self.recover()?;
+        }
+
+        self.level.store(0, Ordering::Relaxed);
+        self.limit_reached.store(false, Ordering::SeqCst);
+        {
+            // Hold the pre-roll lock while flipping the flag so the callback
+            // can't slip samples into the ring after it has been drained
+            let mut preroll = self.preroll.lock();
+            let mut buffer = self.buffer.lock();
+            buffer.clear();
+            preroll.drain_into(&mut buffer);
+            if !buffer.is_empty() {
+                log::debug!("Seeded recording with {} pre-roll samples", buffer.len());
+            }
+            self.is_recording.store(true, Ordering::SeqCst);
+        }
+
+        // Reuse a prewarmed stream so the pre-roll runs straight into the recording
+        if self.stream.is_none() {
+            let stream = match self.build_stream() {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Failed to start input stream ({}), re-enumerating devices", e);
+                    self.recover()
+                        .and_then(|_| self.build_stream())
+                        .inspect_err(|_| self.is_recording.store(false, Ordering::SeqCst))?
+                }
+            };
+            self.stream = Some(stream);
+        }
+        if let Some(stream) = self.stream.as_mut() {
+            stream.recording.get_or_insert_with(|| Activity::Recording.begin());
+        }
+
         log::info!("Recording started");
         Ok(())
     }
 
-    pub fn stop_recording(&mut self) -> Vec<f32> {
+    fn stop_recording(&mut self) -> Vec<f32> {
         self.is_recording.store(false, Ordering::SeqCst);
+        self.paused = false;
         self.stream = None;
-        let samples = std::mem::take(&mut *self.buffer.lock());
-        log::info!("Recording stopped, captured {} samples", samples.len());
+        let mut samples = std::mem::take(&mut *self.buffer.lock());
+        if let Some(resampler) = self.resampler.lock().as_mut() {
+            let started = Instant::now();
+            resampler.flush(&mut samples);
+            log::debug!("Flushed the capture resampler in {:?}", started.elapsed());
+        }
+        log::info!(
+            "Recording stopped, captured {} samples at {} Hz",
+            samples.len(),
+            self.sample_rate()
+        );
         samples
     }
 
-    pub fn is_recording(&self) -> bool {
-        self.is_recording.load(Ordering::SeqCst)
+    /// Copy of the samples captured since `offset` in the current recording
+    fn samples_since(&self, offset: usize) -> Vec<f32> {
+        let buffer = self.buffer.lock();
+        buffer.get(offset..).map(<[f32]>::to_vec).unwrap_or_default()
+    }
+
+    fn is_recording(&self) -> bool {
+        self.paused || self.is_recording.load(Ordering::SeqCst)
+    }
+
+    /// The stream keeps running, feeding the pre-roll ring as between
+    /// recordings
+    fn pause_recording(&mut self) -> bool {
+        if self.paused || !self.is_recording.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.paused = true;
+        self.level.store(0, Ordering::Relaxed);
+        log::info!("Recording paused");
+        true
+    }
+
+    /// Audio from the pause, left in the pre-roll ring, is dropped
+    fn resume_recording(&mut self) -> bool {
+        if !std::mem::take(&mut self.paused) {
+            return false;
+        }
+        {
+            // Under the pre-roll lock, as in `start_recording`
+            let mut preroll = self.preroll.lock();
+            preroll.clear();
+            self.is_recording.store(true, Ordering::SeqCst);
+        }
+        log::info!("Recording resumed");
+        true
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
     }
 
     /// Pre-warm the audio stream without starting actual recording.
     /// This creates the stream so it's ready for instant recording start.
     /// The stream exists but doesn't buffer audio (is_recording is false).
-    pub fn prewarm(&mut self) -> Result<()> {
+    fn prewarm(&mut self) -> Result<()> {
         if self.stream.is_some() {
             // Already warm
             return Ok(());
         }
 
-        let buffer = self.buffer.clone();
-        let is_recording = self.is_recording.clone();
-        let channels = self.config.channels as usize;
-
-        let stream = self.device.build_input_stream(
-            &self.config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                if is_recording.load(Ordering::SeqCst) {
-                    let mut buf = buffer.lock();
-                    if channels == 1 {
-                        buf.extend_from_slice(data);
-                    } else {
-                        for chunk in data.chunks(channels) {
-                            let mono = chunk.iter().sum::<f32>() / channels as f32;
-                            buf.push(mono);
-                        }
-                    }
-                }
-                // When is_recording is false, we just discard the samples (no CPU cost)
-            },
-            |err| log::error!("Audio stream error: {}", err),
-            None,
-        )?;
-
-        stream.play()?;
+        let stream = self.build_stream()?;
         self.stream = Some(stream);
         log::info!("Audio prewarmed (stream ready)");
         Ok(())
@@ -138,8 +402,8 @@ impl AudioCapture {
 
     /// Cool down the audio stream (destroy it) to save resources.
     /// Safe to call even if not warm or currently recording.
-    pub fn cooldown(&mut self) {
-        if self.is_recording.load(Ordering::SeqCst) {
+    fn cooldown(&mut self) {
+        if self.is_recording() {
             // Don't cooldown while actively recording
             return;
         }
@@ -150,7 +414,179 @@ impl AudioCapture {
     }
 
     /// Check if the audio stream is pre-warmed and ready.
-    pub fn is_warm(&self) -> bool {
+    fn is_warm(&self) -> bool {
         self.stream.is_some()
     }
+
+    /// Record silence instead of input for the next `duration`, e.g. while
+    /// the start cue is playing so it doesn't leak into the transcript.
+    fn mute_for(&self, duration: Duration) {
+        // Counted in device frames, before resampling
+        let samples = (duration.as_secs_f64() * self.config.sample_rate.0 as f64) as usize;
+        self.mute_samples.store(samples, Ordering::SeqCst);
+    }
+}
+
+/// Downmix interleaved `data` to mono, with the first `silent_frames`
+/// replaced by silence, and resample it to the capture rate if needed
+fn capture<'a>(
+    data: &[f32],
+    channels: usize,
+    silent_frames: usize,
+    resampler: &Mutex<Option<StreamResampler>>,
+    mono: &'a mut Vec<f32>,
+    resampled: &'a mut Vec<f32>,
+) -> &'a [f32] {
+    mono.clear();
+    mono.resize(silent_frames, 0.0);
+    for_each_mono(&data[silent_frames * channels..], channels, |s| mono.push(s));
+    match resampler.lock().as_mut() {
+        Some(resampler) => {
+            resampled.clear();
+            resampler.push(mono, resampled);
+            resampled
+        }
+        None => mono,
+    }
+}
+
+/// Resampler from the device's `rate` to `CAPTURE_RATE`, if one is needed
+/// and can be built
+fn stream_resampler(rate: u32) -> Option<StreamResampler> {
+    if rate == CAPTURE_RATE {
+        return None;
+    }
+    StreamResampler::new(rate, CAPTURE_RATE)
+        .inspect(|_| log::info!("Resampling {} Hz input to {} Hz while capturing", rate, CAPTURE_RATE))
+        .inspect_err(|e| log::warn!("{}, resampling {} Hz input after recording instead", e, rate))
+        .ok()
+}
+
+/// Rate of the captured samples for a device running at `rate`
+fn capture_rate(rate: u32, resampler: &Option<StreamResampler>) -> u32 {
+    if resampler.is_some() {
+        CAPTURE_RATE
+    } else {
+        rate
+    }
+}
+
+/// Downmix interleaved frames to mono, calling `f` with each mono sample.
+fn for_each_mono(data: &[f32], channels: usize, mut f: impl FnMut(f32)) {
+    for chunk in data.chunks(channels) {
+        f(chunk.iter().sum::<f32>() / channels as f32);
+    }
+}
+
+fn preroll_samples(preroll_ms: u32, sample_rate: u32) -> usize {
+    (sample_rate as u64 * preroll_ms as u64 / 1000) as usize
+}
+
+/// Root-mean-square of a buffer of samples.
+fn rms(data: &[f32]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    (data.iter().map(|s| s * s).sum::<f32>() / data.len() as f32).sqrt()
+}
+
+/// Sample formats we can capture, in order of preference
+const SUPPORTED_FORMATS: [SampleFormat; 3] = [SampleFormat::F32, SampleFormat::I16, SampleFormat::U16];
+
+/// Select an input device (by name, falling back to default) and negotiate
+/// its stream config, preferring F32 over the integer formats.
+fn open_device(device_name: Option<&str>) -> Result<(Device, StreamConfig, SampleFormat)> {
+    let host = cpal::default_host();
+
+    let device = match device_name.and_then(|name| find_input_device(&host, name)) {
+        Some(device) => device,
+        None => {
+            if let Some(name) = device_name {
+                log::warn!(
+                    "Input device matching '{}' not found, falling back to default",
+                    name
+                );
+            }
+            host.default_input_device().ok_or(AudioError::NoDevice)?
+        }
+    };
+
+    let name = device.name().unwrap_or_default();
+    log::info!("Using input device: {}", name);
+
+    let configs: Vec<_> = device.supported_input_configs()?.collect();
+    let supported_config = SUPPORTED_FORMATS
+        .iter()
+        .find_map(|format| configs.iter().find(|c| c.sample_format() == *format))
+        .copied()
+        .ok_or(AudioError::UnsupportedFormat(name))?
+        .with_max_sample_rate();
+
+    let sample_format = supported_config.sample_format();
+    let config: StreamConfig = supported_config.into();
+    log::info!(
+        "Audio config: {} channels, {} Hz, {:?}",
+        config.channels,
+        config.sample_rate.0,
+        sample_format
+    );
+
+    Ok((device, config, sample_format))
+}
+
+/// An input device as cpal sees it
+#[derive(Debug)]
+pub struct InputDeviceInfo {
+    pub name: String,
+    /// Whether this is the system default input
+    pub is_default: bool,
+    /// Supported formats; empty if the device couldn't be queried
+    pub configs: Vec<SupportedStreamConfigRange>,
+}
+
+/// All input devices on the default host, in enumeration order.
+pub fn input_devices() -> Result<Vec<InputDeviceInfo>> {
+    let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
+
+    let devices = host
+        .input_devices()?
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let configs = match device.supported_input_configs() {
+                Ok(configs) => configs.collect(),
+                Err(e) => {
+                    log::warn!("Failed to query formats of {}: {}", name, e);
+                    Vec::new()
+                }
+            };
+            Some(InputDeviceInfo {
+                is_default: default_name.as_deref() == Some(name.as_str()),
+                name,
+                configs,
+            })
+        })
+        .collect();
+    Ok(devices)
+}
+
+/// Names of all input devices on the default host, in enumeration order.
+pub fn input_device_names() -> Vec<String> {
+    match input_devices() {
+        Ok(devices) => devices.into_iter().map(|d| d.name).collect(),
+        Err(e) => {
+            log::warn!("Failed to enumerate input devices: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Find the first input device whose name contains `wanted` (case-insensitive).
+fn find_input_device(host: &cpal::Host, wanted: &str) -> Option<Device> {
+    let wanted = wanted.to_lowercase();
+    host.input_devices().ok()?.find(|d| {
+        d.name()
+            .map(|name| name.to_lowercase().contains(&wanted))
+            .unwrap_or(false)
+    })
 }
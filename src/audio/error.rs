@@ -0,0 +1,36 @@
+//! Why the microphone couldn't be used
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AudioError {
+    /// Microphone access was refused in System Settings
+    #[error("microphone access is not granted")]
+    PermissionDenied,
+    #[error("no input device available")]
+    NoDevice,
+    /// The device offers none of the sample formats we can capture
+    #[error("{0} has no F32, I16 or U16 input format")]
+    UnsupportedFormat(String),
+    /// Listing devices or their formats failed
+    #[error("couldn't query input devices: {0}")]
+    Query(String),
+    #[error("couldn't build the input stream: {0}")]
+    BuildStream(#[from] cpal::BuildStreamError),
+    #[error("couldn't start the input stream: {0}")]
+    PlayStream(#[from] cpal::PlayStreamError),
+    #[error("resampler creation failed: {0}")]
+    Resampler(#[from] rubato::ResamplerConstructionError),
+}
+
+impl From<cpal::DevicesError> for AudioError {
+    fn from(e: cpal::DevicesError) -> Self {
+        Self::Query(e.to_string())
+    }
+}
+
+impl From<cpal::SupportedStreamConfigsError> for AudioError {
+    fn from(e: cpal::SupportedStreamConfigsError) -> Self {
+        Self::Query(e.to_string())
+    }
+}
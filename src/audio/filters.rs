@@ -0,0 +1,298 @@
+//! Optional preprocessing applied to recorded audio before VAD.
+
+use crate::config::PreprocessConfig;
+use std::f32::consts::PI;
+
+/// Length of the noise gate analysis window
+const GATE_WINDOW_MS: u32 = 10;
+
+/// Upper bound on normalization gain so near-silence isn't blown up into noise
+const MAX_NORMALIZE_GAIN_DB: f32 = 20.0;
+
+/// Second-order IIR section (RBJ audio EQ cookbook, direct form I)
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    fn highpass(cutoff_hz: f32, sample_rate: u32) -> Self {
+        let q = std::f32::consts::FRAC_1_SQRT_2;
+        let w0 = 2.0 * PI * cutoff_hz / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha;
+
+        Self {
+            b0: (1.0 + cos_w0) / 2.0 / a0,
+            b1: -(1.0 + cos_w0) / a0,
+            b2: (1.0 + cos_w0) / 2.0 / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha) / a0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Fourth-order (two cascaded biquads) high-pass filter, in place.
+pub fn highpass(samples: &mut [f32], cutoff_hz: f32, sample_rate: u32) {
+    // Cutoff must sit below Nyquist for the coefficients to be stable
+    if cutoff_hz <= 0.0 || cutoff_hz >= sample_rate as f32 / 2.0 {
+        return;
+    }
+
+    let mut first = Biquad::highpass(cutoff_hz, sample_rate);
+    let mut second = Biquad::highpass(cutoff_hz, sample_rate);
+    for s in samples.iter_mut() {
+        *s = second.process(first.process(*s));
+    }
+}
+
+/// Silence every window whose RMS level is below `threshold_db` (dBFS), in place.
+pub fn noise_gate(samples: &mut [f32], threshold_db: f32, sample_rate: u32) {
+    let window = (sample_rate * GATE_WINDOW_MS / 1000).max(1) as usize;
+    for chunk in samples.chunks_mut(window) {
+        if rms_db(chunk) < threshold_db {
+            chunk.fill(0.0);
+        }
+    }
+}
+
+/// RMS level of `samples` in dBFS (silence maps to a very low value).
+pub fn rms_db(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return f32::NEG_INFINITY;
+    }
+    let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+    20.0 * rms.max(1e-10).log10()
+}
+
+/// Whether `samples` are too quiet to hold any speech, their RMS level
+/// under `floor_db` (dBFS): what a muted mic records. Never for a floor of
+/// `-inf`.
+pub fn is_silent(samples: &[f32], floor_db: f32) -> bool {
+    rms_db(samples) < floor_db
+}
+
+/// Scale `samples` so the peak lands on `target_db` (dBFS), in place. Gain is
+/// capped at `MAX_NORMALIZE_GAIN_DB`; loud audio is brought down to the target.
+/// Returns the applied gain factor.
+pub fn normalize(samples: &mut [f32], target_db: f32) -> f32 {
+    let peak = samples.iter().fold(0.0f32, |max, s| max.max(s.abs()));
+    if peak <= 0.0 {
+        return 1.0;
+    }
+
+    let target = db_to_amplitude(target_db.min(0.0));
+    let gain = (target / peak).min(db_to_amplitude(MAX_NORMALIZE_GAIN_DB));
+    for s in samples.iter_mut() {
+        *s *= gain;
+    }
+    gain
+}
+
+fn db_to_amplitude(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Run the configured filters over `samples`, in place. No-op when disabled.
+pub fn preprocess(samples: &mut [f32], config: &PreprocessConfig, sample_rate: u32) {
+    if config.highpass_hz > 0.0 {
+        highpass(samples, config.highpass_hz, sample_rate);
+    }
+    if let Some(threshold_db) = config.gate_threshold_db {
+        noise_gate(samples, threshold_db, sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn sine(freq: f32, amplitude: f32, secs: f32) -> Vec<f32> {
+        (0..(RATE as f32 * secs) as usize)
+            .map(|i| amplitude * (2.0 * PI * freq * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    /// Amplitude of the `freq` component, via correlation with sin/cos
+    fn tone_amplitude(samples: &[f32], freq: f32) -> f32 {
+        let (mut re, mut im) = (0.0f32, 0.0f32);
+        for (i, s) in samples.iter().enumerate() {
+            let phase = 2.0 * PI * freq * i as f32 / RATE as f32;
+            re += s * phase.cos();
+            im += s * phase.sin();
+        }
+        2.0 * (re * re + im * im).sqrt() / samples.len() as f32
+    }
+
+    /// A 50 Hz hum mixed with a 1 kHz tone: the hum is attenuated, the tone survives
+    #[test]
+    fn test_highpass_attenuates_hum_and_keeps_speech_band() {
+        let hum = sine(50.0, 0.5, 1.0);
+        let tone = sine(1000.0, 0.5, 1.0);
+        let mut mixed: Vec<f32> = hum.iter().zip(&tone).map(|(a, b)| a + b).collect();
+
+        highpass(&mut mixed, 80.0, RATE);
+
+        // Skip the filter's settling time
+        let settled = &mixed[RATE as usize / 4..];
+        let hum_after = tone_amplitude(settled, 50.0);
+        let tone_after = tone_amplitude(settled, 1000.0);
+
+        assert!(hum_after < 0.5 * 0.2, "Hum should drop by >14 dB, got amplitude {}", hum_after);
+        assert!(tone_after > 0.5 * 0.9, "1 kHz tone should pass, got amplitude {}", tone_after);
+    }
+
+    #[test]
+    fn test_highpass_ignores_invalid_cutoff() {
+        let original = sine(50.0, 0.5, 0.1);
+
+        let mut samples = original.clone();
+        highpass(&mut samples, 0.0, RATE);
+        assert_eq!(samples, original);
+
+        highpass(&mut samples, RATE as f32, RATE);
+        assert_eq!(samples, original);
+    }
+
+    #[test]
+    fn test_noise_gate_silences_quiet_windows_only() {
+        // 100 ms at -60 dBFS followed by 100 ms of a loud tone
+        let mut samples = sine(1000.0, 0.001, 0.1);
+        samples.extend(sine(1000.0, 0.5, 0.1));
+
+        noise_gate(&mut samples, -40.0, RATE);
+
+        let half = samples.len() / 2;
+        assert!(samples[..half].iter().all(|&s| s == 0.0), "Quiet part should be gated");
+        assert!(rms_db(&samples[half..]) > -10.0, "Loud part should pass untouched");
+    }
+
+    #[test]
+    fn test_rms_db_reference_levels() {
+        // A full-scale sine has RMS 1/sqrt(2), i.e. about -3 dBFS
+        let full = sine(1000.0, 1.0, 0.1);
+        assert!((rms_db(&full) + 3.01).abs() < 0.1);
+
+        assert!(rms_db(&[0.0; 160]) < -150.0);
+    }
+
+    /// One 16-bit step either way, the noise a hardware mute leaves
+    fn dither(secs: f32) -> Vec<f32> {
+        let step = 1.0 / 32768.0;
+        (0..(RATE as f32 * secs) as usize)
+            .map(|i| if i % 2 == 0 { step } else { -step })
+            .collect()
+    }
+
+    #[test]
+    fn test_rms_db_of_quiet_signals() {
+        // A sine's RMS is 3 dB under its peak
+        assert!((rms_db(&sine(440.0, 0.01, 1.0)) + 43.01).abs() < 0.1);
+        assert!((rms_db(&sine(440.0, 0.001, 1.0)) + 63.01).abs() < 0.1);
+        assert!((rms_db(&dither(1.0)) + 90.31).abs() < 0.1);
+        assert_eq!(rms_db(&[]), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_muted_mic_is_silent() {
+        assert!(is_silent(&vec![0.0; RATE as usize], -60.0));
+        assert!(is_silent(&dither(5.0), -60.0));
+        assert!(is_silent(&sine(440.0, 0.001, 1.0), -60.0));
+        assert!(!is_silent(&sine(440.0, 0.01, 1.0), -60.0));
+
+        // A second of quiet speech in ten of silence is still something
+        let mut mostly_silent = vec![0.0; RATE as usize * 9];
+        mostly_silent.extend(sine(440.0, 0.05, 1.0));
+        assert!(!is_silent(&mostly_silent, -60.0));
+
+        // A floor of -inf never skips anything
+        assert!(!is_silent(&vec![0.0; RATE as usize], f32::NEG_INFINITY));
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0f32, |max, s| max.max(s.abs()))
+    }
+
+    #[test]
+    fn test_normalize_boosts_quiet_audio_to_target() {
+        let mut samples = sine(1000.0, 0.1, 0.1);
+
+        normalize(&mut samples, -3.0);
+
+        assert!((peak(&samples) - db_to_amplitude(-3.0)).abs() < 1e-3);
+    }
+
+    /// Already-loud audio is brought down to the target, never pushed past full scale
+    #[test]
+    fn test_normalize_clamps_loud_audio() {
+        let mut samples = sine(1000.0, 1.0, 0.1);
+
+        let gain = normalize(&mut samples, -3.0);
+
+        assert!(gain < 1.0, "Loud audio should not be amplified");
+        assert!(peak(&samples) <= db_to_amplitude(-3.0) + 1e-4);
+    }
+
+    /// A positive target is treated as 0 dBFS so output can't clip
+    #[test]
+    fn test_normalize_never_exceeds_full_scale() {
+        let mut samples = sine(1000.0, 0.5, 0.1);
+
+        normalize(&mut samples, 6.0);
+
+        assert!(peak(&samples) <= 1.0 + 1e-4);
+    }
+
+    #[test]
+    fn test_normalize_gain_is_capped_for_near_silence() {
+        let mut samples = sine(1000.0, 0.0001, 0.1);
+
+        let gain = normalize(&mut samples, -3.0);
+
+        assert!((gain - db_to_amplitude(MAX_NORMALIZE_GAIN_DB)).abs() < 1e-3);
+        assert!(peak(&samples) < 0.01, "Near-silence must stay quiet");
+    }
+
+    #[test]
+    fn test_normalize_all_zero_is_noop() {
+        let mut samples = vec![0.0; 160];
+
+        assert_eq!(normalize(&mut samples, -3.0), 1.0);
+        assert!(samples.iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_preprocess_disabled_is_noop() {
+        let original = sine(50.0, 0.5, 0.1);
+        let mut samples = original.clone();
+
+        preprocess(&mut samples, &PreprocessConfig::default(), RATE);
+
+        assert_eq!(samples, original);
+    }
+}
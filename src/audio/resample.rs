@@ -0,0 +1,162 @@
+//! Resampling captured audio to whisper's rate as it arrives, so nothing is
+//! left to do after the recording stops
+
+use rubato::{Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction};
+
+use super::AudioError;
+
+type Result<T> = std::result::Result<T, AudioError>;
+
+/// Input frames per resampler run; about 20 ms at 48 kHz
+const CHUNK_FRAMES: usize = 1024;
+
+/// Mono resampler fed in whatever sizes the audio callback delivers
+pub struct StreamResampler {
+    resampler: SincFixedIn<f32>,
+    ratio: f64,
+    /// Input waiting for a full chunk
+    pending: Vec<f32>,
+    output: Vec<Vec<f32>>,
+    input_total: usize,
+    output_total: usize,
+}
+
+impl StreamResampler {
+    pub fn new(from_rate: u32, to_rate: u32) -> Result<Self> {
+        let params = SincInterpolationParameters {
+            sinc_len: 64,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 128,
+            window: WindowFunction::BlackmanHarris2,
+        };
+        let ratio = to_rate as f64 / from_rate as f64;
+        let resampler = SincFixedIn::<f32>::new(ratio, 1.0, params, CHUNK_FRAMES, 1)?;
+        Ok(Self {
+            output: resampler.output_buffer_allocate(true),
+            resampler,
+            ratio,
+            pending: Vec::with_capacity(CHUNK_FRAMES * 2),
+            input_total: 0,
+            output_total: 0,
+        })
+    }
+
+    /// Resample `input`, appending what's ready to `out`. Up to a chunk of
+    /// input is held back until more arrives or `flush`.
+    pub fn push(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.pending.extend_from_slice(input);
+        self.input_total += input.len();
+        let mut start = 0;
+        while self.pending.len() - start >= CHUNK_FRAMES {
+            let chunk = [&self.pending[start..start + CHUNK_FRAMES]];
+            match self.resampler.process_into_buffer(&chunk, &mut self.output, None) {
+                Ok((_, frames)) => self.emit(frames, usize::MAX, out),
+                Err(e) => log::warn!("Resampling failed: {}", e),
+            }
+            start += CHUNK_FRAMES;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// Resample whatever is held back and start over, so that `out` has
+    /// received exactly as much audio as was pushed, at the new rate
+    pub fn flush(&mut self, out: &mut Vec<f32>) {
+        let expected = (self.input_total as f64 * self.ratio).round() as usize;
+        let mut pending = Some(std::mem::take(&mut self.pending));
+        // Padding pushes the delayed tail out; a few runs is always enough
+        for _ in 0..4 {
+            if self.output_total >= expected {
+                break;
+            }
+            let input = pending.take().map(|pending| [pending]);
+            match self
+                .resampler
+                .process_partial_into_buffer(input.as_ref().map(|input| &input[..]), &mut self.output, None)
+            {
+                Ok((_, frames)) => self.emit(frames, expected - self.output_total, out),
+                Err(e) => {
+                    log::warn!("Resampling failed: {}", e);
+                    break;
+                }
+            }
+        }
+        self.reset();
+    }
+
+    /// Forget everything pushed so far
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+        self.pending.clear();
+        self.input_total = 0;
+        self.output_total = 0;
+    }
+
+    /// Append up to `limit` of the `frames` just produced
+    fn emit(&mut self, frames: usize, limit: usize, out: &mut Vec<f32>) {
+        let produced = &self.output[0][..frames.min(limit)];
+        out.extend_from_slice(produced);
+        self.output_total += produced.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(hz: f32, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (i as f32 * hz * std::f32::consts::TAU / rate as f32).sin() * 0.5)
+            .collect()
+    }
+
+    /// Odd callback sizes in, the right length out, in step with the input
+    #[test]
+    fn test_stream_matches_the_signal() {
+        let input = sine(440.0, 48000, 48000);
+        let mut resampler = StreamResampler::new(48000, 16000).unwrap();
+        let mut out = Vec::new();
+        for (i, piece) in input.chunks(333).enumerate() {
+            let before = out.len();
+            resampler.push(piece, &mut out);
+            assert!(out.len() - before <= CHUNK_FRAMES, "Callback {} produced too much", i);
+        }
+        resampler.flush(&mut out);
+        assert_eq!(out.len(), 16000);
+
+        let expected = sine(440.0, 16000, 16000);
+        // The ends see the zero padding; the middle is within a sample of
+        // the signal (a step is up to 0.09 here)
+        let worst = out[100..15900]
+            .iter()
+            .zip(&expected[100..15900])
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f32::max);
+        assert!(worst < 0.06, "Off by up to {}", worst);
+    }
+
+    /// After a flush the next recording starts from scratch
+    #[test]
+    fn test_flush_starts_over() {
+        let mut resampler = StreamResampler::new(44100, 16000).unwrap();
+        let mut first = Vec::new();
+        resampler.push(&sine(440.0, 44100, 4410), &mut first);
+        resampler.flush(&mut first);
+        assert_eq!(first.len(), 1600);
+
+        let mut second = Vec::new();
+        resampler.push(&sine(440.0, 44100, 4410), &mut second);
+        resampler.flush(&mut second);
+        assert_eq!(second, first);
+    }
+
+    #[test]
+    fn test_reset_drops_held_back_input() {
+        let mut resampler = StreamResampler::new(48000, 16000).unwrap();
+        let mut out = Vec::new();
+        resampler.push(&[0.5; 100], &mut out);
+        resampler.reset();
+        resampler.flush(&mut out);
+        assert!(out.is_empty());
+    }
+}
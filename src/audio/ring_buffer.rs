@@ -0,0 +1,116 @@
+/// Fixed-capacity ring buffer of samples. Once full, each push overwrites the
+/// oldest sample, so memory use never grows past the initial allocation.
+pub struct RingBuffer {
+    data: Vec<f32>,
+    pos: usize,
+    len: usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: vec![0.0; capacity],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+        self.data[self.pos] = sample;
+        self.pos = (self.pos + 1) % capacity;
+        self.len = (self.len + 1).min(capacity);
+    }
+
+    /// Append the buffered samples, oldest first, to `out` and empty the ring.
+    pub fn drain_into(&mut self, out: &mut Vec<f32>) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+        let start = (self.pos + capacity - self.len) % capacity;
+        out.extend((0..self.len).map(|i| self.data[(start + i) % capacity]));
+        self.len = 0;
+    }
+
+    /// Forget the buffered samples
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drain_returns_samples_in_order() {
+        let mut ring = RingBuffer::new(4);
+        ring.push(1.0);
+        ring.push(2.0);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+
+        assert_eq!(out, vec![1.0, 2.0]);
+        assert_eq!(ring.len, 0, "Drain should empty the ring");
+    }
+
+    /// Overflow keeps only the newest `capacity` samples
+    #[test]
+    fn test_overflow_overwrites_oldest() {
+        let mut ring = RingBuffer::new(3);
+        for i in 0..10 {
+            ring.push(i as f32);
+        }
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+
+        assert_eq!(out, vec![7.0, 8.0, 9.0]);
+        assert_eq!(ring.capacity(), 3, "Capacity must never grow");
+    }
+
+    #[test]
+    fn test_drain_appends_to_existing_output() {
+        let mut ring = RingBuffer::new(2);
+        ring.push(5.0);
+
+        let mut out = vec![0.5];
+        ring.drain_into(&mut out);
+
+        assert_eq!(out, vec![0.5, 5.0]);
+    }
+
+    #[test]
+    fn test_clear_forgets_samples() {
+        let mut ring = RingBuffer::new(3);
+        ring.push(1.0);
+        ring.push(2.0);
+        ring.clear();
+        ring.push(3.0);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+
+        assert_eq!(out, vec![3.0]);
+    }
+
+    #[test]
+    fn test_zero_capacity_is_a_noop() {
+        let mut ring = RingBuffer::new(0);
+        ring.push(1.0);
+
+        let mut out = Vec::new();
+        ring.drain_into(&mut out);
+
+        assert!(out.is_empty());
+    }
+}
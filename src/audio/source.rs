@@ -0,0 +1,238 @@
+//! What the recording flow needs from an input, so it can run without a
+//! microphone
+
+use std::time::Duration;
+
+use super::AudioError;
+
+type Result<T> = std::result::Result<T, AudioError>;
+
+/// Somewhere recordings come from; `AudioCapture` for the microphone
+pub trait AudioSource {
+    /// Start buffering input as a new recording
+    fn start_recording(&mut self) -> Result<()>;
+    /// End the recording and hand over everything it captured, as mono
+    /// samples at `sample_rate()`
+    fn stop_recording(&mut self) -> Vec<f32>;
+    fn sample_rate(&self) -> u32;
+    /// True from `start_recording` to `stop_recording`, paused or not
+    fn is_recording(&self) -> bool;
+    /// Stop buffering input without ending the recording or the stream;
+    /// false unless recording and not already paused
+    fn pause_recording(&mut self) -> bool;
+    /// Buffer input into the paused recording again; false unless paused
+    fn resume_recording(&mut self) -> bool;
+    fn is_paused(&self) -> bool;
+    /// Copy of the samples captured since `offset` in the current recording
+    fn samples_since(&self, offset: usize) -> Vec<f32>;
+    /// Replace the next `duration` of the recording with silence
+    fn mute_for(&self, duration: Duration);
+    /// Get ready to record without recording yet
+    fn prewarm(&mut self) -> Result<()>;
+    /// Release what `prewarm` set up; not while recording
+    fn cooldown(&mut self);
+    fn is_warm(&self) -> bool;
+}
+
+/// `secs` of a sine wave at `hz` and half amplitude
+#[cfg(test)]
+pub fn sine(hz: f32, secs: f32, sample_rate: u32) -> Vec<f32> {
+    let len = (secs * sample_rate as f32) as usize;
+    (0..len)
+        .map(|i| (i as f32 * hz * std::f32::consts::TAU / sample_rate as f32).sin() * 0.5)
+        .collect()
+}
+
+/// Replays queued recordings, one per `start_recording` / `stop_recording`
+/// pair, instead of listening to a microphone
+#[cfg(test)]
+pub struct FakeAudioSource {
+    sample_rate: u32,
+    recordings: std::collections::VecDeque<Vec<f32>>,
+    recording: bool,
+    paused: bool,
+    warm: bool,
+    /// From `mute_for`, applied to the next recording
+    mute_samples: std::cell::Cell<usize>,
+    /// Number of `start_recording` calls, failed or not
+    pub starts: usize,
+    /// Makes the next `start_recording` fail, as an unplugged device would
+    pub fail_next_start: bool,
+}
+
+#[cfg(test)]
+impl FakeAudioSource {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            recordings: Default::default(),
+            recording: false,
+            paused: false,
+            warm: false,
+            mute_samples: Default::default(),
+            starts: 0,
+            fail_next_start: false,
+        }
+    }
+
+    /// Queue what the next recording captures
+    pub fn push(&mut self, samples: Vec<f32>) -> &mut Self {
+        self.recordings.push_back(samples);
+        self
+    }
+}
+
+#[cfg(test)]
+impl AudioSource for FakeAudioSource {
+    fn start_recording(&mut self) -> Result<()> {
+        self.starts += 1;
+        if std::mem::take(&mut self.fail_next_start) {
+            return Err(AudioError::NoDevice);
+        }
+        self.recording = true;
+        Ok(())
+    }
+
+    /// Empty when nothing was queued
+    fn stop_recording(&mut self) -> Vec<f32> {
+        self.paused = false;
+        if !std::mem::take(&mut self.recording) {
+            return Vec::new();
+        }
+        let mut samples = self.recordings.pop_front().unwrap_or_default();
+        let muted = self.mute_samples.take().min(samples.len());
+        samples[..muted].fill(0.0);
+        samples
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// The queued recording is replayed whole, pauses or not
+    fn pause_recording(&mut self) -> bool {
+        let pausing = self.recording && !self.paused;
+        self.paused |= pausing;
+        pausing
+    }
+
+    fn resume_recording(&mut self) -> bool {
+        std::mem::take(&mut self.paused)
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The whole of the queued recording counts as already captured
+    fn samples_since(&self, offset: usize) -> Vec<f32> {
+        match self.recordings.front() {
+            Some(samples) if self.recording => samples.get(offset..).map(<[f32]>::to_vec).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn mute_for(&self, duration: Duration) {
+        self.mute_samples
+            .set((duration.as_secs_f64() * self.sample_rate as f64) as usize);
+    }
+
+    fn prewarm(&mut self) -> Result<()> {
+        self.warm = true;
+        Ok(())
+    }
+
+    fn cooldown(&mut self) {
+        if !self.recording {
+            self.warm = false;
+        }
+    }
+
+    fn is_warm(&self) -> bool {
+        self.warm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sine() {
+        let samples = sine(440.0, 0.5, 16000);
+        assert_eq!(samples.len(), 8000);
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - 0.5).abs() < 0.01, "Peak {}", peak);
+    }
+
+    #[test]
+    fn test_fake_replays_in_order() {
+        let mut source = FakeAudioSource::new(16000);
+        source.push(vec![0.1; 10]).push(vec![0.2; 20]);
+
+        source.start_recording().unwrap();
+        assert!(source.is_recording());
+        assert_eq!(source.samples_since(4).len(), 6);
+        assert_eq!(source.stop_recording(), vec![0.1; 10]);
+        assert!(!source.is_recording());
+
+        source.start_recording().unwrap();
+        assert_eq!(source.stop_recording(), vec![0.2; 20]);
+        // Nothing left, and stopping twice gives nothing
+        source.start_recording().unwrap();
+        assert!(source.stop_recording().is_empty());
+        assert!(source.stop_recording().is_empty());
+        assert_eq!(source.starts, 3);
+    }
+
+    #[test]
+    fn test_fake_mutes_the_start() {
+        let mut source = FakeAudioSource::new(1000);
+        source.push(vec![0.5; 100]);
+        source.mute_for(Duration::from_millis(30));
+        source.start_recording().unwrap();
+        let samples = source.stop_recording();
+        assert!(samples[..30].iter().all(|&s| s == 0.0));
+        assert!(samples[30..].iter().all(|&s| s == 0.5));
+    }
+
+    /// A paused recording is still recording, and only a recording pauses
+    #[test]
+    fn test_fake_pauses() {
+        let mut source = FakeAudioSource::new(16000);
+        assert!(!source.pause_recording());
+        source.push(vec![0.1; 10]);
+        source.start_recording().unwrap();
+
+        assert!(source.pause_recording());
+        assert!(!source.pause_recording());
+        assert!(source.is_paused());
+        assert!(source.is_recording());
+        assert!(source.resume_recording());
+        assert!(!source.resume_recording());
+
+        source.pause_recording();
+        assert_eq!(source.stop_recording(), vec![0.1; 10]);
+        assert!(!source.is_paused());
+    }
+
+    #[test]
+    fn test_fake_failures_and_warmth() {
+        let mut source = FakeAudioSource::new(16000);
+        source.fail_next_start = true;
+        assert!(source.start_recording().is_err());
+        assert!(!source.is_recording());
+        assert!(source.start_recording().is_ok());
+
+        source.prewarm().unwrap();
+        source.cooldown();
+        assert!(source.is_warm(), "Cooled down while recording");
+        source.stop_recording();
+        source.cooldown();
+        assert!(!source.is_warm());
+    }
+}
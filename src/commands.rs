@@ -0,0 +1,284 @@
+//! One-shot subcommands that run without the event loop, hotkeys, or indicator
+
+use crate::audio::{self, filters, AudioError};
+use crate::config::{Config, WhisperModel};
+use crate::model;
+use crate::stats::Stats;
+use crate::transcriber::{ModelError, Transcriber, TranscriptionOutcome};
+use crate::vad::{VadError, VadProcessor};
+use crate::wav::{self, WavError};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, CommandError>;
+
+/// Why a subcommand failed
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("Failed to read {path:?}: {source}")]
+    ReadWav { path: PathBuf, source: WavError },
+    #[error(transparent)]
+    Model(#[from] ModelError),
+    #[error(transparent)]
+    Vad(#[from] VadError),
+    #[error(transparent)]
+    Audio(#[from] AudioError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("No speech detected")]
+    NoSpeech,
+    /// A flag value that can't be used
+    #[error("{0}")]
+    BadOption(&'static str),
+    /// `bench --assert-rtf` failed
+    #[error("Real-time factor {rtf:.3} is above {limit:.3}")]
+    TooSlow { rtf: f64, limit: f64 },
+}
+
+/// `wav::read_wav`, naming the file when it fails
+fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    wav::read_wav(path).map_err(|source| CommandError::ReadWav {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Sample rate of the audio `bench` makes up, the rate whisper runs at
+const BENCH_SAMPLE_RATE: u32 = 16000;
+
+/// `transcribe --json` output
+#[derive(Debug, Serialize)]
+struct TranscriptJson<'a> {
+    text: &'a str,
+    /// Time spent transcribing, excluding model load
+    duration_ms: u128,
+    model: WhisperModel,
+}
+
+/// Transcribe a WAV file with the configured model and VAD settings and
+/// print the text to stdout. No speech is reported as an error.
+pub fn transcribe(config: &Config, path: &Path, json: bool) -> Result<()> {
+    let (mut samples, sample_rate) = read_wav(path)?;
+    log::info!(
+        "Read {:.1}s of audio at {} Hz from {:?}",
+        samples.len() as f32 / sample_rate as f32,
+        sample_rate,
+        path
+    );
+
+    let transcriber = Transcriber::new(config.model_path(), config.whisper_threads)?;
+    let start = Instant::now();
+
+    filters::preprocess(&mut samples, &config.preprocess, sample_rate);
+    if config.vad.enabled {
+        match VadProcessor::new(&config.vad).process(&samples, sample_rate)? {
+            Some(speech) => samples = speech.samples,
+            None => return Err(CommandError::NoSpeech),
+        }
+    }
+    if config.normalize_audio {
+        filters::normalize(&mut samples, config.normalize_target_db);
+    }
+
+    let text = match transcriber.transcribe(&samples, sample_rate, None)? {
+        TranscriptionOutcome::Text(result) => result.text,
+        TranscriptionOutcome::NoSpeech => return Err(CommandError::NoSpeech),
+    };
+
+    if json {
+        let output = TranscriptJson {
+            text: &text,
+            duration_ms: start.elapsed().as_millis(),
+            model: config.model,
+        };
+        println!("{}", serde_json::to_string(&output)?);
+    } else {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+/// `bench` options
+pub struct BenchOptions {
+    /// WAV file to time instead of made-up audio
+    pub file: Option<PathBuf>,
+    /// Length of the made-up audio
+    pub seconds: f32,
+    /// Timed runs after the warmup
+    pub runs: usize,
+    pub json: bool,
+    /// Fail when the mean real-time factor is above this
+    pub assert_rtf: Option<f64>,
+}
+
+/// What `bench` measured; also its `--json` output
+#[derive(Debug, Serialize)]
+struct BenchReport {
+    model: WhisperModel,
+    model_path: PathBuf,
+    threads: usize,
+    audio_secs: f64,
+    load_ms: u64,
+    warmup_ms: u64,
+    /// Wall time of each timed run, resampling included
+    runs_ms: Vec<u64>,
+    mean_ms: f64,
+    min_ms: u64,
+    max_ms: u64,
+    /// Mean run time over the audio's length; under 1.0 is faster than real time
+    rtf: f64,
+}
+
+impl BenchReport {
+    fn print_table(&self) {
+        println!("{:<18}{}", "Model", self.model_path.display());
+        println!("{:<18}{}", "Threads", self.threads);
+        println!("{:<18}{:.1} s", "Audio", self.audio_secs);
+        println!("{:<18}{} ms", "Load", self.load_ms);
+        println!("{:<18}{} ms", "Warmup", self.warmup_ms);
+        for (i, ms) in self.runs_ms.iter().enumerate() {
+            println!("{:<18}{} ms", format!("Run {}", i + 1), ms);
+        }
+        println!("{:<18}{:.0} / {} / {} ms", "Mean / min / max", self.mean_ms, self.min_ms, self.max_ms);
+        println!("{:<18}{:.3}", "Real-time factor", self.rtf);
+    }
+}
+
+/// Time loading the configured model, a warmup, and `runs` transcriptions
+/// of the same audio, then print the numbers. Uses the local model even with
+/// `backend = "remote"`.
+pub fn bench(config: &Config, options: &BenchOptions) -> Result<()> {
+    if options.runs == 0 {
+        return Err(CommandError::BadOption("--runs must be at least 1"));
+    }
+    let (samples, sample_rate) = match &options.file {
+        Some(path) => read_wav(path)?,
+        None if options.seconds > 0.0 => (
+            wav::synthetic_speech(options.seconds, BENCH_SAMPLE_RATE),
+            BENCH_SAMPLE_RATE,
+        ),
+        None => return Err(CommandError::BadOption("--seconds must be more than 0")),
+    };
+    let audio_secs = samples.len() as f64 / f64::from(sample_rate);
+
+    let started = Instant::now();
+    let transcriber = Transcriber::new(config.model_path(), config.whisper_threads)?;
+    let load_ms = started.elapsed().as_millis() as u64;
+
+    let started = Instant::now();
+    let (clip, clip_rate) = model::warmup_clip(config);
+    transcriber.warmup(&clip, clip_rate)?;
+    let warmup_ms = started.elapsed().as_millis() as u64;
+
+    let mut runs_ms = Vec::with_capacity(options.runs);
+    for run in 1..=options.runs {
+        let started = Instant::now();
+        transcriber.transcribe_detailed(&samples, sample_rate, None, None, None)?;
+        let ms = started.elapsed().as_millis() as u64;
+        log::info!("Run {}/{}: {} ms", run, options.runs, ms);
+        runs_ms.push(ms);
+    }
+
+    let report = summarize(BenchReport {
+        model: config.model,
+        model_path: config.model_path(),
+        threads: transcriber.threads(),
+        audio_secs,
+        load_ms,
+        warmup_ms,
+        runs_ms,
+        mean_ms: 0.0,
+        min_ms: 0,
+        max_ms: 0,
+        rtf: 0.0,
+    });
+    if options.json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else {
+        report.print_table();
+    }
+
+    match options.assert_rtf {
+        Some(limit) if report.rtf > limit => Err(CommandError::TooSlow {
+            rtf: report.rtf,
+            limit,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Fill in the mean, min, max and real-time factor from `runs_ms`
+fn summarize(mut report: BenchReport) -> BenchReport {
+    let runs = &report.runs_ms;
+    report.mean_ms = runs.iter().sum::<u64>() as f64 / runs.len().max(1) as f64;
+    report.min_ms = runs.iter().copied().min().unwrap_or_default();
+    report.max_ms = runs.iter().copied().max().unwrap_or_default();
+    report.rtf = if report.audio_secs > 0.0 {
+        report.mean_ms / 1000.0 / report.audio_secs
+    } else {
+        0.0
+    };
+    report
+}
+
+/// Print every input device, marking the default, with its supported
+/// formats. Meant to be pasted into bug reports as-is.
+pub fn devices() -> Result<()> {
+    let devices = audio::input_devices()?;
+    if devices.is_empty() {
+        println!("No input devices found");
+        return Ok(());
+    }
+
+    for device in devices {
+        let marker = if device.is_default { " (default)" } else { "" };
+        println!("{}{}", device.name, marker);
+        if device.configs.is_empty() {
+            println!("    no supported formats reported");
+        }
+        for config in &device.configs {
+            let (min, max) = (config.min_sample_rate().0, config.max_sample_rate().0);
+            let rates = if min == max {
+                format!("{} Hz", min)
+            } else {
+                format!("{}-{} Hz", min, max)
+            };
+            println!("    {}, {} ch, {}", config.sample_format(), config.channels(), rates);
+        }
+    }
+    Ok(())
+}
+
+/// Print the all-time usage numbers from `stats.toml`
+pub fn stats() -> Result<()> {
+    let stats = Stats::load(&Config::stats_path());
+    println!("{}", stats.total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let report = summarize(BenchReport {
+            model: WhisperModel::BaseEn,
+            model_path: PathBuf::from("ggml-base.en.bin"),
+            threads: 4,
+            audio_secs: 10.0,
+            load_ms: 300,
+            warmup_ms: 100,
+            runs_ms: vec![1200, 800, 1000],
+            mean_ms: 0.0,
+            min_ms: 0,
+            max_ms: 0,
+            rtf: 0.0,
+        });
+        assert_eq!(report.mean_ms, 1000.0);
+        assert_eq!((report.min_ms, report.max_ms), (800, 1200));
+        assert!((report.rtf - 0.1).abs() < 1e-9);
+    }
+}
@@ -1,7 +1,52 @@
-use anyhow::Result;
+use global_hotkey::hotkey::Modifiers;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use toml::{Table, Value};
+
+use crate::hotkey;
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::pipeline;
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+/// Why a config couldn't be loaded, saved or used
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("invalid TOML: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("couldn't serialize the config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+    /// `version` isn't a usable number
+    #[error("config version must be a positive number, got {0}")]
+    BadVersion(String),
+    #[error("config is from a newer version of dictation (version {found}, this build understands up to {supported})")]
+    TooNew { found: u32, supported: u32 },
+    /// `active_profile` names a missing profile, or a profile sets
+    /// something only the top level can
+    #[error("{0}")]
+    Profile(String),
+    /// A setting is out of range or conflicts with another
+    #[error("{0}")]
+    Invalid(String),
+    /// The model file `setting` points at can't be loaded
+    #[error("{path:?} (from {setting}) {problem}")]
+    ModelFile {
+        path: PathBuf,
+        setting: String,
+        problem: String,
+    },
+    #[error("invalid config {path:?}: {source}")]
+    InFile {
+        path: PathBuf,
+        source: Box<ConfigError>,
+    },
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -9,6 +54,8 @@ pub enum RecordingMode {
     #[default]
     PushToTalk,
     Toggle,
+    /// Holding the hotkey past `hybrid_hold_ms` is push-to-talk; a quick tap toggles
+    Hybrid,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
@@ -17,6 +64,175 @@ pub enum OutputMode {
     #[default]
     Clipboard,
     Keystroke,
+    /// Copy to the clipboard without pasting
+    ClipboardOnly,
+    /// Append to `output_file`
+    File,
+    /// Insert at the caret through the Accessibility API, falling back to
+    /// `accessibility_fallback`
+    Accessibility,
+}
+
+/// How `output_mode = "clipboard"` presses Cmd+V
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PasteBackend {
+    #[default]
+    Enigo,
+    /// CGEvents with the V key looked up in the current keyboard layout
+    #[serde(rename = "cgevent")]
+    CgEvent,
+}
+
+/// Where audio is transcribed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// whisper.cpp on this Mac, with `model`
+    #[default]
+    Local,
+    /// A server with an OpenAI-compatible transcription API, see `[remote]`
+    Remote,
+}
+
+/// Modifier that, held when the hotkey is released, changes what's done with
+/// that transcript: `clipboard_modifier` or `skip_return_modifier`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClipboardModifier {
+    #[default]
+    #[serde(alias = "alt")]
+    Option,
+    #[serde(alias = "ctrl")]
+    Control,
+    Shift,
+    #[serde(alias = "cmd")]
+    Command,
+    /// No override
+    Off,
+}
+
+impl ClipboardModifier {
+    /// The same key in a hotkey's modifiers; `None` for `Off`
+    pub fn hotkey_modifier(self) -> Option<Modifiers> {
+        match self {
+            Self::Option => Some(Modifiers::ALT),
+            Self::Control => Some(Modifiers::CONTROL),
+            Self::Shift => Some(Modifiers::SHIFT),
+            Self::Command => Some(Modifiers::SUPER),
+            Self::Off => None,
+        }
+    }
+}
+
+/// What a double press of the dictation hotkey does, on top of what each
+/// press does anyway
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DoublePressAction {
+    /// Nothing; a double press is two single presses
+    #[default]
+    Off,
+    /// Press Return after the recording's transcript
+    SendReturn,
+    /// Leave the recording's transcript on the clipboard
+    ClipboardOnly,
+    /// Throw the recording away and output the last transcript again
+    Repaste,
+}
+
+/// What to do with a transcript when a secure text field (e.g. a password
+/// box) has keyboard focus
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SecureInputBehavior {
+    /// Copy the text to the clipboard without pasting or typing it
+    #[default]
+    ClipboardOnly,
+    /// Don't output anything
+    Block,
+    /// Output as usual
+    Ignore,
+}
+
+/// Added after each transcript when it's output
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Append {
+    #[default]
+    None,
+    Space,
+    Newline,
+}
+
+/// Recasing applied to each transcript, see `postprocess::case`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseTransform {
+    /// As whisper wrote it
+    #[default]
+    None,
+    Sentence,
+    Title,
+    Lower,
+    Upper,
+}
+
+/// What spell mode does with a word that stands for no character
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownWords {
+    /// Output it as said, between the spelled characters
+    #[default]
+    Keep,
+    /// Output nothing for that utterance
+    Fail,
+}
+
+/// Which request to drop when the transcription queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOverflow {
+    /// Keep what is already queued and drop the new recording
+    #[default]
+    DropNewest,
+    /// Make room by dropping the oldest queued recording
+    DropOldest,
+}
+
+/// Most detailed messages logged when `RUST_LOG` isn't set
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<LogLevel> for log::LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => Self::Error,
+            LogLevel::Warn => Self::Warn,
+            LogLevel::Info => Self::Info,
+            LogLevel::Debug => Self::Debug,
+            LogLevel::Trace => Self::Trace,
+        }
+    }
+}
+
+/// Speech detector used by VAD
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum VadEngine {
+    /// Silero neural detector, falls back to `Energy` if it cannot be built
+    #[default]
+    Silero,
+    /// Signal energy and zero-crossing rate; much cheaper on long recordings
+    Energy,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -25,9 +241,30 @@ pub enum WhisperModel {
     TinyEn,
     BaseEn,
     SmallEn,
+    MediumEn,
     Tiny,
     Base,
     Small,
+    Medium,
+    LargeV3,
+    /// Large-v3 with a much smaller decoder; close to large quality at medium speed
+    LargeV3Turbo,
+    // Quantized files: a fraction of the size and faster on Apple Silicon,
+    // for a small accuracy cost
+    #[serde(rename = "medium_en_q5_0")]
+    MediumEnQ5,
+    #[serde(rename = "medium_en_q8_0")]
+    MediumEnQ8,
+    #[serde(rename = "medium_q5_0")]
+    MediumQ5,
+    #[serde(rename = "medium_q8_0")]
+    MediumQ8,
+    #[serde(rename = "large_v3_q5_0")]
+    LargeV3Q5,
+    #[serde(rename = "large_v3_turbo_q5_0")]
+    LargeV3TurboQ5,
+    #[serde(rename = "large_v3_turbo_q8_0")]
+    LargeV3TurboQ8,
 }
 
 impl Default for WhisperModel {
@@ -37,14 +274,62 @@ impl Default for WhisperModel {
 }
 
 impl WhisperModel {
+    pub const ALL: [WhisperModel; 17] = [
+        Self::TinyEn,
+        Self::BaseEn,
+        Self::SmallEn,
+        Self::MediumEn,
+        Self::Tiny,
+        Self::Base,
+        Self::Small,
+        Self::Medium,
+        Self::LargeV3,
+        Self::LargeV3Turbo,
+        Self::MediumEnQ5,
+        Self::MediumEnQ8,
+        Self::MediumQ5,
+        Self::MediumQ8,
+        Self::LargeV3Q5,
+        Self::LargeV3TurboQ5,
+        Self::LargeV3TurboQ8,
+    ];
+
     pub fn filename(&self) -> &'static str {
         match self {
             Self::TinyEn => "ggml-tiny.en.bin",
             Self::BaseEn => "ggml-base.en.bin",
             Self::SmallEn => "ggml-small.en.bin",
+            Self::MediumEn => "ggml-medium.en.bin",
             Self::Tiny => "ggml-tiny.bin",
             Self::Base => "ggml-base.bin",
             Self::Small => "ggml-small.bin",
+            Self::Medium => "ggml-medium.bin",
+            Self::LargeV3 => "ggml-large-v3.bin",
+            Self::LargeV3Turbo => "ggml-large-v3-turbo.bin",
+            Self::MediumEnQ5 => "ggml-medium.en-q5_0.bin",
+            Self::MediumEnQ8 => "ggml-medium.en-q8_0.bin",
+            Self::MediumQ5 => "ggml-medium-q5_0.bin",
+            Self::MediumQ8 => "ggml-medium-q8_0.bin",
+            Self::LargeV3Q5 => "ggml-large-v3-q5_0.bin",
+            Self::LargeV3TurboQ5 => "ggml-large-v3-turbo-q5_0.bin",
+            Self::LargeV3TurboQ8 => "ggml-large-v3-turbo-q8_0.bin",
+        }
+    }
+
+    /// Rough download size in megabytes
+    pub fn approx_size_mb(&self) -> u64 {
+        match self {
+            Self::TinyEn | Self::Tiny => 75,
+            Self::BaseEn | Self::Base => 142,
+            Self::SmallEn | Self::Small => 466,
+            Self::MediumEn | Self::Medium => 1533,
+            Self::LargeV3 => 3095,
+            Self::LargeV3Turbo => 1624,
+            Self::MediumEnQ5 | Self::MediumQ5 => 539,
+            Self::MediumEnQ8 | Self::MediumQ8 => 823,
+            Self::LargeV3Q5 => 1081,
+            Self::LargeV3TurboQ5 => 574,
+            Self::LargeV3TurboQ8 => 874,
         }
     }
 
@@ -58,83 +343,2263 @@ impl WhisperModel {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Layout version, see `migrations`
+    #[serde(default = "default_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub backend: Backend,
     #[serde(default)]
     pub model: WhisperModel,
+    /// Threads whisper uses (unset = half the logical cores)
+    #[serde(default)]
+    pub whisper_threads: Option<usize>,
+    /// Any ggml/gguf Whisper file to load instead of `model`. Relative paths
+    /// are taken from the config directory and `~` is expanded.
+    #[serde(default, alias = "model_path_override")]
+    pub model_path: Option<PathBuf>,
+    /// A bigger model `redo_hotkey` and the tray's Redo item run the last
+    /// recording through again, loaded the first time it's used (unset =
+    /// disabled)
+    #[serde(default)]
+    pub redo_model: Option<WhisperModel>,
+    /// Take the last transcript back with backspaces before typing the redo,
+    /// rather than only copying the redo to the clipboard
+    #[serde(default)]
+    pub redo_replaces: bool,
+    /// Transcribe a clip once the model loads so the first recording isn't slow
+    #[serde(default = "default_warmup")]
+    pub warmup: bool,
+    /// WAV file the warmup transcribes instead of the built-in clip
+    #[serde(default)]
+    pub warmup_audio: Option<PathBuf>,
     #[serde(default = "default_idle_timeout")]
     pub idle_timeout_secs: u64,
     #[serde(default)]
     pub recording_mode: RecordingMode,
+    /// In hybrid mode, presses at least this long stop recording on release
+    #[serde(default = "default_hybrid_hold_ms")]
+    pub hybrid_hold_ms: u64,
+    /// Toggle recordings still running after this many seconds are submitted (0 = never)
+    #[serde(default = "default_toggle_max_secs")]
+    pub toggle_max_secs: u64,
+    /// In push-to-talk mode, shorter presses are treated as accidental and discarded
+    #[serde(default = "default_min_hold_ms")]
+    pub min_hold_ms: u64,
+    #[serde(default)]
+    pub double_press_action: DoublePressAction,
+    /// Longest gap between two presses of the hotkey that makes a double press
+    #[serde(default = "default_double_press_ms")]
+    pub double_press_ms: u64,
+    /// Recordings (and speech left after VAD) shorter than this aren't transcribed
+    #[serde(default = "default_min_recording_ms")]
+    pub min_recording_ms: u64,
     #[serde(default)]
     pub output_mode: OutputMode,
-    #[serde(default = "default_vad_enabled")]
-    pub vad_enabled: bool,
-    #[serde(default = "default_vad_threshold")]
-    pub vad_threshold: f32,
+    /// Applies while macOS secure input is on
+    #[serde(default)]
+    pub secure_input_behavior: SecureInputBehavior,
+    #[serde(default)]
+    pub paste_backend: PasteBackend,
+    #[serde(default)]
+    pub append: Append,
+    /// Where `output_mode = "file"` appends transcripts
+    #[serde(default)]
+    pub output_file: Option<PathBuf>,
+    /// With `output_mode = "file"`, also copy each transcript to the clipboard
+    #[serde(default)]
+    pub also_copy: bool,
+    /// Used when `output_mode = "accessibility"` can't insert into the
+    /// focused element; `clipboard` or `keystroke`
+    #[serde(default)]
+    pub accessibility_fallback: OutputMode,
+    /// Held when the hotkey is released, copies instead of `output_mode`
+    #[serde(default)]
+    pub clipboard_modifier: ClipboardModifier,
+    /// Press Return after pasting or typing a transcript
+    #[serde(default)]
+    pub send_return_after: bool,
+    /// Held when the hotkey is released, skips that Return
+    #[serde(default = "default_skip_return_modifier")]
+    pub skip_return_modifier: ClipboardModifier,
+    /// Pause before that Return, so the paste lands first
+    #[serde(default = "default_return_delay_ms")]
+    pub return_delay_ms: u64,
+    /// Saying just "scratch that" deletes the transcript typed or pasted last
+    #[serde(default)]
+    pub enable_scratch_that: bool,
+    /// Longer transcripts aren't deleted, in case they weren't the last thing typed
+    #[serde(default = "default_scratch_that_max_chars")]
+    pub scratch_that_max_chars: usize,
+    #[serde(default = "default_hotkey")]
+    pub hotkey: String,
+    #[serde(default = "default_repaste_hotkey")]
+    pub repaste_hotkey: String,
+    /// Abandons the recording or transcription in progress (unset = disabled)
+    #[serde(default)]
+    pub cancel_hotkey: Option<String>,
+    /// `[[hotkeys]]` entries: more dictation hotkeys, each transcribing in
+    /// its own language
+    #[serde(default)]
+    pub hotkeys: Vec<LanguageHotkey>,
+    /// Dictates in spell mode, e.g. "alpha bravo seven" -> "ab7" (unset = disabled)
+    #[serde(default)]
+    pub spell_hotkey: Option<String>,
+    /// Pauses the recording in progress and resumes it, leaving out what's
+    /// said in between (unset = disabled)
+    #[serde(default)]
+    pub pause_hotkey: Option<String>,
+    /// Transcribes the last recording again with `redo_model` (unset = disabled)
+    #[serde(default)]
+    pub redo_hotkey: Option<String>,
+    /// Mouse button (counting from 1 = left) that also works as the dictation
+    /// hotkey, e.g. 4 for the first side button. Must be 3 or higher.
+    #[serde(default)]
+    pub mouse_button: Option<u32>,
+    /// Keep `mouse_button` clicks from reaching other apps
+    #[serde(default)]
+    pub swallow_mouse_button: bool,
+    /// Name (or case-insensitive substring) of the input device to use.
+    /// Falls back to the system default when unset or not found.
+    #[serde(default)]
+    pub input_device: Option<String>,
+    /// Recording stops automatically after this many seconds (0 = no limit)
+    #[serde(default = "default_max_recording_secs")]
+    pub max_recording_secs: u64,
+    /// Audio kept from before the hotkey press while the stream is warm (0 = off)
+    #[serde(default = "default_preroll_ms")]
+    pub preroll_ms: u32,
+    /// Play a short sound when recording starts and stops
+    #[serde(default)]
+    pub sound_feedback: bool,
+    /// Cue volume relative to the system volume (0.0 - 1.0)
+    #[serde(default = "default_sound_volume")]
+    pub sound_volume: f32,
+    /// Briefly show the start of each transcript above the indicator
+    #[serde(default = "default_show_transcript_toast")]
+    pub show_transcript_toast: bool,
+    /// A left click on the menu bar icon starts or stops recording, leaving
+    /// the menu to a right or Control click
+    #[serde(default = "default_tray_click_toggles")]
+    pub tray_click_toggles: bool,
+    /// Post a macOS notification for each finished or failed transcription
+    #[serde(default)]
+    pub notifications: bool,
+    /// Save the audio sent to whisper as `debug/last_recording.wav`
+    #[serde(default)]
+    pub debug_save_audio: bool,
+    /// Log each dictation's timing summary at info level rather than debug
+    #[serde(default)]
+    pub log_timings: bool,
+    #[serde(default)]
+    pub log_level: LogLevel,
+    /// Also write the log to `logs/dictation.log`, rotated at 2 MB
+    #[serde(default)]
+    pub log_to_file: bool,
+    /// Leave transcript text out of the log file
+    #[serde(default)]
+    pub redact_transcripts: bool,
+    /// Keep all-time totals in `stats.toml`
+    #[serde(default = "default_save_stats")]
+    pub save_stats: bool,
+    /// Nothing from a dictation is written to disk, whatever the settings
+    /// above say; see `effective`
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// Scale post-VAD audio so its peak reaches `normalize_target_db`
+    #[serde(default)]
+    pub normalize_audio: bool,
+    #[serde(default = "default_normalize_target_db")]
+    pub normalize_target_db: f32,
+    /// Recordings with an RMS level under this (dBFS) are taken for a muted
+    /// mic and not transcribed (`-inf` = never)
+    #[serde(default = "default_silence_floor_db")]
+    pub silence_floor_db: f32,
+    /// Give up on a transcription after this many seconds (0 = never). Long
+    /// recordings get more time, see `Config::transcription_timeout`.
+    #[serde(default = "default_transcription_timeout_secs")]
+    pub transcription_timeout_secs: u64,
+    /// Reload the model after this many transcription errors in a row (0 = never)
+    #[serde(default = "default_reload_after_failures")]
+    pub reload_after_failures: u32,
+    /// Whisper segments with a lower mean token probability are dropped (0 = keep all)
+    #[serde(default)]
+    pub min_segment_confidence: f32,
+    /// Give whisper the previous utterances as context for the next one
+    #[serde(default)]
+    pub use_context: bool,
+    /// Context is dropped after this many seconds without dictating
+    #[serde(default = "default_context_reset_secs")]
+    pub context_reset_secs: u64,
+    /// Most characters of earlier text passed as context
+    #[serde(default = "default_context_max_chars")]
+    pub context_max_chars: usize,
+    /// Tidy whisper's whitespace before anything else (false = raw output)
+    #[serde(default = "default_normalize_whitespace")]
+    pub normalize_whitespace: bool,
+    /// Strip `filler_words` from transcripts
+    #[serde(default)]
+    pub remove_fillers: bool,
+    /// Words or phrases removed by `remove_fillers`
+    #[serde(default = "default_filler_words")]
+    pub filler_words: Vec<String>,
+    /// Shell command the transcript is piped through before output
+    #[serde(default)]
+    pub postprocess_command: Option<String>,
+    /// `postprocess_command` is killed after this long and the original text used
+    #[serde(default = "default_postprocess_timeout_secs")]
+    pub postprocess_timeout_secs: u64,
+    /// Recasing of each transcript, after `postprocess_command`; a spoken
+    /// prefix like "title case" picks another for one utterance
+    #[serde(default)]
+    pub case_transform: CaseTransform,
+    /// Recordings waiting for transcription beyond this many are dropped
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+    #[serde(default)]
+    pub queue_overflow: QueueOverflow,
+    /// Recordings transcribed at once, each with its own whisper state (at
+    /// most `MAX_WORKER_PARALLELISM`)
+    #[serde(default = "default_worker_parallelism")]
+    pub worker_parallelism: usize,
+    /// Transcribe periodically while recording and show the running text
+    /// above the indicator
+    #[serde(default)]
+    pub streaming_preview: bool,
+    /// Free the model's memory while the Mac sleeps; it reloads on wake
+    #[serde(default)]
+    pub unload_model_on_sleep: bool,
+    /// Serve `GET /status` as JSON on 127.0.0.1 at this port, for status bars
+    #[serde(default)]
+    pub http_status_port: Option<u16>,
+    #[serde(default)]
+    pub vad: VadConfig,
+    #[serde(default)]
+    pub preprocess: PreprocessConfig,
+    #[serde(default)]
+    pub indicator: IndicatorConfig,
+    #[serde(default)]
+    pub hallucination: HallucinationConfig,
+    #[serde(default)]
+    pub spell: SpellConfig,
+    #[serde(default)]
+    pub remote: RemoteConfig,
+    /// `[app_overrides."<bundle id>"]` tables, settings for one app
+    #[serde(default)]
+    pub app_overrides: BTreeMap<String, AppOverride>,
+    /// Name of the `profiles` entry applied on top of the settings above
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    /// `[profiles.<name>]` tables, each overriding any of the settings above
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Table>,
 }
 
-fn default_idle_timeout() -> u64 {
-    300
+/// Settings that apply while dictating into one app; unset ones follow the
+/// global setting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub send_return_after: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case_transform: Option<CaseTransform>,
 }
 
-fn default_vad_enabled() -> bool {
-    true
+/// A dictation hotkey that works like `hotkey`, but transcribes in `language`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageHotkey {
+    /// Written like `hotkey`, e.g. "Cmd+Shift+G"
+    pub key: String,
+    /// Whisper's code for the language, e.g. "de"
+    pub language: String,
 }
 
-fn default_vad_threshold() -> f32 {
-    0.5
+/// Voice activity detection used to trim silence before transcription
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VadConfig {
+    pub enabled: bool,
+    pub engine: VadEngine,
+    /// Speech probability above which a chunk counts as speech (0.0 - 1.0)
+    pub threshold: f32,
+    /// Detected speech shorter than this is discarded
+    pub min_speech_ms: u32,
+    /// Audio kept on either side of detected speech
+    pub padding_ms: u32,
+    /// Pauses shorter than this stay inside one speech segment; longer ones are cut out
+    pub max_gap_ms: u32,
+    /// Silence inserted between kept speech segments so whisper can tell them apart
+    pub join_silence_ms: u32,
 }
 
-impl Default for Config {
+impl Default for VadConfig {
     fn default() -> Self {
         Self {
-            model: WhisperModel::default(),
-            idle_timeout_secs: default_idle_timeout(),
-            recording_mode: RecordingMode::default(),
-            output_mode: OutputMode::default(),
-            vad_enabled: default_vad_enabled(),
-            vad_threshold: default_vad_threshold(),
+            enabled: default_vad_enabled(),
+            engine: VadEngine::default(),
+            threshold: default_vad_threshold(),
+            min_speech_ms: 100,
+            padding_ms: 50,
+            max_gap_ms: 500,
+            join_silence_ms: 100,
         }
     }
 }
 
-impl Config {
-    pub fn load() -> Result<Self> {
-        let config_path = Self::config_path();
-        if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            Ok(toml::from_str(&content)?)
-        } else {
-            let config = Config::default();
-            config.save()?;
-            Ok(config)
+/// Heuristics that drop text whisper likely made up for near-silent audio
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HallucinationConfig {
+    pub enabled: bool,
+    /// A transcript that is one phrase said more times than this is dropped
+    pub max_repeats: usize,
+    /// Transcripts that are exactly one of these (ignoring case and
+    /// punctuation) are dropped; a trailing `*` matches any continuation
+    pub blocklist: Vec<String>,
+    /// More non-space characters than this per second of audio is dropped
+    pub max_chars_per_sec: f32,
+}
+
+impl Default for HallucinationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_repeats: 2,
+            blocklist: [
+                "thanks for watching",
+                "thank you for watching",
+                "thank you so much for watching",
+                "please subscribe",
+                "please subscribe to my channel",
+                "don't forget to like and subscribe",
+                "subtitles by the *",
+                "transcription by castingwords",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            max_chars_per_sec: 40.0,
         }
     }
+}
 
-    pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_path();
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)?;
+/// Spell mode, which turns words like "alpha" or "seven" into the characters
+/// they stand for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpellConfig {
+    /// Starting an utterance with these words spells out the rest ("" = only
+    /// `spell_hotkey` does)
+    pub prefix: String,
+    pub unknown_words: UnknownWords,
+}
+
+impl Default for SpellConfig {
+    fn default() -> Self {
+        Self {
+            prefix: "spell".to_string(),
+            unknown_words: UnknownWords::default(),
         }
-        let content = toml::to_string_pretty(self)?;
-        fs::write(&config_path, content)?;
-        Ok(())
     }
+}
 
-    pub fn config_dir() -> PathBuf {
-        dirs::home_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join(".dictation")
+/// Server used with `backend = "remote"`. Its `Debug` leaves out the API key.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Full URL of an OpenAI-compatible `/v1/audio/transcriptions` endpoint
+    pub endpoint: String,
+    /// Sent as the request's `model` field
+    pub model: String,
+    /// Bearer token; `DICTATION_API_KEY` is used when unset
+    pub api_key: Option<String>,
+    /// Longest a request may take before it fails
+    pub timeout_secs: u64,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            model: "whisper-1".to_string(),
+            api_key: None,
+            timeout_secs: 30,
+        }
     }
+}
 
-    pub fn config_path() -> PathBuf {
-        Self::config_dir().join("config.toml")
+impl std::fmt::Debug for RemoteConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteConfig")
+            .field("endpoint", &self.endpoint)
+            .field("model", &self.model)
+            .field("api_key", &self.api_key.as_ref().map(|_| "<redacted>"))
+            .field("timeout_secs", &self.timeout_secs)
+            .finish()
     }
+}
 
-    pub fn models_dir() -> PathBuf {
-        Self::config_dir().join("models")
+impl RemoteConfig {
+    /// `api_key`, or else the `DICTATION_API_KEY` environment variable
+    pub fn api_key(&self) -> Option<String> {
+        self.api_key
+            .clone()
+            .or_else(|| std::env::var("DICTATION_API_KEY").ok())
+            .filter(|key| !key.is_empty())
     }
+}
 
-    pub fn model_path(&self) -> PathBuf {
-        Self::models_dir().join(self.model.filename())
+/// Seconds of transcription allowed per second of audio before timing out
+const TIMEOUT_PER_AUDIO_SEC: f32 = 2.0;
+
+/// Upper bound for `vad.padding_ms`
+const MAX_VAD_PADDING_MS: u32 = 2000;
+
+/// Upper bound for `worker_parallelism`. Every extra whisper state has its
+/// own KV caches and compute buffers, measured with the bundled whisper.cpp
+/// at about 190 MB for tiny, 230 MB for base, 330 MB for small, 510 MB for
+/// medium, 410 MB for large-v3-turbo and 700 MB for large-v3 (quantized or
+/// not); past this the runs mostly slow each other down.
+pub const MAX_WORKER_PARALLELISM: usize = 3;
+
+/// Filters applied to recorded audio before VAD. Everything is off by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreprocessConfig {
+    /// High-pass cutoff in Hz to remove fan hum and rumble (0 = disabled)
+    pub highpass_hz: f32,
+    /// Silence 10 ms windows quieter than this many dBFS (unset = disabled)
+    pub gate_threshold_db: Option<f32>,
+}
+
+/// On-screen recording indicator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndicatorConfig {
+    /// Off hides the pill, its error flashes, and the transcript toast
+    pub enabled: bool,
+    pub position: IndicatorPosition,
+    pub screen: IndicatorScreen,
+    /// Pill size in points; corner radius and glow follow the height
+    pub width: f64,
+    pub height: f64,
+    pub recording_color: HexColor,
+    pub processing_color: HexColor,
+    pub error_color: HexColor,
+    /// Pulse while recording and shimmer while processing
+    pub animate: bool,
+    /// Show the time spent recording on the pill, which grows to fit it
+    pub show_timer: bool,
+}
+
+impl Default for IndicatorConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            position: IndicatorPosition::default(),
+            screen: IndicatorScreen::default(),
+            width: 60.0,
+            height: 8.0,
+            recording_color: HexColor::rgb(0xFF, 0x4D, 0x4D),
+            processing_color: HexColor::rgb(0x00, 0xCC, 0xFF),
+            error_color: HexColor::rgb(0xFF, 0x99, 0x00),
+            animate: true,
+            show_timer: false,
+        }
+    }
+}
+
+/// Color written as `"#RRGGBB"` or `"#RRGGBBAA"`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HexColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl HexColor {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 0xFF }
+    }
+
+    /// Components scaled to 0.0 - 1.0, as (red, green, blue, alpha)
+    pub fn components(&self) -> (f64, f64, f64, f64) {
+        let unit = |c: u8| c as f64 / 255.0;
+        (unit(self.r), unit(self.g), unit(self.b), unit(self.a))
+    }
+
+    /// A darker, more see-through version of this color
+    pub fn dimmed(&self) -> HexColor {
+        HexColor {
+            r: self.r / 2,
+            g: self.g / 2,
+            b: self.b / 2,
+            a: (self.a as u16 * 3 / 5) as u8,
+        }
+    }
+
+    /// Black or white, whichever reads better on top of this color
+    pub fn contrasting(&self) -> HexColor {
+        let (r, g, b, _) = self.components();
+        if 0.299 * r + 0.587 * g + 0.114 * b > 0.6 {
+            HexColor::rgb(0, 0, 0)
+        } else {
+            HexColor::rgb(0xFF, 0xFF, 0xFF)
+        }
+    }
+}
+
+impl TryFrom<String> for HexColor {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        let invalid = || format!("invalid color '{}', expected \"#RRGGBB\" or \"#RRGGBBAA\"", s);
+        let hex = s.strip_prefix('#').ok_or_else(invalid)?;
+        if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Self {
+            r: byte(0)?,
+            g: byte(2)?,
+            b: byte(4)?,
+            a: if hex.len() == 8 { byte(6)? } else { 0xFF },
+        })
+    }
+}
+
+impl From<HexColor> for String {
+    fn from(c: HexColor) -> Self {
+        if c.a == 0xFF {
+            format!("#{:02X}{:02X}{:02X}", c.r, c.g, c.b)
+        } else {
+            format!("#{:02X}{:02X}{:02X}{:02X}", c.r, c.g, c.b, c.a)
+        }
+    }
+}
+
+/// Which display the indicator appears on, decided each time it is shown
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorScreen {
+    /// The display with the frontmost app's focused window
+    #[default]
+    Active,
+    /// The primary display (the one with the menu bar)
+    Main,
+    /// The display under the mouse cursor
+    Mouse,
+}
+
+/// Where the indicator sits within the screen's visible area (excluding
+/// Dock and menu bar). Kept on screen whatever the value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndicatorPosition {
+    /// e.g. `position = "top_center"`
+    Preset(IndicatorAnchor),
+    /// Points from the bottom-left corner, e.g. `position = { x = 40, y = 20 }`
+    Offset { x: f64, y: f64 },
+}
+
+impl Default for IndicatorPosition {
+    fn default() -> Self {
+        IndicatorPosition::Preset(IndicatorAnchor::BottomCenter)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndicatorAnchor {
+    BottomCenter,
+    BottomLeft,
+    BottomRight,
+    TopCenter,
+    TopLeft,
+    TopRight,
+}
+
+fn default_warmup() -> bool {
+    true
+}
+
+fn default_idle_timeout() -> u64 {
+    300
+}
+
+fn default_hybrid_hold_ms() -> u64 {
+    300
+}
+
+fn default_toggle_max_secs() -> u64 {
+    300
+}
+
+fn default_min_hold_ms() -> u64 {
+    150
+}
+
+fn default_double_press_ms() -> u64 {
+    400
+}
+
+fn default_min_recording_ms() -> u64 {
+    200
+}
+
+fn default_version() -> u32 {
+    CURRENT_VERSION
+}
+
+fn default_vad_enabled() -> bool {
+    true
+}
+
+fn default_vad_threshold() -> f32 {
+    0.5
+}
+
+fn default_max_recording_secs() -> u64 {
+    120
+}
+
+fn default_preroll_ms() -> u32 {
+    500
+}
+
+fn default_transcription_timeout_secs() -> u64 {
+    30
+}
+
+fn default_reload_after_failures() -> u32 {
+    3
+}
+
+fn default_context_reset_secs() -> u64 {
+    60
+}
+
+fn default_context_max_chars() -> usize {
+    400
+}
+
+fn default_filler_words() -> Vec<String> {
+    ["um", "umm", "uh", "uhh", "er", "erm", "hmm", "you know"]
+        .iter()
+        .map(|word| word.to_string())
+        .collect()
+}
+
+fn default_postprocess_timeout_secs() -> u64 {
+    5
+}
+
+fn default_max_queue() -> usize {
+    4
+}
+
+fn default_worker_parallelism() -> usize {
+    1
+}
+
+fn default_show_transcript_toast() -> bool {
+    true
+}
+
+fn default_tray_click_toggles() -> bool {
+    true
+}
+
+fn default_save_stats() -> bool {
+    true
+}
+
+fn default_sound_volume() -> f32 {
+    0.5
+}
+
+fn default_normalize_target_db() -> f32 {
+    -3.0
+}
+
+fn default_silence_floor_db() -> f32 {
+    -60.0
+}
+
+fn default_hotkey() -> String {
+    "Cmd+Shift+D".to_string()
+}
+
+fn default_skip_return_modifier() -> ClipboardModifier {
+    ClipboardModifier::Control
+}
+
+fn default_return_delay_ms() -> u64 {
+    100
+}
+
+fn default_normalize_whitespace() -> bool {
+    true
+}
+
+fn default_scratch_that_max_chars() -> usize {
+    500
+}
+
+fn default_repaste_hotkey() -> String {
+    "Cmd+Shift+R".to_string()
+}
+
+/// Values given on the command line; they win over the config file for
+/// this run only and are never saved
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    pub model: Option<WhisperModel>,
+    pub output_mode: Option<OutputMode>,
+    pub recording_mode: Option<RecordingMode>,
+    pub hotkey: Option<String>,
+    pub input_device: Option<String>,
+    pub vad_enabled: Option<bool>,
+    pub vad_engine: Option<VadEngine>,
+    pub vad_threshold: Option<f32>,
+    pub max_recording_secs: Option<u64>,
+    pub debug_save_audio: Option<bool>,
+    pub log_timings: Option<bool>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            backend: Backend::default(),
+            model: WhisperModel::default(),
+            whisper_threads: None,
+            model_path: None,
+            redo_model: None,
+            redo_replaces: false,
+            warmup: true,
+            warmup_audio: None,
+            idle_timeout_secs: default_idle_timeout(),
+            recording_mode: RecordingMode::default(),
+            hybrid_hold_ms: default_hybrid_hold_ms(),
+            toggle_max_secs: default_toggle_max_secs(),
+            min_hold_ms: default_min_hold_ms(),
+            double_press_action: DoublePressAction::default(),
+            double_press_ms: default_double_press_ms(),
+            min_recording_ms: default_min_recording_ms(),
+            output_mode: OutputMode::default(),
+            paste_backend: PasteBackend::default(),
+            append: Append::default(),
+            output_file: None,
+            also_copy: false,
+            accessibility_fallback: OutputMode::default(),
+            clipboard_modifier: ClipboardModifier::default(),
+            send_return_after: false,
+            skip_return_modifier: default_skip_return_modifier(),
+            return_delay_ms: default_return_delay_ms(),
+            enable_scratch_that: false,
+            scratch_that_max_chars: default_scratch_that_max_chars(),
+            secure_input_behavior: SecureInputBehavior::default(),
+            hotkey: default_hotkey(),
+            repaste_hotkey: default_repaste_hotkey(),
+            cancel_hotkey: None,
+            hotkeys: Vec::new(),
+            spell_hotkey: None,
+            pause_hotkey: None,
+            redo_hotkey: None,
+            mouse_button: None,
+            swallow_mouse_button: false,
+            input_device: None,
+            max_recording_secs: default_max_recording_secs(),
+            preroll_ms: default_preroll_ms(),
+            sound_feedback: false,
+            sound_volume: default_sound_volume(),
+            show_transcript_toast: default_show_transcript_toast(),
+            tray_click_toggles: default_tray_click_toggles(),
+            notifications: false,
+            debug_save_audio: false,
+            log_timings: false,
+            log_level: LogLevel::default(),
+            log_to_file: false,
+            redact_transcripts: false,
+            save_stats: default_save_stats(),
+            privacy_mode: false,
+            normalize_audio: false,
+            normalize_target_db: default_normalize_target_db(),
+            silence_floor_db: default_silence_floor_db(),
+            transcription_timeout_secs: default_transcription_timeout_secs(),
+            reload_after_failures: default_reload_after_failures(),
+            min_segment_confidence: 0.0,
+            use_context: false,
+            context_reset_secs: default_context_reset_secs(),
+            context_max_chars: default_context_max_chars(),
+            normalize_whitespace: default_normalize_whitespace(),
+            remove_fillers: false,
+            filler_words: default_filler_words(),
+            postprocess_command: None,
+            postprocess_timeout_secs: default_postprocess_timeout_secs(),
+            case_transform: CaseTransform::default(),
+            max_queue: default_max_queue(),
+            queue_overflow: QueueOverflow::default(),
+            worker_parallelism: default_worker_parallelism(),
+            streaming_preview: false,
+            unload_model_on_sleep: false,
+            http_status_port: None,
+            vad: VadConfig::default(),
+            preprocess: PreprocessConfig::default(),
+            indicator: IndicatorConfig::default(),
+            hallucination: HallucinationConfig::default(),
+            spell: SpellConfig::default(),
+            remote: RemoteConfig::default(),
+            app_overrides: BTreeMap::new(),
+            active_profile: None,
+            profiles: BTreeMap::new(),
+        }
+    }
+}
+
+/// Lay `profile` over `base`: tables merge key by key, anything else replaces
+/// the base value, and keys the profile doesn't mention keep the base value
+fn merge_profile(base: &mut Table, profile: Table) -> Result<()> {
+    for key in ["version", "active_profile", "profiles"] {
+        if profile.contains_key(key) {
+            return Err(ConfigError::Profile(format!("`{}` can't be set inside a profile", key)));
+        }
+    }
+    merge_tables(base, profile);
+    Ok(())
+}
+
+fn merge_tables(base: &mut Table, overlay: Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge_tables(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Folder under Application Support that holds the config, models and stats
+const APP_DIR_NAME: &str = "Dictation";
+
+/// Where releases before the move to Application Support kept everything
+fn legacy_config_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(".dictation")
+}
+
+/// `preferred` (under Application Support), or the `legacy` folder
+/// (`~/.dictation`) while only that one exists
+fn resolve_config_dir(legacy: PathBuf, preferred: PathBuf) -> PathBuf {
+    if legacy.is_dir() && !preferred.exists() {
+        legacy
+    } else {
+        preferred
+    }
+}
+
+/// Move the `legacy` folder to `preferred` when only that one exists. Models
+/// can be gigabytes, so they're moved rather than copied; if the move fails
+/// `legacy` stays in use.
+fn migrate_config_dir(legacy: &Path, preferred: &Path) {
+    if !legacy.is_dir() {
+        return;
+    }
+    if preferred.exists() {
+        log::warn!("Using {:?}; {:?} is no longer read and can be removed", preferred, legacy);
+        return;
+    }
+    let moved = match preferred.parent() {
+        Some(parent) => fs::create_dir_all(parent).and_then(|_| fs::rename(legacy, preferred)),
+        None => fs::rename(legacy, preferred),
+    };
+    match moved {
+        Ok(()) => log::info!("Moved {:?} to {:?}", legacy, preferred),
+        Err(e) => log::warn!("Couldn't move {:?} to {:?} ({}), still using it", legacy, preferred, e),
+    }
+}
+
+/// Expand a leading `~` and anchor relative paths at `base`
+fn resolve_path(path: &Path, base: &Path) -> PathBuf {
+    let path = match path.strip_prefix("~") {
+        Ok(rest) => dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")).join(rest),
+        Err(_) => path.to_path_buf(),
+    };
+    if path.is_absolute() {
+        path
+    } else {
+        base.join(path)
+    }
+}
+
+/// Parse TOML text without interpreting it as a config yet
+fn parse_table(content: &str) -> Result<Table> {
+    Ok(content.parse::<Table>()?)
+}
+
+impl Config {
+    /// Load `path` (writing the defaults there if it doesn't exist yet), then
+    /// apply `overrides` on top without saving them. A file in an older layout
+    /// is upgraded in place, keeping the original as `config.toml.bak`.
+    pub fn load_with_overrides(path: &Path, overrides: &Overrides) -> Result<Self> {
+        let mut config = if path.exists() {
+            let content = fs::read_to_string(path)?;
+            Self::from_toml(&content)
+                .and_then(|config| Self::upgrade_file(path, &content).map(|_| config))
+                .map_err(|e| ConfigError::InFile {
+                    path: path.to_path_buf(),
+                    source: Box::new(e),
+                })?
+        } else {
+            let config = Config::default();
+            config.save_to(path)?;
+            config
+        };
+        config.apply_overrides(overrides)?;
+        Ok(config)
+    }
+
+    pub fn apply_overrides(&mut self, overrides: &Overrides) -> Result<()> {
+        if let Some(model) = overrides.model {
+            self.model = model;
+        }
+        if let Some(output_mode) = overrides.output_mode {
+            self.output_mode = output_mode;
+        }
+        if let Some(recording_mode) = overrides.recording_mode {
+            self.recording_mode = recording_mode;
+        }
+        if let Some(hotkey) = &overrides.hotkey {
+            self.hotkey = hotkey.clone();
+        }
+        if let Some(input_device) = &overrides.input_device {
+            self.input_device = Some(input_device.clone());
+        }
+        if let Some(enabled) = overrides.vad_enabled {
+            self.vad.enabled = enabled;
+        }
+        if let Some(engine) = overrides.vad_engine {
+            self.vad.engine = engine;
+        }
+        if let Some(threshold) = overrides.vad_threshold {
+            self.vad.threshold = threshold;
+        }
+        if let Some(secs) = overrides.max_recording_secs {
+            self.max_recording_secs = secs;
+        }
+        if let Some(save) = overrides.debug_save_audio {
+            self.debug_save_audio = save;
+        }
+        if let Some(log) = overrides.log_timings {
+            self.log_timings = log;
+        }
+        self.validate()
+    }
+
+    /// Make `name` the active profile in the file at `path` and load the result
+    pub fn switch_profile(path: &Path, name: &str, overrides: &Overrides) -> Result<Self> {
+        let mut doc = parse_table(&fs::read_to_string(path)?)?;
+        doc.insert("active_profile".to_string(), Value::String(name.to_string()));
+        let content = toml::to_string_pretty(&doc)?;
+        // Check before writing so a bad profile leaves the file alone
+        Self::from_toml(&content)?;
+        fs::write(path, content)?;
+        Self::load_with_overrides(path, overrides)
+    }
+
+    /// Turn `privacy_mode` on or off in the file at `path` and load the result
+    pub fn set_privacy_mode(path: &Path, on: bool, overrides: &Overrides) -> Result<Self> {
+        let mut doc = parse_table(&fs::read_to_string(path)?)?;
+        doc.insert("privacy_mode".to_string(), Value::Boolean(on));
+        let content = toml::to_string_pretty(&doc)?;
+        // Check before writing so a file that no longer loads is left alone
+        Self::from_toml(&content)?;
+        fs::write(path, content)?;
+        Self::load_with_overrides(path, overrides)
+    }
+
+    /// The settings to run with. `privacy_mode` wins over everything that
+    /// would put audio or transcripts on disk, or hand them to another
+    /// program: debug audio, stats, transcripts in the log file, the
+    /// `postprocess_command` hook, and `output_mode = "file"` (which copies
+    /// to the clipboard instead).
+    pub fn effective(&self) -> Config {
+        let mut config = self.clone();
+        if config.privacy_mode {
+            config.debug_save_audio = false;
+            config.save_stats = false;
+            config.redact_transcripts = true;
+            config.postprocess_command = None;
+            if config.output_mode == OutputMode::File {
+                config.output_mode = OutputMode::ClipboardOnly;
+            }
+        }
+        config
+    }
+
+    /// Set `model` in the file at `path`, leaving the rest of it as written.
+    /// Drops `model_path`, which would otherwise win over the new model.
+    pub fn set_model(path: &Path, model: WhisperModel) -> Result<()> {
+        let mut doc = parse_table(&fs::read_to_string(path)?)?;
+        doc.insert("model".to_string(), Value::try_from(model)?);
+        doc.remove("model_path");
+        doc.remove("model_path_override");
+        fs::write(path, toml::to_string_pretty(&doc)?)?;
+        Ok(())
+    }
+
+    /// Rewrite `path` in the current layout if `content` (its text) is older,
+    /// saving the original next to it
+    fn upgrade_file(path: &Path, content: &str) -> Result<()> {
+        let mut doc = parse_table(content)?;
+        if migrations::migrate(&mut doc)? {
+            let backup = path.with_extension("toml.bak");
+            fs::write(&backup, content)?;
+            fs::write(path, toml::to_string_pretty(&doc)?)?;
+            log::info!(
+                "Upgraded {:?} to config version {}, original saved as {:?}",
+                path,
+                CURRENT_VERSION,
+                backup
+            );
+        }
+        Ok(())
+    }
+
+    /// Parse a config document, upgrading older layouts and validating ranges.
+    pub fn from_toml(content: &str) -> Result<Self> {
+        let mut doc = parse_table(content)?;
+        migrations::migrate(&mut doc)?;
+        Self::from_table(doc)
+    }
+
+    /// Deserialize an already migrated document, merging in its active
+    /// profile, and validate it
+    fn from_table(mut doc: Table) -> Result<Self> {
+        if let Some(name) = doc.get("active_profile").and_then(Value::as_str).map(str::to_owned) {
+            let profile = doc
+                .get("profiles")
+                .and_then(|profiles| profiles.get(&name))
+                .and_then(Value::as_table)
+                .cloned()
+                .ok_or_else(|| {
+                    ConfigError::Profile(format!("active_profile '{}' has no [profiles.{}] table", name, name))
+                })?;
+            merge_profile(&mut doc, profile)?;
+        }
+        let config: Config = Value::Table(doc).try_into()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.output_mode == OutputMode::File && self.output_file.is_none() {
+            return Err(ConfigError::Invalid("output_mode = \"file\" needs output_file to be set".to_string()));
+        }
+        if !matches!(self.accessibility_fallback, OutputMode::Clipboard | OutputMode::Keystroke) {
+            return Err(ConfigError::Invalid(format!(
+                "accessibility_fallback must be \"clipboard\" or \"keystroke\", got {:?}",
+                self.accessibility_fallback
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.vad.threshold) {
+            return Err(ConfigError::Invalid(format!(
+                "vad.threshold must be between 0.0 and 1.0, got {}",
+                self.vad.threshold
+            )));
+        }
+        if self.indicator.width <= 0.0 || self.indicator.height <= 0.0 {
+            return Err(ConfigError::Invalid(format!(
+                "indicator.width and indicator.height must be positive, got {} x {}",
+                self.indicator.width,
+                self.indicator.height
+            )));
+        }
+        if let Some(button) = self.mouse_button.filter(|b| !(3..=32).contains(b)) {
+            return Err(ConfigError::Invalid(format!(
+                "mouse_button must be between 3 (middle) and 32, got {}",
+                button
+            )));
+        }
+        if let Some(threads) = self.whisper_threads {
+            let cores = num_cpus::get();
+            if !(1..=cores).contains(&threads) {
+                return Err(ConfigError::Invalid(format!(
+                    "whisper_threads must be between 1 and {} (the number of logical cores), got {}",
+                    cores,
+                    threads
+                )));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.min_segment_confidence) {
+            return Err(ConfigError::Invalid(format!(
+                "min_segment_confidence must be between 0.0 and 1.0, got {}",
+                self.min_segment_confidence
+            )));
+        }
+        if self.hallucination.max_chars_per_sec <= 0.0 {
+            return Err(ConfigError::Invalid(format!(
+                "hallucination.max_chars_per_sec must be positive, got {}",
+                self.hallucination.max_chars_per_sec
+            )));
+        }
+        if self.backend == Backend::Remote {
+            let endpoint = &self.remote.endpoint;
+            if !(endpoint.starts_with("http://") || endpoint.starts_with("https://")) {
+                return Err(ConfigError::Invalid(format!(
+                    "backend = \"remote\" needs remote.endpoint to be an http(s) URL, got {:?}",
+                    endpoint
+                )));
+            }
+            if self.remote.timeout_secs == 0 {
+                return Err(ConfigError::Invalid("remote.timeout_secs must be at least 1".to_string()));
+            }
+        }
+        for (i, entry) in self.hotkeys.iter().enumerate() {
+            let language = &entry.language;
+            if !(2..=3).contains(&language.len()) || !language.chars().all(|c| c.is_ascii_lowercase()) {
+                return Err(ConfigError::Invalid(format!(
+                    "hotkeys[{}].language must be a language code like \"de\", got {:?}",
+                    i,
+                    language
+                )));
+            }
+            if language != "en" && self.english_only_model() {
+                return Err(ConfigError::Invalid(format!(
+                    "hotkeys[{}] dictates in \"{}\", but the model {:?} only understands English; \
+                     pick a multilingual model such as \"small\"",
+                    i,
+                    language,
+                    self.model_path()
+                )));
+            }
+        }
+        if let Some((name, spec)) = self.stop_hotkey_holding(self.clipboard_modifier) {
+            return Err(ConfigError::Invalid(format!(
+                "clipboard_modifier {:?} is part of {} '{}', so every dictation it stops would only be copied; \
+                 pick another modifier",
+                self.clipboard_modifier,
+                name,
+                spec
+            )));
+        }
+        if let Some((name, spec)) = self.stop_hotkey_holding(self.skip_return_modifier) {
+            return Err(ConfigError::Invalid(format!(
+                "skip_return_modifier {:?} is part of {} '{}', so every dictation it stops would skip the Return; \
+                 pick another modifier",
+                self.skip_return_modifier,
+                name,
+                spec
+            )));
+        }
+        for (name, spec) in self.hotkey_specs() {
+            if hotkey::parse_hotkey(spec).is_ok_and(|key| hotkey::types_text(&key)) {
+                log::warn!(
+                    "{} '{}' has no modifier, so that key won't type anywhere while the app runs",
+                    name,
+                    spec
+                );
+            }
+        }
+        if self.silence_floor_db.is_nan() || self.silence_floor_db > 0.0 {
+            return Err(ConfigError::Invalid(format!(
+                "silence_floor_db must be 0 dBFS or lower, got {}",
+                self.silence_floor_db
+            )));
+        }
+        if !(1..=MAX_WORKER_PARALLELISM).contains(&self.worker_parallelism) {
+            return Err(ConfigError::Invalid(format!(
+                "worker_parallelism must be between 1 and {}, got {}",
+                MAX_WORKER_PARALLELISM,
+                self.worker_parallelism
+            )));
+        }
+        if self.http_status_port == Some(0) {
+            return Err(ConfigError::Invalid("http_status_port must be a port number from 1 to 65535".to_string()));
+        }
+        if self.vad.padding_ms >= MAX_VAD_PADDING_MS {
+            return Err(ConfigError::Invalid(format!(
+                "vad.padding_ms must be under {} ms, got {}",
+                MAX_VAD_PADDING_MS,
+                self.vad.padding_ms
+            )));
+        }
+        Ok(())
+    }
+
+    /// Each hotkey setting that's set, with its name
+    pub fn hotkey_specs(&self) -> Vec<(String, &str)> {
+        let mut hotkeys = vec![
+            ("hotkey".to_string(), self.hotkey.as_str()),
+            ("repaste_hotkey".to_string(), self.repaste_hotkey.as_str()),
+        ];
+        if let Some(cancel) = &self.cancel_hotkey {
+            hotkeys.push(("cancel_hotkey".to_string(), cancel.as_str()));
+        }
+        if let Some(spell) = &self.spell_hotkey {
+            hotkeys.push(("spell_hotkey".to_string(), spell.as_str()));
+        }
+        if let Some(pause) = &self.pause_hotkey {
+            hotkeys.push(("pause_hotkey".to_string(), pause.as_str()));
+        }
+        if let Some(redo) = &self.redo_hotkey {
+            hotkeys.push(("redo_hotkey".to_string(), redo.as_str()));
+        }
+        for (i, entry) in self.hotkeys.iter().enumerate() {
+            hotkeys.push((format!("hotkeys[{}].key", i), entry.key.as_str()));
+        }
+        hotkeys
+    }
+
+    /// The first hotkey that stops a recording and is pressed with
+    /// `modifier`, which is then always held when that hotkey stops one
+    fn stop_hotkey_holding(&self, modifier: ClipboardModifier) -> Option<(String, &str)> {
+        let modifier = modifier.hotkey_modifier()?;
+        let mut stopping = vec![("hotkey".to_string(), self.hotkey.as_str())];
+        stopping.extend(self.spell_hotkey.as_deref().map(|spell| ("spell_hotkey".to_string(), spell)));
+        for (i, entry) in self.hotkeys.iter().enumerate() {
+            stopping.push((format!("hotkeys[{}].key", i), entry.key.as_str()));
+        }
+        stopping
+            .into_iter()
+            .find(|(_, spec)| hotkey::parse_hotkey(spec).is_ok_and(|key| key.mods.contains(modifier)))
+    }
+
+    /// Whether the local model is one of the English-only `.en` ones
+    pub fn english_only_model(&self) -> bool {
+        if self.backend != Backend::Local {
+            return false;
+        }
+        let path = self.model_path();
+        let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        name.contains(".en.") || name.contains(".en-")
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self)?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// `~/Library/Application Support/Dictation`, or `~/.dictation` until
+    /// that has been moved there. Every other path is under it.
+    pub fn config_dir() -> PathBuf {
+        let legacy = legacy_config_dir();
+        match dirs::data_dir() {
+            Some(data) => resolve_config_dir(legacy, data.join(APP_DIR_NAME)),
+            None => legacy,
+        }
+    }
+
+    /// Move `~/.dictation` under Application Support. Only the app itself
+    /// does this, once it holds the instance lock, so no other copy is
+    /// reading or writing the folder while it moves.
+    pub fn migrate_config_dir() {
+        if let Some(data) = dirs::data_dir() {
+            migrate_config_dir(&legacy_config_dir(), &data.join(APP_DIR_NAME));
+        }
+    }
+
+    /// The instance lock, kept next to the config folder rather than in it
+    /// so it stays put when the folder is moved
+    pub fn lock_path() -> PathBuf {
+        match dirs::data_dir() {
+            Some(data) => data.join(format!("{}.lock", APP_DIR_NAME)),
+            None => legacy_config_dir().join("app.lock"),
+        }
+    }
+
+    pub fn config_path() -> PathBuf {
+        Self::config_dir().join("config.toml")
+    }
+
+    pub fn models_dir() -> PathBuf {
+        Self::config_dir().join("models")
+    }
+
+    pub fn stats_path() -> PathBuf {
+        Self::config_dir().join("stats.toml")
+    }
+
+    pub fn logs_dir() -> PathBuf {
+        Self::config_dir().join("logs")
+    }
+
+    pub fn debug_dir() -> PathBuf {
+        Self::config_dir().join("debug")
+    }
+
+    /// `send_return_after` for the app with bundle id `app`
+    pub fn send_return_after(&self, app: Option<&str>) -> bool {
+        app.and_then(|app| self.app_overrides.get(app))
+            .and_then(|app| app.send_return_after)
+            .unwrap_or(self.send_return_after)
+    }
+
+    /// `case_transform` for the app with bundle id `app`
+    pub fn case_transform(&self, app: Option<&str>) -> CaseTransform {
+        app.and_then(|app| self.app_overrides.get(app))
+            .and_then(|app| app.case_transform)
+            .unwrap_or(self.case_transform)
+    }
+
+    /// `output_file` with `~` expanded, relative to the config dir
+    pub fn output_file(&self) -> Option<PathBuf> {
+        self.output_file
+            .as_deref()
+            .map(|path| resolve_path(path, &Self::config_dir()))
+    }
+
+    pub fn warmup_audio(&self) -> Option<PathBuf> {
+        self.warmup_audio
+            .as_deref()
+            .map(|path| resolve_path(path, &Self::config_dir()))
+    }
+
+    pub fn model_path(&self) -> PathBuf {
+        match &self.model_path {
+            Some(path) => resolve_path(path, &Self::config_dir()),
+            None => Self::models_dir().join(self.model.filename()),
+        }
+    }
+
+    /// The file of `redo_model`, if it's set
+    pub fn redo_model_path(&self) -> Option<PathBuf> {
+        self.redo_model.map(|model| Self::models_dir().join(model.filename()))
+    }
+
+    /// Whether recordings are kept for a redo: with `redo_model` set, and
+    /// never in privacy mode
+    pub fn redo_available(&self) -> bool {
+        self.redo_model.is_some() && !self.privacy_mode
+    }
+
+    /// The model file to load, or why it can't be loaded
+    pub fn check_model_path(&self) -> Result<PathBuf> {
+        let path = self.model_path();
+        let setting = match &self.model_path {
+            Some(configured) => format!("model_path = {:?}", configured),
+            None => format!("model = {:?}", self.model),
+        };
+        let problem = match fs::metadata(&path) {
+            Ok(meta) if meta.is_file() => return Ok(path),
+            Ok(_) => "is not a file".to_string(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => "does not exist".to_string(),
+            Err(e) => format!("can't be read: {}", e),
+        };
+        Err(ConfigError::ModelFile { path, setting, problem })
+    }
+
+    /// Settings that differ in `new` but only take effect after a restart
+    pub fn restart_required(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        let mut check = |name, differs: bool| {
+            if differs {
+                changed.push(name);
+            }
+        };
+        check("backend", self.backend != new.backend);
+        check("remote", self.remote != new.remote);
+        check("model", self.model != new.model);
+        check("model_path", self.model_path != new.model_path);
+        check("whisper_threads", self.whisper_threads != new.whisper_threads);
+        check("warmup", self.warmup != new.warmup);
+        check("warmup_audio", self.warmup_audio != new.warmup_audio);
+        check("hotkey", self.hotkey != new.hotkey);
+        check("repaste_hotkey", self.repaste_hotkey != new.repaste_hotkey);
+        check("cancel_hotkey", self.cancel_hotkey != new.cancel_hotkey);
+        check("hotkeys", self.hotkeys != new.hotkeys);
+        check("spell_hotkey", self.spell_hotkey != new.spell_hotkey);
+        check("pause_hotkey", self.pause_hotkey != new.pause_hotkey);
+        check("redo_hotkey", self.redo_hotkey != new.redo_hotkey);
+        check("mouse_button", self.mouse_button != new.mouse_button);
+        check("swallow_mouse_button", self.swallow_mouse_button != new.swallow_mouse_button);
+        check("input_device", self.input_device != new.input_device);
+        check("profiles", !self.profiles.keys().eq(new.profiles.keys()));
+        check("max_recording_secs", self.max_recording_secs != new.max_recording_secs);
+        check("preroll_ms", self.preroll_ms != new.preroll_ms);
+        check("show_transcript_toast", self.show_transcript_toast != new.show_transcript_toast);
+        check("max_queue", self.max_queue != new.max_queue);
+        check("queue_overflow", self.queue_overflow != new.queue_overflow);
+        check("worker_parallelism", self.worker_parallelism != new.worker_parallelism);
+        check("indicator.enabled", self.indicator.enabled != new.indicator.enabled);
+        check("http_status_port", self.http_status_port != new.http_status_port);
+        changed
+    }
+
+    /// Whether any of the hotkeys `HotkeyHandler` registers differ in `new`
+    pub fn hotkeys_differ(&self, new: &Config) -> bool {
+        self.hotkey != new.hotkey
+            || self.repaste_hotkey != new.repaste_hotkey
+            || self.cancel_hotkey != new.cancel_hotkey
+            || self.spell_hotkey != new.spell_hotkey
+            || self.pause_hotkey != new.pause_hotkey
+            || self.redo_hotkey != new.redo_hotkey
+            || self.hotkeys != new.hotkeys
+    }
+
+    /// Take all of those hotkeys from `new`, once they're registered, so the
+    /// reload doesn't ask for a restart over them
+    pub fn take_hotkeys(&mut self, new: &Config) {
+        self.hotkey = new.hotkey.clone();
+        self.repaste_hotkey = new.repaste_hotkey.clone();
+        self.cancel_hotkey = new.cancel_hotkey.clone();
+        self.spell_hotkey = new.spell_hotkey.clone();
+        self.pause_hotkey = new.pause_hotkey.clone();
+        self.redo_hotkey = new.redo_hotkey.clone();
+        self.hotkeys = new.hotkeys.clone();
+    }
+
+    /// Whether `len` samples at `sample_rate` are under `min_recording_ms`
+    pub fn too_short(&self, len: usize, sample_rate: u32) -> bool {
+        pipeline::too_short(len, sample_rate, self.min_recording_ms)
+    }
+
+    /// Time allowed to transcribe `audio_secs` of audio: the configured
+    /// timeout, stretched for recordings long enough to legitimately need more.
+    pub fn transcription_timeout(&self, audio_secs: f32) -> Option<Duration> {
+        if self.transcription_timeout_secs == 0 {
+            return None;
+        }
+        let scaled = Duration::from_secs_f32(audio_secs.max(0.0) * TIMEOUT_PER_AUDIO_SEC);
+        Some(Duration::from_secs(self.transcription_timeout_secs).max(scaled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default config must survive a save/load round trip
+    #[test]
+    fn test_default_config_round_trips() {
+        let config = Config::default();
+        let text = toml::to_string_pretty(&config).expect("Should serialize");
+        let parsed: Config = toml::from_str(&text).expect("Should parse what we wrote");
+
+        assert_eq!(parsed.hotkey, config.hotkey);
+        assert_eq!(parsed.max_recording_secs, config.max_recording_secs);
+        assert_eq!(parsed.preprocess.highpass_hz, config.preprocess.highpass_hz);
+    }
+
+    /// Old config files without newer fields still load with defaults
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let parsed: Config = toml::from_str("model = \"tiny_en\"\n").expect("Should parse");
+
+        assert_eq!(parsed.model, WhisperModel::TinyEn);
+        assert_eq!(parsed.vad.threshold, default_vad_threshold());
+        assert!(parsed.preprocess.gate_threshold_db.is_none());
+    }
+
+    #[test]
+    fn test_vad_table_parses() {
+        let parsed = Config::from_toml(
+            "[vad]\nthreshold = 0.3\nmin_speech_ms = 250\npadding_ms = 300\n",
+        )
+        .expect("Should parse");
+
+        assert_eq!(parsed.vad.threshold, 0.3);
+        assert_eq!(parsed.vad.min_speech_ms, 250);
+        assert_eq!(parsed.vad.padding_ms, 300);
+        assert!(parsed.vad.enabled);
+        assert_eq!(parsed.vad.engine, VadEngine::Silero);
+    }
+
+    #[test]
+    fn test_vad_engine_parses() {
+        let parsed = Config::from_toml("[vad]\nengine = \"energy\"\n").expect("Should parse");
+        assert_eq!(parsed.vad.engine, VadEngine::Energy);
+
+        assert!(Config::from_toml("[vad]\nengine = \"webrtc\"\n").is_err());
+    }
+
+    /// Top-level `vad_enabled` / `vad_threshold` from older configs still apply
+    #[test]
+    fn test_legacy_vad_keys_are_honored() {
+        let parsed = Config::from_toml("vad_enabled = false\nvad_threshold = 0.7\n")
+            .expect("Should parse");
+
+        assert!(!parsed.vad.enabled);
+        assert_eq!(parsed.vad.threshold, 0.7);
+
+        // Legacy keys are not written back out
+        let text = toml::to_string_pretty(&parsed).unwrap();
+        assert!(!text.contains("vad_threshold"));
+    }
+
+    #[test]
+    fn test_vad_threshold_out_of_range_is_rejected() {
+        let err = Config::from_toml("[vad]\nthreshold = 1.5\n").unwrap_err();
+        assert!(err.to_string().contains("vad.threshold"));
+    }
+
+    /// A bare letter key only warns, since it can still be registered
+    #[test]
+    fn test_modifierless_hotkeys_load() {
+        let config = Config::from_toml("hotkey = \"F5\"\ncancel_hotkey = \"D\"\n").unwrap();
+        let specs: Vec<_> = config.hotkey_specs().into_iter().map(|(name, spec)| (name, spec.to_string())).collect();
+        assert_eq!(
+            specs,
+            [("hotkey", "F5"), ("repaste_hotkey", "Cmd+Shift+R"), ("cancel_hotkey", "D")]
+                .map(|(name, spec)| (name.to_string(), spec.to_string()))
+        );
+    }
+
+    /// `[[hotkeys]]` entries need a language code, and a multilingual model
+    /// for anything but English
+    #[test]
+    fn test_language_hotkeys() {
+        let entries = "[[hotkeys]]\nkey = \"Cmd+Shift+D\"\nlanguage = \"en\"\n\
+                       [[hotkeys]]\nkey = \"Cmd+Shift+G\"\nlanguage = \"de\"\n";
+        let config = Config::from_toml(&format!("model = \"small\"\n{}", entries)).unwrap();
+        assert_eq!(config.hotkeys.len(), 2);
+        assert_eq!(config.hotkeys[1].language, "de");
+        assert_eq!(config.hotkey_specs().last().unwrap(), &("hotkeys[1].key".to_string(), "Cmd+Shift+G"));
+        assert_eq!(Config::default().restart_required(&config), vec!["model", "hotkeys"]);
+
+        let err = Config::from_toml(&format!("model = \"small_en\"\n{}", entries)).unwrap_err();
+        assert!(err.to_string().contains("hotkeys[1]"), "{}", err);
+        assert!(Config::from_toml(&format!("model_path = \"custom.en-q5_0.bin\"\n{}", entries)).is_err());
+        assert!(Config::from_toml("model = \"small_en\"\n[[hotkeys]]\nkey = \"F6\"\nlanguage = \"en\"\n").is_ok());
+
+        for bad in ["German", "", "DE", "d3"] {
+            let toml = format!("model = \"small\"\n[[hotkeys]]\nkey = \"F6\"\nlanguage = \"{}\"\n", bad);
+            assert!(Config::from_toml(&toml).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_vad_padding_too_long_is_rejected() {
+        let err = Config::from_toml("[vad]\npadding_ms = 5000\n").unwrap_err();
+        assert!(err.to_string().contains("vad.padding_ms"));
+    }
+
+    #[test]
+    fn test_preprocess_table_parses() {
+        let parsed: Config = toml::from_str(
+            "[preprocess]\nhighpass_hz = 80.0\ngate_threshold_db = -50.0\n",
+        )
+        .expect("Should parse");
+
+        assert_eq!(parsed.preprocess.highpass_hz, 80.0);
+        assert_eq!(parsed.preprocess.gate_threshold_db, Some(-50.0));
+    }
+
+    #[test]
+    fn test_transcription_timeout_scales_with_audio() {
+        let config = Config::default();
+
+        assert_eq!(config.transcription_timeout(5.0), Some(Duration::from_secs(30)));
+        // A long dictation gets proportionally more time
+        assert_eq!(config.transcription_timeout(100.0), Some(Duration::from_secs(200)));
+
+        let disabled = Config {
+            transcription_timeout_secs: 0,
+            ..Config::default()
+        };
+        assert_eq!(disabled.transcription_timeout(100.0), None);
+    }
+
+    #[test]
+    fn test_indicator_position_parses() {
+        let preset = Config::from_toml("[indicator]\nposition = \"top_center\"\n").unwrap();
+        assert_eq!(
+            preset.indicator.position,
+            IndicatorPosition::Preset(IndicatorAnchor::TopCenter)
+        );
+
+        let offset = Config::from_toml("[indicator]\nposition = { x = 40, y = 20.5 }\n").unwrap();
+        assert_eq!(
+            offset.indicator.position,
+            IndicatorPosition::Offset { x: 40.0, y: 20.5 }
+        );
+
+        assert_eq!(
+            Config::default().indicator.position,
+            IndicatorPosition::Preset(IndicatorAnchor::BottomCenter)
+        );
+        assert!(Config::from_toml("[indicator]\nposition = \"middle\"\n").is_err());
+    }
+
+    #[test]
+    fn test_indicator_colors_parse() {
+        let parsed = Config::from_toml(
+            "[indicator]\nrecording_color = \"#ffcc00\"\nerror_color = \"#11223380\"\n",
+        )
+        .expect("Should parse");
+
+        assert_eq!(parsed.indicator.recording_color, HexColor::rgb(0xFF, 0xCC, 0x00));
+        assert_eq!(parsed.indicator.error_color.a, 0x80);
+        assert_eq!(
+            parsed.indicator.processing_color,
+            Config::default().indicator.processing_color
+        );
+
+        // Colors round-trip through the saved file
+        let text = toml::to_string_pretty(&parsed).unwrap();
+        assert!(text.contains("\"#FFCC00\""));
+        assert!(text.contains("\"#11223380\""));
+    }
+
+    #[test]
+    fn test_contrasting_text_color() {
+        let white = HexColor::rgb(0xFF, 0xFF, 0xFF);
+        let black = HexColor::rgb(0, 0, 0);
+        let indicator = IndicatorConfig::default();
+        assert_eq!(indicator.recording_color.contrasting(), white);
+        assert_eq!(indicator.processing_color.contrasting(), white);
+        assert_eq!(indicator.error_color.contrasting(), black);
+        assert_eq!(HexColor::rgb(0xFF, 0xCC, 0x00).contrasting(), black);
+        assert_eq!(HexColor::rgb(0x20, 0x20, 0x60).contrasting(), white);
+    }
+
+    /// The paused indicator is a dim version of the recording color
+    #[test]
+    fn test_dimmed_color() {
+        let dimmed = IndicatorConfig::default().recording_color.dimmed();
+        assert_eq!(dimmed, HexColor { r: 0x7F, g: 0x26, b: 0x26, a: 0x99 });
+        assert_eq!(HexColor { r: 0, g: 0, b: 0, a: 0 }.dimmed().a, 0);
+    }
+
+    #[test]
+    fn test_invalid_indicator_color_is_rejected() {
+        for bad in ["FF4D4D", "#FF4D", "#GG0000", "#FF4D4D4"] {
+            let toml = format!("[indicator]\nrecording_color = \"{}\"\n", bad);
+            let err = Config::from_toml(&toml).unwrap_err();
+            assert!(
+                err.to_string().contains(&format!("invalid color '{}'", bad)),
+                "Unexpected error for {}: {}",
+                bad,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_indicator_can_be_disabled() {
+        assert!(Config::default().indicator.enabled);
+        let parsed = Config::from_toml("[indicator]\nenabled = false\n").unwrap();
+        assert!(!parsed.indicator.enabled);
+        assert!(parsed.indicator.animate);
+    }
+
+    #[test]
+    fn test_restart_required_lists_only_restart_fields() {
+        let old = Config::default();
+        let new = Config::from_toml(
+            "model = \"tiny_en\"\nhotkey = \"Cmd+Shift+F\"\noutput_mode = \"keystroke\"\n[vad]\nthreshold = 0.3\n[indicator]\nrecording_color = \"#00FF00\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(old.restart_required(&new), vec!["model", "hotkey"]);
+        assert!(old.restart_required(&old.clone()).is_empty());
+    }
+
+    /// Every hotkey `HotkeyHandler` registers counts, and once taken none
+    /// of them is left for a restart
+    #[test]
+    fn test_hotkeys_are_taken_as_a_set() {
+        let new = Config {
+            spell_hotkey: Some("Cmd+Shift+S".to_string()),
+            hotkeys: vec![LanguageHotkey {
+                key: "Cmd+Shift+G".to_string(),
+                language: "de".to_string(),
+            }],
+            ..Config::default()
+        };
+        let mut config = Config::default();
+        assert!(config.hotkeys_differ(&new));
+        assert_eq!(config.restart_required(&new), vec!["hotkeys", "spell_hotkey"]);
+
+        config.take_hotkeys(&new);
+        assert!(!config.hotkeys_differ(&new));
+        assert!(config.restart_required(&new).is_empty());
+    }
+
+    #[test]
+    fn test_overrides_replace_file_values() {
+        let mut config = Config::from_toml("model = \"small\"\n[vad]\nthreshold = 0.7\n").unwrap();
+        let overrides = Overrides {
+            model: Some(WhisperModel::TinyEn),
+            output_mode: Some(OutputMode::Keystroke),
+            vad_threshold: Some(0.3),
+            ..Overrides::default()
+        };
+        config.apply_overrides(&overrides).unwrap();
+
+        assert_eq!(config.model, WhisperModel::TinyEn);
+        assert_eq!(config.output_mode, OutputMode::Keystroke);
+        assert_eq!(config.vad.threshold, 0.3);
+        // Untouched values keep what the file said
+        assert_eq!(config.recording_mode, RecordingMode::PushToTalk);
+        assert!(config.vad.enabled);
+    }
+
+    #[test]
+    fn test_overrides_are_validated() {
+        let overrides = Overrides {
+            vad_threshold: Some(2.0),
+            ..Overrides::default()
+        };
+        assert!(Config::default().apply_overrides(&overrides).is_err());
+    }
+
+    /// Loading an old-format file upgrades it on disk and keeps a backup
+    #[test]
+    fn test_load_migrates_old_file_with_backup() {
+        let dir = std::env::temp_dir().join(format!("dictation-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let old = "model = \"tiny_en\"\nvad_enabled = false\nvad_threshold = 0.7\n";
+        fs::write(&path, old).unwrap();
+
+        let config = Config::load_with_overrides(&path, &Overrides::default()).unwrap();
+        assert_eq!(config.version, CURRENT_VERSION);
+        assert!(!config.vad.enabled);
+        assert_eq!(config.vad.threshold, 0.7);
+
+        assert_eq!(fs::read_to_string(dir.join("config.toml.bak")).unwrap(), old);
+        let upgraded = fs::read_to_string(&path).unwrap();
+        assert!(upgraded.contains("version = 1"), "{}", upgraded);
+        assert!(!upgraded.contains("vad_threshold"), "{}", upgraded);
+
+        // Loading again changes nothing
+        let again = Config::load_with_overrides(&path, &Overrides::default()).unwrap();
+        assert_eq!(again.vad.threshold, 0.7);
+        assert_eq!(fs::read_to_string(&path).unwrap(), upgraded);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_newer_config_version_is_rejected() {
+        let err = Config::from_toml("version = 2\n").unwrap_err();
+        assert!(err.to_string().contains("newer version"), "{}", err);
+    }
+
+    /// A profile overrides the base settings it names, merges into tables
+    /// key by key, and leaves everything else as the base has it
+    #[test]
+    fn test_active_profile_overrides_base() {
+        let content = "model = \"base_en\"\nrecording_mode = \"push_to_talk\"\nhotkey = \"Cmd+Shift+F\"\n\
+            active_profile = \"meetings\"\n\
+            [vad]\nthreshold = 0.4\nmin_speech_ms = 120\n\
+            [profiles.meetings]\nmodel = \"small_en\"\nrecording_mode = \"toggle\"\n[profiles.meetings.vad]\nthreshold = 0.6\n\
+            [profiles.coding]\nmodel = \"tiny_en\"\n";
+        let config = Config::from_toml(content).unwrap();
+
+        assert_eq!(config.model, WhisperModel::SmallEn);
+        assert_eq!(config.recording_mode, RecordingMode::Toggle);
+        assert_eq!(config.vad.threshold, 0.6);
+        // Inherited from the base
+        assert_eq!(config.hotkey, "Cmd+Shift+F");
+        assert_eq!(config.vad.min_speech_ms, 120);
+        assert_eq!(config.profiles.keys().collect::<Vec<_>>(), ["coding", "meetings"]);
+
+        let base = Config::from_toml(&content.replace("active_profile = \"meetings\"\n", "")).unwrap();
+        assert_eq!(base.model, WhisperModel::BaseEn);
+        assert_eq!(base.vad.threshold, 0.4);
+    }
+
+    #[test]
+    fn test_bad_profiles_are_rejected() {
+        let missing = Config::from_toml("active_profile = \"nope\"\n").unwrap_err();
+        assert!(matches!(missing, ConfigError::Profile(_)), "{:?}", missing);
+        assert!(missing.to_string().contains("nope"), "{}", missing);
+
+        // Profile values go through the same validation as the base
+        assert!(Config::from_toml("active_profile = \"p\"\n[profiles.p]\nmodel = \"huge\"\n").is_err());
+        assert!(Config::from_toml("active_profile = \"p\"\n[profiles.p.vad]\nthreshold = 2.0\n").is_err());
+        assert!(Config::from_toml("active_profile = \"p\"\n[profiles.p]\nactive_profile = \"q\"\n").is_err());
+    }
+
+    /// Switching rewrites only `active_profile` in the file
+    #[test]
+    fn test_switch_profile_updates_file() {
+        let dir = std::env::temp_dir().join(format!("dictation-profile-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            "version = 1\nmodel = \"base_en\"\n[profiles.coding]\nmodel = \"tiny_en\"\n",
+        )
+        .unwrap();
+
+        let config = Config::switch_profile(&path, "coding", &Overrides::default()).unwrap();
+        assert_eq!(config.model, WhisperModel::TinyEn);
+        assert_eq!(config.active_profile.as_deref(), Some("coding"));
+
+        assert!(Config::switch_profile(&path, "missing", &Overrides::default()).is_err());
+        let reloaded = Config::load_with_overrides(&path, &Overrides::default()).unwrap();
+        assert_eq!(reloaded.active_profile.as_deref(), Some("coding"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A file that wouldn't load is left as it was rather than saved with
+    /// privacy mode on
+    #[test]
+    fn test_set_privacy_mode_leaves_an_invalid_file_alone() {
+        let dir = std::env::temp_dir().join(format!("dictation-privacy-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let content = "version = 1\nworker_parallelism = 9\n";
+        fs::write(&path, content).unwrap();
+
+        assert!(Config::set_privacy_mode(&path, true, &Overrides::default()).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+
+        fs::write(&path, "version = 1\n").unwrap();
+        let config = Config::set_privacy_mode(&path, true, &Overrides::default()).unwrap();
+        assert!(config.privacy_mode);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_set_model_keeps_other_settings() {
+        let dir = std::env::temp_dir().join(format!("dictation-set-model-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(&path, "version = 1\nhotkey = \"Cmd+Shift+K\"\nmodel_path = \"custom.bin\"\n").unwrap();
+
+        Config::set_model(&path, WhisperModel::SmallEn).unwrap();
+        let config = Config::load_with_overrides(&path, &Overrides::default()).unwrap();
+        assert_eq!(config.model, WhisperModel::SmallEn);
+        assert_eq!(config.hotkey, "Cmd+Shift+K");
+        assert_eq!(config.model_path, None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_privacy_mode_overrides_disk_writes() {
+        let mut config = Config::from_toml(
+            "debug_save_audio = true\nredact_transcripts = false\npostprocess_command = \"tee ~/t.txt\"\n\
+             output_mode = \"file\"\noutput_file = \"notes.md\"\n",
+        )
+        .unwrap();
+        let effective = config.effective();
+        assert!(effective.debug_save_audio && effective.save_stats && !effective.redact_transcripts);
+        assert_eq!(effective.output_mode, OutputMode::File);
+
+        config.privacy_mode = true;
+        let effective = config.effective();
+        assert!(!effective.debug_save_audio);
+        assert!(!effective.save_stats);
+        assert!(effective.redact_transcripts);
+        assert_eq!(effective.postprocess_command, None);
+        assert_eq!(effective.output_mode, OutputMode::ClipboardOnly);
+        // The file's own values are left for when it's turned off
+        assert!(config.debug_save_audio);
+    }
+
+    /// A legacy `~/.dictation` is used until it's moved under Application
+    /// Support, which doesn't happen if that already exists or the move fails
+    #[test]
+    fn test_config_dir_resolution() {
+        let home = std::env::temp_dir().join(format!("dictation-home-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&home);
+        let legacy = home.join(".dictation");
+        let preferred = home.join("Library").join("Application Support").join(APP_DIR_NAME);
+
+        // Fresh install: nothing is created until something is saved
+        migrate_config_dir(&legacy, &preferred);
+        assert_eq!(resolve_config_dir(legacy.clone(), preferred.clone()), preferred);
+        assert!(!preferred.exists());
+
+        // Only the legacy folder: used as is, then moved along with
+        // everything in it
+        fs::create_dir_all(legacy.join("models")).unwrap();
+        fs::write(legacy.join("config.toml"), "version = 1\n").unwrap();
+        fs::write(legacy.join("models").join("ggml-base.en.bin"), "model").unwrap();
+        assert_eq!(resolve_config_dir(legacy.clone(), preferred.clone()), legacy);
+        migrate_config_dir(&legacy, &preferred);
+        assert_eq!(resolve_config_dir(legacy.clone(), preferred.clone()), preferred);
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(preferred.join("config.toml")).unwrap(), "version = 1\n");
+        assert!(preferred.join("models").join("ggml-base.en.bin").is_file());
+
+        // Both: the new one wins and the old one is left alone
+        fs::create_dir_all(&legacy).unwrap();
+        migrate_config_dir(&legacy, &preferred);
+        assert_eq!(resolve_config_dir(legacy.clone(), preferred.clone()), preferred);
+        assert!(legacy.is_dir());
+
+        // The move can't happen: keep using the legacy folder
+        let blocked = home.join("file").join(APP_DIR_NAME);
+        fs::write(home.join("file"), "not a directory").unwrap();
+        migrate_config_dir(&legacy, &blocked);
+        assert_eq!(resolve_config_dir(legacy.clone(), blocked), legacy);
+        assert!(legacy.is_dir());
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    /// Every model round-trips through its config name and has its own file
+    #[test]
+    fn test_whisper_models_round_trip() {
+        #[derive(Serialize, Deserialize)]
+        struct Wrapper {
+            model: WhisperModel,
+        }
+
+        let mut filenames = std::collections::HashSet::new();
+        for model in WhisperModel::ALL {
+            let text = toml::to_string(&Wrapper { model }).unwrap();
+            let parsed: Wrapper = toml::from_str(&text).unwrap();
+            assert_eq!(parsed.model, model, "{}", text);
+            assert!(filenames.insert(model.filename()), "{:?} reuses a filename", model);
+        }
+
+        let parsed = Config::from_toml("model = \"large_v3_turbo_q5_0\"\n").unwrap();
+        assert_eq!(parsed.model, WhisperModel::LargeV3TurboQ5);
+        assert!(parsed.model_path().ends_with("ggml-large-v3-turbo-q5_0.bin"));
+    }
+
+    #[test]
+    fn test_model_path_takes_precedence() {
+        let parsed = Config::from_toml("model = \"tiny_en\"\nmodel_path = \"/models/custom.gguf\"\n").unwrap();
+        assert_eq!(parsed.model_path(), PathBuf::from("/models/custom.gguf"));
+    }
+
+    #[test]
+    fn test_warmup_settings() {
+        let default = Config::default();
+        assert!(default.warmup);
+        assert_eq!(default.warmup_audio(), None);
+
+        let parsed = Config::from_toml("warmup = false\nwarmup_audio = \"/clips/hello.wav\"\n").unwrap();
+        assert!(!parsed.warmup);
+        assert_eq!(parsed.warmup_audio(), Some(PathBuf::from("/clips/hello.wav")));
+        assert_eq!(default.restart_required(&parsed), vec!["warmup", "warmup_audio"]);
+    }
+
+    #[test]
+    fn test_spell_settings() {
+        let default = Config::default();
+        assert_eq!(default.spell.prefix, "spell");
+        assert_eq!(default.spell.unknown_words, UnknownWords::Keep);
+
+        let parsed = Config::from_toml("spell_hotkey = \"Cmd+Shift+S\"\n[spell]\nunknown_words = \"fail\"\n").unwrap();
+        assert_eq!(parsed.spell.prefix, "spell");
+        assert_eq!(parsed.spell.unknown_words, UnknownWords::Fail);
+        assert!(parsed.hotkey_specs().contains(&("spell_hotkey".to_string(), "Cmd+Shift+S")));
+        assert_eq!(default.restart_required(&parsed), vec!["spell_hotkey"]);
+        assert!(Config::from_toml("[spell]\nunknown_words = \"drop\"\n").is_err());
+    }
+
+    #[test]
+    fn test_pause_hotkey() {
+        let parsed = Config::from_toml("pause_hotkey = \"Cmd+Shift+P\"\n").unwrap();
+        assert!(parsed.hotkey_specs().contains(&("pause_hotkey".to_string(), "Cmd+Shift+P")));
+        assert_eq!(Config::default().restart_required(&parsed), vec!["pause_hotkey"]);
+    }
+
+    #[test]
+    fn test_silence_floor() {
+        assert_eq!(Config::default().silence_floor_db, -60.0);
+        let off = Config::from_toml("silence_floor_db = -inf\n").unwrap();
+        assert_eq!(off.silence_floor_db, f32::NEG_INFINITY);
+        assert!(Config::from_toml("silence_floor_db = 6.0\n").is_err());
+        assert!(Config::from_toml("silence_floor_db = nan\n").is_err());
+    }
+
+    #[test]
+    fn test_http_status_port() {
+        assert_eq!(Config::default().http_status_port, None);
+        let parsed = Config::from_toml("http_status_port = 8765\n").unwrap();
+        assert_eq!(parsed.http_status_port, Some(8765));
+        assert_eq!(Config::default().restart_required(&parsed), vec!["http_status_port"]);
+        assert!(Config::from_toml("http_status_port = 0\n").is_err());
+        assert!(Config::from_toml("http_status_port = 70000\n").is_err());
+    }
+
+    #[test]
+    fn test_worker_parallelism_is_capped() {
+        assert_eq!(Config::default().worker_parallelism, 1);
+        let parsed = Config::from_toml("worker_parallelism = 3\n").unwrap();
+        assert_eq!(parsed.worker_parallelism, 3);
+        assert_eq!(Config::default().restart_required(&parsed), vec!["worker_parallelism"]);
+        assert!(Config::from_toml("worker_parallelism = 0\n").is_err());
+        assert!(Config::from_toml("worker_parallelism = 4\n").is_err());
+    }
+
+    #[test]
+    fn test_redo_settings() {
+        let default = Config::default();
+        assert_eq!(default.redo_model_path(), None);
+        assert!(!default.redo_replaces);
+
+        let parsed = Config::from_toml("redo_model = \"small_en\"\nredo_hotkey = \"Cmd+Shift+R\"\n").unwrap();
+        assert_eq!(parsed.redo_model_path(), Some(Config::models_dir().join("ggml-small.en.bin")));
+        assert!(parsed.hotkey_specs().contains(&("redo_hotkey".to_string(), "Cmd+Shift+R")));
+        // A new redo_model is picked up on the next redo
+        assert_eq!(default.restart_required(&parsed), vec!["redo_hotkey"]);
+
+        assert!(!default.redo_available());
+        assert!(parsed.redo_available());
+        let private = Config {
+            privacy_mode: true,
+            ..parsed
+        };
+        assert!(!private.redo_available());
+    }
+
+    #[test]
+    fn test_normalize_whitespace_can_be_turned_off() {
+        assert!(Config::default().normalize_whitespace);
+        assert!(!Config::from_toml("normalize_whitespace = false\n").unwrap().normalize_whitespace);
+    }
+
+    #[test]
+    fn test_scratch_that_settings() {
+        let default = Config::default();
+        assert!(!default.enable_scratch_that);
+        assert_eq!(default.scratch_that_max_chars, 500);
+
+        let parsed = Config::from_toml("enable_scratch_that = true\nscratch_that_max_chars = 80\n").unwrap();
+        assert!(parsed.enable_scratch_that);
+        assert_eq!(parsed.scratch_that_max_chars, 80);
+        assert!(default.restart_required(&parsed).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_path_expands_home_and_relative() {
+        let base = Path::new("/cfg");
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(resolve_path(Path::new("~/m.bin"), base), home.join("m.bin"));
+        assert_eq!(resolve_path(Path::new("models/m.bin"), base), PathBuf::from("/cfg/models/m.bin"));
+        assert_eq!(resolve_path(Path::new("/abs/m.bin"), base), PathBuf::from("/abs/m.bin"));
+        // Only a bare `~` component is the home directory
+        assert_eq!(resolve_path(Path::new("~other/m.bin"), base), PathBuf::from("/cfg/~other/m.bin"));
+    }
+
+    /// The error names the resolved path, where it came from, and what is wrong
+    #[test]
+    fn test_check_model_path_explains_failures() {
+        let dir = std::env::temp_dir().join(format!("dictation-model-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("custom.bin");
+        fs::write(&file, b"ggml").unwrap();
+
+        let mut config = Config {
+            model_path: Some(file.clone()),
+            ..Config::default()
+        };
+        assert_eq!(config.check_model_path().unwrap(), file);
+
+        config.model_path = Some(dir.join("missing.bin"));
+        let err = config.check_model_path().unwrap_err().to_string();
+        assert!(err.contains("missing.bin") && err.contains("model_path") && err.contains("does not exist"), "{}", err);
+
+        config.model_path = Some(dir.clone());
+        let err = config.check_model_path().unwrap_err().to_string();
+        assert!(err.contains("not a file"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_whisper_threads_must_fit_the_machine() {
+        assert_eq!(Config::default().whisper_threads, None);
+        assert_eq!(Config::from_toml("whisper_threads = 1\n").unwrap().whisper_threads, Some(1));
+
+        let cores = num_cpus::get();
+        assert!(Config::from_toml(&format!("whisper_threads = {}\n", cores)).is_ok());
+        for bad in [0, cores + 1] {
+            let err = Config::from_toml(&format!("whisper_threads = {}\n", bad)).unwrap_err();
+            assert!(err.to_string().contains("whisper_threads"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_clipboard_modifier() {
+        assert_eq!(Config::default().clipboard_modifier, ClipboardModifier::Option);
+        for (value, expected) in [
+            ("alt", ClipboardModifier::Option),
+            ("ctrl", ClipboardModifier::Control),
+            ("cmd", ClipboardModifier::Command),
+            ("shift", ClipboardModifier::Shift),
+            ("off", ClipboardModifier::Off),
+        ] {
+            let toml = format!("hotkey = \"F13\"\nclipboard_modifier = \"{}\"\n", value);
+            let config = Config::from_toml(&toml).unwrap();
+            assert_eq!(config.clipboard_modifier, expected, "{}", value);
+        }
+        assert!(Config::from_toml("clipboard_modifier = \"hyper\"\n").is_err());
+
+        let config = Config::from_toml("double_press_action = \"send_return\"\ndouble_press_ms = 300\n").unwrap();
+        assert_eq!(config.double_press_action, DoublePressAction::SendReturn);
+        assert_eq!(config.double_press_ms, 300);
+        assert_eq!(Config::default().double_press_action, DoublePressAction::Off);
+
+        let config = Config::from_toml("output_mode = \"clipboard_only\"\n").unwrap();
+        assert_eq!(config.output_mode, OutputMode::ClipboardOnly);
+    }
+
+    /// A modifier the dictation hotkeys are pressed with would be held at
+    /// every stop, so it can't be the clipboard modifier
+    #[test]
+    fn test_clipboard_modifier_outside_the_hotkeys() {
+        // The default Cmd+Shift+D
+        for value in ["shift", "cmd"] {
+            let err = Config::from_toml(&format!("clipboard_modifier = \"{}\"\n", value)).unwrap_err();
+            assert!(err.to_string().contains("clipboard_modifier"), "{}", err);
+        }
+        let toml = "hotkey = \"F13\"\nspell_hotkey = \"Ctrl+F14\"\nclipboard_modifier = \"ctrl\"\n";
+        assert!(Config::from_toml(toml).is_err());
+        let toml = "hotkey = \"F13\"\nrepaste_hotkey = \"Ctrl+F14\"\nclipboard_modifier = \"ctrl\"\n";
+        assert!(Config::from_toml(toml).is_ok(), "only hotkeys that stop a recording count");
+    }
+
+    #[test]
+    fn test_file_output_needs_a_path() {
+        let err = Config::from_toml("output_mode = \"file\"\n").unwrap_err();
+        assert!(err.to_string().contains("output_file"), "{}", err);
+
+        let config = Config::from_toml("output_mode = \"file\"\noutput_file = \"notes.md\"\nalso_copy = true\n").unwrap();
+        assert_eq!(config.output_mode, OutputMode::File);
+        assert!(config.also_copy);
+        assert_eq!(config.output_file(), Some(Config::config_dir().join("notes.md")));
+    }
+
+    /// An app's override wins over the global setting, other apps follow it
+    #[test]
+    fn test_send_return_after_per_app() {
+        let config = Config::from_toml(
+            "send_return_after = true\n\
+             [app_overrides.\"com.apple.TextEdit\"]\n\
+             send_return_after = false\n\
+             [app_overrides.\"com.tinyspeck.slackmacgap\"]\n",
+        )
+        .unwrap();
+        assert!(!config.send_return_after(Some("com.apple.TextEdit")));
+        assert!(config.send_return_after(Some("com.tinyspeck.slackmacgap")));
+        assert!(config.send_return_after(Some("com.apple.Safari")));
+        assert!(config.send_return_after(None));
+
+        let config = Config::from_toml("[app_overrides.\"com.tinyspeck.slackmacgap\"]\nsend_return_after = true\n").unwrap();
+        assert!(config.send_return_after(Some("com.tinyspeck.slackmacgap")));
+        assert!(!config.send_return_after(Some("com.apple.Safari")));
+        assert_eq!(config.return_delay_ms, 100);
+    }
+
+    /// Shift is held at every stop of the default Cmd+Shift+D, so it can't
+    /// be what skips the Return
+    #[test]
+    fn test_skip_return_modifier_outside_the_hotkeys() {
+        assert_eq!(Config::default().skip_return_modifier, ClipboardModifier::Control);
+        let err = Config::from_toml("skip_return_modifier = \"shift\"\n").unwrap_err();
+        assert!(err.to_string().contains("skip_return_modifier"), "{}", err);
+
+        let config = Config::from_toml("hotkey = \"Ctrl+F13\"\nskip_return_modifier = \"shift\"\n").unwrap();
+        assert_eq!(config.skip_return_modifier, ClipboardModifier::Shift);
+        assert!(Config::from_toml("hotkey = \"Ctrl+F13\"\n").is_err(), "the default Control is taken");
+    }
+
+    #[test]
+    fn test_case_transform_per_app() {
+        assert_eq!(Config::default().case_transform(None), CaseTransform::None);
+
+        let config = Config::from_toml(
+            "case_transform = \"sentence\"\n\
+             [app_overrides.\"com.microsoft.VSCode\"]\n\
+             case_transform = \"lower\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.case_transform(Some("com.microsoft.VSCode")), CaseTransform::Lower);
+        assert_eq!(config.case_transform(Some("com.apple.Safari")), CaseTransform::Sentence);
+        assert_eq!(config.case_transform(None), CaseTransform::Sentence);
+        assert!(Config::from_toml("case_transform = \"camel\"\n").is_err());
+    }
+
+    /// The same duration is the cut-off whatever the device's rate
+    #[test]
+    fn test_too_short_is_by_duration() {
+        let config = Config::default();
+        assert_eq!(config.min_recording_ms, 200);
+        for rate in [16000, 44100, 48000] {
+            let min = rate as usize / 5;
+            assert!(config.too_short(min - 1, rate), "{} Hz", rate);
+            assert!(!config.too_short(min, rate), "{} Hz", rate);
+        }
+
+        let config = Config::from_toml("min_recording_ms = 0\n").unwrap();
+        assert!(!config.too_short(0, 16000));
+    }
+
+    #[test]
+    fn test_remote_backend_needs_an_endpoint() {
+        assert_eq!(Config::default().backend, Backend::Local);
+        let err = Config::from_toml("backend = \"remote\"\n").unwrap_err();
+        assert!(err.to_string().contains("remote.endpoint"), "{}", err);
+
+        let config = Config::from_toml(
+            "backend = \"remote\"\n\
+             [remote]\n\
+             endpoint = \"https://whisper.example.com/v1/audio/transcriptions\"\n\
+             api_key = \"sk-test\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.backend, Backend::Remote);
+        assert_eq!(config.remote.model, "whisper-1");
+        assert_eq!(config.remote.api_key().as_deref(), Some("sk-test"));
+        let logged = format!("{:?}", config);
+        assert!(!logged.contains("sk-test"), "{}", logged);
+        assert!(Config::from_toml(
+            "backend = \"remote\"\n[remote]\nendpoint = \"http://localhost:8000\"\ntimeout_secs = 0\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_paste_backend() {
+        assert_eq!(Config::default().paste_backend, PasteBackend::Enigo);
+        let config = Config::from_toml("paste_backend = \"cgevent\"\n").unwrap();
+        assert_eq!(config.paste_backend, PasteBackend::CgEvent);
+        assert!(Config::from_toml("paste_backend = \"applescript\"\n").is_err());
+    }
+
+    #[test]
+    fn test_accessibility_fallback() {
+        let config = Config::from_toml("output_mode = \"accessibility\"\n").unwrap();
+        assert_eq!(config.output_mode, OutputMode::Accessibility);
+        assert_eq!(config.accessibility_fallback, OutputMode::Clipboard);
+
+        let config = Config::from_toml("accessibility_fallback = \"keystroke\"\n").unwrap();
+        assert_eq!(config.accessibility_fallback, OutputMode::Keystroke);
+
+        for bad in ["accessibility", "file", "clipboard_only"] {
+            let err = Config::from_toml(&format!("accessibility_fallback = \"{}\"\n", bad)).unwrap_err();
+            assert!(err.to_string().contains("accessibility_fallback"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_secure_input_behavior() {
+        assert_eq!(Config::default().secure_input_behavior, SecureInputBehavior::ClipboardOnly);
+        let parsed = Config::from_toml("secure_input_behavior = \"block\"\n").unwrap();
+        assert_eq!(parsed.secure_input_behavior, SecureInputBehavior::Block);
+        assert!(Config::from_toml("secure_input_behavior = \"paste\"\n").is_err());
+    }
+
+    #[test]
+    fn test_mouse_button_must_not_be_left_or_right() {
+        let parsed = Config::from_toml("mouse_button = 4\n").unwrap();
+        assert_eq!(parsed.mouse_button, Some(4));
+        assert!(!parsed.swallow_mouse_button);
+
+        for bad in [0, 1, 2, 33] {
+            let err = Config::from_toml(&format!("mouse_button = {}\n", bad)).unwrap_err();
+            assert!(err.to_string().contains("mouse_button"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_indicator_size_must_be_positive() {
+        let err = Config::from_toml("[indicator]\nheight = 0\n").unwrap_err();
+        assert!(err.to_string().contains("indicator.height"));
     }
 }
@@ -0,0 +1,46 @@
+use crate::config::{Config, Overrides};
+use crate::events::AppEvent;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tao::event_loop::EventLoopProxy;
+
+/// How often the config file's mtime is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Poll `path` and send `ConfigReloaded` (or `ConfigReloadFailed` for a file
+/// that doesn't parse) to the main loop whenever it changes on disk.
+/// Command-line `overrides` are re-applied to every reload.
+pub fn watch(path: PathBuf, overrides: Overrides, proxy: EventLoopProxy<AppEvent>) {
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            // Deleted or mid-replace: keep running with what we have
+            if current.is_none() {
+                continue;
+            }
+
+            let event = match Config::load_with_overrides(&path, &overrides) {
+                Ok(config) => AppEvent::ConfigReloaded(Box::new(config)),
+                Err(e) => AppEvent::ConfigReloadFailed(e.to_string()),
+            };
+            // Event loop gone, app is exiting
+            if proxy.send_event(event).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
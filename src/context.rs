@@ -0,0 +1,134 @@
+//! Recent transcript text fed back to whisper as a prompt (`use_context`)
+
+use std::time::{Duration, Instant};
+
+/// The last few utterances, forgotten after a pause or when the text starts
+/// going to a different app
+pub struct ContextBuffer {
+    text: String,
+    /// App the buffered text went to
+    app: Option<String>,
+    last_used: Option<Instant>,
+    max_chars: usize,
+    reset_after: Duration,
+}
+
+impl ContextBuffer {
+    pub fn new(max_chars: usize, reset_after: Duration) -> Self {
+        Self {
+            text: String::new(),
+            app: None,
+            last_used: None,
+            max_chars,
+            reset_after,
+        }
+    }
+
+    /// Context for an utterance made at `now` into `app`, if any survives
+    pub fn prompt(&mut self, now: Instant, app: Option<&str>) -> Option<&str> {
+        let stale = self
+            .last_used
+            .is_some_and(|last| now.saturating_duration_since(last) >= self.reset_after);
+        if stale || self.app.as_deref() != app {
+            self.clear();
+        }
+        (!self.text.is_empty()).then_some(self.text.as_str())
+    }
+
+    /// Remember `text`, keeping only the last `max_chars` characters (cut at a
+    /// word boundary)
+    pub fn push(&mut self, text: &str, now: Instant, app: Option<&str>) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(text);
+        self.app = app.map(str::to_string);
+        self.last_used = Some(now);
+
+        let excess = self.text.chars().count().saturating_sub(self.max_chars);
+        if excess > 0 {
+            let cut = self.text.char_indices().nth(excess).map_or(self.text.len(), |(i, _)| i);
+            let kept = &self.text[cut..];
+            // Drop the partial word at the front
+            let kept = match kept.find(' ') {
+                Some(space) if cut > 0 && !self.text[..cut].ends_with(' ') => &kept[space + 1..],
+                _ => kept,
+            };
+            self.text = kept.trim_start().to_string();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.app = None;
+        self.last_used = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APP: Option<&str> = Some("com.apple.TextEdit");
+
+    fn buffer() -> ContextBuffer {
+        ContextBuffer::new(40, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_utterances_accumulate() {
+        let mut context = buffer();
+        let start = Instant::now();
+        assert_eq!(context.prompt(start, APP), None);
+
+        context.push("Hello there.", start, APP);
+        context.push(" How are you? ", start, APP);
+        assert_eq!(context.prompt(start + Duration::from_secs(5), APP), Some("Hello there. How are you?"));
+    }
+
+    /// A long enough pause starts over
+    #[test]
+    fn test_inactivity_resets() {
+        let mut context = buffer();
+        let start = Instant::now();
+        context.push("Earlier thought.", start, APP);
+
+        assert!(context.prompt(start + Duration::from_secs(59), APP).is_some());
+        assert_eq!(context.prompt(start + Duration::from_secs(60), APP), None);
+        // Stays cleared
+        assert_eq!(context.prompt(start + Duration::from_secs(61), APP), None);
+    }
+
+    /// Dictating into a different app starts over
+    #[test]
+    fn test_app_change_resets() {
+        let mut context = buffer();
+        let start = Instant::now();
+        context.push("Dear team,", start, APP);
+
+        assert_eq!(context.prompt(start, Some("com.apple.Terminal")), None);
+        assert_eq!(context.prompt(start, APP), None);
+    }
+
+    /// Only the most recent text is kept, without a partial word at the front
+    #[test]
+    fn test_length_is_capped() {
+        let mut context = buffer();
+        let now = Instant::now();
+        context.push("The quick brown fox jumps over the lazy dog.", now, APP);
+        context.push("Pack my box.", now, APP);
+
+        let prompt = context.prompt(now, APP).unwrap();
+        assert!(prompt.chars().count() <= 40, "{:?}", prompt);
+        assert_eq!(prompt, "jumps over the lazy dog. Pack my box.");
+
+        // A single word longer than the cap is cut rather than kept whole
+        let mut context = ContextBuffer::new(5, Duration::from_secs(60));
+        context.push("supercalifragilistic", now, APP);
+        assert!(context.prompt(now, APP).unwrap().chars().count() <= 5);
+    }
+}
@@ -0,0 +1,101 @@
+//! Telling a double press of the dictation hotkey from two single ones
+
+use std::time::{Duration, Instant};
+
+use crate::config::RecordingMode;
+
+/// Spots a second press within `double_press_ms` of the one before. Every
+/// press still does what it usually does; a double press only adds the
+/// `double_press_action`, so single presses are never held back.
+#[derive(Debug, Default)]
+pub struct DoublePress {
+    /// The last press, if it could be the first of a double press
+    last_press: Option<Instant>,
+    /// Push-to-talk: the key is down for the second of a double press
+    held: bool,
+}
+
+impl DoublePress {
+    /// Whether this press completes a double press to act on now. In
+    /// push-to-talk the press still records as usual and its release
+    /// decides instead. A press that completes one doesn't start another,
+    /// so a triple press is a double press and a single one.
+    pub fn press(&mut self, mode: RecordingMode, now: Instant, window: Duration) -> bool {
+        let double = self
+            .last_press
+            .is_some_and(|last| now.saturating_duration_since(last) <= window);
+        self.last_press = (!double).then_some(now);
+        if mode == RecordingMode::PushToTalk {
+            self.held = double;
+            false
+        } else {
+            double
+        }
+    }
+
+    /// Whether this release ends a push-to-talk double press
+    pub fn release(&mut self, mode: RecordingMode) -> bool {
+        mode == RecordingMode::PushToTalk && std::mem::take(&mut self.held)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(400);
+
+    /// Press (and release) the hotkey at each time in ms, returning whether
+    /// each press, then each release, acted on a double press
+    fn presses(mode: RecordingMode, times: &[u64]) -> Vec<(bool, bool)> {
+        let start = Instant::now();
+        let mut tracker = DoublePress::default();
+        times
+            .iter()
+            .map(|&ms| {
+                let pressed = tracker.press(mode, start + Duration::from_millis(ms), WINDOW);
+                (pressed, tracker.release(mode))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_single_presses() {
+        for mode in [RecordingMode::PushToTalk, RecordingMode::Toggle] {
+            assert_eq!(presses(mode, &[0]), vec![(false, false)]);
+            // Too far apart to be a double press
+            assert_eq!(presses(mode, &[0, 500]), vec![(false, false); 2]);
+        }
+    }
+
+    /// Toggle acts on the second press, push-to-talk on its release
+    #[test]
+    fn test_double_press() {
+        assert_eq!(presses(RecordingMode::Toggle, &[0, 300]), vec![(false, false), (true, false)]);
+        assert_eq!(presses(RecordingMode::PushToTalk, &[0, 300]), vec![(false, false), (false, true)]);
+        assert_eq!(presses(RecordingMode::Toggle, &[0, 400]), vec![(false, false), (true, false)]);
+    }
+
+    #[test]
+    fn test_triple_press_is_a_double_then_a_single() {
+        assert_eq!(
+            presses(RecordingMode::Toggle, &[0, 200, 400]),
+            vec![(false, false), (true, false), (false, false)]
+        );
+        assert_eq!(
+            presses(RecordingMode::PushToTalk, &[0, 200, 400]),
+            vec![(false, false), (false, true), (false, false)]
+        );
+        // A fourth press pairs with the third
+        assert_eq!(
+            presses(RecordingMode::Toggle, &[0, 200, 400, 600]),
+            vec![(false, false), (true, false), (false, false), (true, false)]
+        );
+    }
+
+    /// Hybrid acts on the press, like toggle
+    #[test]
+    fn test_hybrid_acts_on_press() {
+        assert_eq!(presses(RecordingMode::Hybrid, &[0, 150]), vec![(false, false), (true, false)]);
+    }
+}
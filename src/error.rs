@@ -0,0 +1,45 @@
+//! Errors from any part of the app, for code that reports them to the user
+
+use thiserror::Error;
+
+use crate::audio::AudioError;
+use crate::config::ConfigError;
+use crate::output::OutputError;
+use crate::permissions::PermissionKind;
+use crate::transcriber::ModelError;
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Audio(#[from] AudioError),
+    #[error(transparent)]
+    Model(#[from] ModelError),
+    #[error(transparent)]
+    Output(#[from] OutputError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+}
+
+impl AppError {
+    /// The permission whose absence caused this
+    pub fn missing_permission(&self) -> Option<PermissionKind> {
+        match self {
+            Self::Audio(AudioError::PermissionDenied) => Some(PermissionKind::Microphone),
+            _ => None,
+        }
+    }
+
+    /// What the user can do about it, when there's something specific
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Audio(AudioError::NoDevice) => Some("Connect a microphone, or set input_device to one that's plugged in"),
+            Self::Audio(AudioError::UnsupportedFormat(_)) => Some("Pick another microphone with input_device"),
+            Self::Model(ModelError::NotFound(_)) => Some("Download the model, or point model_path at one"),
+            Self::Model(ModelError::Server { status: 401 | 403, .. }) => {
+                Some("Check remote.api_key or DICTATION_API_KEY")
+            }
+            Self::Output(OutputError::NoOutputFile) => Some("Set output_file, or pick another output_mode"),
+            _ => None,
+        }
+    }
+}
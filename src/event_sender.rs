@@ -0,0 +1,31 @@
+//! Where the model and the transcription worker send their events. Tests
+//! can't create an event loop off the main thread, so they collect the
+//! events on a channel instead.
+
+use tao::event_loop::{EventLoopClosed, EventLoopProxy};
+
+use crate::events::AppEvent;
+
+#[derive(Clone)]
+pub enum EventSender {
+    Proxy(EventLoopProxy<AppEvent>),
+    #[cfg(test)]
+    Channel(crossbeam_channel::Sender<AppEvent>),
+}
+
+impl EventSender {
+    /// Like `EventLoopProxy::send_event`: fails once nothing receives
+    pub fn send_event(&self, event: AppEvent) -> Result<(), EventLoopClosed<AppEvent>> {
+        match self {
+            Self::Proxy(proxy) => proxy.send_event(event),
+            #[cfg(test)]
+            Self::Channel(tx) => tx.send(event).map_err(|e| EventLoopClosed(e.0)),
+        }
+    }
+}
+
+impl From<EventLoopProxy<AppEvent>> for EventSender {
+    fn from(proxy: EventLoopProxy<AppEvent>) -> Self {
+        Self::Proxy(proxy)
+    }
+}
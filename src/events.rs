@@ -1,10 +1,153 @@
+use std::fmt;
+use std::time::Instant;
+
+use crate::config::{Config, OutputMode};
+use crate::permissions::PermissionKind;
+use crate::timings::Timings;
+use crate::transcriber::TranscriptionResult;
+
 /// Events sent to the main event loop from background threads
 #[derive(Debug)]
 pub enum AppEvent {
-    /// Transcription completed successfully with the transcribed text
-    TranscriptionComplete(String),
-    /// Transcription failed (no speech detected, or inference error)
-    TranscriptionFailed,
+    /// Transcription completed successfully with the transcribed text.
+    /// `id` is the one `TranscriptionWorker::submit` returned; results arrive in submission order.
+    /// `result.text` is the post-processed text to output; the segments are whisper's own.
+    /// `output_override` is the request's, replacing `output_mode`, and
+    /// `send_return` says whether to press Return after the text.
+    TranscriptionComplete {
+        id: u64,
+        result: TranscriptionResult,
+        output_override: Option<OutputMode>,
+        send_return: bool,
+        timings: Timings,
+    },
+    /// The recording was just "scratch that" (`enable_scratch_that`): delete
+    /// the transcript output last instead of outputting anything
+    ScratchThat { id: u64 },
+    /// Running text for the recording in progress (`streaming_preview`)
+    PartialTranscription(String),
+    /// Secure input was on, so the transcript wasn't pasted or typed;
+    /// `copied` says whether it was left on the clipboard instead
+    OutputSuppressed { copied: bool },
+    /// Transcription produced no text
+    TranscriptionFailed(FailureReason),
+    /// The audio input was rebuilt against a (possibly different) device
+    AudioDeviceChanged(String),
+    /// Start recording until `StopRecording`; a no-op while recording
+    StartRecording,
+    /// Stop recording and transcribe what was captured; a no-op otherwise
+    StopRecording,
+    /// Stop recording if recording, otherwise start a toggle recording
+    ToggleRecording,
+    /// Recording began (the audio stream is capturing) at this instant
+    RecordingStarted(Instant),
+    /// Recording ended and `samples` were captured, `duration_ms` long,
+    /// whether or not they go on to be transcribed
+    RecordingStopped { duration_ms: u64, samples: usize },
+    /// Stop recording and throw the audio away
+    DiscardRecording,
+    /// Output the last transcription again
+    Repaste,
+    /// Transcribe the last recording again with `redo_model`, from
+    /// `redo_hotkey` or the tray menu
+    Retranscribe,
+    /// The recording hit `max_recording_secs` and should be submitted
+    RecordingLimitReached,
+    /// `config.toml` changed on disk and parsed cleanly
+    ConfigReloaded(Box<Config>),
+    /// `config.toml` changed on disk but couldn't be loaded; the old config stays
+    ConfigReloadFailed(String),
+    /// A permission the app needs was refused or hasn't been granted yet
+    PermissionMissing(PermissionKind),
+    /// The Mac is about to sleep
+    SystemWillSleep,
+    /// The Mac woke from sleep; audio streams opened before sleeping are dead
+    SystemDidWake,
+    /// A model load moved on to `ModelLoadStage`
+    ModelLoadProgress(ModelLoadStage),
+    /// Transcription failed `reload_after_failures` times in a row, so the
+    /// model is being loaded again
+    ModelReloading,
+    /// The transcription worker panicked; it carries on with a fresh VAD
+    /// and a reloaded model
+    WorkerCrashed(String),
+    /// The transcription worker thread is gone, so a new one is started
+    WorkerStopped,
+    /// A profile was picked from the tray's Profile menu
+    SwitchProfile(String),
+    /// "Statistics…" chosen from the tray menu
+    ShowStats,
+    /// "Open Log Folder" chosen from the tray menu
+    OpenLogFolder,
+    /// "Privacy Mode" chosen from the tray menu
+    TogglePrivacyMode,
     /// Quit requested from tray menu
     Quit,
 }
+
+/// Steps of `ModelManager::load_async`, in order
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelLoadStage {
+    Starting,
+    ReadingFile,
+    Warmup,
+    Ready,
+    Failed(String),
+}
+
+/// Why a transcription produced no text
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureReason {
+    /// VAD found no speech, or whisper returned nothing
+    NoSpeech,
+    /// The recording was under `silence_floor_db` throughout, as a muted mic
+    /// records
+    MicSilent,
+    /// Too little audio left to be worth transcribing
+    TooShort,
+    /// The model hasn't finished loading (or failed to load)
+    ModelNotLoaded,
+    InferenceError(String),
+    /// Abandoned via `TranscriptionWorker::cancel`
+    Cancelled,
+    /// Ran past `transcription_timeout_secs`
+    Timeout,
+    /// Spell mode heard this word, which stands for no character, with
+    /// `spell.unknown_words = "fail"`
+    Unspellable(String),
+    /// A redo with no recording kept to redo: none yet, or privacy mode is on
+    NothingToRedo,
+}
+
+impl FailureReason {
+    /// Short stable name, used as the key in `stats.toml`
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::NoSpeech => "no_speech",
+            Self::MicSilent => "mic_silent",
+            Self::TooShort => "too_short",
+            Self::ModelNotLoaded => "model_not_loaded",
+            Self::InferenceError(_) => "inference_error",
+            Self::Cancelled => "cancelled",
+            Self::Timeout => "timeout",
+            Self::Unspellable(_) => "unspellable",
+            Self::NothingToRedo => "nothing_to_redo",
+        }
+    }
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSpeech => write!(f, "No speech detected"),
+            Self::MicSilent => write!(f, "Your microphone appears to be muted"),
+            Self::TooShort => write!(f, "Recording too short"),
+            Self::ModelNotLoaded => write!(f, "Model not loaded yet"),
+            Self::InferenceError(e) => write!(f, "Error: {}", e),
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::Timeout => write!(f, "Timed out"),
+            Self::Unspellable(word) => write!(f, "Can't spell \"{}\"", word),
+            Self::NothingToRedo => write!(f, "No recording to redo"),
+        }
+    }
+}
@@ -1,76 +1,355 @@
-use anyhow::Result;
 use crossbeam_channel::Sender;
-use global_hotkey::{
-    hotkey::{Code, HotKey, Modifiers},
-    GlobalHotKeyEvent, GlobalHotKeyManager,
-};
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, HotkeyError>;
+
+/// Why hotkeys couldn't be parsed or registered
+#[derive(Debug, Error)]
+pub enum HotkeyError {
+    #[error("Invalid hotkey '{spec}': {reason}")]
+    Invalid { spec: String, reason: String },
+    /// One combination is bound to two actions
+    #[error("The {action} hotkey '{spec}' is already bound to another action")]
+    Duplicate { action: String, spec: String },
+    #[error("Can't register the {action} hotkey {combo}: {reason}")]
+    Register {
+        action: String,
+        combo: String,
+        reason: String,
+    },
+    /// The hotkey manager couldn't be created
+    #[error(transparent)]
+    Manager(#[from] global_hotkey::Error),
+}
 
 #[derive(Debug, Clone)]
 pub enum HotkeyEvent {
     Pressed,
     Released,
+    /// The repaste hotkey was pressed
+    Repaste,
+    /// The cancel hotkey was pressed
+    Cancel,
+    /// A `[[hotkeys]]` entry was pressed, to dictate in this language. Its
+    /// release comes as `Released`.
+    PressedIn(String),
+    /// The spell hotkey was pressed, to dictate in spell mode. Its release
+    /// comes as `Released`.
+    PressedToSpell,
+    /// The pause hotkey was pressed, to pause or resume the recording
+    Pause,
+    /// The redo hotkey was pressed, to transcribe the last recording again
+    Redo,
 }
 
 pub struct HotkeyHandler {
     manager: GlobalHotKeyManager,
     hotkey: HotKey,
-    hotkey_id: u32,
+    /// Single-press hotkeys other than dictation, with the event each sends
+    actions: Vec<(HotKey, HotkeyEvent)>,
+    /// Ids the listener thread translates; replaced by `rebind`
+    ids: Arc<Mutex<HotkeyIds>>,
 }
 
-impl HotkeyHandler {
-    pub fn new() -> Result<Self> {
-        let manager = GlobalHotKeyManager::new()?;
+#[derive(Debug, Clone, Default)]
+struct HotkeyIds {
+    hotkey: u32,
+    actions: Vec<(u32, HotkeyEvent)>,
+}
 
-        let hotkey = HotKey::new(
-            Some(Modifiers::META | Modifiers::SHIFT),
-            Code::KeyD,
-        );
-        let hotkey_id = hotkey.id();
+impl HotkeyIds {
+    fn new(hotkey: &HotKey, actions: &[(HotKey, HotkeyEvent)]) -> Self {
+        Self {
+            hotkey: hotkey.id(),
+            actions: actions.iter().map(|(key, event)| (key.id(), event.clone())).collect(),
+        }
+    }
+}
 
-        manager.register(hotkey)?;
-        log::info!("Registered hotkey: Cmd+Shift+D");
+impl HotkeyHandler {
+    /// Register the dictation hotkey plus single-press `actions` such as repaste.
+    pub fn new(hotkey: &str, actions: &[(&str, HotkeyEvent)]) -> Result<Self> {
+        let manager = GlobalHotKeyManager::new()?;
+        let (dictation, bound) = bind(hotkey, actions)?;
+        register(&manager, &dictation, &bound)?;
+        log_bindings(hotkey, actions);
 
         Ok(Self {
             manager,
-            hotkey,
-            hotkey_id,
+            ids: Arc::new(Mutex::new(HotkeyIds::new(&dictation, &bound))),
+            hotkey: dictation,
+            actions: bound,
         })
     }
 
-    pub fn hotkey_id(&self) -> u32 {
-        self.hotkey_id
+    /// Swap in a different set of hotkeys. On failure the previous ones
+    /// stay registered.
+    pub fn rebind(&mut self, hotkey: &str, actions: &[(&str, HotkeyEvent)]) -> Result<()> {
+        let (dictation, bound) = bind(hotkey, actions)?;
+
+        self.unregister();
+        if let Err(e) = register(&self.manager, &dictation, &bound) {
+            unregister(&self.manager, &dictation, &bound);
+            register(&self.manager, &self.hotkey, &self.actions)?;
+            return Err(e);
+        }
+        log_bindings(hotkey, actions);
+
+        *self.ids.lock() = HotkeyIds::new(&dictation, &bound);
+        self.hotkey = dictation;
+        self.actions = bound;
+        Ok(())
     }
 
-    pub fn listen(tx: Sender<HotkeyEvent>, hotkey_id: u32) {
+    /// Register every hotkey again, for when the system dropped them (e.g.
+    /// across sleep). Ids don't change, so the listener keeps working.
+    pub fn reregister(&self) -> Result<()> {
+        let _ = self.manager.unregister(self.hotkey);
+        register_one(&self.manager, &self.hotkey, "dictation")?;
+        for (key, event) in &self.actions {
+            let _ = self.manager.unregister(*key);
+            register_one(&self.manager, key, &action_name(event))?;
+        }
+        log::info!("Hotkeys re-registered");
+        Ok(())
+    }
+
+    /// Forward presses of the registered hotkeys to `tx` from a background
+    /// thread. Keeps working across `rebind`.
+    pub fn listen(&self, tx: Sender<HotkeyEvent>) {
         let receiver = GlobalHotKeyEvent::receiver();
+        let ids = self.ids.clone();
 
         std::thread::spawn(move || {
             loop {
                 if let Ok(event) = receiver.recv() {
-                    if event.id == hotkey_id {
-                        let evt = if event.state == global_hotkey::HotKeyState::Pressed {
+                    let pressed = event.state == global_hotkey::HotKeyState::Pressed;
+                    let ids = ids.lock();
+                    if event.id == ids.hotkey {
+                        let evt = if pressed {
                             HotkeyEvent::Pressed
                         } else {
                             HotkeyEvent::Released
                         };
                         let _ = tx.send(evt);
+                    } else if let Some((_, action)) = ids.actions.iter().find(|(id, _)| *id == event.id) {
+                        match (action, pressed) {
+                            (_, true) => {
+                                let _ = tx.send(action.clone());
+                            }
+                            (HotkeyEvent::PressedIn(_) | HotkeyEvent::PressedToSpell, false) => {
+                                let _ = tx.send(HotkeyEvent::Released);
+                            }
+                            _ => {}
+                        }
                     }
                 }
             }
         });
     }
+
+    fn unregister(&self) {
+        unregister(&self.manager, &self.hotkey, &self.actions);
+    }
+}
+
+/// Modifier names `global_hotkey` understands, uppercased
+const MODIFIERS: &[&str] = &[
+    "OPTION",
+    "ALT",
+    "CONTROL",
+    "CTRL",
+    "COMMAND",
+    "CMD",
+    "SUPER",
+    "SHIFT",
+    "COMMANDORCONTROL",
+    "COMMANDORCTRL",
+    "CMDORCTRL",
+    "CMDORCONTROL",
+];
+
+/// Keys `global_hotkey` parses but has no macOS key code for, so they'd
+/// only fail once registered
+const UNREGISTERABLE: &[Code] = &[
+    Code::F21,
+    Code::F22,
+    Code::F23,
+    Code::F24,
+    Code::MediaPlay,
+    Code::MediaPause,
+    Code::MediaStop,
+];
+
+/// Parse a hotkey like "Cmd+Shift+D", "F5" or "Ctrl + MediaPlayPause",
+/// naming the part that's wrong when it can't be used
+pub fn parse_hotkey(s: &str) -> Result<HotKey> {
+    let invalid = |reason: String| HotkeyError::Invalid {
+        spec: s.to_string(),
+        reason,
+    };
+    let tokens: Vec<&str> = s.split('+').map(str::trim).collect();
+    if tokens.iter().any(|token| token.is_empty()) {
+        return Err(invalid("it has an empty part".to_string()));
+    }
+    let (key, modifiers) = tokens.split_last().expect("split always yields a part");
+    for token in tokens.iter() {
+        if matches!(token.to_uppercase().as_str(), "FN" | "GLOBE") {
+            return Err(invalid(format!(
+                "macOS doesn't let apps use '{}' in global hotkeys; try an F-key such as F13",
+                token
+            )));
+        }
+    }
+    if let Some(token) = modifiers.iter().find(|token| !MODIFIERS.contains(&token.to_uppercase().as_str())) {
+        return Err(invalid(format!(
+            "'{}' isn't a modifier (Cmd, Shift, Option, Ctrl); only the last part can be a key",
+            token
+        )));
+    }
+    let code = key
+        .parse::<HotKey>()
+        .map_err(|_| invalid(format!("'{}' isn't a key name", key)))?
+        .key;
+    if UNREGISTERABLE.contains(&code) {
+        return Err(invalid(format!("macOS has no key code for '{}'", key)));
+    }
+    tokens.join("+").parse::<HotKey>().map_err(|e| invalid(e.to_string()))
+}
+
+/// Whether `hotkey` is a key that types something, with no modifier or only
+/// Shift, so registering it would swallow that key everywhere
+pub fn types_text(hotkey: &HotKey) -> bool {
+    let name = hotkey.key.to_string();
+    hotkey.mods.difference(Modifiers::SHIFT).is_empty()
+        && (name.starts_with("Key")
+            || name.starts_with("Digit")
+            || matches!(
+                hotkey.key,
+                Code::Space
+                    | Code::Backquote
+                    | Code::Backslash
+                    | Code::BracketLeft
+                    | Code::BracketRight
+                    | Code::Comma
+                    | Code::Equal
+                    | Code::Minus
+                    | Code::Period
+                    | Code::Quote
+                    | Code::Semicolon
+                    | Code::Slash
+            ))
+}
+
+/// `hotkey` the way macOS menus write it, e.g. "Ctrl+Shift+Cmd+D"
+pub fn describe(hotkey: &HotKey) -> String {
+    let mut parts: Vec<String> = [
+        (Modifiers::CONTROL, "Ctrl"),
+        (Modifiers::ALT, "Option"),
+        (Modifiers::SHIFT, "Shift"),
+        (Modifiers::SUPER, "Cmd"),
+    ]
+    .iter()
+    .filter(|(modifier, _)| hotkey.mods.contains(*modifier))
+    .map(|(_, name)| name.to_string())
+    .collect();
+    let key = hotkey.key.to_string();
+    let key = key.strip_prefix("Key").or_else(|| key.strip_prefix("Digit")).unwrap_or(&key);
+    parts.push(key.to_string());
+    parts.join("+")
+}
+
+/// Parse the dictation hotkey and action hotkeys, rejecting any combination
+/// bound twice
+fn bind(hotkey: &str, actions: &[(&str, HotkeyEvent)]) -> Result<(HotKey, Vec<(HotKey, HotkeyEvent)>)> {
+    let dictation = parse_hotkey(hotkey)?;
+    let mut bound: Vec<(HotKey, HotkeyEvent)> = Vec::with_capacity(actions.len());
+    for (spec, event) in actions {
+        let key = parse_hotkey(spec)?;
+        if key.id() == dictation.id() || bound.iter().any(|(k, _)| k.id() == key.id()) {
+            return Err(HotkeyError::Duplicate {
+                action: action_name(event),
+                spec: spec.to_string(),
+            });
+        }
+        bound.push((key, event.clone()));
+    }
+    Ok((dictation, bound))
+}
+
+fn register(manager: &GlobalHotKeyManager, hotkey: &HotKey, actions: &[(HotKey, HotkeyEvent)]) -> Result<()> {
+    register_one(manager, hotkey, "dictation")?;
+    for (key, event) in actions {
+        register_one(manager, key, &action_name(event))?;
+    }
+    Ok(())
+}
+
+fn register_one(manager: &GlobalHotKeyManager, hotkey: &HotKey, name: &str) -> Result<()> {
+    manager.register(*hotkey).map_err(|e| registration_error(name, hotkey, e))
+}
+
+/// Why macOS wouldn't take `hotkey`, saying whether the key itself or the
+/// whole combination was refused
+fn registration_error(name: &str, hotkey: &HotKey, error: global_hotkey::Error) -> HotkeyError {
+    let combo = describe(hotkey);
+    let reason = match &error {
+        global_hotkey::Error::FailedToRegister(message) if message.contains("scancode") => {
+            format!("the key '{}' has no macOS key code; pick another key", hotkey.key)
+        }
+        global_hotkey::Error::FailedToRegister(_) => format!(
+            "macOS refused {}; another app or a system shortcut may already use it",
+            combo
+        ),
+        global_hotkey::Error::AlreadyRegistered(_) => format!("{} is already registered", combo),
+        global_hotkey::Error::FailedToWatchMediaKeyEvent => format!(
+            "media key {} needs Input Monitoring permission for this app",
+            hotkey.key
+        ),
+        _ => error.to_string(),
+    };
+    HotkeyError::Register {
+        action: name.to_string(),
+        combo,
+        reason,
+    }
+}
+
+fn action_name(event: &HotkeyEvent) -> String {
+    match event {
+        HotkeyEvent::PressedIn(language) => format!("\"{}\" dictation", language),
+        HotkeyEvent::PressedToSpell => "spell".to_string(),
+        _ => format!("{:?}", event).to_lowercase(),
+    }
+}
+
+fn unregister(manager: &GlobalHotKeyManager, hotkey: &HotKey, actions: &[(HotKey, HotkeyEvent)]) {
+    let _ = manager.unregister(*hotkey);
+    for (key, _) in actions {
+        let _ = manager.unregister(*key);
+    }
+}
+
+fn log_bindings(hotkey: &str, actions: &[(&str, HotkeyEvent)]) {
+    log::info!("Registered hotkey: {}", hotkey);
+    for (spec, event) in actions {
+        log::info!("Registered {} hotkey: {}", action_name(event), spec);
+    }
 }
 
 impl Drop for HotkeyHandler {
     fn drop(&mut self) {
-        let _ = self.manager.unregister(self.hotkey);
+        self.unregister();
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use global_hotkey::hotkey::{Code, HotKey, Modifiers};
     use crossbeam_channel::unbounded;
 
     /// Test Hypothesis #4: HotKey ID Consistency
@@ -190,4 +469,213 @@ mod tests {
         
         assert_ne!(hotkey.id(), 0, "Hotkey ID should not be zero");
     }
+
+    /// Test that config strings parse to the same hotkey as the built-in default
+    #[test]
+    fn test_parse_hotkey_matches_default() {
+        let parsed = parse_hotkey("Cmd+Shift+D").expect("Should parse default hotkey");
+        let expected = HotKey::new(
+            Some(Modifiers::SUPER | Modifiers::SHIFT),
+            Code::KeyD,
+        );
+
+        assert_eq!(parsed.id(), expected.id());
+    }
+
+    /// Test that the repaste default differs from the dictation default
+    #[test]
+    fn test_repaste_hotkey_is_distinct() {
+        let dictation = parse_hotkey("Cmd+Shift+D").unwrap();
+        let repaste = parse_hotkey("Cmd+Shift+R").unwrap();
+
+        assert_ne!(dictation.id(), repaste.id());
+    }
+
+    /// Test that garbage hotkey strings are rejected with the offending input
+    #[test]
+    fn test_parse_hotkey_rejects_unknown_key() {
+        let err = parse_hotkey("Cmd+Shift+Banana").unwrap_err();
+        assert!(err.to_string().contains("Cmd+Shift+Banana"));
+    }
+
+    /// Action hotkeys may not reuse the dictation hotkey or each other
+    #[test]
+    fn test_bind_rejects_duplicates() {
+        let (dictation, bound) =
+            bind("Cmd+Shift+D", &[("Cmd+Shift+R", HotkeyEvent::Repaste)]).unwrap();
+        assert_eq!(dictation.id(), parse_hotkey("Cmd+Shift+D").unwrap().id());
+        assert_eq!(bound.len(), 1);
+
+        assert!(bind("Cmd+Shift+D", &[("Cmd+Shift+D", HotkeyEvent::Cancel)]).is_err());
+        let err = bind(
+            "Cmd+Shift+D",
+            &[("Cmd+Shift+R", HotkeyEvent::Repaste), ("Cmd+Shift+R", HotkeyEvent::Cancel)],
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("already bound"), "{}", err);
+    }
+
+    /// Every key name in the config docs parses to its key, alone or with
+    /// modifiers
+    #[test]
+    fn test_key_names() {
+        let mut table = vec![
+            ("A", Code::KeyA),
+            ("z", Code::KeyZ),
+            ("KeyQ", Code::KeyQ),
+            ("0", Code::Digit0),
+            ("Digit9", Code::Digit9),
+            ("Space", Code::Space),
+            ("Enter", Code::Enter),
+            ("Tab", Code::Tab),
+            ("Escape", Code::Escape),
+            ("Esc", Code::Escape),
+            ("Backspace", Code::Backspace),
+            ("Delete", Code::Delete),
+            ("Home", Code::Home),
+            ("End", Code::End),
+            ("PageUp", Code::PageUp),
+            ("PageDown", Code::PageDown),
+            ("Up", Code::ArrowUp),
+            ("ArrowDown", Code::ArrowDown),
+            ("Left", Code::ArrowLeft),
+            ("Right", Code::ArrowRight),
+            ("`", Code::Backquote),
+            ("-", Code::Minus),
+            ("=", Code::Equal),
+            ("[", Code::BracketLeft),
+            ("]", Code::BracketRight),
+            ("\\", Code::Backslash),
+            (";", Code::Semicolon),
+            ("'", Code::Quote),
+            (",", Code::Comma),
+            (".", Code::Period),
+            ("/", Code::Slash),
+            ("Num0", Code::Numpad0),
+            ("NumpadEnter", Code::NumpadEnter),
+            ("VolumeUp", Code::AudioVolumeUp),
+            ("AudioVolumeDown", Code::AudioVolumeDown),
+            ("VolumeMute", Code::AudioVolumeMute),
+            ("MediaPlayPause", Code::MediaPlayPause),
+            ("MediaTrackNext", Code::MediaTrackNext),
+            ("MediaTrackPrev", Code::MediaTrackPrevious),
+            ("MediaTrackPrevious", Code::MediaTrackPrevious),
+        ];
+        let function_keys = [
+            Code::F1,
+            Code::F2,
+            Code::F3,
+            Code::F4,
+            Code::F5,
+            Code::F6,
+            Code::F7,
+            Code::F8,
+            Code::F9,
+            Code::F10,
+            Code::F11,
+            Code::F12,
+            Code::F13,
+            Code::F14,
+            Code::F15,
+            Code::F16,
+            Code::F17,
+            Code::F18,
+            Code::F19,
+            Code::F20,
+        ];
+        let names: Vec<String> = (1..=20).map(|n| format!("F{}", n)).collect();
+        table.extend(names.iter().map(String::as_str).zip(function_keys));
+
+        for (name, code) in table {
+            let alone = parse_hotkey(name).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!((alone.mods, alone.key), (Modifiers::empty(), code), "{}", name);
+            let combo = parse_hotkey(&format!("Ctrl+Option+{}", name)).unwrap();
+            assert_eq!((combo.mods, combo.key), (Modifiers::CONTROL | Modifiers::ALT, code), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_modifiers_and_spacing() {
+        let spaced = parse_hotkey(" Cmd + shift + d ").unwrap();
+        assert_eq!(spaced.id(), parse_hotkey("Cmd+Shift+D").unwrap().id());
+        for (spec, mods) in [
+            ("Option+F5", Modifiers::ALT),
+            ("Alt+F5", Modifiers::ALT),
+            ("Control+F5", Modifiers::CONTROL),
+            ("Command+F5", Modifiers::SUPER),
+            ("Super+F5", Modifiers::SUPER),
+            ("Shift+F5", Modifiers::SHIFT),
+        ] {
+            assert_eq!(parse_hotkey(spec).unwrap().mods, mods, "{}", spec);
+        }
+    }
+
+    /// Errors name the part that was rejected
+    #[test]
+    fn test_parse_errors_name_the_part() {
+        for (spec, part) in [
+            ("Cmd+Shift+Banana", "'Banana' isn't a key name"),
+            ("Cmd+D+Shift", "'D' isn't a modifier"),
+            ("Hyper+D", "'Hyper' isn't a modifier"),
+            ("Cmd++D", "empty part"),
+            ("", "empty part"),
+            ("Fn+F5", "'Fn'"),
+            ("Globe", "'Globe'"),
+            ("F21", "no key code for 'F21'"),
+            ("Cmd+MediaPlay", "no key code for 'MediaPlay'"),
+        ] {
+            let err = parse_hotkey(spec).unwrap_err().to_string();
+            assert!(err.contains(part) && err.contains(spec), "{}: {}", spec, err);
+        }
+    }
+
+    /// Only typing keys with no modifier but Shift get the warning
+    #[test]
+    fn test_types_text() {
+        for spec in ["D", "Shift+D", "5", "Space", "/", "Shift+Slash"] {
+            assert!(types_text(&parse_hotkey(spec).unwrap()), "{}", spec);
+        }
+        for spec in ["F5", "Shift+F5", "Cmd+D", "Option+Space", "Escape", "VolumeUp", "Ctrl+Shift+5"] {
+            assert!(!types_text(&parse_hotkey(spec).unwrap()), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(describe(&parse_hotkey("Shift+Cmd+D").unwrap()), "Shift+Cmd+D");
+        assert_eq!(describe(&parse_hotkey("Cmd+Ctrl+Option+7").unwrap()), "Ctrl+Option+Cmd+7");
+        assert_eq!(describe(&parse_hotkey("F5").unwrap()), "F5");
+    }
+
+    /// A key with no key code is blamed on the key, anything else on the
+    /// combination
+    #[test]
+    fn test_registration_errors() {
+        let hotkey = parse_hotkey("Cmd+Shift+D").unwrap();
+        let err = registration_error(
+            "dictation",
+            &hotkey,
+            global_hotkey::Error::FailedToRegister("Unable to register hotkey: KeyD".to_string()),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Can't register the dictation hotkey Shift+Cmd+D: macOS refused Shift+Cmd+D; \
+             another app or a system shortcut may already use it"
+        );
+
+        let f24 = HotKey::new(None, Code::F24);
+        let err = registration_error(
+            "repaste",
+            &f24,
+            global_hotkey::Error::FailedToRegister(
+                "Unable to register accelerator (unknown scancode for this key: F24).".to_string(),
+            ),
+        );
+        assert_eq!(
+            err.to_string(),
+            "Can't register the repaste hotkey F24: the key 'F24' has no macOS key code; pick another key"
+        );
+        assert_eq!(action_name(&HotkeyEvent::Cancel), "cancel");
+        assert_eq!(action_name(&HotkeyEvent::PressedIn("de".to_string())), "\"de\" dictation");
+    }
 }
@@ -1,34 +1,160 @@
 use cocoa::appkit::{NSBackingStoreType, NSColor, NSScreen, NSView, NSWindow, NSWindowStyleMask};
 use cocoa::base::{id, nil, NO, YES};
-use cocoa::foundation::{NSPoint, NSRect, NSSize};
+use cocoa::foundation::{NSArray, NSPoint, NSRect, NSSize, NSString};
+use std::cell::RefCell;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{HexColor, IndicatorConfig, IndicatorPosition, IndicatorScreen};
+use core_foundation::array::CFArray;
+use core_foundation::base::{CFType, TCFType};
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::number::CFNumber;
+use core_foundation::string::CFString;
+use core_graphics::geometry::CGRect;
+use core_graphics::window::{
+    copy_window_info, kCGNullWindowID, kCGWindowBounds, kCGWindowLayer,
+    kCGWindowListExcludeDesktopElements, kCGWindowListOptionOnScreenOnly, kCGWindowOwnerPID,
+};
+
+mod layout;
+
+use layout::Frame;
+
+/// Height the glow radii are tuned for; other heights scale them
+const BASE_HEIGHT: f64 = 8.0;
+
+/// Half of one recording pulse; autoreverse makes the full cycle ~1.2 s
+const PULSE_HALF_PERIOD: f64 = 0.6;
+const PULSE_MIN_OPACITY: f32 = 0.6;
+/// Half of one processing shimmer of the glow
+const SHIMMER_HALF_PERIOD: f64 = 1.0;
+/// Dim gray shown while a press waits for the model to load
+const LOADING_COLOR: HexColor = HexColor { r: 0x80, g: 0x80, b: 0x80, a: 0x99 };
+const TIMER_FONT_SIZE: f64 = 10.0;
+/// Timer text while the recording is transcribed
+const PROCESSING_TEXT: &str = "…";
+
+/// How the indicator looks and where it goes, built from `[indicator]` config
+#[derive(Debug, Clone)]
+pub struct IndicatorStyle {
+    pub position: IndicatorPosition,
+    pub screen: IndicatorScreen,
+    pub width: f64,
+    pub height: f64,
+    pub recording_color: HexColor,
+    pub processing_color: HexColor,
+    pub error_color: HexColor,
+    pub animate: bool,
+    pub show_timer: bool,
+}
+
+impl From<&IndicatorConfig> for IndicatorStyle {
+    fn from(config: &IndicatorConfig) -> Self {
+        Self {
+            position: config.position,
+            screen: config.screen,
+            width: config.width,
+            height: config.height,
+            recording_color: config.recording_color,
+            processing_color: config.processing_color,
+            error_color: config.error_color,
+            animate: config.animate,
+            show_timer: config.show_timer,
+        }
+    }
+}
+
+impl IndicatorStyle {
+    /// Glow radius multiplier for the configured height
+    fn glow_scale(&self) -> f64 {
+        self.height / BASE_HEIGHT
+    }
+
+    /// Pill size, with room for the timer when it's shown
+    fn size(&self) -> (f64, f64) {
+        layout::pill_size(self.width, self.height, self.show_timer)
+    }
+}
 
 pub struct RecordingIndicator {
     window: id,
+    /// Elapsed time, hidden unless `show_timer` is on
+    timer: id,
     is_visible: Arc<AtomicBool>,
+    /// Running text above the pill while recording with `streaming_preview`
+    preview: Bubble,
+    /// Replaced by `set_style` when the config is reloaded
+    style: RefCell<IndicatorStyle>,
+}
+
+/// What the app drives while recording and transcribing; `NullIndicator`
+/// stands in when `indicator.enabled` is off
+pub trait Indicator {
+    fn show(&self);
+    fn hide(&self);
+    fn set_color_recording(&self);
+    /// The recording is paused
+    fn set_color_paused(&self);
+    fn set_color_processing(&self);
+    /// Waiting for the model to load before recording can start
+    fn set_color_loading(&self);
+    /// Scale the glow with the live input level (RMS, 0.0..=1.0)
+    fn set_level(&self, level: f32);
+    fn set_color_error(&self);
+    /// Briefly blink the error color while staying visible
+    fn blink_error(&self);
+    /// Two quick error blinks, distinct from `blink_error`
+    fn blink_error_twice(&self);
+    /// Three short error blinks: the transcription queue was full
+    fn blink_queue_full(&self);
+    /// Flash the error color, then hide
+    fn flash_error(&self);
+    /// Apply a reloaded `[indicator]` config
+    fn set_style(&self, style: IndicatorStyle);
+    /// Time since recording started, for `indicator.show_timer`
+    fn set_elapsed(&self, elapsed: Duration);
+    /// Running text of the recording in progress, for `streaming_preview`;
+    /// cleared once the recording stops
+    fn set_preview(&self, text: &str);
+}
+
+/// Indicator that shows nothing
+pub struct NullIndicator;
+
+impl Indicator for NullIndicator {
+    fn show(&self) {}
+    fn hide(&self) {}
+    fn set_color_recording(&self) {}
+    fn set_color_paused(&self) {}
+    fn set_color_processing(&self) {}
+    fn set_color_loading(&self) {}
+    fn set_level(&self, _level: f32) {}
+    fn set_color_error(&self) {}
+    fn blink_error(&self) {}
+    fn blink_error_twice(&self) {}
+    fn blink_queue_full(&self) {}
+    fn flash_error(&self) {}
+    fn set_style(&self, _style: IndicatorStyle) {}
+    fn set_elapsed(&self, _elapsed: Duration) {}
+    fn set_preview(&self, _text: &str) {}
 }
 
 impl RecordingIndicator {
-    pub fn new() -> Self {
+    pub fn new(style: IndicatorStyle) -> Self {
         unsafe {
             let main_screen = NSScreen::mainScreen(nil);
             // Use visibleFrame to respect Dock and Menu Bar
             let visible_frame: NSRect = msg_send![main_screen, visibleFrame];
             
-            let width = 60.0;
-            let height = 8.0;
-            let margin_bottom = 12.0; // Distance from bottom of visible area
-            
-            // Calculate center x
-            let x = visible_frame.origin.x + (visible_frame.size.width - width) / 2.0;
-            // Calculate bottom y (Cocoa origin is bottom-left)
-            let y = visible_frame.origin.y + margin_bottom;
-            
-            let rect = NSRect::new(
-                NSPoint::new(x, y),
-                NSSize::new(width, height),
-            );
+            let (width, height) = style.size();
+            let rect = to_ns_rect(layout::place(
+                style.position,
+                from_ns_rect(visible_frame),
+                width,
+                height,
+            ));
 
             let window = NSWindow::alloc(nil).initWithContentRect_styleMask_backing_defer_(
                 rect,
@@ -55,30 +181,144 @@ impl RecordingIndicator {
             
             let layer: id = msg_send![content_view, layer];
             
-            // Initial color (Recording Red default?)
-            let red_color = NSColor::colorWithRed_green_blue_alpha_(nil, 1.0, 0.3, 0.3, 1.0);
-            let cg_color: id = msg_send![red_color, CGColor];
+            // Initial color is the recording color
+            let cg_color = cg_color(style.recording_color);
             let _: () = msg_send![layer, setBackgroundColor: cg_color];
             let _: () = msg_send![layer, setCornerRadius: height / 2.0];
             
             // Glow effect
             let _: () = msg_send![layer, setShadowOpacity: 0.8f32];
-            let _: () = msg_send![layer, setShadowRadius: 8.0f64];
+            let _: () = msg_send![layer, setShadowRadius: 8.0 * style.glow_scale()];
             let shadow_offset = NSSize::new(0.0, 0.0); // Center shadow for glow
             let _: () = msg_send![layer, setShadowOffset: shadow_offset];
             
             let _: () = msg_send![layer, setShadowColor: cg_color]; // Glow matches color
 
+            let empty = NSString::alloc(nil).init_str("");
+            let timer: id = msg_send![class!(NSTextField), labelWithString: empty];
+            // NSFontWeightMedium
+            let font: id = msg_send![class!(NSFont), monospacedDigitSystemFontOfSize: TIMER_FONT_SIZE weight: 0.23f64];
+            let _: () = msg_send![timer, setFont: font];
+            let _: () = msg_send![timer, setTextColor: ns_color(style.recording_color.contrasting())];
+            let _: () = msg_send![timer, setHidden: !style.show_timer];
+            let _: () = msg_send![content_view, addSubview: timer];
+
             Self {
                 window,
+                timer,
                 is_visible: Arc::new(AtomicBool::new(false)),
+                preview: Bubble::new(window),
+                style: RefCell::new(style),
             }
         }
     }
 
-    pub fn show(&self) {
+    /// Show `text` in the middle of the pill
+    fn set_timer_text(&self, text: &str) {
+        let style = self.style.borrow();
+        if !style.show_timer {
+            return;
+        }
+        unsafe {
+            let string = NSString::alloc(nil).init_str(text);
+            let _: () = msg_send![self.timer, setStringValue: string];
+            let _: () = msg_send![string, release];
+            let _: () = msg_send![self.timer, sizeToFit];
+            let label: NSRect = msg_send![self.timer, frame];
+            let (width, height) = style.size();
+            let origin = NSPoint::new(
+                ((width - label.size.width) / 2.0).round(),
+                ((height - label.size.height) / 2.0).round(),
+            );
+            let _: () = msg_send![self.timer, setFrameOrigin: origin];
+        }
+    }
+
+    unsafe fn clear_preview(&self) {
+        let _: () = msg_send![self.preview.layer(), setOpacity: 0.0f32];
+    }
+
+    /// Re-place the window on the display chosen by `indicator.screen`
+    unsafe fn move_to_target_screen(&self) {
+        let style = self.style.borrow();
+        let screen = target_screen(style.screen);
+        let visible: NSRect = msg_send![screen, visibleFrame];
+        let (width, height) = style.size();
+        let frame = layout::place(style.position, from_ns_rect(visible), width, height);
+        let _: () = msg_send![self.window, setFrame: to_ns_rect(frame) display: YES];
+    }
+
+    /// Loop `key_path` between `from` and `to` until removed
+    unsafe fn add_repeating(&self, key_path: &str, from: f32, to: f32, half_period: f64, key: &str) {
+        let content_view: id = self.window.contentView();
+        let layer: id = msg_send![content_view, layer];
+
+        let key_path = NSString::alloc(nil).init_str(key_path);
+        let anim: id = msg_send![class!(CABasicAnimation), animationWithKeyPath: key_path];
+        let from: id = msg_send![class!(NSNumber), numberWithFloat: from];
+        let to: id = msg_send![class!(NSNumber), numberWithFloat: to];
+        let _: () = msg_send![anim, setFromValue: from];
+        let _: () = msg_send![anim, setToValue: to];
+        let _: () = msg_send![anim, setDuration: half_period];
+        let _: () = msg_send![anim, setAutoreverses: YES];
+        let _: () = msg_send![anim, setRepeatCount: f32::INFINITY];
+        // kCAMediaTimingFunctionEaseInEaseOut
+        let name = NSString::alloc(nil).init_str("easeInEaseOut");
+        let timing: id = msg_send![class!(CAMediaTimingFunction), functionWithName: name];
+        let _: () = msg_send![anim, setTimingFunction: timing];
+
+        let key = NSString::alloc(nil).init_str(key);
+        let _: () = msg_send![layer, addAnimation: anim forKey: key];
+    }
+
+    /// Jump to the error color and fade back to the current one, `repeats` times
+    unsafe fn blink(&self, duration: f64, repeats: f32) {
+        let content_view: id = self.window.contentView();
+        let layer: id = msg_send![content_view, layer];
+
+        let cg_color = cg_color(self.style.borrow().error_color);
+
+        let key_path = NSString::alloc(nil).init_str("backgroundColor");
+        let anim: id = msg_send![class!(CABasicAnimation), animationWithKeyPath: key_path];
+        let _: () = msg_send![anim, setFromValue: cg_color];
+        let _: () = msg_send![anim, setDuration: duration];
+        let _: () = msg_send![anim, setRepeatCount: repeats];
+
+        let key = NSString::alloc(nil).init_str("blink");
+        let _: () = msg_send![layer, addAnimation: anim forKey: key];
+    }
+
+    /// Remove the recording pulse and processing shimmer
+    unsafe fn stop_animations(&self) {
+        let content_view: id = self.window.contentView();
+        let layer: id = msg_send![content_view, layer];
+        for key in ["pulse", "shimmer"] {
+            let key = NSString::alloc(nil).init_str(key);
+            let _: () = msg_send![layer, removeAnimationForKey: key];
+        }
+    }
+
+    /// Fill and glow both take `color`, and the timer a color that
+    /// stands out against it
+    fn set_color(&self, color: HexColor) {
+        unsafe {
+            let content_view: id = self.window.contentView();
+            let layer: id = msg_send![content_view, layer];
+            let cg_color = cg_color(color);
+            let _: () = msg_send![layer, setBackgroundColor: cg_color];
+            let _: () = msg_send![layer, setShadowColor: cg_color];
+            let _: () = msg_send![self.timer, setTextColor: ns_color(color.contrasting())];
+        }
+    }
+}
+
+impl Indicator for RecordingIndicator {
+    fn show(&self) {
         if !self.is_visible.swap(true, Ordering::SeqCst) {
+            // Left over from the last recording until the first tick
+            self.set_timer_text("");
             unsafe {
+                self.move_to_target_screen();
                 let _: () = msg_send![self.window, setAlphaValue: 0.0f64];
                 let _: () = msg_send![self.window, orderFrontRegardless];
 
@@ -95,9 +335,13 @@ impl RecordingIndicator {
         }
     }
 
-    pub fn hide(&self) {
+    fn hide(&self) {
         if self.is_visible.swap(false, Ordering::SeqCst) {
             unsafe {
+                self.clear_preview();
+                // Otherwise a pulse would still be running on the next show()
+                self.stop_animations();
+
                 let cls = class!(NSAnimationContext);
                 let _: () = msg_send![cls, beginGrouping];
                 let ctx: id = msg_send![cls, currentContext];
@@ -111,55 +355,94 @@ impl RecordingIndicator {
         }
     }
 
-    pub fn set_color_recording(&self) {
+    fn set_color_recording(&self) {
+        self.set_color(self.style.borrow().recording_color);
+        if self.style.borrow().animate {
+            unsafe {
+                self.stop_animations();
+                self.add_repeating("opacity", 1.0, PULSE_MIN_OPACITY, PULSE_HALF_PERIOD, "pulse");
+            }
+        }
+    }
+
+    /// The recording color dimmed, holding still
+    fn set_color_paused(&self) {
         unsafe {
-            let content_view: id = self.window.contentView();
-            let layer: id = msg_send![content_view, layer];
-            
-            // Neon Red
-            let red_color = NSColor::colorWithRed_green_blue_alpha_(nil, 1.0, 0.3, 0.3, 1.0);
-            let cg_color: id = msg_send![red_color, CGColor];
-            let _: () = msg_send![layer, setBackgroundColor: cg_color];
-            
-            // Glow
-            let _: () = msg_send![layer, setShadowColor: cg_color];
+            self.stop_animations();
         }
+        self.set_color(self.style.borrow().recording_color.dimmed());
+        self.set_level(0.0);
     }
 
-    pub fn set_color_processing(&self) {
+    fn set_color_processing(&self) {
         unsafe {
-            let content_view: id = self.window.contentView();
-            let layer: id = msg_send![content_view, layer];
-            
-            // Cyan / Electric Blue
-            let blue_color = NSColor::colorWithRed_green_blue_alpha_(nil, 0.0, 0.8, 1.0, 1.0);
-            let cg_color: id = msg_send![blue_color, CGColor];
-            let _: () = msg_send![layer, setBackgroundColor: cg_color];
-            
-            // Glow
-            let _: () = msg_send![layer, setShadowColor: cg_color];
+            self.clear_preview();
+        }
+        self.set_color(self.style.borrow().processing_color);
+        self.set_timer_text(PROCESSING_TEXT);
+        if self.style.borrow().animate {
+            unsafe {
+                self.stop_animations();
+                self.add_repeating("shadowOpacity", 0.3, 1.0, SHIMMER_HALF_PERIOD, "shimmer");
+            }
         }
     }
 
-    /// Set indicator to orange/amber color (for errors)
-    pub fn set_color_error(&self) {
+    fn set_color_loading(&self) {
+        unsafe {
+            self.stop_animations();
+        }
+        self.set_color(LOADING_COLOR);
+        self.set_timer_text("");
+    }
+
+    /// Scale the glow with the live input level (RMS, 0.0..=1.0) so the user
+    /// can see the mic is picking something up.
+    fn set_level(&self, level: f32) {
+        // Map -60..0 dBFS onto 0..1
+        let db = 20.0 * level.max(1e-6).log10();
+        let intensity = ((db + 60.0) / 60.0).clamp(0.0, 1.0) as f64;
+
         unsafe {
             let content_view: id = self.window.contentView();
             let layer: id = msg_send![content_view, layer];
-            
-            // Orange / Amber
-            let orange_color = NSColor::colorWithRed_green_blue_alpha_(nil, 1.0, 0.6, 0.0, 1.0);
-            let cg_color: id = msg_send![orange_color, CGColor];
-            let _: () = msg_send![layer, setBackgroundColor: cg_color];
-            
-            // Glow
-            let _: () = msg_send![layer, setShadowColor: cg_color];
+            let radius = (3.0 + intensity * 12.0) * self.style.borrow().glow_scale();
+            let _: () = msg_send![layer, setShadowRadius: radius];
+            let _: () = msg_send![layer, setShadowOpacity: (0.4 + intensity * 0.6) as f32];
+        }
+    }
+
+    /// Set indicator to the error color (orange/amber by default)
+    fn set_color_error(&self) {
+        unsafe {
+            self.stop_animations();
+        }
+        self.set_color(self.style.borrow().error_color);
+    }
+
+    /// Briefly blink the error color while staying visible, then fade back to the
+    /// current color. Used when the app stopped something on the user's behalf.
+    fn blink_error(&self) {
+        unsafe {
+            self.blink(0.6, 1.0);
+        }
+    }
+
+    fn blink_error_twice(&self) {
+        unsafe {
+            self.blink(0.3, 2.0);
+        }
+    }
+
+    fn blink_queue_full(&self) {
+        unsafe {
+            self.blink(0.15, 3.0);
         }
     }
 
     /// Flash orange briefly to indicate an error, then hide.
     /// Shows error color at full opacity, then immediately starts fade-out.
-    pub fn flash_error(&self) {
+    fn flash_error(&self) {
         unsafe {
             // Make sure we're visible at full opacity with error color
             self.is_visible.store(true, Ordering::SeqCst);
@@ -182,6 +465,312 @@ impl RecordingIndicator {
             let _: () = msg_send![cls, endGrouping];
         }
     }
+
+    /// Size, placement and the timer change right away; colors and
+    /// animation take effect on the next state change
+    fn set_style(&self, style: IndicatorStyle) {
+        *self.style.borrow_mut() = style;
+        let style = self.style.borrow();
+        unsafe {
+            if self.is_visible.load(Ordering::SeqCst) {
+                self.move_to_target_screen();
+            }
+            let content_view: id = self.window.contentView();
+            let layer: id = msg_send![content_view, layer];
+            let _: () = msg_send![layer, setCornerRadius: style.size().1 / 2.0];
+            let _: () = msg_send![layer, setShadowRadius: 8.0 * style.glow_scale()];
+            let _: () = msg_send![self.timer, setHidden: !style.show_timer];
+        }
+    }
+
+    fn set_elapsed(&self, elapsed: Duration) {
+        self.set_timer_text(&layout::format_elapsed(elapsed));
+    }
+
+    /// The end of `text`, as the newest words are the ones to check
+    fn set_preview(&self, text: &str) {
+        if !self.is_visible.load(Ordering::SeqCst) {
+            return;
+        }
+        unsafe {
+            self.preview.set_text(&last_chars(text, TOAST_MAX_CHARS));
+            let _: () = msg_send![self.preview.layer(), setOpacity: 1.0f32];
+        }
+    }
+}
+
+/// Characters of the transcript shown in the toast
+const TOAST_MAX_CHARS: usize = 80;
+const TOAST_MAX_WIDTH: f64 = 320.0;
+const TOAST_MAX_LINES: i64 = 3;
+const TOAST_PADDING: f64 = 8.0;
+/// Gap between the pill and the toast above it
+const TOAST_GAP: f64 = 10.0;
+/// Total time the toast is on screen, including fades
+const TOAST_DURATION: f64 = 3.0;
+
+/// Small translucent text bubble above the indicator
+struct Bubble {
+    window: id,
+    label: id,
+    /// The indicator window the bubble is positioned against
+    anchor: id,
+}
+
+impl Bubble {
+    fn new(anchor: id) -> Self {
+        unsafe {
+            let rect = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(TOAST_MAX_WIDTH, 40.0));
+            let window = NSWindow::alloc(nil).initWithContentRect_styleMask_backing_defer_(
+                rect,
+                NSWindowStyleMask::NSBorderlessWindowMask,
+                NSBackingStoreType::NSBackingStoreBuffered,
+                NO,
+            );
+            let _: () = msg_send![window, setLevel: 25i32];
+            let _: () = msg_send![window, setOpaque: NO];
+            let _: () = msg_send![window, setHasShadow: NO];
+            let _: () = msg_send![window, setBackgroundColor: NSColor::clearColor(nil)];
+            let _: () = msg_send![window, setIgnoresMouseEvents: YES];
+            let _: () = msg_send![window, setCollectionBehavior: 1u64 << 0 | 1u64 << 6];
+
+            let content_view: id = window.contentView();
+            let _: () = msg_send![content_view, setWantsLayer: YES];
+            let layer: id = msg_send![content_view, layer];
+            let background = NSColor::colorWithRed_green_blue_alpha_(nil, 0.1, 0.1, 0.1, 0.8);
+            let cg_color: id = msg_send![background, CGColor];
+            let _: () = msg_send![layer, setBackgroundColor: cg_color];
+            let _: () = msg_send![layer, setCornerRadius: 8.0f64];
+            // Hidden until there's text to show
+            let _: () = msg_send![layer, setOpacity: 0.0f32];
+
+            let empty = NSString::alloc(nil).init_str("");
+            let label: id = msg_send![class!(NSTextField), wrappingLabelWithString: empty];
+            let font: id = msg_send![class!(NSFont), systemFontOfSize: 13.0f64];
+            let _: () = msg_send![label, setFont: font];
+            let white = NSColor::colorWithRed_green_blue_alpha_(nil, 1.0, 1.0, 1.0, 1.0);
+            let _: () = msg_send![label, setTextColor: white];
+            let _: () = msg_send![label, setMaximumNumberOfLines: TOAST_MAX_LINES];
+            // NSLineBreakByTruncatingTail
+            let _: () = msg_send![label, setLineBreakMode: 4u64];
+            let cell: id = msg_send![label, cell];
+            let _: () = msg_send![cell, setTruncatesLastVisibleLine: YES];
+            let _: () = msg_send![content_view, addSubview: label];
+            let _: () = msg_send![window, orderFrontRegardless];
+
+            Self { window, label, anchor }
+        }
+    }
+
+    /// Fit the bubble to `shown` and place it above the indicator
+    unsafe fn set_text(&self, shown: &str) {
+        let string = NSString::alloc(nil).init_str(shown);
+        let _: () = msg_send![self.label, setStringValue: string];
+        let _: () = msg_send![string, release];
+
+        // Measure single-line width first so short text gets a snug bubble,
+        // then wrap within the maximum width
+        let cell: id = msg_send![self.label, cell];
+        let unbounded = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(f64::MAX, f64::MAX));
+        let natural: NSSize = msg_send![cell, cellSizeForBounds: unbounded];
+        let text_width = natural.width.min(TOAST_MAX_WIDTH - 2.0 * TOAST_PADDING).ceil();
+        let _: () = msg_send![self.label, setPreferredMaxLayoutWidth: text_width];
+        let fitting: NSSize = msg_send![self.label, fittingSize];
+        let text_size = NSSize::new(text_width, fitting.height.ceil());
+
+        let label_frame = NSRect::new(NSPoint::new(TOAST_PADDING, TOAST_PADDING), text_size);
+        let _: () = msg_send![self.label, setFrame: label_frame];
+
+        let size = NSSize::new(
+            text_size.width + 2.0 * TOAST_PADDING,
+            text_size.height + 2.0 * TOAST_PADDING,
+        );
+        let _: () = msg_send![self.window, setFrame: self.frame_above_anchor(size) display: YES];
+    }
+
+    unsafe fn layer(&self) -> id {
+        let content_view: id = self.window.contentView();
+        msg_send![content_view, layer]
+    }
+
+    /// Centered above the indicator, kept inside that screen's visible area
+    unsafe fn frame_above_anchor(&self, size: NSSize) -> NSRect {
+        let pill: NSRect = msg_send![self.anchor, frame];
+        let mut screen: id = msg_send![self.anchor, screen];
+        if screen == nil {
+            screen = NSScreen::mainScreen(nil);
+        }
+        let visible: NSRect = msg_send![screen, visibleFrame];
+
+        let above = Frame {
+            x: pill.origin.x + (pill.size.width - size.width) / 2.0,
+            y: pill.origin.y + pill.size.height + TOAST_GAP,
+            width: size.width,
+            height: size.height,
+        };
+        to_ns_rect(layout::clamp_into(above, from_ns_rect(visible)))
+    }
+}
+
+/// The bubble briefly showing the text that was just output
+pub struct TranscriptToast {
+    bubble: Bubble,
+}
+
+impl TranscriptToast {
+    pub fn new(indicator: &RecordingIndicator) -> Self {
+        Self {
+            bubble: Bubble::new(indicator.window),
+        }
+    }
+
+    /// Show the start of `text` above the indicator for a few seconds
+    pub fn show_text(&self, text: &str) {
+        unsafe {
+            self.bubble.set_text(&truncate_chars(text, TOAST_MAX_CHARS));
+            self.animate_in_and_out();
+        }
+    }
+
+    /// Fade in, hold, fade out in a single layer animation; the layer's own
+    /// opacity stays 0 so it is invisible once the animation ends.
+    unsafe fn animate_in_and_out(&self) {
+        let layer = self.bubble.layer();
+
+        let number = |v: f32| -> id { msg_send![class!(NSNumber), numberWithFloat: v] };
+        let values = NSArray::arrayWithObjects(nil, &[number(0.0), number(1.0), number(1.0), number(0.0)]);
+        let key_times = NSArray::arrayWithObjects(nil, &[number(0.0), number(0.05), number(0.85), number(1.0)]);
+
+        let key_path = NSString::alloc(nil).init_str("opacity");
+        let anim: id = msg_send![class!(CAKeyframeAnimation), animationWithKeyPath: key_path];
+        let _: () = msg_send![anim, setValues: values];
+        let _: () = msg_send![anim, setKeyTimes: key_times];
+        let _: () = msg_send![anim, setDuration: TOAST_DURATION];
+
+        let key = NSString::alloc(nil).init_str("toast");
+        let _: () = msg_send![layer, addAnimation: anim forKey: key];
+    }
+}
+
+unsafe fn ns_color(color: HexColor) -> id {
+    let (r, g, b, a) = color.components();
+    NSColor::colorWithRed_green_blue_alpha_(nil, r, g, b, a)
+}
+
+unsafe fn cg_color(color: HexColor) -> id {
+    msg_send![ns_color(color), CGColor]
+}
+
+/// Resolve `mode` to an NSScreen, falling back to the primary display
+unsafe fn target_screen(mode: IndicatorScreen) -> id {
+    let screens: id = msg_send![class!(NSScreen), screens];
+    let count: usize = msg_send![screens, count];
+    let primary: id = msg_send![screens, objectAtIndex: 0usize];
+
+    let frames: Vec<Frame> = (0..count)
+        .map(|i| {
+            let screen: id = msg_send![screens, objectAtIndex: i];
+            let frame: NSRect = msg_send![screen, frame];
+            from_ns_rect(frame)
+        })
+        .collect();
+
+    let point = match mode {
+        IndicatorScreen::Main => None,
+        IndicatorScreen::Mouse => Some(mouse_location()),
+        IndicatorScreen::Active => {
+            let primary_height = frames.first().map_or(0.0, |f| f.height);
+            focused_window_center(primary_height).or_else(|| Some(mouse_location()))
+        }
+    };
+
+    match point.and_then(|(x, y)| layout::screen_containing(&frames, x, y)) {
+        Some(index) => msg_send![screens, objectAtIndex: index],
+        None => primary,
+    }
+}
+
+unsafe fn mouse_location() -> (f64, f64) {
+    let point: NSPoint = msg_send![class!(NSEvent), mouseLocation];
+    (point.x, point.y)
+}
+
+/// Center, in Cocoa coordinates, of the frontmost app's topmost normal window
+unsafe fn focused_window_center(primary_height: f64) -> Option<(f64, f64)> {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    let app: id = msg_send![workspace, frontmostApplication];
+    if app == nil {
+        return None;
+    }
+    let pid: i32 = msg_send![app, processIdentifier];
+
+    // Ordered front to back
+    let windows: CFArray = copy_window_info(
+        kCGWindowListOptionOnScreenOnly | kCGWindowListExcludeDesktopElements,
+        kCGNullWindowID,
+    )?;
+    let number = |dict: &CFDictionary<CFString, CFType>, key| -> Option<i64> {
+        dict.find(CFString::wrap_under_get_rule(key))?
+            .downcast::<CFNumber>()?
+            .to_i64()
+    };
+
+    windows.iter().find_map(|item| {
+        let dict = CFDictionary::<CFString, CFType>::wrap_under_get_rule(*item as CFDictionaryRef);
+        // Layer 0 is ordinary app windows, not menus or overlays
+        if number(&dict, kCGWindowOwnerPID)? != pid as i64 || number(&dict, kCGWindowLayer)? != 0 {
+            return None;
+        }
+        let bounds = dict
+            .find(CFString::wrap_under_get_rule(kCGWindowBounds))?
+            .downcast::<CFDictionary>()?;
+        let rect = CGRect::from_dict_representation(&bounds)?;
+        let x = rect.origin.x + rect.size.width / 2.0;
+        let y = layout::cg_to_cocoa_y(rect.origin.y + rect.size.height / 2.0, primary_height);
+        Some((x, y))
+    })
+}
+
+fn from_ns_rect(rect: NSRect) -> Frame {
+    Frame {
+        x: rect.origin.x,
+        y: rect.origin.y,
+        width: rect.size.width,
+        height: rect.size.height,
+    }
+}
+
+fn to_ns_rect(frame: Frame) -> NSRect {
+    NSRect::new(
+        NSPoint::new(frame.x, frame.y),
+        NSSize::new(frame.width, frame.height),
+    )
+}
+
+/// First `max` characters of `text`, with an ellipsis if anything was cut
+pub fn truncate_chars(text: &str, max: usize) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+/// Last `max` characters of `text`, with an ellipsis if anything was cut
+fn last_chars(text: &str, max: usize) -> String {
+    let text = text.trim();
+    match text.char_indices().rev().nth(max.saturating_sub(1)) {
+        Some((start, _)) if start > 0 => format!("…{}", text[start..].trim_start()),
+        _ => text.to_string(),
+    }
+}
+
+impl Drop for Bubble {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.window, close];
+        }
+    }
 }
 
 impl Drop for RecordingIndicator {
@@ -194,3 +783,5 @@ impl Drop for RecordingIndicator {
 
 unsafe impl Send for RecordingIndicator {}
 unsafe impl Sync for RecordingIndicator {}
+unsafe impl Send for TranscriptToast {}
+unsafe impl Sync for TranscriptToast {}
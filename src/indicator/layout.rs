@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use crate::config::{IndicatorAnchor, IndicatorPosition};
+
+/// Distance from the edges of the visible area for preset positions
+const MARGIN: f64 = 12.0;
+/// Width the pill grows by to fit the timer
+const TIMER_WIDTH: f64 = 40.0;
+/// Shortest pill the timer fits in
+const TIMER_HEIGHT: f64 = 16.0;
+
+/// Rectangle in Cocoa screen coordinates (origin bottom-left, y up). Origins
+/// can be negative on secondary displays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Frame for a `width` x `height` indicator at `position` within `visible`,
+/// clamped so it never ends up off-screen.
+pub fn place(position: IndicatorPosition, visible: Frame, width: f64, height: f64) -> Frame {
+    let left = visible.x + MARGIN;
+    let center = visible.x + (visible.width - width) / 2.0;
+    let right = visible.x + visible.width - width - MARGIN;
+    let bottom = visible.y + MARGIN;
+    let top = visible.y + visible.height - height - MARGIN;
+
+    let (x, y) = match position {
+        IndicatorPosition::Preset(anchor) => match anchor {
+            IndicatorAnchor::BottomCenter => (center, bottom),
+            IndicatorAnchor::BottomLeft => (left, bottom),
+            IndicatorAnchor::BottomRight => (right, bottom),
+            IndicatorAnchor::TopCenter => (center, top),
+            IndicatorAnchor::TopLeft => (left, top),
+            IndicatorAnchor::TopRight => (right, top),
+        },
+        IndicatorPosition::Offset { x, y } => (visible.x + x, visible.y + y),
+    };
+
+    clamp_into(
+        Frame {
+            x,
+            y,
+            width,
+            height,
+        },
+        visible,
+    )
+}
+
+/// Pill size for the configured `width` x `height`, grown to fit the timer
+/// when it's shown
+pub fn pill_size(width: f64, height: f64, show_timer: bool) -> (f64, f64) {
+    if show_timer {
+        (width + TIMER_WIDTH, height.max(TIMER_HEIGHT))
+    } else {
+        (width, height)
+    }
+}
+
+/// `elapsed` as mm:ss, with minutes counting on past an hour
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{:02}:{:02}", secs / 60, secs % 60)
+}
+
+/// Index of the frame in `screens` containing the point, if any
+pub fn screen_containing(screens: &[Frame], x: f64, y: f64) -> Option<usize> {
+    screens
+        .iter()
+        .position(|s| x >= s.x && x < s.x + s.width && y >= s.y && y < s.y + s.height)
+}
+
+/// Convert a y coordinate from CoreGraphics global space (origin at the top-left
+/// of the primary display, y down) to Cocoa space (bottom-left, y up).
+pub fn cg_to_cocoa_y(y: f64, primary_height: f64) -> f64 {
+    primary_height - y
+}
+
+/// Move `frame` the least distance needed to lie inside `bounds`. A frame
+/// larger than `bounds` is aligned to its bottom-left corner.
+pub fn clamp_into(frame: Frame, bounds: Frame) -> Frame {
+    let max_x = (bounds.x + bounds.width - frame.width).max(bounds.x);
+    let max_y = (bounds.y + bounds.height - frame.height).max(bounds.y);
+    Frame {
+        x: frame.x.clamp(bounds.x, max_x),
+        y: frame.y.clamp(bounds.y, max_y),
+        ..frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Frame = Frame {
+        x: 0.0,
+        y: 80.0,
+        width: 1440.0,
+        height: 795.0,
+    };
+
+    #[test]
+    fn test_default_is_bottom_center() {
+        let frame = place(IndicatorPosition::default(), SCREEN, 60.0, 8.0);
+
+        assert_eq!(frame.x, 690.0);
+        assert_eq!(frame.y, 92.0);
+    }
+
+    #[test]
+    fn test_presets_stay_within_margins() {
+        let top_right = place(
+            IndicatorPosition::Preset(IndicatorAnchor::TopRight),
+            SCREEN,
+            60.0,
+            8.0,
+        );
+        assert_eq!(top_right.x + top_right.width, 1440.0 - MARGIN);
+        assert_eq!(top_right.y + top_right.height, 875.0 - MARGIN);
+
+        let bottom_left = place(
+            IndicatorPosition::Preset(IndicatorAnchor::BottomLeft),
+            SCREEN,
+            60.0,
+            8.0,
+        );
+        assert_eq!((bottom_left.x, bottom_left.y), (MARGIN, 80.0 + MARGIN));
+    }
+
+    #[test]
+    fn test_offset_is_relative_to_visible_area() {
+        let frame = place(IndicatorPosition::Offset { x: 100.0, y: 50.0 }, SCREEN, 60.0, 8.0);
+        assert_eq!((frame.x, frame.y), (100.0, 130.0));
+    }
+
+    /// Offsets that would push the indicator off-screen are clamped back in
+    #[test]
+    fn test_offset_off_screen_is_clamped() {
+        let far = place(IndicatorPosition::Offset { x: 5000.0, y: 5000.0 }, SCREEN, 60.0, 8.0);
+        assert_eq!((far.x, far.y), (1380.0, 867.0));
+
+        let negative = place(IndicatorPosition::Offset { x: -50.0, y: -50.0 }, SCREEN, 60.0, 8.0);
+        assert_eq!((negative.x, negative.y), (0.0, 80.0));
+    }
+
+    /// A display left of and below the main one has negative origins
+    #[test]
+    fn test_negative_screen_origin() {
+        let left_screen = Frame {
+            x: -1920.0,
+            y: -200.0,
+            width: 1920.0,
+            height: 1080.0,
+        };
+        let frame = place(IndicatorPosition::default(), left_screen, 60.0, 8.0);
+
+        assert_eq!(frame.x, -990.0);
+        assert_eq!(frame.y, -188.0);
+    }
+
+    /// Primary display with a second display to its left and slightly lower
+    fn dual_screens() -> [Frame; 2] {
+        [
+            Frame {
+                x: 0.0,
+                y: 0.0,
+                width: 1440.0,
+                height: 900.0,
+            },
+            Frame {
+                x: -1920.0,
+                y: -300.0,
+                width: 1920.0,
+                height: 1080.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_screen_containing_point() {
+        let screens = dual_screens();
+
+        assert_eq!(screen_containing(&screens, 700.0, 450.0), Some(0));
+        assert_eq!(screen_containing(&screens, -500.0, -100.0), Some(1));
+        // Right edge belongs to whichever screen starts there
+        assert_eq!(screen_containing(&screens, 0.0, 10.0), Some(0));
+        assert_eq!(screen_containing(&screens, 5000.0, 0.0), None);
+    }
+
+    /// A window near the top of the lower-left display, reported by CoreGraphics,
+    /// lands on that display once flipped into Cocoa coordinates
+    #[test]
+    fn test_cg_point_maps_to_secondary_screen() {
+        let screens = dual_screens();
+        let primary_height = screens[0].height;
+
+        // CG y grows downwards from the top of the primary display, so a
+        // point 1000pt down is below the primary display's bottom edge
+        let y = cg_to_cocoa_y(1000.0, primary_height);
+        assert_eq!(y, -100.0);
+        assert_eq!(screen_containing(&screens, -800.0, y), Some(1));
+    }
+
+    #[test]
+    fn test_pill_grows_for_timer() {
+        assert_eq!(pill_size(60.0, 8.0, false), (60.0, 8.0));
+        assert_eq!(pill_size(60.0, 8.0, true), (100.0, 16.0));
+        assert_eq!(pill_size(60.0, 24.0, true), (100.0, 24.0));
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::ZERO), "00:00");
+        assert_eq!(format_elapsed(Duration::from_millis(59_900)), "00:59");
+        assert_eq!(format_elapsed(Duration::from_secs(65)), "01:05");
+        assert_eq!(format_elapsed(Duration::from_secs(3725)), "62:05");
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, InstanceError>;
+
+/// Why the instance lock couldn't be taken
+#[derive(Debug, Error)]
+pub enum InstanceError {
+    /// Another process holds the lock
+    #[error("Dictation is already running (pid {0}); quit it first")]
+    Running(u32),
+    /// Held by a copy that hasn't written its PID yet
+    #[error("Dictation is already running; quit it first")]
+    RunningNoPid,
+    #[error("failed to {action} {path:?}: {source}")]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+}
+
+/// Exclusive `flock` on a file holding the PID of the running instance. The
+/// kernel releases it when the process exits, so a crash leaves nothing
+/// stale behind. The file itself is left in place: removing it would let a
+/// third copy lock a new file while a second still holds the old one.
+#[derive(Debug)]
+pub struct InstanceLock {
+    /// Held open for as long as the lock is
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Take the lock at `path`, failing if another process holds it
+    pub fn acquire(path: &Path) -> Result<Self> {
+        let io_error = |action| {
+            move |source| InstanceError::Io {
+                action,
+                path: path.to_path_buf(),
+                source,
+            }
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error("create the folder of"))?;
+        }
+        // Not truncated until the lock is ours, so the holder's PID can
+        // still be read for the error
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(io_error("open"))?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != ErrorKind::WouldBlock {
+                return Err(io_error("lock")(e));
+            }
+            return Err(match fs::read_to_string(path).ok().and_then(|pid| pid.trim().parse::<u32>().ok()) {
+                Some(pid) => InstanceError::Running(pid),
+                None => InstanceError::RunningNoPid,
+            });
+        }
+
+        file.set_len(0).map_err(io_error("write"))?;
+        write!(file, "{}", std::process::id()).map_err(io_error("write"))?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("dictation-lock-{}-{}", std::process::id(), name))
+    }
+
+    /// A second acquire fails while the first lock is held, and succeeds once it is dropped
+    #[test]
+    fn test_second_acquire_fails_until_released() {
+        let path = lock_path("concurrent");
+        let first = InstanceLock::acquire(&path).expect("First acquire should succeed");
+
+        let err = InstanceLock::acquire(&path).unwrap_err();
+        assert!(err.to_string().contains("already running"), "{}", err);
+        assert!(err.to_string().contains(&std::process::id().to_string()), "{}", err);
+
+        drop(first);
+        let again = InstanceLock::acquire(&path).expect("Should acquire after release");
+        drop(again);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// A file left by a process that has exited doesn't hold the lock,
+    /// whatever PID it names
+    #[test]
+    fn test_leftover_file_is_taken_over() {
+        let path = lock_path("leftover");
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        fs::write(&path, dead_pid.to_string()).unwrap();
+
+        let lock = InstanceLock::acquire(&path).expect("Leftover lock file should be taken over");
+        assert_eq!(fs::read_to_string(&path).unwrap(), std::process::id().to_string());
+        drop(lock);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// A live process that doesn't hold the lock doesn't block it either,
+    /// e.g. an unrelated process that got a crashed instance's PID
+    #[test]
+    fn test_reused_pid_is_not_a_holder() {
+        let path = lock_path("reused");
+        fs::write(&path, "1").unwrap();
+
+        let lock = InstanceLock::acquire(&path).expect("A PID alone shouldn't hold the lock");
+        drop(lock);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+//! Logging to stderr and, with `log_to_file`, to a rotating file in the logs
+//! folder, where it's still there after a launch at login
+
+use log::{LevelFilter, Log, Metadata, Record};
+use parking_lot::Mutex;
+use std::cell::Cell;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::timestamp;
+
+const LOG_FILE_NAME: &str = "dictation.log";
+/// Size past which the log file is rotated
+const MAX_LOG_BYTES: u64 = 2 * 1024 * 1024;
+/// Rotated files kept next to the current one, `dictation.log.1` the newest
+const KEPT_LOGS: usize = 3;
+
+thread_local! {
+    /// Set while a record is formatted for the file with `redact_transcripts` on
+    static REDACTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Transcript text in a log message; only its length goes in the log file
+/// when `redact_transcripts` is on
+pub struct Transcript<'a>(pub &'a str);
+
+impl fmt::Display for Transcript<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if REDACTING.get() {
+            write!(f, "[{} chars redacted]", self.0.chars().count())
+        } else {
+            f.write_str(self.0)
+        }
+    }
+}
+
+struct Logger {
+    stderr: env_logger::Logger,
+    /// `RUST_LOG` was set, so it picks the level rather than `log_level`
+    level_from_env: bool,
+    file: Mutex<Option<RotatingFile>>,
+    redact: AtomicBool,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.stderr.matches(record) {
+            return;
+        }
+        self.stderr.log(record);
+        if let Some(file) = self.file.lock().as_mut() {
+            REDACTING.set(self.redact.load(Ordering::Relaxed));
+            let line = format!(
+                "{} {:<5} {}: {}\n",
+                timestamp::now_iso8601(),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+            REDACTING.set(false);
+            // Nowhere left to report a failure to log
+            let _ = file.write(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(file) = self.file.lock().as_mut() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Log to stderr at `RUST_LOG`'s level, or info until `configure` applies
+/// `log_level`
+pub fn init() {
+    let level_from_env = std::env::var_os("RUST_LOG").is_some();
+    // Without RUST_LOG, everything gets this far and the max level filters
+    let stderr = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trace")).build();
+    let max_level = if level_from_env { stderr.filter() } else { LevelFilter::Info };
+    let logger = LOGGER.get_or_init(|| Logger {
+        stderr,
+        level_from_env,
+        file: Mutex::new(None),
+        redact: AtomicBool::new(false),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Apply `log_level`, `log_to_file` and `redact_transcripts`, again after
+/// each config reload
+pub fn configure(config: &Config) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    if !logger.level_from_env {
+        log::set_max_level(config.log_level.into());
+    }
+    logger.redact.store(config.redact_transcripts, Ordering::Relaxed);
+
+    let path = Config::logs_dir().join(LOG_FILE_NAME);
+    let opened = {
+        let mut file = logger.file.lock();
+        match (config.log_to_file, file.is_some()) {
+            (true, false) => match RotatingFile::open(path.clone(), MAX_LOG_BYTES, KEPT_LOGS) {
+                Ok(opened) => {
+                    *file = Some(opened);
+                    Ok(true)
+                }
+                Err(e) => Err(e),
+            },
+            (false, true) => {
+                *file = None;
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    };
+    // Logged once the lock is released, or the message would wait for it
+    match opened {
+        Ok(true) => log::info!("Logging to {:?}", path),
+        Ok(false) => {}
+        Err(e) => log::warn!("Can't log to {:?}: {}", path, e),
+    }
+}
+
+/// Appends to `path`, moving it to `path.1` (and older ones along, up to
+/// `path.<keep>`) when it would grow past `max_bytes`
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf, max_bytes: u64, keep: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = append_to(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            keep,
+            file,
+            size,
+        })
+    }
+
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// The oldest file is overwritten by the one after it
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..self.keep).rev() {
+            let older = numbered(&self.path, n);
+            if older.exists() {
+                fs::rename(&older, numbered(&self.path, n + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            fs::rename(&self.path, numbered(&self.path, 1))?;
+        } else {
+            fs::remove_file(&self.path)?;
+        }
+        self.file = append_to(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn append_to(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `path` with `.n` added, e.g. `dictation.log.2`
+fn numbered(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transcripts_are_redacted_only_for_the_file() {
+        let text = "Meet me at nine";
+        assert_eq!(format!("Heard: {}", Transcript(text)), "Heard: Meet me at nine");
+        REDACTING.set(true);
+        assert_eq!(format!("Heard: {}", Transcript(text)), "Heard: [15 chars redacted]");
+        REDACTING.set(false);
+    }
+
+    /// Full files move along one number each time and the oldest is dropped
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("dictation-logs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join(LOG_FILE_NAME);
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        for line in ["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            file.write(line.as_bytes()).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddd\n");
+        assert_eq!(fs::read_to_string(numbered(&path, 1)).unwrap(), "cccccc\n");
+        assert_eq!(fs::read_to_string(numbered(&path, 2)).unwrap(), "bbbbbb\n");
+        assert!(!numbered(&path, 3).exists());
+
+        // Reopening carries on where the file left off
+        let mut file = RotatingFile::open(path.clone(), 10, 2).unwrap();
+        file.write(b"ee\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddd\nee\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
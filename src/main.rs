@@ -1,113 +1,403 @@
 #[macro_use]
 extern crate objc;
 
+mod accessibility;
 mod audio;
+mod commands;
 mod config;
+mod config_watch;
+mod context;
+mod double_press;
+mod error;
+mod event_sender;
 mod events;
 mod hotkey;
 mod indicator;
+mod instance;
+mod logging;
+mod migrations;
 mod model;
+mod notify;
 mod output;
+mod panic_hook;
+mod pending_start;
+mod permissions;
+mod pipeline;
+mod postprocess;
+mod power;
+mod recording_press;
+mod setup;
+mod sounds;
 mod state;
+mod stats;
+mod status;
+mod timestamp;
+mod timings;
 mod transcriber;
 mod triggers;
 mod tray;
+mod url_scheme;
 mod vad;
+mod wav;
 mod worker;
 
 use anyhow::Result;
+use clap::{Parser, Subcommand};
 use crossbeam_channel::unbounded;
+use parking_lot::Mutex;
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::de::DeserializeOwned;
 use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tao::event::{Event, StartCause};
-use tao::event_loop::{ControlFlow, EventLoopBuilder};
+use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy};
 
-use crate::audio::AudioCapture;
-use crate::config::{Config, RecordingMode};
-use crate::events::AppEvent;
+use crate::audio::{AudioCapture, AudioSource};
+use crate::config::{Backend, Config, DoublePressAction, OutputMode, Overrides, RecordingMode, VadEngine, WhisperModel};
+use crate::double_press::DoublePress;
+use crate::error::AppError;
+use crate::events::{AppEvent, FailureReason, ModelLoadStage};
 use crate::hotkey::{HotkeyEvent, HotkeyHandler};
-use crate::indicator::RecordingIndicator;
+use crate::indicator::{Indicator, IndicatorStyle, NullIndicator, RecordingIndicator, TranscriptToast};
+use crate::instance::InstanceLock;
+use crate::logging::Transcript;
 use crate::model::ModelManager;
-use crate::output::OutputHandler;
-use crate::state::StateManager;
+use crate::output::{FrontmostApp, OutputHandler, Scratch};
+use crate::pending_start::{PendingStart, Queued};
+use crate::recording_press::{RecordingPress, Release};
+use crate::sounds::{Cue, SoundPlayer};
+use crate::state::{ReadinessState, StateManager};
+use crate::stats::Stats;
+use crate::status::{StatusServer, StatusSnapshot};
 use crate::tray::TrayIcon;
-use crate::triggers::{TriggerEvent, TriggerMonitor};
-use crate::vad::VadProcessor;
-use crate::worker::{TranscriptionRequest, TranscriptionWorker};
+use crate::triggers::{MouseTrigger, TriggerEvent, TriggerMonitor};
+use crate::worker::{
+    RetrySlot, StreamChunk, Submitted, TranscriptionOptions, TranscriptionRequest, TranscriptionWorker,
+};
+
+/// Local voice dictation: press the hotkey, speak, and the text is typed
+/// into the active window. Flags override config.toml for this run only.
+#[derive(Debug, Parser)]
+#[command(name = "dictation", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Config file to use instead of ~/Library/Application Support/Dictation/config.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Whisper model, e.g. tiny_en, base_en, small_en, medium_en, large_v3_turbo, large_v3_turbo_q5_0
+    #[arg(long, global = true, value_parser = parse_config_value::<WhisperModel>)]
+    model: Option<WhisperModel>,
+    /// How text is output: clipboard or keystroke
+    #[arg(long, global = true, value_parser = parse_config_value::<OutputMode>)]
+    output_mode: Option<OutputMode>,
+    /// push_to_talk, toggle, or hybrid
+    #[arg(long, global = true, value_parser = parse_config_value::<RecordingMode>)]
+    recording_mode: Option<RecordingMode>,
+    /// Dictation hotkey, e.g. "Cmd+Shift+D"
+    #[arg(long, global = true)]
+    hotkey: Option<String>,
+    /// Microphone name (case-insensitive substring)
+    #[arg(long, global = true, value_name = "NAME")]
+    input_device: Option<String>,
+    /// Turn voice activity detection off
+    #[arg(long, global = true)]
+    no_vad: bool,
+    /// Speech detector: silero or energy
+    #[arg(long, global = true, value_parser = parse_config_value::<VadEngine>)]
+    vad_engine: Option<VadEngine>,
+    /// Speech probability threshold (0.0 - 1.0)
+    #[arg(long, global = true, value_name = "THRESHOLD")]
+    vad_threshold: Option<f32>,
+    /// Stop recording after this many seconds (0 = no limit)
+    #[arg(long, global = true, value_name = "SECS")]
+    max_recording_secs: Option<u64>,
+    /// Save the audio sent to whisper to the debug/ folder next to the config
+    #[arg(long, global = true)]
+    debug_save_audio: bool,
+    /// Log where the time goes in each dictation (recording, VAD, inference, output)
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Print whether microphone and accessibility access are granted, then exit
+    #[arg(long)]
+    check_permissions: bool,
+    /// Pick and download a model and test the microphone before starting
+    /// (also offered on the first run from a terminal)
+    #[arg(long)]
+    setup: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Transcribe a WAV file and print the text; exits non-zero if no speech is found
+    Transcribe {
+        /// WAV file (any sample rate, integer or float, mono or multi-channel)
+        file: PathBuf,
+        /// Print {"text", "duration_ms", "model"} as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List audio input devices and the formats they support
+    Devices,
+    /// Print all-time dictation statistics
+    Stats,
+    /// Time model load, warmup, and repeated transcriptions with the
+    /// configured (or --model) model
+    Bench {
+        /// WAV file to transcribe instead of generated audio
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+        /// Length of the generated audio
+        #[arg(long, value_name = "SECS", default_value_t = 10.0)]
+        seconds: f32,
+        /// Timed runs after the warmup
+        #[arg(long, default_value_t = 5)]
+        runs: usize,
+        /// Print the results as JSON
+        #[arg(long)]
+        json: bool,
+        /// Exit non-zero if the mean real-time factor is above this, e.g.
+        /// 1.0 for slower than real time
+        #[arg(long, value_name = "RTF")]
+        assert_rtf: Option<f64>,
+    },
+}
+
+impl Cli {
+    fn overrides(&self) -> Overrides {
+        Overrides {
+            model: self.model,
+            output_mode: self.output_mode,
+            recording_mode: self.recording_mode,
+            hotkey: self.hotkey.clone(),
+            input_device: self.input_device.clone(),
+            vad_enabled: self.no_vad.then_some(false),
+            vad_engine: self.vad_engine,
+            vad_threshold: self.vad_threshold,
+            max_recording_secs: self.max_recording_secs,
+            debug_save_audio: self.debug_save_audio.then_some(true),
+            log_timings: self.timings.then_some(true),
+        }
+    }
+}
+
+/// Parse a flag value the way config.toml spells it (e.g. `tiny_en`)
+fn parse_config_value<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    T::deserialize(StrDeserializer::<ValueError>::new(value)).map_err(|e| e.to_string())
+}
 
 fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let cli = Cli::parse();
+
+    logging::init();
+    panic_hook::install();
+
+    // Needs neither the config nor the model
+    if let Some(Command::Devices) = cli.command {
+        return Ok(commands::devices()?);
+    }
+    if let Some(Command::Stats) = cli.command {
+        return Ok(commands::stats()?);
+    }
+    if cli.check_permissions {
+        println!("{}", permissions::report());
+        return Ok(());
+    }
 
     log::info!("Starting Dictation App");
 
-    let config = Config::load()?;
-    log::info!("Config loaded: {:?}", config);
+    // A second copy would fight over the hotkey and paste everything twice.
+    // Released explicitly on quit since the event loop never returns. Taken
+    // before the config folder is touched, so it can be moved safely.
+    let mut instance_lock = match cli.command {
+        None => Some(InstanceLock::acquire(&Config::lock_path())?),
+        Some(_) => None,
+    };
+    if instance_lock.is_some() {
+        Config::migrate_config_dir();
+    }
 
-    if !Config::models_dir().exists() {
-        fs::create_dir_all(Config::models_dir())?;
+    let config_path = cli.config.clone().unwrap_or_else(Config::config_path);
+    let overrides = cli.overrides();
+    // Without a terminal to answer in, the defaults are written as before
+    let first_run = !config_path.exists() && io::stdin().is_terminal();
+    // `config` always holds the effective settings, with privacy mode applied
+    let mut config = Config::load_with_overrides(&config_path, &overrides)?.effective();
+    logging::configure(&config);
+
+    if let Some(Command::Transcribe { file, json }) = &cli.command {
+        return Ok(commands::transcribe(&config, file, *json)?);
+    }
+    if let Some(Command::Bench {
+        file,
+        seconds,
+        runs,
+        json,
+        assert_rtf,
+    }) = &cli.command
+    {
+        let options = commands::BenchOptions {
+            file: file.clone(),
+            seconds: *seconds,
+            runs: *runs,
+            json: *json,
+            assert_rtf: *assert_rtf,
+        };
+        return Ok(commands::bench(&config, &options)?);
     }
 
-    if !config.model_path().exists() {
-        log::error!(
-            "Model not found at {:?}. Please download a Whisper model.",
-            config.model_path()
-        );
-        log::info!("Download from: {}", config.model.download_url());
-        log::info!("Place the model file in: {:?}", Config::models_dir());
-        return Err(anyhow::anyhow!("Model not found"));
+    // Not the settings themselves: a profile table can hold `remote.api_key`
+    log::info!("Config loaded from {:?}", config_path);
+
+    if !Config::models_dir().exists() {
+        fs::create_dir_all(Config::models_dir())?;
     }
 
     // Build event loop with our custom AppEvent type
     let event_loop = EventLoopBuilder::<AppEvent>::with_user_event().build();
     let proxy = event_loop.create_proxy();
 
+    if cli.setup || first_run {
+        config = setup::run(&config_path, &overrides, proxy.clone())?.effective();
+    }
+
+    // The remote backend has no local model to find
+    let model_missing = match config.backend {
+        Backend::Local => config.check_model_path().err(),
+        Backend::Remote => None,
+    };
+    if let Some(e) = model_missing {
+        log::error!("Can't load the Whisper model: {}", e);
+        if config.model_path.is_none() {
+            log::info!(
+                "Download from: {} (about {} MB)",
+                config.model.download_url(),
+                config.model.approx_size_mb()
+            );
+            log::info!("Place the model file in: {:?}", Config::models_dir());
+        }
+        log::info!("Or run with --setup to pick one and download it");
+        return Err(e.into());
+    }
+
+    // Reported through the event loop so the indicator can warn about them
+    permissions::check(&proxy);
+
     // State management
     let state = StateManager::new();
+    state.log_transitions();
 
-    // Model manager (will be moved to worker)
-    let model_manager = ModelManager::new(state.clone(), config.clone());
+    // Model manager (a clone is moved to the worker)
+    let model_manager = ModelManager::new(state.clone(), config.clone(), proxy.clone().into());
     log::info!("Pre-loading model...");
     model_manager.load_async();
 
-    // VAD processor (will be moved to worker)
-    let mut audio_capture = AudioCapture::new()?;
-    let vad_processor = if config.vad_enabled {
-        Some(VadProcessor::new(config.vad_threshold, audio_capture.sample_rate()))
-    } else {
-        None
-    };
+    let mut audio_capture = AudioCapture::new(&config, proxy.clone())?;
+
+    let mut worker = spawn_worker(&model_manager, &config, &proxy);
 
-    // Create transcription worker - takes ownership of model_manager and vad_processor
-    let worker = TranscriptionWorker::new(model_manager, vad_processor, proxy.clone());
+    // Pick up edits to config.toml without a restart
+    config_watch::watch(config_path.clone(), overrides.clone(), proxy.clone());
+
+    power::observe_sleep_wake(proxy.clone());
 
     // Tray icon
-    let _tray = TrayIcon::new(proxy)?;
+    let profiles: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+    let tray = TrayIcon::new(
+        proxy.clone(),
+        &profiles,
+        config.privacy_mode,
+        config.tray_click_toggles,
+        config.redo_available(),
+    )?;
 
     // Hotkey handling
-    let hotkey_handler = HotkeyHandler::new()?;
-    let hotkey_id = hotkey_handler.hotkey_id();
+    let hotkey_handler = HotkeyHandler::new(&config.hotkey, &hotkey_actions(&config))?;
 
     let (hotkey_tx, hotkey_rx) = unbounded::<HotkeyEvent>();
-    HotkeyHandler::listen(hotkey_tx, hotkey_id);
+    // The mouse button shares the hotkey channel so either can drive dictation
+    let mouse_trigger = config.mouse_button.map(|button| MouseTrigger {
+        button: i64::from(button) - 1,
+        swallow: config.swallow_mouse_button,
+        tx: hotkey_tx.clone(),
+    });
+    hotkey_handler.listen(hotkey_tx);
+    // Dropped on quit to unregister the hotkeys
+    let mut hotkey_handler = Some(hotkey_handler);
 
     // Smart triggers for activity-based prewarming
     let trigger_monitor = TriggerMonitor::new();
     let (trigger_tx, trigger_rx) = unbounded::<TriggerEvent>();
-    trigger_monitor.start(trigger_tx);
+    trigger_monitor.start(trigger_tx, mouse_trigger);
 
     // Cooldown configuration
     const COOLDOWN_SECS: u64 = 5;
     let mut last_activity = Instant::now();
 
     // Output handler and indicator
-    let mut output_handler = OutputHandler::new(config.output_mode)?;
-    let indicator = Arc::new(RecordingIndicator::new());
+    let mut output_handler = OutputHandler::new(&config, proxy.clone())?;
+    let (indicator, toast): (Arc<dyn Indicator>, _) = if config.indicator.enabled {
+        let indicator = RecordingIndicator::new(IndicatorStyle::from(&config.indicator));
+        let toast = config
+            .show_transcript_toast
+            .then(|| TranscriptToast::new(&indicator));
+        (Arc::new(indicator), toast)
+    } else {
+        (Arc::new(NullIndicator), None)
+    };
+    let panic_indicator = indicator.clone();
+    panic_hook::on_main_thread_panic(move || panic_indicator.hide());
+    let mut sounds = SoundPlayer::new(config.sound_feedback, config.sound_volume);
 
-    let recording_mode = config.recording_mode;
-    let mut is_toggle_recording = false;
+    let mut recording_mode = config.recording_mode;
+    // The press behind the current recording, and whether it's latched on
+    let mut press = RecordingPress::default();
+    // From `RecordingStarted`, to compare against the audio captured
+    let mut recording_started_at: Option<Instant> = None;
+    // While the recording is paused, when it was
+    let mut paused_at: Option<Instant> = None;
 
-    log::info!("Dictation App ready. Press Cmd+Shift+D to dictate.");
+    // A press made before the model was ready, started once it is
+    let mut pending_start = PendingStart::default();
+    let mut double_press = DoublePress::default();
+    // From a double press, for the current recording to end with
+    let mut recording_action: Option<DoublePressAction> = None;
+    // From the hotkey that started the current recording
+    let mut recording_options = TranscriptionOptions::default();
+
+    let mut mic_hint = MicHint::default();
+    let mut stream_feed = StreamFeed::default();
+
+    let mut stats = Stats::load(&Config::stats_path());
+    stats.set_saving(config.save_stats);
+
+    // A recording the worker's queue turned away, sent once there's room
+    let mut retry: RetrySlot<TranscriptionRequest> = RetrySlot::default();
+
+    // Most recent transcription, kept in memory for the repaste hotkey
+    let mut last_text: Option<String> = None;
+    // A redo with `redo_replaces`, whose text takes the place of the last
+    let mut replacing: Option<u64> = None;
+
+    // Set on quit while transcriptions are pending; the app exits once they
+    // finish or this passes
+    let mut quit_deadline: Option<Instant> = None;
+
+    // What `http_status_port` serves besides the state, kept current below
+    let status = Arc::new(Mutex::new(StatusSnapshot {
+        model: status::model_name(&config),
+        ..StatusSnapshot::default()
+    }));
+    let mut status_server = config.http_status_port.and_then(|port| {
+        StatusServer::start(port, state.clone(), status.clone())
+            .map_err(|e| log::error!("Can't serve the status endpoint on port {}: {}", port, e))
+            .ok()
+    });
+
+    log::info!("Dictation App ready. Press {} to dictate.", config.hotkey);
     log::info!("Recording mode: {:?}", recording_mode);
     log::info!("Smart triggers enabled ({}s cooldown)", COOLDOWN_SECS);
 
@@ -118,6 +408,76 @@ fn main() -> Result<()> {
 
         match event {
             Event::NewEvents(StartCause::Poll | StartCause::ResumeTimeReached { .. }) => {
+                // Quitting: only wait for pending results, ignore everything else
+                if let Some(deadline) = quit_deadline {
+                    if worker.pending() == 0 || Instant::now() >= deadline {
+                        if worker.pending() > 0 {
+                            log::warn!("Pending transcriptions did not finish in time, abandoning them");
+                            worker.cancel();
+                        }
+                        shutdown(&mut worker, &mut hotkey_handler, &mut instance_lock, &mut status_server, &mut stats);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    return;
+                }
+
+                stats.save_if_due();
+
+                // The input device went away: end any recording with what was
+                // captured so far, then rebuild against whatever device is available
+                if audio_capture.has_failed() {
+                    if audio_capture.is_recording() {
+                        log::warn!("Input device failed mid-recording, submitting captured audio");
+                        stop_and_submit(Stop {
+                            audio_capture: &mut audio_capture,
+                            worker: &worker,
+                            retry: &mut retry,
+                            indicator: indicator.as_ref(),
+                            state: &state,
+                            sounds: &sounds,
+                            config: &config,
+                            proxy: &proxy,
+                            action: recording_action.take(),
+                            options: std::mem::take(&mut recording_options),
+                        });
+                        press.stopped();
+                    }
+                    if let Err(e) = audio_capture.recover() {
+                        report("Failed to recover audio input", e, &proxy);
+                    }
+                }
+
+                // A toggle recording nobody stopped: submit it as if the
+                // hotkey had been pressed
+                if audio_capture.is_recording()
+                    && press.over_limit(Duration::from_secs(config.toggle_max_secs), Instant::now())
+                {
+                    log::warn!(
+                        "Toggle recording ran for {}s without being stopped, submitting it",
+                        config.toggle_max_secs
+                    );
+                    stop_and_submit(Stop {
+                        audio_capture: &mut audio_capture,
+                        worker: &worker,
+                        retry: &mut retry,
+                        indicator: indicator.as_ref(),
+                        state: &state,
+                        sounds: &sounds,
+                        config: &config,
+                        proxy: &proxy,
+                        action: recording_action.take(),
+                        options: std::mem::take(&mut recording_options),
+                    });
+                    press.stopped();
+                    indicator.blink_error_twice();
+                }
+
+                // The model is taking too long: give up on the queued press
+                if pending_start.expire(Instant::now()) {
+                    log::warn!("Model still not ready, dropping the queued recording");
+                    indicator.flash_error();
+                }
+
                 // Process trigger events (activity-based prewarming)
                 while let Ok(TriggerEvent::Activity) = trigger_rx.try_recv() {
                     last_activity = Instant::now();
@@ -128,6 +488,34 @@ fn main() -> Result<()> {
                     }
                 }
 
+                // Live level meter while recording. A paused recording
+                // captures nothing, so it has no level, time or preview, and
+                // its silence isn't a muted mic.
+                if audio_capture.is_paused() {
+                    mic_hint.reset();
+                } else if audio_capture.is_recording() {
+                    mic_hint.update(audio_capture.level(), indicator.as_ref());
+                    if let Some(started) = recording_started_at {
+                        indicator.set_elapsed(started.elapsed());
+                    }
+                    if config.streaming_preview {
+                        stream_feed.update(&audio_capture, &worker);
+                    }
+                } else {
+                    mic_hint.reset();
+                    stream_feed.reset();
+                }
+                if status_server.is_some() {
+                    let recording = audio_capture.is_recording();
+                    let mut snapshot = status.lock();
+                    snapshot.toggle_recording = recording && press.is_latched();
+                    snapshot.level = if recording && !audio_capture.is_paused() { audio_capture.level() } else { 0.0 };
+                    // Frozen while paused, as the indicator's timer is
+                    snapshot.recording_secs = recording_started_at
+                        .filter(|_| recording)
+                        .map(|started| paused_at.unwrap_or_else(Instant::now).duration_since(started).as_secs_f64());
+                }
+
                 // Cooldown check: if no activity for COOLDOWN_SECS, release audio stream
                 if audio_capture.is_warm() 
                     && !audio_capture.is_recording()
@@ -141,103 +529,1029 @@ fn main() -> Result<()> {
                     // Activity from hotkey press also resets cooldown
                     last_activity = Instant::now();
 
-                    match recording_mode {
-                        RecordingMode::PushToTalk => {
-                            match evt {
-                                HotkeyEvent::Pressed => {
-                                    start_recording(&mut audio_capture, &indicator, &state);
-                                }
-                                HotkeyEvent::Released => {
-                                    stop_and_submit(
-                                        &mut audio_capture,
-                                        &worker,
-                                        &indicator,
-                                        &state,
-                                    );
+                    // `[[hotkeys]]` entries and the spell hotkey start a
+                    // recording like the dictation hotkey, with their options
+                    let evt = match evt {
+                        HotkeyEvent::Pressed | HotkeyEvent::PressedIn(_) | HotkeyEvent::PressedToSpell => {
+                            if !audio_capture.is_recording() {
+                                recording_options = TranscriptionOptions {
+                                    language: match &evt {
+                                        HotkeyEvent::PressedIn(language) => Some(language.clone()),
+                                        _ => None,
+                                    },
+                                    spell: matches!(evt, HotkeyEvent::PressedToSpell),
+                                    redo: false,
+                                };
+                            }
+                            HotkeyEvent::Pressed
+                        }
+                        evt => evt,
+                    };
+                    match evt {
+                        HotkeyEvent::Pressed => press.hotkey_down(true),
+                        HotkeyEvent::Released => press.hotkey_down(false),
+                        _ => {}
+                    }
+
+                    match evt {
+                        HotkeyEvent::Repaste => {
+                            repaste_last(&mut output_handler, indicator.as_ref(), last_text.as_deref(), &proxy);
+                            continue;
+                        }
+                        HotkeyEvent::Redo => {
+                            let _ = proxy.send_event(AppEvent::Retranscribe);
+                            continue;
+                        }
+                        HotkeyEvent::Cancel => {
+                            if pending_start.clear() {
+                                log::info!("Queued recording cancelled");
+                                indicator.hide();
+                            }
+                            cancel(&mut audio_capture, &worker, &mut retry, indicator.as_ref(), &state);
+                            press.stopped();
+                            recording_action = None;
+                            recording_options = TranscriptionOptions::default();
+                            continue;
+                        }
+                        // In push-to-talk that means while the hotkey is held
+                        HotkeyEvent::Pause => {
+                            if audio_capture.resume_recording() {
+                                // The timer leaves out the pause
+                                if let (Some(started), Some(paused)) = (&mut recording_started_at, paused_at.take()) {
+                                    *started += paused.elapsed();
                                 }
+                                indicator.set_color_recording();
+                            } else if audio_capture.pause_recording() {
+                                paused_at = Some(Instant::now());
+                                indicator.set_color_paused();
+                            } else {
+                                log::debug!("Pause hotkey pressed while not recording");
                             }
+                            continue;
                         }
-                        RecordingMode::Toggle => {
+                        HotkeyEvent::Pressed
+                        | HotkeyEvent::Released
+                        | HotkeyEvent::PressedIn(_)
+                        | HotkeyEvent::PressedToSpell => {}
+                    }
+
+                    // Audio recorded now couldn't be transcribed, so the press
+                    // waits for the model instead
+                    if matches!(state.get(), ReadinessState::Cold | ReadinessState::Loading)
+                        && !audio_capture.is_recording()
+                    {
+                        model_manager.ensure_loaded();
+                        match evt {
+                            HotkeyEvent::Pressed => pending_start.press(recording_mode, Instant::now()),
+                            _ => pending_start.release(
+                                recording_mode,
+                                Instant::now(),
+                                Duration::from_millis(config.hybrid_hold_ms),
+                            ),
+                        }
+                        if pending_start.is_queued() {
+                            log::info!("Model is still loading, recording will start when it's ready");
+                            indicator.show();
+                            indicator.set_color_loading();
+                        } else {
+                            indicator.hide();
+                        }
+                        continue;
+                    }
+
+                    if config.double_press_action != DoublePressAction::Off {
+                        let double = match evt {
+                            HotkeyEvent::Pressed => double_press.press(
+                                recording_mode,
+                                Instant::now(),
+                                Duration::from_millis(config.double_press_ms),
+                            ),
+                            _ => double_press.release(recording_mode),
+                        };
+                        if double {
+                            log::info!("Hotkey double press: {:?}", config.double_press_action);
+                            if config.double_press_action == DoublePressAction::Repaste {
+                                let _ = proxy.send_event(AppEvent::DiscardRecording);
+                                let _ = proxy.send_event(AppEvent::Repaste);
+                                continue;
+                            }
+                            recording_action = Some(config.double_press_action);
+                            // Toggle and hybrid keep recording, or start again
+                            // if the first press stopped a recording; a
+                            // push-to-talk release goes on to stop as usual
                             if matches!(evt, HotkeyEvent::Pressed) {
-                                if !is_toggle_recording {
-                                    start_recording(&mut audio_capture, &indicator, &state);
-                                    is_toggle_recording = true;
-                                } else {
-                                    stop_and_submit(
-                                        &mut audio_capture,
-                                        &worker,
-                                        &indicator,
-                                        &state,
-                                    );
-                                    is_toggle_recording = false;
+                                if !audio_capture.is_recording() {
+                                    let _ = proxy.send_event(AppEvent::ToggleRecording);
                                 }
+                                continue;
                             }
                         }
                     }
+
+                    let _ = proxy.send_event(match evt {
+                        HotkeyEvent::Pressed if recording_mode == RecordingMode::PushToTalk => {
+                            AppEvent::StartRecording
+                        }
+                        // A press starts a toggle or hybrid recording, or
+                        // stops one latched by an earlier tap
+                        HotkeyEvent::Pressed => AppEvent::ToggleRecording,
+                        _ => match press.released(
+                            recording_mode,
+                            Instant::now(),
+                            Duration::from_millis(config.min_hold_ms),
+                            Duration::from_millis(config.hybrid_hold_ms),
+                        ) {
+                            Release::Stop => AppEvent::StopRecording,
+                            Release::Discard => {
+                                log::debug!("Hotkey released within min_hold_ms, treating as accidental");
+                                AppEvent::DiscardRecording
+                            }
+                            Release::Keep => continue,
+                        },
+                    });
                 }
             }
 
+            // Recording control: hotkeys arrive here, and anything else can
+            // send the same events
+            Event::UserEvent(AppEvent::StopRecording | AppEvent::ToggleRecording)
+                if audio_capture.is_recording() =>
+            {
+                stop_and_submit(Stop {
+                    audio_capture: &mut audio_capture,
+                    worker: &worker,
+                    retry: &mut retry,
+                    indicator: indicator.as_ref(),
+                    state: &state,
+                    sounds: &sounds,
+                    config: &config,
+                    proxy: &proxy,
+                    action: recording_action.take(),
+                    options: std::mem::take(&mut recording_options),
+                });
+                press.stopped();
+            }
+
+            Event::UserEvent(AppEvent::StopRecording) => log::debug!("Not recording, nothing to stop"),
+
+            Event::UserEvent(AppEvent::StartRecording) if audio_capture.is_recording() => {
+                log::debug!("Already recording");
+            }
+
+            Event::UserEvent(event @ (AppEvent::StartRecording | AppEvent::ToggleRecording)) => {
+                let toggle = matches!(event, AppEvent::ToggleRecording);
+                if start_recording(&mut audio_capture, indicator.as_ref(), &state, &sounds, &proxy) {
+                    press.started(recording_mode, toggle, Instant::now());
+                } else {
+                    press.start_failed();
+                    recording_action = None;
+                    recording_options = TranscriptionOptions::default();
+                }
+            }
+
+            Event::UserEvent(AppEvent::RecordingStarted(at)) => {
+                recording_started_at = Some(at);
+                paused_at = None;
+            }
+
+            // Captured audio falling well short of the time spent recording
+            // means the stream dropped buffers or started late
+            Event::UserEvent(AppEvent::RecordingStopped { duration_ms, samples }) => {
+                // Less a pause it ended in
+                let paused = paused_at.take().map(|at| at.elapsed()).unwrap_or_default();
+                let wall_ms = recording_started_at
+                    .take()
+                    .map(|at| at.elapsed().saturating_sub(paused).as_millis() as u64);
+                let message = format!(
+                    "Recorded {} ms ({} samples){}",
+                    duration_ms,
+                    samples,
+                    wall_ms.map(|ms| format!(" in {} ms", ms)).unwrap_or_default()
+                );
+                if config.log_timings {
+                    log::info!("{}", message);
+                } else {
+                    log::debug!("{}", message);
+                }
+            }
+
+            Event::UserEvent(AppEvent::Repaste) => {
+                repaste_last(&mut output_handler, indicator.as_ref(), last_text.as_deref(), &proxy);
+            }
+
+            Event::UserEvent(AppEvent::Retranscribe) => {
+                if audio_capture.is_recording() {
+                    log::info!("Not redoing the last recording while recording");
+                } else if !config.redo_available() {
+                    log::warn!("Nothing to redo with: set redo_model, and turn privacy mode off");
+                    indicator.flash_error();
+                } else if matches!(state.get(), ReadinessState::Cold | ReadinessState::Loading) {
+                    log::warn!("Model not loaded yet, not redoing the last recording");
+                    indicator.flash_error();
+                } else {
+                    let output_override = (!config.redo_replaces).then_some(OutputMode::ClipboardOnly);
+                    let options = TranscriptionOptions {
+                        redo: true,
+                        ..TranscriptionOptions::default()
+                    };
+                    let request = worker.request(Vec::new(), 0, None, output_override, false, options);
+                    log::info!("Redoing the last recording with {:?} as request {}", config.redo_model, request.id);
+                    if config.redo_replaces {
+                        replacing = Some(request.id);
+                    }
+                    indicator.set_color_processing();
+                    state.transition_to_transcribing();
+                    submit(&worker, &mut retry, indicator.as_ref(), &proxy, request);
+                }
+            }
+
+            Event::UserEvent(AppEvent::DiscardRecording) => {
+                if audio_capture.is_recording() {
+                    discard_recording(&mut audio_capture, indicator.as_ref(), &state);
+                }
+                press.stopped();
+                recording_action = None;
+                recording_options = TranscriptionOptions::default();
+            }
+
             // Handle transcription results from worker
-            Event::UserEvent(AppEvent::TranscriptionComplete(text)) => {
-                log::info!("Transcription complete, outputting text");
-                if let Err(e) = output_handler.output_text(&text) {
-                    log::error!("Failed to output text: {}", e);
+            Event::UserEvent(AppEvent::TranscriptionComplete {
+                id,
+                result,
+                output_override,
+                send_return,
+                timings,
+            }) => {
+                log::info!("Transcription {} complete, outputting text", id);
+                resubmit_held(&mut retry, &worker);
+                let text = result.text;
+                let app = output::frontmost_app();
+                log::debug!("Outputting into {:?}", app);
+                // The redo types over the text it redoes, or failing that is
+                // only copied
+                let output_override = if replacing.take_if(|replacing| *replacing == id).is_some() {
+                    match output_handler.scratch_last(config.scratch_that_max_chars) {
+                        Ok(Scratch::Delete(..)) => output_override,
+                        Ok(_) => {
+                            log::warn!("Can't take back the last transcript, copying the redo to the clipboard");
+                            Some(OutputMode::ClipboardOnly)
+                        }
+                        Err(e) => {
+                            report("Failed to take back the last transcript", e, &proxy);
+                            Some(OutputMode::ClipboardOnly)
+                        }
+                    }
+                } else {
+                    output_override
+                };
+                let output_started = Instant::now();
+                let output = output_handler.output_text(&text, output_override, send_return);
+                let output_failed = output.is_err();
+                if let Err(e) = output {
+                    report("Failed to output text", e, &proxy);
+                }
+                let summary = timings.summary(id, output_started.elapsed(), timings.stopped_at.elapsed());
+                if config.log_timings {
+                    log::info!("{}", summary);
+                } else {
+                    log::debug!("{}", summary);
+                }
+                stats.record_success(
+                    &text,
+                    timings.recorded_ms as f32 / 1000.0,
+                    output_started.duration_since(timings.stopped_at),
+                    app.as_ref().and_then(FrontmostApp::key),
+                );
+                if let Some(toast) = &toast {
+                    toast.show_text(&text);
+                }
+                if config.notifications {
+                    notify::transcript(&text);
+                }
+                last_text = Some(text);
+                status.lock().last_transcription_at = Some(timestamp::now_iso8601());
+                if transcription_done(&audio_capture, &worker) {
+                    if !output_failed {
+                        indicator.hide();
+                    } else {
+                        indicator.flash_error();
+                    }
+                    state.transition_to_idle();
+                } else if output_failed {
+                    indicator.blink_error();
                 }
-                indicator.hide();
-                state.transition_to_idle();
             }
 
-            Event::UserEvent(AppEvent::TranscriptionFailed) => {
-                log::info!("Transcription failed or no speech detected");
-                indicator.flash_error();
-                state.transition_to_idle();
+            Event::UserEvent(AppEvent::ScratchThat { id }) => {
+                log::info!("Transcription {} was \"scratch that\"", id);
+                resubmit_held(&mut retry, &worker);
+                let scratched = match output_handler.scratch_last(config.scratch_that_max_chars) {
+                    Ok(Scratch::Delete(..)) => {
+                        last_text = None;
+                        true
+                    }
+                    Ok(Scratch::Nothing) => {
+                        log::warn!("Nothing typed or pasted to scratch");
+                        false
+                    }
+                    Ok(Scratch::TooLong(chars)) => {
+                        log::warn!(
+                            "Not scratching {} characters, more than scratch_that_max_chars ({})",
+                            chars,
+                            config.scratch_that_max_chars
+                        );
+                        false
+                    }
+                    Err(e) => {
+                        report("Failed to scratch the last transcript", e, &proxy);
+                        false
+                    }
+                };
+                if transcription_done(&audio_capture, &worker) {
+                    if scratched {
+                        indicator.hide();
+                    } else {
+                        indicator.flash_error();
+                    }
+                    state.transition_to_idle();
+                } else if !scratched {
+                    indicator.blink_error();
+                }
+            }
+
+            Event::UserEvent(AppEvent::ModelLoadProgress(stage)) => match stage {
+                ModelLoadStage::Starting => log::info!("Model load starting"),
+                ModelLoadStage::ReadingFile => log::info!("Reading model file"),
+                ModelLoadStage::Warmup => log::info!("Warming up model"),
+                ModelLoadStage::Ready => {
+                    log::info!("Model ready");
+                    match pending_start.ready() {
+                        Some(Queued::Held { since }) => {
+                            press.queued(since);
+                            let _ = proxy.send_event(AppEvent::StartRecording);
+                        }
+                        Some(Queued::Latched) => {
+                            let _ = proxy.send_event(AppEvent::ToggleRecording);
+                        }
+                        None => {}
+                    }
+                }
+                ModelLoadStage::Failed(e) => {
+                    log::error!("Model failed to load: {}", e);
+                    if pending_start.clear() {
+                        indicator.flash_error();
+                    }
+                }
+            },
+
+            // The recording it was working on fails on its own
+            Event::UserEvent(AppEvent::WorkerCrashed(message)) => {
+                log::warn!("Transcription worker recovered from a crash ({}), reloading the model", message);
+            }
+
+            Event::UserEvent(AppEvent::WorkerStopped) if quit_deadline.is_none() => {
+                worker = spawn_worker(&model_manager, &config, &proxy);
+                match retry.resubmit(|request| worker.submit(request)) {
+                    Some(Submitted::Accepted(_)) => indicator.set_color_processing(),
+                    Some(_) => {
+                        log::error!("New transcription worker didn't take the recording, dropping it");
+                        retry.clear();
+                        indicator.flash_error();
+                        state.transition_to_idle();
+                    }
+                    None => {}
+                }
+            }
+
+            // The worker already started the reload, and the failure that
+            // caused it was flashed; the load reports progress as usual
+            Event::UserEvent(AppEvent::ModelReloading) => {
+                log::info!("Model reload started, dictations fail until it's done");
+            }
+
+            Event::UserEvent(AppEvent::OutputSuppressed { copied }) => {
+                if copied {
+                    log::warn!(
+                        "Secure input is on (password field?), copied the text to the clipboard instead of pasting it"
+                    );
+                } else {
+                    log::warn!("Secure input is on (password field?), not outputting the text");
+                }
+                if !audio_capture.is_recording() {
+                    indicator.flash_error();
+                }
+            }
+
+            Event::UserEvent(AppEvent::PartialTranscription(text)) if audio_capture.is_recording() => {
+                log::info!("Preview: {}", Transcript(&text));
+                indicator.set_preview(&text);
+            }
+
+            Event::UserEvent(AppEvent::TranscriptionFailed(reason)) => {
+                resubmit_held(&mut retry, &worker);
+                match &reason {
+                    FailureReason::NoSpeech => log::info!("No speech detected"),
+                    FailureReason::MicSilent => {
+                        if let Some(toast) = &toast {
+                            toast.show_text(&reason.to_string());
+                        }
+                    }
+                    FailureReason::TooShort => log::info!("Recording too short to transcribe"),
+                    FailureReason::ModelNotLoaded => {
+                        log::warn!("Model not loaded, loading it for the next attempt")
+                    }
+                    FailureReason::InferenceError(e) => log::error!("Transcription error: {}", e),
+                    FailureReason::Cancelled => log::info!("Transcription cancelled"),
+                    FailureReason::Timeout => log::warn!("Transcription timed out"),
+                    FailureReason::Unspellable(_) => log::info!("Nothing output for the spelled recording"),
+                    FailureReason::NothingToRedo => log::info!("No recording kept to redo"),
+                }
+                if config.notifications && reason != FailureReason::Cancelled {
+                    notify::failure(&reason);
+                }
+                stats.record_failure(reason.label());
+
+                if transcription_done(&audio_capture, &worker) {
+                    if reason != FailureReason::Cancelled {
+                        indicator.flash_error();
+                    } else {
+                        indicator.hide();
+                    }
+                    state.transition_to_idle();
+                }
+
+                // After the idle transition so the load can claim the state
+                if reason == FailureReason::ModelNotLoaded && !model_manager.is_loading() {
+                    state.transition_to_cold();
+                    model_manager.ensure_loaded();
+                }
+            }
+
+            Event::UserEvent(AppEvent::RecordingLimitReached) if audio_capture.is_recording() => {
+                log::warn!(
+                    "Recording reached the {}s limit, stopping",
+                    config.max_recording_secs
+                );
+                stop_and_submit(Stop {
+                    audio_capture: &mut audio_capture,
+                    worker: &worker,
+                    retry: &mut retry,
+                    indicator: indicator.as_ref(),
+                    state: &state,
+                    sounds: &sounds,
+                    config: &config,
+                    proxy: &proxy,
+                    action: recording_action.take(),
+                    options: std::mem::take(&mut recording_options),
+                });
+                press.stopped();
+                indicator.blink_error();
+            }
+
+            Event::UserEvent(AppEvent::AudioDeviceChanged(name)) => {
+                log::info!("Audio input device is now: {}", name);
+                if state.get() != ReadinessState::Transcribing {
+                    indicator.flash_error();
+                }
+            }
+
+            Event::UserEvent(AppEvent::ConfigReloaded(new_config)) => {
+                let new_config = Box::new(new_config.effective());
+                if new_config.privacy_mode != config.privacy_mode {
+                    log::info!("Privacy mode {}", if new_config.privacy_mode { "on" } else { "off" });
+                    tray.set_privacy_mode(new_config.privacy_mode);
+                }
+                tray.set_click_toggles(new_config.tray_click_toggles);
+                tray.set_redo_available(new_config.redo_available());
+                for name in config.restart_required(&new_config) {
+                    log::warn!("Config: {} changed, restart to apply it", name);
+                }
+
+                if output_handler.differs_from(&new_config) {
+                    match OutputHandler::new(&new_config, proxy.clone()) {
+                        Ok(handler) => output_handler = handler,
+                        Err(e) => log::error!("Failed to switch output mode: {}", e),
+                    }
+                }
+                if new_config.recording_mode != recording_mode {
+                    recording_mode = new_config.recording_mode;
+                    press.mode_changed();
+                }
+                sounds = SoundPlayer::new(new_config.sound_feedback, new_config.sound_volume);
+                indicator.set_style(IndicatorStyle::from(&new_config.indicator));
+                worker.reconfigure((*new_config).clone());
+
+                config = *new_config;
+                logging::configure(&config);
+                stats.set_saving(config.save_stats);
+                log::info!("Config reloaded");
             }
 
-            Event::UserEvent(AppEvent::Quit) => {
+            Event::UserEvent(AppEvent::ConfigReloadFailed(e)) => {
+                log::error!("Ignoring config change: {}", e);
+                if audio_capture.is_recording() || state.get() == ReadinessState::Transcribing {
+                    indicator.blink_error();
+                } else {
+                    indicator.flash_error();
+                }
+            }
+
+            Event::UserEvent(AppEvent::PermissionMissing(kind)) => {
+                log::error!(
+                    "{} permission is missing, grant it in {}",
+                    kind,
+                    kind.settings_path()
+                );
+                if !audio_capture.is_recording() {
+                    indicator.flash_error();
+                }
+            }
+
+            // A recording in progress is left alone; the device failure path
+            // picks it up if the stream died
+            Event::UserEvent(AppEvent::SystemWillSleep) if !audio_capture.is_recording() => {
+                log::info!("System going to sleep");
+                audio_capture.cooldown();
+                if config.unload_model_on_sleep
+                    && state.get() != ReadinessState::Transcribing
+                    && model_manager.is_loaded()
+                {
+                    model_manager.unload();
+                }
+            }
+
+            Event::UserEvent(AppEvent::SystemDidWake) => {
+                log::info!("System woke up");
+                if let Some(Err(e)) = hotkey_handler.as_ref().map(HotkeyHandler::reregister) {
+                    log::error!("Failed to re-register hotkeys: {}", e);
+                }
+                if !audio_capture.is_recording() {
+                    last_activity = Instant::now();
+                    if let Err(e) = audio_capture.reset().and_then(|_| audio_capture.prewarm()) {
+                        log::warn!("Failed to restart audio after wake: {}", e);
+                    }
+                }
+                model_manager.ensure_loaded();
+            }
+
+            Event::UserEvent(AppEvent::SwitchProfile(name)) => {
+                if audio_capture.is_recording() || state.get() == ReadinessState::Transcribing {
+                    log::warn!("Finish the current dictation before switching to profile '{}'", name);
+                    indicator.blink_error();
+                    return;
+                }
+
+                match Config::switch_profile(&config_path, &name, &overrides) {
+                    Ok(new_config) => {
+                        // The reload below treats these as restart-only, so
+                        // apply them here first
+                        if new_config.model_path() != config.model_path() {
+                            log::info!("Loading model {:?} for profile '{}'", new_config.model_path(), name);
+                            model_manager.switch_model(new_config.clone());
+                            config.model = new_config.model;
+                            config.model_path = new_config.model_path.clone();
+                            status.lock().model = status::model_name(&config);
+                        }
+                        if config.hotkeys_differ(&new_config) {
+                            let rebound = hotkey_handler.as_mut().map(|handler| {
+                                handler.rebind(&new_config.hotkey, &hotkey_actions(&new_config))
+                            });
+                            match rebound {
+                                Some(Err(e)) => log::error!("Failed to switch hotkeys: {}", e),
+                                _ => config.take_hotkeys(&new_config),
+                            }
+                        }
+                        log::info!("Switched to profile '{}'", name);
+                        let _ = proxy.send_event(AppEvent::ConfigReloaded(Box::new(new_config)));
+                    }
+                    Err(e) => {
+                        report(&format!("Failed to switch to profile '{}'", name), e, &proxy);
+                        indicator.flash_error();
+                    }
+                }
+            }
+
+            Event::UserEvent(AppEvent::TogglePrivacyMode) => {
+                match Config::set_privacy_mode(&config_path, !config.privacy_mode, &overrides) {
+                    Ok(new_config) => {
+                        let _ = proxy.send_event(AppEvent::ConfigReloaded(Box::new(new_config)));
+                    }
+                    Err(e) => {
+                        report("Failed to switch privacy mode", e, &proxy);
+                        indicator.flash_error();
+                    }
+                }
+            }
+
+            Event::UserEvent(AppEvent::OpenLogFolder) => {
+                let dir = Config::logs_dir();
+                let opened = fs::create_dir_all(&dir).and_then(|_| std::process::Command::new("open").arg(&dir).status());
+                if let Err(e) = opened {
+                    log::error!("Failed to open {:?}: {}", dir, e);
+                }
+            }
+
+            Event::UserEvent(AppEvent::ShowStats) => {
+                log::info!("This session: {}", stats.session);
+                log::info!("All time: {}", stats.total);
+            }
+
+            Event::UserEvent(AppEvent::Quit) if quit_deadline.is_none() => {
                 log::info!("Quit requested");
-                *control_flow = ControlFlow::Exit;
+                if audio_capture.is_recording() {
+                    let samples = audio_capture.stop_recording();
+                    log::info!("Discarded {} samples recorded before quitting", samples.len());
+                }
+
+                if worker.pending() > 0 {
+                    log::info!(
+                        "Waiting up to {:?} for {} pending transcription(s)",
+                        QUIT_GRACE,
+                        worker.pending()
+                    );
+                    quit_deadline = Some(Instant::now() + QUIT_GRACE);
+                } else {
+                    indicator.hide();
+                    shutdown(&mut worker, &mut hotkey_handler, &mut instance_lock, &mut status_server, &mut stats);
+                    *control_flow = ControlFlow::Exit;
+                }
             }
 
+            // `dictation://` URLs, from Shortcuts and the like
+            Event::Opened { urls } => {
+                for url in urls {
+                    match url_scheme::parse(url.as_str()) {
+                        Some(action) => {
+                            log::info!("Opened by URL {}", url);
+                            let _ = proxy.send_event(action.event());
+                        }
+                        None => log::warn!("Ignoring unknown URL {}", url),
+                    }
+                }
+            }
+
+            // Once per batch of events, so the icon follows every way a
+            // recording starts or ends
+            Event::MainEventsCleared => tray.set_recording(audio_capture.is_recording()),
+
             _ => {}
         }
     });
 }
 
+/// Repaste, cancel, spell and `[[hotkeys]]` hotkeys from `config`, for
+/// `HotkeyHandler`
+fn hotkey_actions(config: &Config) -> Vec<(&str, HotkeyEvent)> {
+    let mut actions = vec![(config.repaste_hotkey.as_str(), HotkeyEvent::Repaste)];
+    if let Some(cancel) = &config.cancel_hotkey {
+        actions.push((cancel.as_str(), HotkeyEvent::Cancel));
+    }
+    if let Some(spell) = &config.spell_hotkey {
+        actions.push((spell.as_str(), HotkeyEvent::PressedToSpell));
+    }
+    if let Some(pause) = &config.pause_hotkey {
+        actions.push((pause.as_str(), HotkeyEvent::Pause));
+    }
+    if let Some(redo) = &config.redo_hotkey {
+        actions.push((redo.as_str(), HotkeyEvent::Redo));
+    }
+    for entry in &config.hotkeys {
+        actions.push((entry.key.as_str(), HotkeyEvent::PressedIn(entry.language.clone())));
+    }
+    actions
+}
+
+/// Longest quit waits for pending transcriptions to be output
+const QUIT_GRACE: Duration = Duration::from_secs(5);
+
+/// Let the worker finish, save stats, and release the hotkeys, instance
+/// lock and status port before exiting
+fn shutdown(
+    worker: &mut TranscriptionWorker,
+    hotkey_handler: &mut Option<HotkeyHandler>,
+    instance_lock: &mut Option<InstanceLock>,
+    status_server: &mut Option<StatusServer>,
+    stats: &mut Stats,
+) {
+    worker.shutdown();
+    stats.save();
+    hotkey_handler.take();
+    instance_lock.take();
+    if let Some(mut server) = status_server.take() {
+        server.shutdown();
+    }
+}
+
+/// Input level below which the mic is considered silent
+const SILENT_LEVEL: f32 = 0.001;
+/// How long the input must stay silent before hinting that the mic may be muted
+const SILENT_HINT_AFTER: Duration = Duration::from_secs(1);
+
+/// Drives the indicator's level meter and tints it amber when the input has
+/// been silent long enough that the mic is probably muted.
+#[derive(Default)]
+struct MicHint {
+    quiet_since: Option<Instant>,
+    hinting: bool,
+}
+
+impl MicHint {
+    fn update(&mut self, level: f32, indicator: &dyn Indicator) {
+        indicator.set_level(level);
+
+        if level > SILENT_LEVEL {
+            self.quiet_since = None;
+            if self.hinting {
+                indicator.set_color_recording();
+                self.hinting = false;
+            }
+        } else {
+            let since = *self.quiet_since.get_or_insert_with(Instant::now);
+            if !self.hinting && since.elapsed() > SILENT_HINT_AFTER {
+                log::warn!("No input level for {:?}, is the microphone muted?", SILENT_HINT_AFTER);
+                indicator.set_color_error();
+                self.hinting = true;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.quiet_since = None;
+        self.hinting = false;
+    }
+}
+
+/// Send audio to the worker this often while recording with `streaming_preview`
+const STREAM_CHUNK: Duration = Duration::from_secs(1);
+
+/// Forwards newly captured audio to the worker for streaming previews.
+#[derive(Default)]
+struct StreamFeed {
+    offset: usize,
+}
+
+impl StreamFeed {
+    fn update(&mut self, audio_capture: &dyn AudioSource, worker: &TranscriptionWorker) {
+        let sample_rate = audio_capture.sample_rate();
+        let samples = audio_capture.samples_since(self.offset);
+        if samples.len() < (sample_rate as f32 * STREAM_CHUNK.as_secs_f32()) as usize {
+            return;
+        }
+
+        let len = samples.len();
+        worker.stream(StreamChunk {
+            samples,
+            sample_rate,
+            offset: self.offset,
+        });
+        self.offset += len;
+    }
+
+    fn reset(&mut self) {
+        self.offset = 0;
+    }
+}
+
+/// Returns false, leaving everything as it was, if the state doesn't allow
+/// recording (no model loaded yet) or the audio stream won't start
 fn start_recording(
-    audio_capture: &mut AudioCapture,
-    indicator: &RecordingIndicator,
+    audio_capture: &mut dyn AudioSource,
+    indicator: &dyn Indicator,
     state: &StateManager,
-) {
+    sounds: &SoundPlayer,
+    proxy: &EventLoopProxy<AppEvent>,
+) -> bool {
+    if !state.transition_to_recording() {
+        log::warn!("Can't start recording while {:?}", state.get());
+        return false;
+    }
     log::info!("Starting recording");
     indicator.show();
     indicator.set_color_recording();
-    if let Err(e) = audio_capture.start_recording() {
-        log::error!("Failed to start recording: {}", e);
+    // Blank out the capture while the cue plays so it isn't transcribed
+    match pipeline::start(audio_capture, sounds.play(Cue::Start)) {
+        Ok(()) => {
+            let _ = proxy.send_event(AppEvent::RecordingStarted(Instant::now()));
+            true
+        }
+        Err(e) => {
+            report("Failed to start recording", e, proxy);
+            indicator.hide();
+            state.transition_to_idle();
+            false
+        }
+    }
+}
+
+/// What `stop_and_submit` needs, filled in by the main loop at each stop.
+/// `action` and `options` are the recording's own, from the press that
+/// started it.
+struct Stop<'a> {
+    audio_capture: &'a mut dyn AudioSource,
+    worker: &'a TranscriptionWorker,
+    retry: &'a mut RetrySlot<TranscriptionRequest>,
+    indicator: &'a dyn Indicator,
+    state: &'a StateManager,
+    sounds: &'a SoundPlayer,
+    config: &'a Config,
+    proxy: &'a EventLoopProxy<AppEvent>,
+    action: Option<DoublePressAction>,
+    options: TranscriptionOptions,
+}
+
+/// Holding `config.clipboard_modifier` as this runs leaves the transcript on
+/// the clipboard instead of outputting it, and holding
+/// `config.skip_return_modifier` skips the Return of `send_return_after`.
+/// `action` is a double press's, which does the same without holding
+/// anything.
+fn stop_and_submit(stop: Stop) {
+    let Stop {
+        audio_capture,
+        worker,
+        retry,
+        indicator,
+        state,
+        sounds,
+        config,
+        proxy,
+        action,
+        options,
+    } = stop;
+    match &options.language {
+        Some(language) => log::info!("Stopping recording, to transcribe in \"{}\"", language),
+        None => log::info!("Stopping recording"),
+    }
+    let output_override = if action == Some(DoublePressAction::ClipboardOnly) {
+        log::info!("Double press, copying this transcript to the clipboard only");
+        Some(OutputMode::ClipboardOnly)
+    } else {
+        output::modifier_held(config.clipboard_modifier).then(|| {
+            log::info!("{:?} held, copying this transcript to the clipboard only", config.clipboard_modifier);
+            OutputMode::ClipboardOnly
+        })
+    };
+    let app = output::frontmost_app().and_then(|app| app.bundle_id);
+    // A double press asks for the Return itself, so the modifier only skips
+    // the one `send_return_after` would press
+    let send_return = output_override.is_none()
+        && (action == Some(DoublePressAction::SendReturn)
+            || config.send_return_after(app.as_deref()) && {
+                let skip = output::modifier_held(config.skip_return_modifier);
+                if skip {
+                    log::info!("{:?} held, not pressing Return after this transcript", config.skip_return_modifier);
+                }
+                !skip
+            });
+    let sample_rate = audio_capture.sample_rate();
+    // Submitting returns immediately
+    let stopped = pipeline::stop_and_submit(
+        audio_capture,
+        config.min_recording_ms,
+        retry,
+        |samples, sample_rate| {
+            // Change indicator to processing color
+            indicator.set_color_processing();
+            state.transition_to_transcribing();
+            worker.request(samples, sample_rate, app, output_override, send_return, options)
+        },
+        |request| worker.submit(request),
+    );
+    sounds.play(Cue::Stop);
+    let duration_ms = worker::duration_ms(stopped.samples, sample_rate);
+    let _ = proxy.send_event(AppEvent::RecordingStopped {
+        duration_ms,
+        samples: stopped.samples,
+    });
+
+    match stopped.submitted {
+        // UI stays responsive, indicator stays visible until worker completes
+        Some(submitted) => report_submitted(submitted, indicator, proxy),
+        None => {
+            log::warn!(
+                "Recording of {} ms is under min_recording_ms ({} ms), ignoring",
+                duration_ms,
+                config.min_recording_ms
+            );
+            indicator.hide();
+            state.transition_to_idle();
+        }
     }
-    state.transition_to_recording();
 }
 
-fn stop_and_submit(
-    audio_capture: &mut AudioCapture,
+/// Hand `request` to the worker, or to `retry` to go once there's room
+fn submit(
     worker: &TranscriptionWorker,
-    indicator: &RecordingIndicator,
+    retry: &mut RetrySlot<TranscriptionRequest>,
+    indicator: &dyn Indicator,
+    proxy: &EventLoopProxy<AppEvent>,
+    request: TranscriptionRequest,
+) {
+    report_submitted(retry.submit(request, |request| worker.submit(request)), indicator, proxy);
+}
+
+/// Show what came of handing a request to the worker
+fn report_submitted(submitted: Submitted, indicator: &dyn Indicator, proxy: &EventLoopProxy<AppEvent>) {
+    match submitted {
+        Submitted::Accepted(_) => {}
+        Submitted::Held => {
+            log::warn!("Transcription queue full, this recording goes once the current one finishes");
+            indicator.blink_queue_full();
+        }
+        Submitted::Dropped => {
+            log::warn!("Transcription queue full with a recording already waiting, dropping this one");
+            indicator.blink_queue_full();
+        }
+        Submitted::Disconnected => {
+            log::error!("Transcription worker is gone, starting a new one for this recording");
+            indicator.set_color_error();
+            let _ = proxy.send_event(AppEvent::WorkerStopped);
+        }
+    }
+}
+
+/// Whether a worker result leaves nothing for the indicator to show: no
+/// queued transcriptions, and no new recording already under way
+fn transcription_done(audio_capture: &dyn AudioSource, worker: &TranscriptionWorker) -> bool {
+    worker.pending() == 0 && !audio_capture.is_recording()
+}
+
+/// Discard the recording in progress, or abandon the pending transcription
+fn cancel(
+    audio_capture: &mut dyn AudioSource,
+    worker: &TranscriptionWorker,
+    retry: &mut RetrySlot<TranscriptionRequest>,
+    indicator: &dyn Indicator,
     state: &StateManager,
 ) {
-    log::info!("Stopping recording");
+    if audio_capture.is_recording() {
+        log::info!("Recording cancelled");
+        discard_recording(audio_capture, indicator, state);
+    } else if state.get() == ReadinessState::Transcribing {
+        if retry.clear() {
+            log::info!("Dropped the recording waiting for the queue");
+        }
+        worker.cancel();
+    }
+}
+
+/// Send the recording the queue turned away, now a transcription has made
+/// room for it
+fn resubmit_held(retry: &mut RetrySlot<TranscriptionRequest>, worker: &TranscriptionWorker) {
+    match retry.resubmit(|request| worker.submit(request)) {
+        Some(Submitted::Accepted(id)) => log::info!("Resubmitted the waiting recording as request {}", id),
+        Some(Submitted::Held) => log::debug!("Transcription queue still full"),
+        _ => {}
+    }
+}
+
+/// A transcription worker with a clone of `model_manager`, its threads
+/// each making their own VAD processor
+fn spawn_worker(
+    model_manager: &ModelManager,
+    config: &Config,
+    proxy: &EventLoopProxy<AppEvent>,
+) -> TranscriptionWorker {
+    TranscriptionWorker::new(model_manager.clone(), config.clone(), proxy.clone().into())
+}
+
+/// Stop recording and throw the audio away without any error feedback
+fn discard_recording(audio_capture: &mut dyn AudioSource, indicator: &dyn Indicator, state: &StateManager) {
     let samples = audio_capture.stop_recording();
-    let sample_rate = audio_capture.sample_rate();
+    log::info!("Discarded {} recorded samples", samples.len());
+    indicator.hide();
+    state.transition_to_idle();
+}
 
-    if samples.len() > 1600 {
-        // Change indicator to processing color
-        indicator.set_color_processing();
-        state.transition_to_transcribing();
+/// Log `error` with what can be done about it. A missing permission is
+/// also sent as `PermissionMissing`, which flashes the indicator.
+fn report(what: &str, error: impl Into<AppError>, proxy: &EventLoopProxy<AppEvent>) {
+    let error = error.into();
+    log::error!("{}: {}", what, error);
+    if let Some(hint) = error.hint() {
+        log::info!("{}", hint);
+    }
+    if let Some(kind) = error.missing_permission() {
+        let _ = proxy.send_event(AppEvent::PermissionMissing(kind));
+    }
+}
 
-        // Submit to worker - this returns immediately
-        worker.submit(TranscriptionRequest { samples, sample_rate });
-        // UI stays responsive, indicator stays visible until worker completes
-    } else {
-        log::warn!("Recording too short, ignoring");
-        indicator.hide();
-        state.transition_to_idle();
+fn repaste_last(
+    output_handler: &mut OutputHandler,
+    indicator: &dyn Indicator,
+    last_text: Option<&str>,
+    proxy: &EventLoopProxy<AppEvent>,
+) {
+    match last_text {
+        Some(text) => {
+            log::info!("Re-outputting last transcription");
+            if let Err(e) = output_handler.output_text(text, None, false) {
+                report("Failed to output text", e, proxy);
+                indicator.flash_error();
+            }
+        }
+        None => {
+            log::warn!("Repaste requested but nothing has been transcribed yet");
+            indicator.flash_error();
+        }
     }
 }
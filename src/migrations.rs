@@ -0,0 +1,112 @@
+//! Step-by-step upgrades of older `config.toml` documents to the current layout
+
+use toml::{Table, Value};
+
+use crate::config::ConfigError;
+
+/// Layout version written by this build. Files without a `version` key are 0.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to `n + 1`
+const MIGRATIONS: [fn(&mut Table); CURRENT_VERSION as usize] = [v0_nest_vad_keys];
+
+/// Upgrade `doc` in place to `CURRENT_VERSION`, returning whether anything
+/// changed. Fails on documents from a newer build rather than guessing.
+pub fn migrate(doc: &mut Table) -> Result<bool, ConfigError> {
+    let version = match doc.get("version") {
+        None => 0,
+        Some(Value::Integer(v)) => u32::try_from(*v).map_err(|_| ConfigError::BadVersion(v.to_string()))?,
+        Some(other) => return Err(ConfigError::BadVersion(other.to_string())),
+    };
+    if version > CURRENT_VERSION {
+        return Err(ConfigError::TooNew {
+            found: version,
+            supported: CURRENT_VERSION,
+        });
+    }
+    if version == CURRENT_VERSION {
+        return Ok(false);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(doc);
+    }
+    doc.insert("version".to_string(), Value::Integer(i64::from(CURRENT_VERSION)));
+    Ok(true)
+}
+
+/// 0 -> 1: top-level `vad_enabled` / `vad_threshold` move into `[vad]`,
+/// taking precedence over anything already there as they always did
+fn v0_nest_vad_keys(doc: &mut Table) {
+    let enabled = doc.remove("vad_enabled");
+    let threshold = doc.remove("vad_threshold");
+    if enabled.is_none() && threshold.is_none() {
+        return;
+    }
+
+    let vad = doc
+        .entry("vad")
+        .or_insert_with(|| Value::Table(Table::new()));
+    if let Value::Table(vad) = vad {
+        if let Some(enabled) = enabled {
+            vad.insert("enabled".to_string(), enabled);
+        }
+        if let Some(threshold) = threshold {
+            vad.insert("threshold".to_string(), threshold);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> Table {
+        content.parse().expect("Test TOML should parse")
+    }
+
+    /// A version 0 file with top-level VAD keys gets them moved under `[vad]`
+    #[test]
+    fn test_v0_vad_keys_move_into_table() {
+        let mut doc = parse(
+            "model = \"tiny_en\"\nvad_enabled = false\nvad_threshold = 0.7\n\n[vad]\nmin_speech_ms = 100\n",
+        );
+        assert!(migrate(&mut doc).unwrap());
+
+        let expected = parse(
+            "version = 1\nmodel = \"tiny_en\"\n\n[vad]\nenabled = false\nthreshold = 0.7\nmin_speech_ms = 100\n",
+        );
+        assert_eq!(doc, expected);
+    }
+
+    /// Old files without legacy keys only gain a version
+    #[test]
+    fn test_v0_without_legacy_keys_only_gains_version() {
+        let mut doc = parse("hotkey = \"Cmd+Shift+F\"\n");
+        assert!(migrate(&mut doc).unwrap());
+        assert_eq!(doc, parse("version = 1\nhotkey = \"Cmd+Shift+F\"\n"));
+    }
+
+    /// Migrated output serializes and migrates again as a no-op
+    #[test]
+    fn test_migrated_document_round_trips() {
+        let mut doc = parse("vad_threshold = 0.2\n");
+        migrate(&mut doc).unwrap();
+
+        let mut reparsed = parse(&toml::to_string_pretty(&doc).unwrap());
+        assert!(!migrate(&mut reparsed).unwrap());
+        assert_eq!(reparsed, doc);
+    }
+
+    /// Files from a newer build are refused with a readable error
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let mut doc = parse("version = 99\n");
+        let err = migrate(&mut doc).unwrap_err();
+        assert!(matches!(err, ConfigError::TooNew { found: 99, .. }));
+        assert!(err.to_string().contains("newer version"), "{}", err);
+
+        assert!(matches!(migrate(&mut parse("version = \"one\"\n")), Err(ConfigError::BadVersion(_))));
+        assert!(matches!(migrate(&mut parse("version = -1\n")), Err(ConfigError::BadVersion(_))));
+    }
+}
@@ -1,49 +1,176 @@
-use crate::config::Config;
+use crate::config::{Backend, Config};
+use crate::event_sender::EventSender;
+use crate::events::{AppEvent, ModelLoadStage};
 use crate::state::{ReadinessState, StateManager};
-use crate::transcriber::Transcriber;
-use anyhow::Result;
+use crate::transcriber::{Abort, ModelError, RemoteBackend, Transcriber, TranscriptionBackend, TranscriptionOutcome};
+use crate::wav;
 use parking_lot::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 
+/// Load whisper or connect to the server, whichever `config.backend` names
+pub fn open_backend(config: &Config) -> Result<Arc<dyn TranscriptionBackend>, ModelError> {
+    match config.backend {
+        Backend::Local => Ok(Arc::new(Transcriber::new(config.model_path(), config.whisper_threads)?)),
+        Backend::Remote => {
+            log::info!("Transcribing on {}", config.remote.endpoint);
+            Ok(Arc::new(RemoteBackend::new(&config.remote)))
+        }
+    }
+}
+
+/// Length of the built-in warmup clip
+const WARMUP_SECS: f32 = 2.0;
+/// Rate of the built-in clip, the one whisper runs at
+const WARMUP_SAMPLE_RATE: u32 = 16000;
+
+/// The audio warmup transcribes: `warmup_audio`, or a couple of seconds of
+/// made-up speech if it isn't set or can't be read
+pub fn warmup_clip(config: &Config) -> (Vec<f32>, u32) {
+    if let Some(path) = config.warmup_audio() {
+        match wav::read_wav(&path) {
+            Ok(clip) => return clip,
+            Err(e) => log::warn!("Can't read warmup_audio {:?}, using the built-in clip: {}", path, e),
+        }
+    }
+    (wav::synthetic_speech(WARMUP_SECS, WARMUP_SAMPLE_RATE), WARMUP_SAMPLE_RATE)
+}
+
+/// A whisper model and the file it was read from
+type LoadedFile = (PathBuf, Arc<dyn TranscriptionBackend>);
+
+/// How a load gets the backend for a config; `open_backend` outside tests
+pub type Opener = Arc<dyn Fn(&Config) -> Result<Arc<dyn TranscriptionBackend>, ModelError> + Send + Sync>;
+
+/// Cheap to clone; clones share the loaded model.
+#[derive(Clone)]
 pub struct ModelManager {
-    transcriber: Arc<Mutex<Option<Transcriber>>>,
+    /// Whisper, or the server for `backend = "remote"`. Only locked to take
+    /// a reference, so worker threads can transcribe at the same time.
+    backend: Arc<Mutex<Option<Arc<dyn TranscriptionBackend>>>>,
+    /// `redo_model` and its file, loaded by `load_secondary` alongside `backend`
+    secondary: Arc<Mutex<Option<LoadedFile>>>,
+    /// Bumped by `unload`, under the `secondary` lock, so a `load_secondary`
+    /// running meanwhile doesn't keep what it read
+    unloads: Arc<AtomicU64>,
+    /// Set while a background load is running so a retry can't start a second one
+    loading: Arc<AtomicBool>,
     state: StateManager,
-    config: Config,
+    /// Shared so a `switch_model` on any clone changes what every clone loads
+    config: Arc<Mutex<Config>>,
+    /// Bumped by `switch_model`, under the `config` lock, so a load that
+    /// started before a switch knows to start over
+    switches: Arc<AtomicU64>,
+    open: Opener,
+    /// Receives `ModelLoadProgress` as a load goes along
+    proxy: EventSender,
 }
 
 impl ModelManager {
-    pub fn new(state: StateManager, config: Config) -> Self {
+    pub fn new(state: StateManager, config: Config, proxy: EventSender) -> Self {
+        Self::with_opener(state, config, Arc::new(open_backend), proxy)
+    }
+
+    /// A manager that loads whatever `open` returns, for tests
+    pub fn with_opener(state: StateManager, config: Config, open: Opener, proxy: EventSender) -> Self {
         Self {
-            transcriber: Arc::new(Mutex::new(None)),
+            backend: Arc::new(Mutex::new(None)),
+            secondary: Arc::new(Mutex::new(None)),
+            unloads: Arc::new(AtomicU64::new(0)),
+            loading: Arc::new(AtomicBool::new(false)),
             state,
-            config,
+            config: Arc::new(Mutex::new(config)),
+            switches: Arc::new(AtomicU64::new(0)),
+            open,
+            proxy,
         }
     }
 
     pub fn load_async(&self) {
+        if self.loading.swap(true, Ordering::SeqCst) {
+            return;
+        }
         if !self.state.transition_to_loading() {
+            self.loading.store(false, Ordering::SeqCst);
             return;
         }
+        self.spawn_load(false);
+    }
 
-        let transcriber = self.transcriber.clone();
-        let state = self.state.clone();
-        let model_path = self.config.model_path();
+    /// Drop the model and load it again in the background, e.g. after it
+    /// kept failing. The state is left alone unless the load fails, and
+    /// transcriptions meanwhile fail with `ModelError::NotLoaded` rather than
+    /// waiting for it.
+    pub fn reload(&self) {
+        if self.loading.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        *self.backend.lock() = None;
+        log::info!("Model unloaded for a reload");
+        self.spawn_load(true);
+    }
 
-        thread::spawn(move || {
-            match Transcriber::new(model_path) {
-                Ok(t) => {
-                    if let Err(e) = t.warmup() {
+    /// Open the backend on a new thread and clear `loading` once it's done.
+    /// A `switch_model` meanwhile throws the result away and loads again.
+    fn spawn_load(&self, reload: bool) {
+        let proxy = self.proxy.clone();
+        let progress = move |stage| {
+            let _ = proxy.send_event(AppEvent::ModelLoadProgress(stage));
+        };
+        progress(ModelLoadStage::Starting);
+
+        let manager = self.clone();
+        thread::spawn(move || loop {
+            let (config, switches) = {
+                let config = manager.config.lock();
+                (config.clone(), manager.switches.load(Ordering::SeqCst))
+            };
+            if config.backend == Backend::Local {
+                progress(ModelLoadStage::ReadingFile);
+            }
+            let opened = (manager.open)(&config).inspect(|t| {
+                if config.warmup {
+                    progress(ModelLoadStage::Warmup);
+                    let (samples, sample_rate) = warmup_clip(&config);
+                    if let Err(e) = t.warmup(&samples, sample_rate) {
                         log::warn!("Warmup failed: {}", e);
                     }
-                    *transcriber.lock() = Some(t);
-                    state.transition_to_idle();
+                }
+            });
+
+            // Held until `loading` is cleared, so a switch either lands
+            // before the check or sees no load running and starts its own
+            let _config = manager.config.lock();
+            if manager.switches.load(Ordering::SeqCst) != switches {
+                log::info!("Model switched while loading, loading the new one");
+                continue;
+            }
+            match opened {
+                Ok(t) => {
+                    *manager.backend.lock() = Some(t);
+                    if !reload {
+                        manager.state.transition_to_idle();
+                    }
+                    progress(ModelLoadStage::Ready);
                 }
                 Err(e) => {
                     log::error!("Failed to load model: {}", e);
-                    state.transition_to_cold();
+                    if reload {
+                        // A recording or transcription in progress finds out
+                        // through `ModelNotLoaded` instead
+                        let _ = manager
+                            .state
+                            .transition_if(&[ReadinessState::Warm, ReadinessState::Hot], ReadinessState::Cold);
+                    } else {
+                        manager.state.transition_to_cold();
+                    }
+                    progress(ModelLoadStage::Failed(e.to_string()));
                 }
             }
+            manager.loading.store(false, Ordering::SeqCst);
+            break;
         });
     }
 
@@ -54,21 +181,112 @@ impl ModelManager {
         }
     }
 
+    /// Replace the loaded model with the one `config` names, for every
+    /// clone, reloads included. A load already running starts over with it
+    /// once it's done.
+    pub fn switch_model(&self, config: Config) {
+        let mut current = self.config.lock();
+        *current = config;
+        self.switches.fetch_add(1, Ordering::SeqCst);
+        if self.is_loading() {
+            log::info!("A model load is running, the new model loads once it's done");
+            return;
+        }
+        drop(current);
+        self.unload();
+        self.load_async();
+    }
+
     pub fn unload(&self) {
-        *self.transcriber.lock() = None;
+        *self.backend.lock() = None;
+        let mut secondary = self.secondary.lock();
+        *secondary = None;
+        self.unloads.fetch_add(1, Ordering::SeqCst);
+        drop(secondary);
         self.state.transition_to_cold();
         log::info!("Model unloaded");
     }
 
-    pub fn transcribe(&self, samples: &[f32], sample_rate: u32) -> Result<String> {
-        let guard = self.transcriber.lock();
-        match guard.as_ref() {
-            Some(t) => t.transcribe(samples, sample_rate),
-            None => Err(anyhow::anyhow!("Model not loaded")),
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+    ) -> Result<TranscriptionOutcome, ModelError> {
+        let backend = self.backend.lock().clone();
+        match backend {
+            Some(t) => t.transcribe(samples, sample_rate, abort, None, None),
+            None => Err(ModelError::NotLoaded),
+        }
+    }
+
+    pub fn transcribe_detailed(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome, ModelError> {
+        let backend = self.backend.lock().clone();
+        match backend {
+            Some(t) => t.transcribe(samples, sample_rate, abort, prompt, language),
+            None => Err(ModelError::NotLoaded),
         }
     }
 
+    /// Load the whisper file at `path` for `transcribe_secondary`, unless
+    /// it's the one already loaded. Blocks until it's ready, and leaves the
+    /// main model as it is.
+    pub fn load_secondary(&self, path: &Path) -> Result<(), ModelError> {
+        let unloads = {
+            let mut secondary = self.secondary.lock();
+            if secondary.as_ref().is_some_and(|(loaded, _)| loaded == path) {
+                return Ok(());
+            }
+            // Freed before the next one is read, so both never take memory at once
+            *secondary = None;
+            self.unloads.load(Ordering::SeqCst)
+        };
+        // Read without the lock, so an `unload` on the main thread never
+        // waits for it
+        log::info!("Loading {:?} as the redo model", path);
+        let threads = self.config.lock().whisper_threads;
+        let transcriber = Transcriber::new(path.to_path_buf(), threads)?;
+
+        let mut secondary = self.secondary.lock();
+        if self.unloads.load(Ordering::SeqCst) != unloads {
+            log::info!("Models unloaded while the redo model loaded, dropping it");
+            return Err(ModelError::NotLoaded);
+        }
+        // Another request may have loaded the same file meanwhile
+        if secondary.as_ref().is_none_or(|(loaded, _)| loaded != path) {
+            *secondary = Some((path.to_path_buf(), Arc::new(transcriber)));
+        }
+        Ok(())
+    }
+
+    /// `transcribe_detailed` with the model `load_secondary` loaded
+    pub fn transcribe_secondary(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome, ModelError> {
+        let secondary = self.secondary.lock().as_ref().map(|(_, t)| t.clone());
+        match secondary {
+            Some(t) => t.transcribe(samples, sample_rate, abort, prompt, language),
+            None => Err(ModelError::NotLoaded),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.load(Ordering::SeqCst)
+    }
+
     pub fn is_loaded(&self) -> bool {
-        self.transcriber.lock().is_some()
+        self.backend.lock().as_ref().is_some_and(|t| t.is_loaded())
     }
 }
@@ -0,0 +1,43 @@
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+
+use crate::events::FailureReason;
+use crate::indicator::truncate_chars;
+
+/// Characters of the transcript shown in a notification
+const NOTIFICATION_MAX_CHARS: usize = 120;
+
+/// Post "Transcription ready" with the start of `text`
+pub fn transcript(text: &str) {
+    post("Transcription ready", &truncate_chars(text, NOTIFICATION_MAX_CHARS));
+}
+
+/// Post "Transcription failed" with the reason
+pub fn failure(reason: &FailureReason) {
+    post("Transcription failed", &reason.to_string());
+}
+
+/// Hand a notification to Notification Center and return right away. The
+/// app doesn't activate, and nothing happens when it's clicked.
+fn post(title: &str, body: &str) {
+    unsafe {
+        // nil when the binary isn't running from an app bundle
+        let center: id = msg_send![class!(NSUserNotificationCenter), defaultUserNotificationCenter];
+        if center == nil {
+            log::debug!("Notification Center unavailable, skipping: {}", title);
+            return;
+        }
+
+        let notification: id = msg_send![class!(NSUserNotification), alloc];
+        let notification: id = msg_send![notification, init];
+        let title = NSString::alloc(nil).init_str(title);
+        let body = NSString::alloc(nil).init_str(body);
+        let _: () = msg_send![notification, setTitle: title];
+        let _: () = msg_send![notification, setInformativeText: body];
+        let _: () = msg_send![center, deliverNotification: notification];
+
+        let _: () = msg_send![title, release];
+        let _: () = msg_send![body, release];
+        let _: () = msg_send![notification, release];
+    }
+}
@@ -1,52 +1,238 @@
-use anyhow::Result;
 use arboard::Clipboard;
+use cocoa::base::{id, nil};
+use core_graphics::event::CGEventFlags;
 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
+use tao::event_loop::EventLoopProxy;
 
-use crate::config::OutputMode;
+use crate::accessibility;
+use crate::config::{ClipboardModifier, Config, OutputMode, PasteBackend};
+use crate::events::AppEvent;
+use crate::logging::Transcript;
+
+mod dispatch;
+mod error;
+mod file;
+mod keyboard;
+mod keys;
+
+pub use dispatch::TextOutput;
+pub use error::OutputError;
+pub use keys::release_held;
+pub use dispatch::Scratch;
+use dispatch::{Dispatcher, Outcome, Outputs};
+
+type Result<T> = std::result::Result<T, OutputError>;
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn IsSecureEventInputEnabled() -> u8;
+}
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceFlagsState(state_id: i32) -> u64;
+}
+
+/// `kCGEventSourceStateCombinedSessionState`: keys held in any app
+const COMBINED_SESSION_STATE: i32 = 0;
+
+/// Whether `modifier` is held down right now
+pub fn modifier_held(modifier: ClipboardModifier) -> bool {
+    let mask = match modifier {
+        ClipboardModifier::Option => CGEventFlags::CGEventFlagAlternate,
+        ClipboardModifier::Control => CGEventFlags::CGEventFlagControl,
+        ClipboardModifier::Shift => CGEventFlags::CGEventFlagShift,
+        ClipboardModifier::Command => CGEventFlags::CGEventFlagCommand,
+        ClipboardModifier::Off => return false,
+    };
+    let flags = unsafe { CGEventSourceFlagsState(COMBINED_SESSION_STATE) };
+    CGEventFlags::from_bits_truncate(flags).contains(mask)
+}
+
+/// Whether some app (usually a focused password field) has turned on secure
+/// keyboard entry
+fn secure_input_enabled() -> bool {
+    unsafe { IsSecureEventInputEnabled() != 0 }
+}
+
+/// The frontmost app, the one text is output into
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontmostApp {
+    pub bundle_id: Option<String>,
+    /// Localized, e.g. "Safari"
+    pub name: Option<String>,
+}
+
+impl FrontmostApp {
+    /// What to file the app's stats under: its bundle id, or its name for
+    /// the odd app without one
+    pub fn key(&self) -> Option<&str> {
+        self.bundle_id.as_deref().or(self.name.as_deref())
+    }
+}
+
+/// `None` when no app is frontmost. Cheap enough to call per transcript.
+pub fn frontmost_app() -> Option<FrontmostApp> {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let app: id = msg_send![workspace, frontmostApplication];
+        if app == nil {
+            return None;
+        }
+        Some(FrontmostApp {
+            bundle_id: ns_string(msg_send![app, bundleIdentifier]),
+            name: ns_string(msg_send![app, localizedName]),
+        })
+    }
+}
+
+/// A copy of `string`, an `NSString` or nil
+unsafe fn ns_string(string: id) -> Option<String> {
+    if string == nil {
+        return None;
+    }
+    let utf8: *const c_char = msg_send![string, UTF8String];
+    (!utf8.is_null()).then(|| CStr::from_ptr(utf8).to_string_lossy().into_owned())
+}
 
 pub struct OutputHandler {
-    clipboard: Clipboard,
+    dispatcher: Dispatcher,
+    /// Presses Return after the text
     enigo: Enigo,
-    mode: OutputMode,
+    /// Between the output and Return, when one is sent
+    return_delay: Duration,
+    paste_backend: PasteBackend,
+    output_file: Option<PathBuf>,
+    also_copy: bool,
+    accessibility_fallback: OutputMode,
+    proxy: EventLoopProxy<AppEvent>,
 }
 
 impl OutputHandler {
-    pub fn new(mode: OutputMode) -> Result<Self> {
-        let clipboard = Clipboard::new()?;
-        let enigo = Enigo::new(&Settings::default())?;
-        Ok(Self { clipboard, enigo, mode })
+    pub fn new(config: &Config, proxy: EventLoopProxy<AppEvent>) -> Result<Self> {
+        let outputs = Outputs {
+            clipboard: Box::new(ClipboardOutput::new(config.paste_backend)?),
+            keystroke: Box::new(KeystrokeOutput::new()?),
+            clipboard_only: Box::new(CopyOutput::new()?),
+            file: Box::new(FileOutput::new(config.output_file(), config.also_copy)?),
+            accessibility: Box::new(AccessibilityOutput::new(config)?),
+        };
+        Ok(Self {
+            dispatcher: Dispatcher::new(
+                outputs,
+                config.output_mode,
+                config.append,
+                config.secure_input_behavior,
+            ),
+            enigo: Enigo::new(&Settings::default())?,
+            return_delay: Duration::from_millis(config.return_delay_ms),
+            paste_backend: config.paste_backend,
+            output_file: config.output_file(),
+            also_copy: config.also_copy,
+            accessibility_fallback: config.accessibility_fallback,
+            proxy,
+        })
+    }
+
+    /// Whether `new` would build a different handler from `config`
+    pub fn differs_from(&self, config: &Config) -> bool {
+        self.dispatcher.mode != config.output_mode
+            || self.dispatcher.secure_input != config.secure_input_behavior
+            || self.paste_backend != config.paste_backend
+            || self.dispatcher.append != config.append
+            || self.return_delay != Duration::from_millis(config.return_delay_ms)
+            || self.output_file != config.output_file()
+            || self.also_copy != config.also_copy
+            || self.accessibility_fallback != config.accessibility_fallback
     }
 
-    pub fn output_text(&mut self, text: &str) -> Result<()> {
-        if text.is_empty() {
-            log::warn!("No text to output");
-            return Ok(());
+    /// `mode` overrides the configured output mode for this text.
+    /// `send_return` presses Return once the text is pasted or typed.
+    pub fn output_text(&mut self, text: &str, mode: Option<OutputMode>, send_return: bool) -> Result<()> {
+        let outcome = self.dispatcher.output(text, mode, secure_input_enabled)?;
+        if let Outcome::Suppressed { copied } = outcome {
+            let _ = self.proxy.send_event(AppEvent::OutputSuppressed { copied });
         }
+        if send_return && outcome.typed() {
+            thread::sleep(self.return_delay);
+            log::info!("Pressing Return");
+            // Backspace can't take back a sent message
+            self.dispatcher.last_typed = None;
+            self.enigo.key(Key::Return, Direction::Click)?;
+        }
+        Ok(())
+    }
 
-        match self.mode {
-            OutputMode::Clipboard => self.paste_text(text),
-            OutputMode::Keystroke => self.type_text(text),
+    /// Delete the text the last output typed or pasted, unless it's over
+    /// `max_chars`. Anything typed since by hand goes first, so this is only
+    /// as safe as the cap.
+    pub fn scratch_last(&mut self, max_chars: usize) -> Result<Scratch> {
+        let scratch = self.dispatcher.scratch(max_chars);
+        if let Scratch::Delete(mode, chars) = scratch {
+            log::info!("Scratching {} character(s) output by {:?}", chars, mode);
+            keys::backspace(&mut self.enigo, chars, Duration::from_millis(5))?;
         }
+        Ok(scratch)
     }
+}
+
+/// Pastes with Cmd+V (`OutputMode::Clipboard`)
+pub struct ClipboardOutput {
+    clipboard: Clipboard,
+    enigo: Enigo,
+    backend: PasteBackend,
+}
+
+impl ClipboardOutput {
+    pub fn new(backend: PasteBackend) -> Result<Self> {
+        Ok(Self {
+            clipboard: Clipboard::new()?,
+            enigo: Enigo::new(&Settings::default())?,
+            backend,
+        })
+    }
+}
 
-    fn paste_text(&mut self, text: &str) -> Result<()> {
-        log::info!("Pasting text via clipboard: {}", text);
+impl TextOutput for ClipboardOutput {
+    fn output_text(&mut self, text: &str) -> Result<()> {
+        log::info!("Pasting text via clipboard: {}", Transcript(text));
 
         self.clipboard.set_text(text)?;
         thread::sleep(Duration::from_millis(50));
 
-        self.enigo.key(Key::Meta, Direction::Press)?;
-        self.enigo.key(Key::Unicode('v'), Direction::Click)?;
-        self.enigo.key(Key::Meta, Direction::Release)?;
+        match self.backend {
+            PasteBackend::Enigo => {
+                keys::shortcut(&mut self.enigo, Key::Meta, Key::Unicode('v'))?;
+            }
+            PasteBackend::CgEvent => keyboard::paste()?,
+        }
 
         Ok(())
     }
+}
+
+/// Types each character (`OutputMode::Keystroke`)
+pub struct KeystrokeOutput {
+    enigo: Enigo,
+}
+
+impl KeystrokeOutput {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            enigo: Enigo::new(&Settings::default())?,
+        })
+    }
+}
+
+impl TextOutput for KeystrokeOutput {
+    fn output_text(&mut self, text: &str) -> Result<()> {
+        log::info!("Typing text via keystrokes: {}", Transcript(text));
 
-    fn type_text(&mut self, text: &str) -> Result<()> {
-        log::info!("Typing text via keystrokes: {}", text);
-        
         for c in text.chars() {
             self.enigo.key(Key::Unicode(c), Direction::Click)?;
             thread::sleep(Duration::from_millis(5));
@@ -55,3 +241,93 @@ impl OutputHandler {
         Ok(())
     }
 }
+
+/// Leaves the text on the clipboard (`OutputMode::ClipboardOnly`)
+pub struct CopyOutput {
+    clipboard: Clipboard,
+}
+
+impl CopyOutput {
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            clipboard: Clipboard::new()?,
+        })
+    }
+}
+
+impl TextOutput for CopyOutput {
+    fn output_text(&mut self, text: &str) -> Result<()> {
+        log::info!("Copying text to the clipboard: {}", Transcript(text));
+        self.clipboard.set_text(text)?;
+        Ok(())
+    }
+}
+
+/// Appends to `output_file` (`OutputMode::File`)
+pub struct FileOutput {
+    path: Option<PathBuf>,
+    /// Set when `also_copy` is on
+    clipboard: Option<Clipboard>,
+}
+
+impl FileOutput {
+    pub fn new(path: Option<PathBuf>, also_copy: bool) -> Result<Self> {
+        Ok(Self {
+            path,
+            clipboard: also_copy.then(Clipboard::new).transpose()?,
+        })
+    }
+}
+
+impl TextOutput for FileOutput {
+    fn output_text(&mut self, text: &str) -> Result<()> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or(OutputError::NoOutputFile)?;
+        log::info!("Appending text to {:?}: {}", path, Transcript(text));
+        if let Some(clipboard) = &mut self.clipboard {
+            clipboard.set_text(text)?;
+        }
+        file::append(path, text)
+    }
+}
+
+/// Inserts at the caret, or hands the text to `accessibility_fallback`
+/// where that isn't possible (`OutputMode::Accessibility`)
+pub struct AccessibilityOutput {
+    fallback_mode: OutputMode,
+    fallback: Box<dyn TextOutput>,
+}
+
+impl AccessibilityOutput {
+    pub fn new(config: &Config) -> Result<Self> {
+        let fallback: Box<dyn TextOutput> = match config.accessibility_fallback {
+            OutputMode::Keystroke => Box::new(KeystrokeOutput::new()?),
+            _ => Box::new(ClipboardOutput::new(config.paste_backend)?),
+        };
+        Ok(Self {
+            fallback_mode: config.accessibility_fallback,
+            fallback,
+        })
+    }
+}
+
+impl TextOutput for AccessibilityOutput {
+    fn output_text(&mut self, text: &str) -> Result<()> {
+        match accessibility::insert_text(text) {
+            Ok(()) => {
+                log::info!("Inserted text via accessibility: {}", Transcript(text));
+                Ok(())
+            }
+            Err(reason) => {
+                log::info!(
+                    "Can't insert via accessibility ({}), using {:?} instead",
+                    reason,
+                    self.fallback_mode
+                );
+                self.fallback.output_text(text)
+            }
+        }
+    }
+}
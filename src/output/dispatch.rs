@@ -0,0 +1,297 @@
+//! Picking the output for a transcript, kept apart from the clipboard and
+//! keyboard so it can be tested
+
+use super::OutputError;
+use crate::config::{Append, OutputMode, SecureInputBehavior};
+use crate::postprocess;
+
+type Result<T> = std::result::Result<T, OutputError>;
+
+/// Somewhere a transcript can be sent
+pub trait TextOutput {
+    fn output_text(&mut self, text: &str) -> Result<()>;
+}
+
+/// One `TextOutput` for each `OutputMode`
+pub struct Outputs {
+    pub clipboard: Box<dyn TextOutput>,
+    pub keystroke: Box<dyn TextOutput>,
+    pub clipboard_only: Box<dyn TextOutput>,
+    pub file: Box<dyn TextOutput>,
+    pub accessibility: Box<dyn TextOutput>,
+}
+
+impl Outputs {
+    pub fn get(&mut self, mode: OutputMode) -> &mut dyn TextOutput {
+        match mode {
+            OutputMode::Clipboard => self.clipboard.as_mut(),
+            OutputMode::Keystroke => self.keystroke.as_mut(),
+            OutputMode::ClipboardOnly => self.clipboard_only.as_mut(),
+            OutputMode::File => self.file.as_mut(),
+            OutputMode::Accessibility => self.accessibility.as_mut(),
+        }
+    }
+}
+
+/// What `Dispatcher::output` did with the text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// There was no text
+    Empty,
+    /// Sent to this mode's output
+    Output(OutputMode),
+    /// Secure input was on, so nothing was pasted or typed; `copied` says
+    /// whether the text went to the clipboard instead
+    Suppressed { copied: bool },
+}
+
+impl Outcome {
+    /// Whether the text went into the focused app, so pressing Return after
+    /// it makes sense
+    pub fn typed(self) -> bool {
+        matches!(
+            self,
+            Self::Output(OutputMode::Clipboard | OutputMode::Keystroke | OutputMode::Accessibility)
+        )
+    }
+}
+
+/// What `Dispatcher::scratch` found to delete
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scratch {
+    /// Nothing typed or pasted since the last scratch or other output
+    Nothing,
+    /// This many characters, typed or pasted by this mode
+    Delete(OutputMode, usize),
+    /// More characters than the cap, so they're left alone
+    TooLong(usize),
+}
+
+pub struct Dispatcher {
+    outputs: Outputs,
+    pub mode: OutputMode,
+    pub append: Append,
+    pub secure_input: SecureInputBehavior,
+    /// The mode and length in characters of the last output, when it was
+    /// typed or pasted and so can be taken back with Backspace
+    pub last_typed: Option<(OutputMode, usize)>,
+}
+
+impl Dispatcher {
+    pub fn new(outputs: Outputs, mode: OutputMode, append: Append, secure_input: SecureInputBehavior) -> Self {
+        Self {
+            outputs,
+            mode,
+            append,
+            secure_input,
+            last_typed: None,
+        }
+    }
+
+    /// Send `text` to the output for `mode`, or the configured mode.
+    /// `secure_input_enabled` is only asked when the answer matters.
+    pub fn output(
+        &mut self,
+        text: &str,
+        mode: Option<OutputMode>,
+        secure_input_enabled: impl FnOnce() -> bool,
+    ) -> Result<Outcome> {
+        self.last_typed = None;
+        if text.is_empty() {
+            log::warn!("No text to output");
+            return Ok(Outcome::Empty);
+        }
+        let mode = mode.unwrap_or(self.mode);
+
+        // Nothing is typed, so secure input doesn't matter
+        if mode == OutputMode::File {
+            self.outputs.file.output_text(text)?;
+            return Ok(Outcome::Output(mode));
+        }
+        let text = &postprocess::with_suffix(text, self.append);
+
+        if self.secure_input != SecureInputBehavior::Ignore && secure_input_enabled() {
+            let copied = self.secure_input == SecureInputBehavior::ClipboardOnly;
+            if copied {
+                self.outputs.clipboard_only.output_text(text)?;
+            }
+            return Ok(Outcome::Suppressed { copied });
+        }
+
+        self.outputs.get(mode).output_text(text)?;
+        if matches!(mode, OutputMode::Clipboard | OutputMode::Keystroke) {
+            self.last_typed = Some((mode, text.chars().count()));
+        }
+        Ok(Outcome::Output(mode))
+    }
+
+    /// Forget the last output, returning how many characters to delete to
+    /// take it back, up to `max_chars`
+    pub fn scratch(&mut self, max_chars: usize) -> Scratch {
+        match self.last_typed.take() {
+            None => Scratch::Nothing,
+            Some((_, chars)) if chars > max_chars => Scratch::TooLong(chars),
+            Some((mode, chars)) => Scratch::Delete(mode, chars),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    type Calls = Rc<RefCell<Vec<(&'static str, String)>>>;
+
+    /// Records what it was asked to output, under its name, or fails
+    struct MockOutput {
+        name: &'static str,
+        calls: Calls,
+        fail: bool,
+    }
+
+    impl TextOutput for MockOutput {
+        fn output_text(&mut self, text: &str) -> Result<()> {
+            if self.fail {
+                return Err(OutputError::Keyboard(format!("{} failed", self.name)));
+            }
+            self.calls.borrow_mut().push((self.name, text.to_string()));
+            Ok(())
+        }
+    }
+
+    fn dispatcher(failing: Option<&'static str>) -> (Dispatcher, Calls) {
+        let calls = Calls::default();
+        let mock = |name| -> Box<dyn TextOutput> {
+            Box::new(MockOutput {
+                name,
+                calls: calls.clone(),
+                fail: failing == Some(name),
+            })
+        };
+        let outputs = Outputs {
+            clipboard: mock("clipboard"),
+            keystroke: mock("keystroke"),
+            clipboard_only: mock("clipboard_only"),
+            file: mock("file"),
+            accessibility: mock("accessibility"),
+        };
+        let dispatcher = Dispatcher::new(outputs, OutputMode::Clipboard, Append::None, SecureInputBehavior::Block);
+        (dispatcher, calls)
+    }
+
+    fn taken(calls: &Calls) -> Vec<(&'static str, String)> {
+        calls.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn test_dispatches_by_mode() {
+        let (mut dispatcher, calls) = dispatcher(None);
+        for (mode, name) in [
+            (OutputMode::Clipboard, "clipboard"),
+            (OutputMode::Keystroke, "keystroke"),
+            (OutputMode::ClipboardOnly, "clipboard_only"),
+            (OutputMode::File, "file"),
+            (OutputMode::Accessibility, "accessibility"),
+        ] {
+            dispatcher.mode = mode;
+            assert_eq!(dispatcher.output("hi", None, || false).unwrap(), Outcome::Output(mode));
+            assert_eq!(taken(&calls), vec![(name, "hi".to_string())]);
+        }
+
+        // An override beats the configured mode
+        dispatcher.mode = OutputMode::Clipboard;
+        let outcome = dispatcher.output("hi", Some(OutputMode::ClipboardOnly), || false).unwrap();
+        assert_eq!(outcome, Outcome::Output(OutputMode::ClipboardOnly));
+        assert_eq!(taken(&calls), vec![("clipboard_only", "hi".to_string())]);
+    }
+
+    #[test]
+    fn test_empty_text_outputs_nothing() {
+        let (mut dispatcher, calls) = dispatcher(None);
+        let outcome = dispatcher.output("", None, || panic!("Asked about secure input")).unwrap();
+        assert_eq!(outcome, Outcome::Empty);
+        assert!(!outcome.typed());
+        assert!(taken(&calls).is_empty());
+    }
+
+    #[test]
+    fn test_errors_are_returned() {
+        let (mut dispatcher, calls) = dispatcher(Some("keystroke"));
+        dispatcher.mode = OutputMode::Keystroke;
+        let err = dispatcher.output("hi", None, || false).unwrap_err();
+        assert_eq!(err.to_string(), "keystroke failed");
+        assert!(taken(&calls).is_empty());
+        // Other modes are unaffected
+        assert!(dispatcher.output("hi", Some(OutputMode::Clipboard), || false).is_ok());
+    }
+
+    /// `append` applies to what's typed or copied, not to the file, which
+    /// adds its own line breaks
+    #[test]
+    fn test_suffix_skips_file() {
+        let (mut dispatcher, calls) = dispatcher(None);
+        dispatcher.append = Append::Space;
+        dispatcher.output("hi", None, || false).unwrap();
+        dispatcher.output("hi", Some(OutputMode::File), || false).unwrap();
+        assert_eq!(
+            taken(&calls),
+            vec![("clipboard", "hi ".to_string()), ("file", "hi".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_secure_input() {
+        let (mut dispatcher, calls) = dispatcher(None);
+        let outcome = dispatcher.output("hi", None, || true).unwrap();
+        assert_eq!(outcome, Outcome::Suppressed { copied: false });
+        assert!(taken(&calls).is_empty());
+
+        dispatcher.secure_input = SecureInputBehavior::ClipboardOnly;
+        let outcome = dispatcher.output("hi", None, || true).unwrap();
+        assert_eq!(outcome, Outcome::Suppressed { copied: true });
+        assert_eq!(taken(&calls), vec![("clipboard_only", "hi".to_string())]);
+
+        // Never asked when ignored or writing to a file
+        dispatcher.secure_input = SecureInputBehavior::Ignore;
+        dispatcher.output("hi", None, || panic!("Asked about secure input")).unwrap();
+        dispatcher.secure_input = SecureInputBehavior::Block;
+        dispatcher.output("hi", Some(OutputMode::File), || panic!("Asked about secure input")).unwrap();
+    }
+
+    /// Only text typed or pasted by the last output can be scratched, once
+    #[test]
+    fn test_scratch_takes_back_the_last_typed_text() {
+        let (mut dispatcher, _calls) = dispatcher(None);
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+
+        dispatcher.append = Append::Space;
+        dispatcher.output("héllo", None, || false).unwrap();
+        assert_eq!(dispatcher.scratch(100), Scratch::Delete(OutputMode::Clipboard, 6));
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+
+        dispatcher.output("hi", Some(OutputMode::Keystroke), || false).unwrap();
+        assert_eq!(dispatcher.scratch(2), Scratch::TooLong(3));
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+
+        // A later output that typed nothing leaves nothing to scratch
+        dispatcher.output("hi", None, || false).unwrap();
+        dispatcher.output("hi", Some(OutputMode::File), || false).unwrap();
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+        dispatcher.output("hi", None, || false).unwrap();
+        dispatcher.output("hi", None, || true).unwrap();
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+        dispatcher.output("hi", Some(OutputMode::Accessibility), || false).unwrap();
+        assert_eq!(dispatcher.scratch(100), Scratch::Nothing);
+    }
+
+    #[test]
+    fn test_return_only_follows_typed_text() {
+        assert!(Outcome::Output(OutputMode::Clipboard).typed());
+        assert!(Outcome::Output(OutputMode::Accessibility).typed());
+        assert!(!Outcome::Output(OutputMode::ClipboardOnly).typed());
+        assert!(!Outcome::Output(OutputMode::File).typed());
+        assert!(!Outcome::Suppressed { copied: true }.typed());
+    }
+}
@@ -0,0 +1,28 @@
+//! Why a transcript couldn't be pasted, typed, copied or written
+
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum OutputError {
+    #[error("clipboard failed: {0}")]
+    Clipboard(#[from] arboard::Error),
+    /// A simulated key press was refused, usually for lack of the
+    /// Accessibility permission
+    #[error("couldn't send key presses: {0}")]
+    Input(#[from] enigo::InputError),
+    #[error("couldn't connect to the input system: {0}")]
+    Connection(#[from] enigo::NewConError),
+    /// Posting CGEvents failed
+    #[error("{0}")]
+    Keyboard(String),
+    #[error("output_mode is \"file\" but output_file isn't set")]
+    NoOutputFile,
+    #[error("failed to {action} {path:?}: {source}")]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+}
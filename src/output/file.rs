@@ -0,0 +1,77 @@
+//! `output_mode = "file"`: transcripts appended to a notes file
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::OutputError;
+use crate::timestamp;
+
+type Result<T> = std::result::Result<T, OutputError>;
+
+/// Append `text` to `path` under a line with the current local time. The
+/// file is opened for each entry, so it can be edited or rotated between
+/// them; missing parent directories are created.
+pub fn append(path: &Path, text: &str) -> Result<()> {
+    append_entry(path, &timestamp::now_iso8601(), text)
+}
+
+fn append_entry(path: &Path, timestamp: &str, text: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(file_error("create", parent))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(file_error("open", path))?;
+    // One write so an entry isn't split by another writer
+    file.write_all(format!("{}\n{}\n\n", timestamp, text).as_bytes())
+        .map_err(file_error("write to", path))
+}
+
+fn file_error(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> OutputError {
+    let path = path.to_path_buf();
+    move |source| OutputError::File { action, path, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("dictation-output-{}-{}", std::process::id(), name))
+    }
+
+    /// Entries go after whatever is already there, parents are created
+    #[test]
+    fn test_entries_are_appended() {
+        let dir = temp_dir("append");
+        let path = dir.join("notes").join("dictation.md");
+        let _ = fs::remove_dir_all(&dir);
+
+        append_entry(&path, "2026-01-02T03:04:05+00:00", "First thought.").unwrap();
+        // Edited by hand in between
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"my own note\n\n").unwrap();
+        append_entry(&path, "2026-01-02T03:05:00+00:00", "Second thought.").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "2026-01-02T03:04:05+00:00\nFirst thought.\n\nmy own note\n\n\
+             2026-01-02T03:05:00+00:00\nSecond thought.\n\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unwritable_path_is_an_error() {
+        let dir = temp_dir("unwritable");
+        fs::create_dir_all(&dir).unwrap();
+        // A directory where the file should be
+        let err = append(&dir, "text").unwrap_err();
+        assert!(matches!(err, OutputError::File { action: "open", .. }), "{}", err);
+        assert!(err.to_string().contains("failed to open"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
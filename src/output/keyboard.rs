@@ -0,0 +1,124 @@
+//! `paste_backend = "cgevent"`: Cmd+V posted as CGEvents, using whichever
+//! key types "v" in the current keyboard layout
+
+use core_foundation::base::TCFType;
+use core_foundation::data::{CFData, CFDataRef};
+use core_foundation::string::CFStringRef;
+use core_graphics::event::{CGEvent, CGEventFlags, CGEventTapLocation, CGKeyCode};
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use std::ffi::c_void;
+
+use super::OutputError;
+
+/// `kVK_ANSI_V`, the V key's position on a US keyboard
+const ANSI_V: CGKeyCode = 9;
+/// Virtual key codes worth searching; higher ones aren't character keys
+const KEYCODE_COUNT: CGKeyCode = 128;
+/// `kUCKeyActionDisplay`
+const KEY_ACTION_DISPLAY: u16 = 3;
+/// `kUCKeyTranslateNoDeadKeysBit` as a mask
+const NO_DEAD_KEYS: u32 = 1;
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+    fn TISCopyCurrentKeyboardLayoutInputSource() -> *const c_void;
+    fn TISGetInputSourceProperty(source: *const c_void, key: CFStringRef) -> *const c_void;
+    fn LMGetKbdType() -> u8;
+    fn UCKeyTranslate(
+        layout: *const c_void,
+        keycode: u16,
+        action: u16,
+        modifiers: u32,
+        keyboard_type: u32,
+        options: u32,
+        dead_key_state: *mut u32,
+        max_length: usize,
+        actual_length: *mut usize,
+        chars: *mut u16,
+    ) -> i32;
+    fn CFRelease(cf: *const c_void);
+}
+
+/// Press and release Cmd+V. Events come from the HID system state, so apps
+/// that ignore obviously synthetic input still see an ordinary key press.
+pub fn paste() -> Result<(), OutputError> {
+    let keycode = keycode_for('v').unwrap_or_else(|| {
+        log::debug!("No key types 'v' in the current layout, using the ANSI V key");
+        ANSI_V
+    });
+    let source = CGEventSource::new(CGEventSourceStateID::HIDSystemState)
+        .map_err(|_| OutputError::Keyboard("failed to create a CGEvent source".into()))?;
+    for down in [true, false] {
+        let event = CGEvent::new_keyboard_event(source.clone(), keycode, down)
+            .map_err(|_| OutputError::Keyboard("failed to create a key event".into()))?;
+        event.set_flags(CGEventFlags::CGEventFlagCommand);
+        event.post(CGEventTapLocation::HID);
+    }
+    Ok(())
+}
+
+/// The virtual key code that types `target` without modifiers in the
+/// current keyboard layout
+fn keycode_for(target: char) -> Option<CGKeyCode> {
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return None;
+        }
+        let data = TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData);
+        let found = (!data.is_null()).then(|| {
+            // Owned by `source`, which outlives this use
+            let data = CFData::wrap_under_get_rule(data as CFDataRef);
+            let layout = data.bytes().as_ptr() as *const c_void;
+            let keyboard_type = u32::from(LMGetKbdType());
+            find_keycode(target, |keycode| {
+                let mut dead_key_state = 0u32;
+                let mut chars = [0u16; 4];
+                let mut length = 0usize;
+                let status = UCKeyTranslate(
+                    layout,
+                    keycode,
+                    KEY_ACTION_DISPLAY,
+                    0,
+                    keyboard_type,
+                    NO_DEAD_KEYS,
+                    &mut dead_key_state,
+                    chars.len(),
+                    &mut length,
+                    chars.as_mut_ptr(),
+                );
+                (status == 0 && length == 1)
+                    .then(|| char::from_u32(u32::from(chars[0])))
+                    .flatten()
+            })
+        });
+        CFRelease(source);
+        found.flatten()
+    }
+}
+
+/// First key code whose `translate`d character is `target`, ignoring case
+fn find_keycode(target: char, translate: impl Fn(CGKeyCode) -> Option<char>) -> Option<CGKeyCode> {
+    (0..KEYCODE_COUNT).find(|&keycode| {
+        translate(keycode).is_some_and(|c| c.to_lowercase().eq(target.to_lowercase()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// On Dvorak, "v" is typed by the key in QWERTY's "." position (47)
+    #[test]
+    fn test_finds_the_key_for_a_character() {
+        let dvorak = |keycode| match keycode {
+            9 => Some('k'),
+            47 => Some('v'),
+            _ => None,
+        };
+        assert_eq!(find_keycode('v', dvorak), Some(47));
+        assert_eq!(find_keycode('V', dvorak), Some(47));
+        assert_eq!(find_keycode('q', dvorak), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Modifier shortcuts like Cmd+V, with the modifier let go of however the
+//! shortcut ends
+
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::sync::{Mutex, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+use super::OutputError;
+
+type Result<T> = std::result::Result<T, OutputError>;
+
+/// Modifiers a `HeldModifier` has down right now, for `release_held`
+static HELD: Mutex<Vec<Key>> = Mutex::new(Vec::new());
+
+/// Somewhere key presses go; `Enigo` for the real keyboard
+pub trait KeyInput {
+    fn send(&mut self, key: Key, direction: Direction) -> Result<()>;
+}
+
+impl KeyInput for Enigo {
+    fn send(&mut self, key: Key, direction: Direction) -> Result<()> {
+        Ok(self.key(key, direction)?)
+    }
+}
+
+/// `modifier` held down until `release` or drop, so a failure partway
+/// through a shortcut can't leave it stuck down
+pub struct HeldModifier<'a> {
+    input: &'a mut dyn KeyInput,
+    modifier: Key,
+    held: bool,
+}
+
+impl<'a> HeldModifier<'a> {
+    pub fn press(input: &'a mut dyn KeyInput, modifier: Key) -> Result<Self> {
+        input.send(modifier, Direction::Press)?;
+        held().push(modifier);
+        Ok(Self {
+            input,
+            modifier,
+            held: true,
+        })
+    }
+
+    /// Press and release `key` with the modifier down
+    pub fn click(&mut self, key: Key) -> Result<()> {
+        self.input.send(key, Direction::Click)
+    }
+
+    /// Let go of the modifier, returning the error a drop would only log
+    pub fn release(mut self) -> Result<()> {
+        self.let_go()
+    }
+
+    fn let_go(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.held) {
+            return Ok(());
+        }
+        let mut held = held();
+        if let Some(i) = held.iter().position(|&key| key == self.modifier) {
+            held.remove(i);
+        }
+        drop(held);
+        self.input.send(self.modifier, Direction::Release)
+    }
+}
+
+impl Drop for HeldModifier<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.let_go() {
+            log::error!("Failed to release {:?}: {}", self.modifier, e);
+        }
+    }
+}
+
+/// Press `key` with `modifier` held, e.g. Cmd+V
+pub fn shortcut(input: &mut dyn KeyInput, modifier: Key, key: Key) -> Result<()> {
+    let mut held = HeldModifier::press(input, modifier)?;
+    held.click(key)?;
+    held.release()
+}
+
+/// Press Backspace `count` times, pausing `pause` after each so the app
+/// keeps up
+pub fn backspace(input: &mut dyn KeyInput, count: usize, pause: Duration) -> Result<()> {
+    for _ in 0..count {
+        input.send(Key::Backspace, Direction::Click)?;
+        thread::sleep(pause);
+    }
+    Ok(())
+}
+
+fn held() -> std::sync::MutexGuard<'static, Vec<Key>> {
+    HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Let go of every modifier a `HeldModifier` still has down, for the panic
+/// hook, which aborts before any of them would be dropped
+pub fn release_held() {
+    let held = match HELD.try_lock() {
+        Ok(held) => held,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        // Taken by the thread that panicked, partway through a press
+        Err(TryLockError::WouldBlock) => return,
+    };
+    if held.is_empty() {
+        return;
+    }
+    match Enigo::new(&Settings::default()) {
+        Ok(mut enigo) => {
+            for &key in held.iter() {
+                let _ = enigo.key(key, Direction::Release);
+            }
+        }
+        Err(e) => log::error!("Can't release {:?}: {}", *held, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what it was sent, failing for `fail_on`
+    #[derive(Default)]
+    struct MockKeys {
+        sent: Vec<(Key, Direction)>,
+        fail_on: Option<Key>,
+    }
+
+    impl KeyInput for MockKeys {
+        fn send(&mut self, key: Key, direction: Direction) -> Result<()> {
+            if self.fail_on == Some(key) {
+                return Err(OutputError::Keyboard(format!("{:?} failed", key)));
+            }
+            self.sent.push((key, direction));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shortcut() {
+        let mut keys = MockKeys::default();
+        shortcut(&mut keys, Key::Meta, Key::Unicode('v')).unwrap();
+        assert_eq!(
+            keys.sent,
+            vec![
+                (Key::Meta, Direction::Press),
+                (Key::Unicode('v'), Direction::Click),
+                (Key::Meta, Direction::Release),
+            ]
+        );
+    }
+
+    /// The modifier comes back up even when the key in between fails
+    #[test]
+    fn test_modifier_released_on_error() {
+        let mut keys = MockKeys {
+            fail_on: Some(Key::Unicode('x')),
+            ..Default::default()
+        };
+        assert!(shortcut(&mut keys, Key::Control, Key::Unicode('x')).is_err());
+        assert_eq!(
+            keys.sent,
+            vec![(Key::Control, Direction::Press), (Key::Control, Direction::Release)]
+        );
+    }
+
+    #[test]
+    fn test_backspace() {
+        let mut keys = MockKeys::default();
+        backspace(&mut keys, 3, Duration::ZERO).unwrap();
+        assert_eq!(keys.sent, vec![(Key::Backspace, Direction::Click); 3]);
+
+        let mut keys = MockKeys {
+            fail_on: Some(Key::Backspace),
+            ..Default::default()
+        };
+        assert!(backspace(&mut keys, 3, Duration::ZERO).is_err());
+        assert!(keys.sent.is_empty());
+    }
+
+    /// Only modifiers still down are left for `release_held`
+    #[test]
+    fn test_held_modifiers_are_tracked() {
+        let mut keys = MockKeys::default();
+        let held = HeldModifier::press(&mut keys, Key::Alt).unwrap();
+        assert!(super::held().contains(&Key::Alt));
+        held.release().unwrap();
+        assert!(!super::held().contains(&Key::Alt));
+        assert_eq!(keys.sent.len(), 2);
+
+        // Released once, not again on drop
+        let held = HeldModifier::press(&mut keys, Key::Alt).unwrap();
+        drop(held);
+        assert_eq!(keys.sent.len(), 4);
+    }
+}
@@ -0,0 +1,72 @@
+//! A panic on any thread ends the app: it logs where, lets go of keys it was
+//! holding down, and aborts rather than carry on with a thread missing. Only
+//! a panic inside `catch` is left to unwind, for code that can recover.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::process;
+use std::thread;
+
+use crate::output;
+
+thread_local! {
+    /// Run when the main thread panics; AppKit can't be touched from others
+    static MAIN_THREAD_CLEANUP: RefCell<Vec<Box<dyn Fn()>>> = RefCell::new(Vec::new());
+    /// Set while this thread is inside `catch`
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub fn install() {
+    panic::set_hook(Box::new(on_panic));
+}
+
+/// Run `cleanup` if the main thread panics, e.g. to hide the indicator. A
+/// panic elsewhere can't reach it, but the abort closes the app's windows
+/// and drops its hotkeys all the same.
+pub fn on_main_thread_panic(cleanup: impl Fn() + 'static) {
+    MAIN_THREAD_CLEANUP.with(|cleanups| cleanups.borrow_mut().push(Box::new(cleanup)));
+}
+
+/// Run `f`, turning a panic into its message instead of ending the app.
+/// Whatever `f` was changing may be left half done, so the caller has to
+/// rebuild it.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let outer = CATCHING.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.set(outer);
+    result.map_err(|payload| message(payload.as_ref()))
+}
+
+/// The text a panic was raised with
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn on_panic(info: &PanicHookInfo) {
+    let thread = thread::current();
+    let name = thread.name().unwrap_or("unnamed");
+    log::error!("Thread '{}' panicked: {}\n{}", name, info, Backtrace::force_capture());
+    log::logger().flush();
+    if CATCHING.get() {
+        return;
+    }
+
+    // Held modifiers would otherwise stay down in the system after the abort
+    output::release_held();
+    if name == "main" {
+        MAIN_THREAD_CLEANUP.with(|cleanups| {
+            if let Ok(cleanups) = cleanups.try_borrow() {
+                cleanups.iter().for_each(|cleanup| cleanup());
+            }
+        });
+    }
+    process::abort();
+}
@@ -0,0 +1,160 @@
+//! A hotkey press made while the model is still loading, held until it's ready
+
+use std::time::{Duration, Instant};
+
+use crate::config::RecordingMode;
+
+/// A queued start still waiting after this long is given up on; by then the
+/// user has likely stopped talking
+pub const GIVE_UP_AFTER: Duration = Duration::from_secs(15);
+
+/// How a queued press starts recording once the model is ready
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Queued {
+    /// The hotkey is still down; record until it's released. `since` is when
+    /// it was pressed.
+    Held { since: Instant },
+    /// Record until the next press, like a toggle recording
+    Latched,
+}
+
+/// At most one queued start; further presses adjust or cancel it rather
+/// than stacking up
+#[derive(Debug, Default)]
+pub struct PendingStart {
+    queued: Option<Queued>,
+    /// When the press that queued the start was made
+    queued_at: Option<Instant>,
+}
+
+impl PendingStart {
+    /// A press while loading queues a start, or cancels the one already queued
+    pub fn press(&mut self, mode: RecordingMode, now: Instant) {
+        self.queued = match self.queued {
+            Some(_) => None,
+            None if mode == RecordingMode::Toggle => Some(Queued::Latched),
+            None => Some(Queued::Held { since: now }),
+        };
+        self.queued_at = self.queued.map(|_| now);
+    }
+
+    /// A release while loading. Push-to-talk gives up on the queued start;
+    /// in hybrid mode a quick tap latches it and a long hold gives up.
+    pub fn release(&mut self, mode: RecordingMode, now: Instant, hybrid_hold: Duration) {
+        let Some(Queued::Held { since }) = self.queued else {
+            return;
+        };
+        self.queued = match mode {
+            RecordingMode::Hybrid if now.saturating_duration_since(since) < hybrid_hold => {
+                Some(Queued::Latched)
+            }
+            _ => None,
+        };
+    }
+
+    /// The model is ready: hand over the queued start, if any
+    pub fn ready(&mut self) -> Option<Queued> {
+        self.queued_at = None;
+        self.queued.take()
+    }
+
+    /// Drop the queued start, returning whether there was one
+    pub fn clear(&mut self) -> bool {
+        self.queued_at = None;
+        self.queued.take().is_some()
+    }
+
+    /// Drop a start that has waited `GIVE_UP_AFTER` or longer, returning
+    /// whether one was dropped
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let expired = self
+            .queued_at
+            .is_some_and(|at| now.saturating_duration_since(at) >= GIVE_UP_AFTER);
+        expired && self.clear()
+    }
+
+    pub fn is_queued(&self) -> bool {
+        self.queued.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOLD: Duration = Duration::from_millis(300);
+
+    /// Push-to-talk starts if the key is still down when the model is ready
+    #[test]
+    fn test_push_to_talk_held_until_ready() {
+        let mut pending = PendingStart::default();
+        let now = Instant::now();
+        pending.press(RecordingMode::PushToTalk, now);
+        assert!(pending.is_queued());
+        assert_eq!(pending.ready(), Some(Queued::Held { since: now }));
+        assert_eq!(pending.ready(), None, "A queued start is handed over once");
+    }
+
+    /// Letting go of push-to-talk before the model is ready records nothing
+    #[test]
+    fn test_push_to_talk_release_cancels() {
+        let mut pending = PendingStart::default();
+        let now = Instant::now();
+        pending.press(RecordingMode::PushToTalk, now);
+        pending.release(RecordingMode::PushToTalk, now + Duration::from_secs(2), HOLD);
+        assert_eq!(pending.ready(), None);
+    }
+
+    /// A toggle press queues once; pressing again takes it back
+    #[test]
+    fn test_toggle_second_press_cancels() {
+        let mut pending = PendingStart::default();
+        let now = Instant::now();
+        pending.press(RecordingMode::Toggle, now);
+        pending.release(RecordingMode::Toggle, now, HOLD);
+        assert!(pending.is_queued(), "Releasing doesn't affect a toggle press");
+
+        pending.press(RecordingMode::Toggle, now);
+        assert!(!pending.is_queued());
+
+        pending.press(RecordingMode::Toggle, now);
+        assert_eq!(pending.ready(), Some(Queued::Latched));
+    }
+
+    /// In hybrid mode a tap latches and a long hold released early gives up
+    #[test]
+    fn test_hybrid_tap_latches() {
+        let mut pending = PendingStart::default();
+        let now = Instant::now();
+        pending.press(RecordingMode::Hybrid, now);
+        pending.release(RecordingMode::Hybrid, now + Duration::from_millis(100), HOLD);
+        assert_eq!(pending.ready(), Some(Queued::Latched));
+
+        pending.press(RecordingMode::Hybrid, now);
+        pending.release(RecordingMode::Hybrid, now + HOLD, HOLD);
+        assert_eq!(pending.ready(), None);
+    }
+
+    /// A start queued for too long is dropped, once
+    #[test]
+    fn test_queued_start_expires() {
+        let mut pending = PendingStart::default();
+        let now = Instant::now();
+        assert!(!pending.expire(now + GIVE_UP_AFTER));
+
+        pending.press(RecordingMode::Toggle, now);
+        assert!(!pending.expire(now + GIVE_UP_AFTER - Duration::from_millis(1)));
+        assert!(pending.expire(now + GIVE_UP_AFTER));
+        assert!(!pending.is_queued());
+        assert!(!pending.expire(now + GIVE_UP_AFTER * 2));
+    }
+
+    #[test]
+    fn test_clear_reports_whether_queued() {
+        let mut pending = PendingStart::default();
+        assert!(!pending.clear());
+        pending.press(RecordingMode::Toggle, Instant::now());
+        assert!(pending.clear());
+        assert!(!pending.is_queued());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::events::AppEvent;
+use block::{Block, ConcreteBlock};
+use cocoa::base::{nil, BOOL, NO};
+use cocoa::foundation::NSString;
+use core_foundation::base::TCFType;
+use core_foundation::boolean::CFBoolean;
+use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
+use core_foundation::string::{CFString, CFStringRef};
+use std::fmt;
+use std::time::Duration;
+use tao::event_loop::EventLoopProxy;
+
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {}
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    static kAXTrustedCheckOptionPrompt: CFStringRef;
+    fn AXIsProcessTrustedWithOptions(options: CFDictionaryRef) -> bool;
+}
+
+/// `AVMediaTypeAudio`
+const MEDIA_TYPE_AUDIO: &str = "soun";
+
+/// A system permission the app can't work without
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermissionKind {
+    /// Without it every recording is silence
+    Microphone,
+    /// Needed to paste or type the transcript into other apps
+    Accessibility,
+}
+
+impl PermissionKind {
+    /// Where the user grants it
+    pub fn settings_path(self) -> &'static str {
+        match self {
+            Self::Microphone => "System Settings > Privacy & Security > Microphone",
+            Self::Accessibility => "System Settings > Privacy & Security > Accessibility",
+        }
+    }
+}
+
+impl fmt::Display for PermissionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Microphone => write!(f, "Microphone"),
+            Self::Accessibility => write!(f, "Accessibility"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PermissionStatus {
+    Granted,
+    Denied,
+    /// Not asked yet (microphone only)
+    Undetermined,
+}
+
+impl fmt::Display for PermissionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Granted => write!(f, "granted"),
+            Self::Denied => write!(f, "denied"),
+            Self::Undetermined => write!(f, "not requested yet"),
+        }
+    }
+}
+
+pub fn microphone_status() -> PermissionStatus {
+    unsafe {
+        let media_type = NSString::alloc(nil).init_str(MEDIA_TYPE_AUDIO);
+        let status: isize =
+            msg_send![class!(AVCaptureDevice), authorizationStatusForMediaType: media_type];
+        let _: () = msg_send![media_type, release];
+        // AVAuthorizationStatus: NotDetermined, Restricted, Denied, Authorized
+        match status {
+            0 => PermissionStatus::Undetermined,
+            3 => PermissionStatus::Granted,
+            _ => PermissionStatus::Denied,
+        }
+    }
+}
+
+/// With `prompt`, macOS also offers to open the Accessibility settings
+pub fn accessibility_status(prompt: bool) -> PermissionStatus {
+    let trusted = unsafe {
+        let key = CFString::wrap_under_get_rule(kAXTrustedCheckOptionPrompt);
+        let options = CFDictionary::from_CFType_pairs(&[(key, CFBoolean::from(prompt))]);
+        AXIsProcessTrustedWithOptions(options.as_concrete_TypeRef())
+    };
+    if trusted {
+        PermissionStatus::Granted
+    } else {
+        PermissionStatus::Denied
+    }
+}
+
+/// Log both permissions at startup, ask for the microphone if it hasn't been
+/// asked yet, and send `PermissionMissing` for anything not granted.
+pub fn check(proxy: &EventLoopProxy<AppEvent>) {
+    let microphone = microphone_status();
+    let accessibility = accessibility_status(true);
+    log::info!(
+        "Permissions: microphone {}, accessibility {}",
+        microphone,
+        accessibility
+    );
+
+    match microphone {
+        PermissionStatus::Granted => {}
+        PermissionStatus::Denied => {
+            let _ = proxy.send_event(AppEvent::PermissionMissing(PermissionKind::Microphone));
+        }
+        PermissionStatus::Undetermined => request_microphone(proxy.clone()),
+    }
+    if accessibility != PermissionStatus::Granted {
+        let _ = proxy.send_event(AppEvent::PermissionMissing(PermissionKind::Accessibility));
+    }
+}
+
+/// Show the system microphone prompt; a refusal is reported as `PermissionMissing`
+fn request_microphone(proxy: EventLoopProxy<AppEvent>) {
+    let handler = ConcreteBlock::new(move |granted: BOOL| {
+        if granted == NO {
+            let _ = proxy.send_event(AppEvent::PermissionMissing(PermissionKind::Microphone));
+        } else {
+            log::info!("Microphone access granted");
+        }
+    })
+    .copy();
+    ask_for_microphone(&handler);
+}
+
+/// Show the system microphone prompt and wait up to `timeout` for the answer
+pub fn request_microphone_and_wait(timeout: Duration) -> PermissionStatus {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let handler = ConcreteBlock::new(move |granted: BOOL| {
+        let _ = tx.try_send(granted != NO);
+    })
+    .copy();
+    ask_for_microphone(&handler);
+    match rx.recv_timeout(timeout) {
+        Ok(true) => PermissionStatus::Granted,
+        Ok(false) => PermissionStatus::Denied,
+        Err(_) => microphone_status(),
+    }
+}
+
+/// `handler` is called with whether access was granted, on some other thread
+fn ask_for_microphone(handler: &Block<(BOOL,), ()>) {
+    unsafe {
+        let media_type = NSString::alloc(nil).init_str(MEDIA_TYPE_AUDIO);
+        let _: () = msg_send![class!(AVCaptureDevice),
+            requestAccessForMediaType: media_type
+            completionHandler: handler];
+        let _: () = msg_send![media_type, release];
+    }
+}
+
+/// Human-readable status of each permission, for `--check-permissions`
+pub fn report() -> String {
+    let mut lines = Vec::new();
+    for (kind, status) in [
+        (PermissionKind::Microphone, microphone_status()),
+        (PermissionKind::Accessibility, accessibility_status(false)),
+    ] {
+        let mut line = format!("{}: {}", kind, status);
+        if status != PermissionStatus::Granted {
+            line.push_str(&format!(" (grant it in {})", kind.settings_path()));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,56 @@
+//! The recording side of the hotkey → worker flow: what a press and a
+//! release do to the `AudioSource`, and how the recording gets to the
+//! worker. main.rs wraps it in the indicator, sounds and state; the
+//! integration tests drive it with a fake source and a stand-in worker.
+
+use std::time::Duration;
+
+use crate::audio::{AudioError, AudioSource};
+use crate::worker::{RetrySlot, SubmitResult, Submitted};
+
+/// What came of stopping a recording
+#[derive(Debug, PartialEq)]
+pub struct Stopped {
+    /// Samples in the recording, at the source's sample rate
+    pub samples: usize,
+    /// What came of handing it to the worker; `None` if it was under
+    /// `min_recording_ms` and never sent
+    pub submitted: Option<Submitted>,
+}
+
+/// Start recording from `source`, blanking out the first `cue` of it so
+/// the start sound isn't transcribed
+pub fn start(source: &mut dyn AudioSource, cue: Duration) -> Result<(), AudioError> {
+    source.mute_for(cue);
+    source.start_recording()
+}
+
+/// End the recording in `source`. One under `min_recording_ms` is dropped;
+/// anything longer is made into a request by `request` and goes to the
+/// worker through `send`, after whatever `retry` is holding.
+pub fn stop_and_submit<T>(
+    source: &mut dyn AudioSource,
+    min_recording_ms: u64,
+    retry: &mut RetrySlot<T>,
+    request: impl FnOnce(Vec<f32>, u32) -> T,
+    send: impl FnMut(T) -> SubmitResult<T>,
+) -> Stopped {
+    let samples = source.stop_recording();
+    let sample_rate = source.sample_rate();
+    let len = samples.len();
+    if too_short(len, sample_rate, min_recording_ms) {
+        return Stopped {
+            samples: len,
+            submitted: None,
+        };
+    }
+    Stopped {
+        samples: len,
+        submitted: Some(retry.submit(request(samples, sample_rate), send)),
+    }
+}
+
+/// Whether `len` samples at `sample_rate` are under `min_recording_ms`
+pub fn too_short(len: usize, sample_rate: u32, min_recording_ms: u64) -> bool {
+    (len as u64) * 1000 < min_recording_ms * u64::from(sample_rate)
+}
@@ -0,0 +1,521 @@
+//! Transcript clean-up applied by the worker before the text is output
+
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::config::{Append, Config};
+use crate::logging::Transcript;
+
+pub mod case;
+pub mod commands;
+pub mod hallucination;
+pub mod spell;
+
+type Result<T> = std::result::Result<T, PostprocessError>;
+
+/// Why `postprocess_command` didn't give back any text
+#[derive(Debug, Error)]
+pub enum PostprocessError {
+    #[error("failed to start {command:?}: {source}")]
+    Spawn { command: String, source: io::Error },
+    /// Checking on the running command failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("timed out after {0:?}")]
+    TimedOut(Duration),
+    #[error("exited with {0}")]
+    Exit(ExitStatus),
+    #[error("produced no output")]
+    NoOutput,
+}
+
+/// How often a running command is checked for exit
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Zero-width characters, which whisper sometimes leaves between words
+const ZERO_WIDTH: [char; 5] = ['\u{200B}', '\u{200C}', '\u{200D}', '\u{2060}', '\u{FEFF}'];
+
+/// Zero-width joiners, kept between visible characters, where they hold
+/// emoji sequences and scripts like Persian together
+const JOINERS: [char; 2] = ['\u{200C}', '\u{200D}'];
+
+/// Punctuation that may trail a filler word and still count as a match
+const TRAILING_PUNCTUATION: [char; 6] = [',', '.', ';', ':', '!', '?'];
+
+/// Apply the configured post-processing to `text`: `normalize`, filler
+/// removal, then `postprocess_command`. Anything that fails is logged and
+/// skipped, keeping the text from before that step.
+pub fn apply(config: &Config, text: String) -> String {
+    let mut text = normalize(&text);
+    if config.remove_fillers {
+        text = remove_fillers(&text, &config.filler_words);
+    }
+    let Some(command) = &config.postprocess_command else {
+        return text;
+    };
+    let timeout = Duration::from_secs(config.postprocess_timeout_secs);
+    match run_command(command, &text, timeout) {
+        Ok(processed) => processed,
+        Err(e) => {
+            log::warn!("postprocess_command failed, using the original text: {}", e);
+            text
+        }
+    }
+}
+
+/// The whitespace clean-up the worker gives whisper's output first
+/// (`normalize_whitespace`): trims the ends, turns every run of whitespace,
+/// newlines and Unicode spaces like U+00A0 included, into one space, and drops
+/// zero-width characters. It runs before anything else that can add line
+/// breaks on purpose, such as `postprocess_command` or `append`.
+pub fn normalize_text(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let visible = |i: usize| chars.get(i).is_some_and(|c| !c.is_whitespace() && !ZERO_WIDTH.contains(c));
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if ZERO_WIDTH.contains(&c) && !(JOINERS.contains(&c) && i > 0 && visible(i - 1) && visible(i + 1)) {
+            continue;
+        }
+        if std::mem::take(&mut space) && !out.is_empty() {
+            out.push(' ');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Tidy up whisper's raw output: drop bracketed tokens such as `[_TT_150]`,
+/// `[BLANK_AUDIO]`, `[Music]` and `<|endoftext|>`, collapse runs of spaces
+/// and trim the ends. Brackets attached to a word (`array[i]`) or holding
+/// punctuation (`[sic.]`, `[1, 2]`) are kept.
+pub fn normalize(text: &str) -> String {
+    let mut cleaned = String::with_capacity(text.len());
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let after_word = cleaned.ends_with(char::is_alphanumeric);
+        if let Some(len) = artifact_len(&text[i..]).filter(|_| !after_word) {
+            i += len;
+            // "watching [_TT_9]." -> "watching."
+            let next = text[i..].chars().next();
+            if next.is_none_or(|c| c.is_whitespace() || TRAILING_PUNCTUATION.contains(&c)) {
+                cleaned.truncate(cleaned.trim_end_matches(' ').len());
+            } else if !cleaned.is_empty() && !cleaned.ends_with(char::is_whitespace) {
+                cleaned.push(' ');
+            }
+            continue;
+        }
+        if !(c == ' ' && cleaned.ends_with(' ')) {
+            cleaned.push(c);
+        }
+        i += c.len_utf8();
+    }
+    cleaned.trim().to_string()
+}
+
+/// Length of the whisper token `text` starts with, if it starts with one
+fn artifact_len(text: &str) -> Option<usize> {
+    if let Some(inner) = text.strip_prefix("<|") {
+        return inner.find("|>").map(|end| end + 4);
+    }
+    let inner = text.strip_prefix('[')?;
+    let end = inner.find(']')?;
+    let token = &inner[..end];
+    let is_token = token.chars().any(|c| c.is_alphabetic() || c == '_')
+        && token.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ' ');
+    is_token.then_some(end + 2)
+}
+
+/// `text` followed by what `append` asks for. Trailing whitespace already
+/// there is replaced rather than added to.
+pub fn with_suffix(text: &str, append: Append) -> String {
+    let suffix = match append {
+        Append::None => return text.to_string(),
+        Append::Space => " ",
+        Append::Newline => "\n",
+    };
+    format!("{}{}", text.trim_end(), suffix)
+}
+
+/// Drop whole-word (or whole-phrase) `fillers` from `text`, case-insensitively.
+/// A comma after a filler goes with it ("so, um, yes" -> "so, yes"), other
+/// punctuation moves to the previous word, and a sentence that started with a
+/// filler gets its next word capitalized. Text without fillers is returned
+/// unchanged; otherwise whitespace is collapsed to single spaces.
+pub fn remove_fillers(text: &str, fillers: &[String]) -> String {
+    let mut fillers: Vec<Vec<String>> = fillers
+        .iter()
+        .map(|filler| filler.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>())
+        .filter(|words| !words.is_empty())
+        .collect();
+    // Longest first so "you know" wins over a bare "you"
+    fillers.sort_by_key(|words| std::cmp::Reverse(words.len()));
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut kept: Vec<String> = Vec::with_capacity(words.len());
+    let mut removed = false;
+    let mut capitalize_next = false;
+    let mut i = 0;
+    while i < words.len() {
+        let Some(len) = fillers.iter().find_map(|filler| filler_len(&words[i..], filler)) else {
+            let mut word = words[i].to_string();
+            if std::mem::take(&mut capitalize_next) {
+                word = capitalize(&word);
+            }
+            kept.push(word);
+            i += 1;
+            continue;
+        };
+
+        removed = true;
+        let sentence_start = kept.last().is_none_or(|prev| prev.ends_with(['.', '!', '?']));
+        if sentence_start && words[i].starts_with(char::is_uppercase) {
+            capitalize_next = true;
+        }
+        // "I think, um." keeps its full stop: "I think."
+        let last = words[i + len - 1];
+        let trailing = &last[last.trim_end_matches(TRAILING_PUNCTUATION).len()..];
+        let carried = trailing.trim_start_matches(',');
+        if let Some(prev) = kept.last_mut().filter(|_| !carried.is_empty()) {
+            let without_comma = prev.trim_end_matches(',').len();
+            prev.truncate(without_comma);
+            prev.push_str(carried);
+        }
+        i += len;
+    }
+
+    if removed {
+        kept.join(" ")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Number of words at the start of `words` that spell out `filler`, if they
+/// do. Only the last word may carry punctuation, and none may have any
+/// leading, so "umbrella", "(um" and "um-hum" never match.
+fn filler_len(words: &[&str], filler: &[String]) -> Option<usize> {
+    if words.len() < filler.len() {
+        return None;
+    }
+    let last = filler.len() - 1;
+    let matches = words.iter().zip(filler).enumerate().all(|(j, (word, expected))| {
+        let core = if j == last {
+            word.trim_end_matches(TRAILING_PUNCTUATION)
+        } else {
+            word
+        };
+        core.to_lowercase() == *expected
+    });
+    matches.then_some(filler.len())
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Run `command` through `sh -c` with `text` on stdin and return its stdout
+/// without the trailing newline. Fails on a non-zero exit, on empty output,
+/// or when it runs past `timeout`, including anything it started that still
+/// holds its output open (all of them are then killed).
+pub fn run_command(command: &str, text: &str, timeout: Duration) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Its own process group, so a timeout reaches what it started too
+        .process_group(0)
+        .spawn()
+        .map_err(|source| PostprocessError::Spawn {
+            command: command.to_string(),
+            source,
+        })?;
+
+    // Separate threads so a command that writes before reading can't deadlock
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = text.to_string();
+    thread::spawn(move || {
+        // A command that ignores its input closes the pipe early; that's fine
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let stdout = read_in_background(child.stdout.take().expect("stdout is piped"));
+    let stderr = read_in_background(child.stderr.take().expect("stderr is piped"));
+
+    let deadline = Instant::now() + timeout;
+    let timed_out = |child: &mut Child| {
+        kill_group(child);
+        PostprocessError::TimedOut(timeout)
+    };
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            return Err(timed_out(&mut child));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    // A process it left running in the background can keep the pipes open
+    // after it exits, so the reads get the rest of the time too
+    let remaining = || deadline.saturating_duration_since(Instant::now());
+    let Ok(stderr) = stderr.recv_timeout(remaining()) else {
+        return Err(timed_out(&mut child));
+    };
+    if !stderr.trim().is_empty() {
+        // The command may echo the transcript it was given
+        log::info!("postprocess_command stderr: {}", Transcript(stderr.trim_end()));
+    }
+    if !status.success() {
+        return Err(PostprocessError::Exit(status));
+    }
+
+    let Ok(output) = stdout.recv_timeout(remaining()) else {
+        return Err(timed_out(&mut child));
+    };
+    let output = output.trim_end_matches(['\n', '\r']);
+    if output.trim().is_empty() {
+        return Err(PostprocessError::NoOutput);
+    }
+    Ok(output.to_string())
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        let _ = tx.send(String::from_utf8_lossy(&buf).into_owned());
+    });
+    rx
+}
+
+/// Kill `child` and everything in its process group, then reap it
+fn kill_group(child: &mut Child) {
+    // The group's id is the child's pid, from `process_group(0)`
+    unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+    let _ = child.wait();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn strip(text: &str) -> String {
+        let fillers: Vec<String> = ["um", "uh", "you know"].iter().map(|s| s.to_string()).collect();
+        remove_fillers(text, &fillers)
+    }
+
+    #[test]
+    fn test_fillers_between_words_are_removed() {
+        assert_eq!(strip("I think um we should go"), "I think we should go");
+        assert_eq!(strip("it was uh  you know fine"), "it was fine");
+    }
+
+    /// Commas around a filler collapse to one, other punctuation is kept
+    #[test]
+    fn test_punctuation_next_to_fillers() {
+        assert_eq!(strip("so, um, yes"), "so, yes");
+        assert_eq!(strip("I went, uh, to the store."), "I went, to the store.");
+        assert_eq!(strip("I think, um."), "I think.");
+        assert_eq!(strip("Is it ready, uh?"), "Is it ready?");
+        assert_eq!(strip("Done. Um, next item"), "Done. Next item");
+    }
+
+    #[test]
+    fn test_repeated_fillers() {
+        assert_eq!(strip("Um, uh, um so we start"), "So we start");
+        assert_eq!(strip("this um um um works"), "this works");
+        assert_eq!(strip("um uh"), "");
+    }
+
+    /// Fillers inside or attached to other words are left alone
+    #[test]
+    fn test_only_whole_words_match() {
+        for text in ["my umbrella", "a humble start", "uh-huh", "(um) no", "thumb drive", "you knowingly"] {
+            assert_eq!(strip(text), text);
+        }
+        assert_eq!(strip("UM yes UH"), "Yes");
+    }
+
+    #[test]
+    fn test_text_without_fillers_is_unchanged() {
+        assert_eq!(strip("keep  these\nspaces"), "keep  these\nspaces");
+    }
+
+    /// Output seen from whisper.cpp over various models and recordings
+    #[test]
+    fn test_normalize_messy_output() {
+        for (raw, expected) in [
+            (" Hello world.", "Hello world."),
+            ("[BLANK_AUDIO]", ""),
+            (" [ Silence ] ", ""),
+            (" [_TT_150] Send it now.", "Send it now."),
+            ("Thanks for watching.[_TT_500]", "Thanks for watching."),
+            ("[_BEG_] Okay, let's go.[_TT_42]", "Okay, let's go."),
+            ("I think [_TT_12], yes.", "I think, yes."),
+            ("Hello [Music] world", "Hello world"),
+            ("Hello[MUSIC] world", "Hello[MUSIC] world"),
+            ("Done.[_TT_88]Next one.", "Done. Next one."),
+            ("<|endoftext|> Done.", "Done."),
+            (" Two segments.  Joined together.", "Two segments. Joined together."),
+            ("Line one.\nLine two.", "Line one.\nLine two."),
+            ("Use array[i] and [1, 2] here.", "Use array[i] and [1, 2] here."),
+            ("An unclosed [bracket", "An unclosed [bracket"),
+        ] {
+            assert_eq!(normalize(raw), expected, "{:?}", raw);
+        }
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        for (raw, expected) in [
+            (" Hello world. ", "Hello world."),
+            ("Two segments.  Joined together.", "Two segments. Joined together."),
+            ("Line one.\nLine two.\n", "Line one. Line two."),
+            ("\t Tabs\tand\r\nbreaks ", "Tabs and breaks"),
+            ("No\u{A0}break\u{202F}spaces\u{3000}here", "No break spaces here"),
+            ("\u{FEFF}Zero\u{200B}width\u{2060} gone", "Zerowidth gone"),
+            ("\u{200D} stray joiner \u{200C}", "stray joiner"),
+            ("", ""),
+            (" \u{A0}\u{200B}\n", ""),
+        ] {
+            assert_eq!(normalize_text(raw), expected, "{:?}", raw);
+        }
+    }
+
+    /// Over every short string of awkward characters: a second pass changes
+    /// nothing, and what's left has no stray whitespace or zero-width
+    /// characters
+    #[test]
+    fn test_normalize_text_is_idempotent() {
+        const ALPHABET: [char; 9] = ['a', 'é', '.', ' ', '\n', '\u{A0}', '\u{200B}', '\u{200D}', '👍'];
+        let mut inputs = vec![String::new()];
+        let mut layer = inputs.clone();
+        for _ in 0..4 {
+            layer = layer
+                .iter()
+                .flat_map(|text| ALPHABET.iter().map(move |&c| format!("{}{}", text, c)))
+                .collect();
+            inputs.extend(layer.iter().cloned());
+        }
+
+        for input in &inputs {
+            let once = normalize_text(input);
+            assert_eq!(normalize_text(&once), once, "{:?}", input);
+            assert_eq!(once.trim(), once, "{:?}", input);
+            assert!(!once.contains("  "), "{:?}", input);
+            assert!(!once.contains(|c: char| c.is_whitespace() && c != ' '), "{:?}", input);
+            assert!(!once.contains(['\u{200B}', '\u{FEFF}', '\u{2060}']), "{:?}", input);
+            // Nothing visible is lost
+            let visible = |text: &str| -> String { text.chars().filter(|c| "aé.👍".contains(*c)).collect() };
+            assert_eq!(visible(&once), visible(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_intentional_formatting_survives() {
+        // Clean text is left alone
+        for text in ["Hello, world!", "Ship v1.2 — today?", "array[i] = \"x\";", "¿Qué tal?"] {
+            assert_eq!(normalize_text(text), text);
+        }
+        // Joiners holding emoji sequences and Persian words together
+        assert_eq!(normalize_text(" 👨\u{200D}👩\u{200D}👧 "), "👨\u{200D}👩\u{200D}👧");
+        assert_eq!(normalize_text("می\u{200C}خواهم"), "می\u{200C}خواهم");
+
+        // Line breaks added later, by postprocess_command or append, are kept
+        let config = Config {
+            postprocess_command: Some("tr ';' '\\n'".to_string()),
+            ..Config::default()
+        };
+        let text = apply(&config, normalize_text(" Milk;\u{A0}eggs\n"));
+        assert_eq!(text, "Milk\n eggs");
+        assert_eq!(with_suffix(&text, Append::Newline), "Milk\n eggs\n");
+    }
+
+    #[test]
+    fn test_suffix() {
+        assert_eq!(with_suffix("Hi.", Append::None), "Hi.");
+        assert_eq!(with_suffix("Hi.", Append::Space), "Hi. ");
+        assert_eq!(with_suffix("Hi.", Append::Newline), "Hi.\n");
+        // No doubled spaces from a command that already added one
+        assert_eq!(with_suffix("Hi. ", Append::Space), "Hi. ");
+        assert_eq!(with_suffix("Hi. ", Append::Newline), "Hi.\n");
+    }
+
+    #[test]
+    fn test_command_output_replaces_text() {
+        let out = run_command("tr a-z A-Z", "hello world", TIMEOUT).unwrap();
+        assert_eq!(out, "HELLO WORLD");
+    }
+
+    /// Only the final newline most tools add is dropped
+    #[test]
+    fn test_trailing_newline_is_trimmed() {
+        let out = run_command("cat; echo", "  indented", TIMEOUT).unwrap();
+        assert_eq!(out, "  indented");
+    }
+
+    #[test]
+    fn test_failures_are_errors() {
+        let err = run_command("echo partial; exit 3", "text", TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("exit"), "{}", err);
+
+        let err = run_command("cat > /dev/null", "text", TIMEOUT).unwrap_err();
+        assert!(err.to_string().contains("no output"), "{}", err);
+    }
+
+    /// A hung command is killed at the timeout instead of blocking the worker
+    #[test]
+    fn test_slow_command_times_out() {
+        let started = Instant::now();
+        let err = run_command("sleep 10", "text", Duration::from_millis(200)).unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// A background process holding stdout open can't make the read outlast
+    /// the timeout
+    #[test]
+    fn test_background_process_times_out() {
+        let started = Instant::now();
+        let err = run_command("sleep 10 & echo early", "text", Duration::from_millis(300)).unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Without a command, and when the command fails, the text passes through
+    #[test]
+    fn test_apply_falls_back_to_original() {
+        let mut config = Config::default();
+        assert_eq!(apply(&config, "as is".to_string()), "as is");
+
+        config.postprocess_command = Some("exit 1".to_string());
+        assert_eq!(apply(&config, "as is".to_string()), "as is");
+
+        config.postprocess_command = Some("sed s/is/was/".to_string());
+        assert_eq!(apply(&config, "as is".to_string()), "as was");
+
+        // Fillers are gone before the command sees the text
+        config.remove_fillers = true;
+        config.postprocess_command = Some("cat".to_string());
+        assert_eq!(apply(&config, "as um is".to_string()), "as is");
+    }
+}
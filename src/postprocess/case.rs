@@ -0,0 +1,181 @@
+//! `case_transform`: recasing a transcript for headings, code comments and
+//! the like, chosen in the config or by starting the utterance with the
+//! transform's name ("title case: my heading here")
+
+use super::spell;
+use crate::config::CaseTransform;
+
+/// Spoken prefixes that pick a transform for one utterance
+const PREFIXES: [(&str, CaseTransform); 6] = [
+    ("no case", CaseTransform::None),
+    ("sentence case", CaseTransform::Sentence),
+    ("title case", CaseTransform::Title),
+    ("lower case", CaseTransform::Lower),
+    ("upper case", CaseTransform::Upper),
+    ("all caps", CaseTransform::Upper),
+];
+
+/// Words left lowercase in title case, unless first or last
+const MINOR_WORDS: [&str; 22] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "into", "nor", "of", "off", "on", "or", "per",
+    "so", "the", "to", "via", "vs",
+];
+
+/// The transform `text` asks for with a spoken prefix, and the rest of it
+pub fn strip_prefix(text: &str) -> Option<(CaseTransform, &str)> {
+    PREFIXES
+        .iter()
+        .find_map(|&(prefix, case)| spell::strip_prefix(text, prefix).map(|rest| (case, rest)))
+}
+
+/// `text` recased per `case`. Words with capitals past their first letter,
+/// like "NASA" or "iPhone", are kept as they are except by `upper` and
+/// `lower`, and so is "I".
+pub fn transform(text: &str, case: CaseTransform) -> String {
+    match case {
+        CaseTransform::None => text.to_string(),
+        CaseTransform::Lower => text.to_lowercase(),
+        CaseTransform::Upper => text.to_uppercase(),
+        CaseTransform::Sentence | CaseTransform::Title => recase_words(text, case == CaseTransform::Title),
+    }
+}
+
+/// Sentence or title case, word by word, leaving the whitespace between
+/// words as it was
+fn recase_words(text: &str, title: bool) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    // The next word starts a sentence, or in title case a subtitle
+    let mut starts = true;
+    for (i, &word) in words.iter().enumerate() {
+        let at = rest.len() - rest.trim_start().len();
+        out.push_str(&rest[..at]);
+        rest = &rest[at + word.len()..];
+
+        let capitalized = if title {
+            let last = i + 1 == words.len();
+            word.split('-')
+                .enumerate()
+                .map(|(j, part)| recase(part, (starts && j == 0) || last || !is_minor(part)))
+                .collect::<Vec<_>>()
+                .join("-")
+        } else {
+            recase(word, starts)
+        };
+        out.push_str(&capitalized);
+
+        let end = word.trim_end_matches(['"', '\'', ')', ']']);
+        starts = end.ends_with(['.', '!', '?']) || (title && end.ends_with(':'));
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `word` lowercased, then with its first letter uppercase when
+/// `capitalize`. Words `keeps_case` passes are returned unchanged.
+fn recase(word: &str, capitalize: bool) -> String {
+    if keeps_case(word) {
+        return word.to_string();
+    }
+    let lower = word.to_lowercase();
+    // Only when the word starts with a letter, so "1st" stays as it is
+    match lower.char_indices().find(|(_, c)| c.is_alphanumeric()) {
+        Some((at, c)) if capitalize && c.is_alphabetic() => {
+            let mut out = lower[..at].to_string();
+            out.extend(c.to_uppercase());
+            out.push_str(&lower[at + c.len_utf8()..]);
+            out
+        }
+        _ => lower,
+    }
+}
+
+/// Acronyms and brand names, with a capital after their first letter, and
+/// the pronoun "I" ("I", "I'm", "I'll")
+fn keeps_case(word: &str) -> bool {
+    let letters = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let mut chars = letters.chars();
+    if chars.next() == Some('I') && !chars.clone().next().is_some_and(char::is_alphanumeric) {
+        return true;
+    }
+    chars.any(char::is_uppercase)
+}
+
+fn is_minor(word: &str) -> bool {
+    let bare = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    MINOR_WORDS.contains(&bare.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn title(text: &str) -> String {
+        transform(text, CaseTransform::Title)
+    }
+
+    fn sentence(text: &str) -> String {
+        transform(text, CaseTransform::Sentence)
+    }
+
+    #[test]
+    fn test_title_case() {
+        assert_eq!(title("my heading here"), "My Heading Here");
+        assert_eq!(title("the lord of the rings"), "The Lord of the Rings");
+        // First and last words are capitalized even when minor
+        assert_eq!(title("what it's made of"), "What It's Made Of");
+        assert_eq!(title("Harry Potter: the return of the king"), "Harry Potter: The Return of the King");
+        assert_eq!(title("state-of-the-art tools"), "State-of-the-Art Tools");
+        assert_eq!(title("HELLO WORLD"), "HELLO WORLD");
+        assert_eq!(title(""), "");
+    }
+
+    #[test]
+    fn test_sentence_case() {
+        assert_eq!(sentence("My Heading Here"), "My heading here");
+        assert_eq!(sentence("hello there. how are you? fine!"), "Hello there. How are you? Fine!");
+        assert_eq!(sentence("\"Quoted.\" Then More"), "\"Quoted.\" Then more");
+        assert_eq!(sentence("Shall We Go: Now"), "Shall we go: now");
+    }
+
+    #[test]
+    fn test_lower_and_upper() {
+        assert_eq!(transform("Fix the NASA API call.", CaseTransform::Lower), "fix the nasa api call.");
+        assert_eq!(transform("Don't stop, I'm here", CaseTransform::Upper), "DON'T STOP, I'M HERE");
+        assert_eq!(transform("Left As Is", CaseTransform::None), "Left As Is");
+    }
+
+    #[test]
+    fn test_punctuation_and_apostrophes() {
+        assert_eq!(title("don't look (back) in anger"), "Don't Look (Back) in Anger");
+        assert_eq!(title("\"rock 'n' roll\", she said"), "\"Rock 'N' Roll\", She Said");
+        assert_eq!(sentence("IT'S FINE, isn't it"), "IT'S FINE, isn't it");
+        assert_eq!(sentence("Rock 'N' Roll"), "Rock 'n' roll");
+        assert_eq!(title("the 1st of may"), "The 1st of May");
+        // Whitespace between words is kept
+        assert_eq!(title("one\ntwo  three"), "One\nTwo  Three");
+    }
+
+    /// Acronyms, brand names and "I" keep their capitals
+    #[test]
+    fn test_capitalized_words_are_kept() {
+        assert_eq!(title("using the GPU on an iPhone"), "Using the GPU on an iPhone");
+        assert_eq!(sentence("Using The GPU On An iPhone"), "Using the GPU on an iPhone");
+        assert_eq!(sentence("Then I Said I'll Go"), "Then I said I'll go");
+        assert_eq!(sentence("NASA's Launch"), "NASA's launch");
+        assert_eq!(sentence("Me And I, Too"), "Me and I, too");
+        assert_eq!(sentence("Is It Done"), "Is it done");
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(strip_prefix("Title case: my heading here"), Some((CaseTransform::Title, "my heading here")));
+        assert_eq!(strip_prefix("lower case, Fix The Bug."), Some((CaseTransform::Lower, "Fix The Bug.")));
+        assert_eq!(strip_prefix("All caps. Stop"), Some((CaseTransform::Upper, "Stop")));
+        assert_eq!(strip_prefix("No case: As Said"), Some((CaseTransform::None, "As Said")));
+        assert_eq!(strip_prefix("Upper case letters are loud"), Some((CaseTransform::Upper, "letters are loud")));
+        assert_eq!(strip_prefix("The title case study"), None);
+        assert_eq!(strip_prefix("Lowercase please"), None);
+    }
+}
@@ -0,0 +1,55 @@
+//! Utterances that are commands to the app rather than text to output
+
+/// Something the user asked for by saying it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceCommand {
+    /// Delete the transcript output last
+    ScratchThat,
+}
+
+/// Each command and the phrasings that trigger it
+const PHRASES: &[(&[&str], VoiceCommand)] = &[(&["scratch", "that"], VoiceCommand::ScratchThat)];
+
+/// The command `text` is, when the whole utterance is one of the phrases,
+/// ignoring case and punctuation: "Scratch that." is, "scratch that part"
+/// isn't
+pub fn detect(text: &str) -> Option<VoiceCommand> {
+    let words: Vec<String> = text
+        .split(|c: char| c.is_whitespace() || (c.is_ascii_punctuation() && c != '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    PHRASES
+        .iter()
+        .find(|(phrase, _)| words.iter().map(String::as_str).eq(phrase.iter().copied()))
+        .map(|&(_, command)| command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_that() {
+        for text in ["scratch that", "Scratch that.", "  SCRATCH THAT!  ", "Scratch, that", "scratch-that"] {
+            assert_eq!(detect(text), Some(VoiceCommand::ScratchThat), "{:?}", text);
+        }
+    }
+
+    /// Only the whole utterance counts, so dictating the phrase in a
+    /// sentence still types it
+    #[test]
+    fn test_phrase_in_text_is_not_a_command() {
+        for text in [
+            "",
+            "scratch",
+            "scratch that part",
+            "Please scratch that.",
+            "I'll scratch that itch",
+            "scratch thats",
+            "scratched that",
+        ] {
+            assert_eq!(detect(text), None, "{:?}", text);
+        }
+    }
+}
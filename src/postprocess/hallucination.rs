@@ -0,0 +1,185 @@
+//! Text whisper makes up for silence and noise, caught so it isn't output
+
+use std::fmt;
+
+use crate::config::HallucinationConfig;
+
+/// Longest phrase, in words, looked for as a repeating unit
+const MAX_PHRASE_WORDS: usize = 8;
+/// Repeats adding up to fewer words than this are let through, so short
+/// exclamations like "No, no, no." survive
+const MIN_REPEATED_WORDS: usize = 4;
+/// Audio shorter than this is judged as if it were this long, so a quick
+/// "Sounds good, see you then." isn't taken for too much text
+const MIN_JUDGED_SECS: f32 = 1.0;
+
+/// Why a transcript looks made up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hallucination {
+    /// The whole text is one phrase said this many times (plus perhaps
+    /// the start of it once more)
+    Repeated { phrase: String, times: usize },
+    /// The whole text matches this `blocklist` entry
+    Blocklisted(String),
+    /// Far more text than the audio could hold
+    TooLong,
+}
+
+impl fmt::Display for Hallucination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Repeated { phrase, times } => write!(f, "\"{}\" repeated {} times", phrase, times),
+            Self::Blocklisted(entry) => write!(f, "matches blocklist entry \"{}\"", entry),
+            Self::TooLong => write!(f, "too much text for the audio"),
+        }
+    }
+}
+
+/// Check `text`, transcribed from `audio_secs` of audio, against each
+/// heuristic. `None` when it looks genuine or the filter is off.
+pub fn detect(text: &str, audio_secs: f32, config: &HallucinationConfig) -> Option<Hallucination> {
+    if !config.enabled {
+        return None;
+    }
+    let words = words(text);
+    if words.is_empty() {
+        return None;
+    }
+    let repeated = repetition(&words)
+        .filter(|&(_, times)| times > config.max_repeats)
+        .filter(|_| words.len() >= MIN_REPEATED_WORDS);
+    if let Some((phrase, times)) = repeated {
+        return Some(Hallucination::Repeated {
+            phrase: phrase.join(" "),
+            times,
+        });
+    }
+    let joined = words.join(" ");
+    if let Some(entry) = config.blocklist.iter().find(|entry| blocklisted(&joined, entry)) {
+        return Some(Hallucination::Blocklisted(entry.clone()));
+    }
+    let chars = text.chars().filter(|c| !c.is_whitespace()).count() as f32;
+    if chars > config.max_chars_per_sec * audio_secs.max(MIN_JUDGED_SECS) {
+        return Some(Hallucination::TooLong);
+    }
+    None
+}
+
+/// Lowercased words with punctuation dropped, so "Thank you." and "thank
+/// you" compare equal
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// The shortest phrase that `words` is made of, repeated, and how many
+/// full times; the last repeat may be cut short. `None` unless it appears
+/// at least twice.
+fn repetition(words: &[String]) -> Option<(&[String], usize)> {
+    (1..=MAX_PHRASE_WORDS.min(words.len() / 2)).find_map(|len| {
+        let phrase = &words[..len];
+        let repeats = words.chunks(len).all(|chunk| chunk == &phrase[..chunk.len()]);
+        repeats.then(|| (phrase, words.len() / len))
+    })
+}
+
+/// Whether the whole of `text` (already run through `words`) is `entry`.
+/// An entry ending in `*` matches anything starting with the rest of it.
+fn blocklisted(text: &str, entry: &str) -> bool {
+    let (entry, prefix) = match entry.strip_suffix('*') {
+        Some(entry) => (entry, true),
+        None => (entry, false),
+    };
+    let entry = words(entry).join(" ");
+    if entry.is_empty() {
+        return false;
+    }
+    if prefix {
+        text == entry || text.starts_with(&format!("{} ", entry))
+    } else {
+        text == entry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(text: &str, audio_secs: f32) -> Option<Hallucination> {
+        detect(text, audio_secs, &HallucinationConfig::default())
+    }
+
+    /// Output whisper has produced for silent or noisy recordings
+    #[test]
+    fn test_known_bad_outputs_are_caught() {
+        for (text, secs) in [
+            ("Thank you. Thank you. Thank you.", 3.0),
+            ("you you you you you you you you you you you you you you you you", 4.0),
+            ("Thanks for watching!", 2.0),
+            ("Thank you for watching.", 1.5),
+            ("Don't forget to like and subscribe!", 2.0),
+            ("Subtitles by the Amara.org community", 2.0),
+            ("Please subscribe to my channel.", 2.5),
+            ("I'm going to go. I'm going to go. I'm going to go. I'm going to go.", 5.0),
+            ("Thank you. Thank you. Thank you. Thank", 3.0),
+        ] {
+            assert!(check(text, secs).is_some(), "{:?} wasn't caught", text);
+        }
+    }
+
+    #[test]
+    fn test_reasons() {
+        assert_eq!(
+            check("Thank you. Thank you. Thank you.", 3.0),
+            Some(Hallucination::Repeated {
+                phrase: "thank you".to_string(),
+                times: 3
+            })
+        );
+        assert_eq!(
+            check("Subtitles by the Amara.org community", 2.0),
+            Some(Hallucination::Blocklisted("subtitles by the *".to_string()))
+        );
+        let essay = "Whisper sometimes continues well past what was said, inventing whole \
+                     paragraphs of plausible text that nobody dictated, usually on a short clip.";
+        assert_eq!(check(essay, 1.0), Some(Hallucination::TooLong));
+        assert_eq!(check(essay, 5.0), None);
+    }
+
+    /// Ordinary dictation, including some that comes close to a heuristic
+    #[test]
+    fn test_real_speech_passes() {
+        for (text, secs) in [
+            ("Thank you.", 1.0),
+            ("Thank you, thank you.", 1.5),
+            ("No, no, no.", 1.0),
+            ("Thanks for watching the kids last night.", 2.5),
+            ("The subtitles by default are off.", 2.0),
+            ("Ok", 0.2),
+            ("Sounds good, see you then.", 0.6),
+            ("Let me check the logs and get back to you this afternoon with the numbers.", 4.0),
+        ] {
+            assert_eq!(check(text, secs), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_can_be_disabled() {
+        let config = HallucinationConfig {
+            enabled: false,
+            ..HallucinationConfig::default()
+        };
+        assert_eq!(detect("Thanks for watching!", 2.0, &config), None);
+    }
+
+    #[test]
+    fn test_blocklist_entries() {
+        assert!(blocklisted("thanks for watching", "Thanks for watching!"));
+        assert!(!blocklisted("thanks for watching everyone", "thanks for watching"));
+        assert!(blocklisted("thanks for watching everyone", "thanks for watching*"));
+        assert!(!blocklisted("thanks for watchingg", "thanks for watching*"));
+        assert!(!blocklisted("anything", "*"));
+    }
+}
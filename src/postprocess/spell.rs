@@ -0,0 +1,228 @@
+//! Spell mode: words said a character at a time, like "alpha bravo seven",
+//! turned into the characters, "ab7"
+
+use crate::config::UnknownWords;
+
+/// Characters words stand for: the NATO alphabet, digit names and a few
+/// symbols that turn up in IDs
+const WORDS: &[(&str, char)] = &[
+    ("alpha", 'a'),
+    ("alfa", 'a'),
+    ("bravo", 'b'),
+    ("charlie", 'c'),
+    ("delta", 'd'),
+    ("echo", 'e'),
+    ("foxtrot", 'f'),
+    ("golf", 'g'),
+    ("hotel", 'h'),
+    ("india", 'i'),
+    ("juliet", 'j'),
+    ("juliett", 'j'),
+    ("kilo", 'k'),
+    ("lima", 'l'),
+    ("mike", 'm'),
+    ("november", 'n'),
+    ("oscar", 'o'),
+    ("papa", 'p'),
+    ("quebec", 'q'),
+    ("romeo", 'r'),
+    ("sierra", 's'),
+    ("tango", 't'),
+    ("uniform", 'u'),
+    ("victor", 'v'),
+    ("whiskey", 'w'),
+    ("whisky", 'w'),
+    ("x-ray", 'x'),
+    ("xray", 'x'),
+    ("yankee", 'y'),
+    ("zulu", 'z'),
+    ("zero", '0'),
+    ("one", '1'),
+    ("two", '2'),
+    ("three", '3'),
+    ("four", '4'),
+    ("five", '5'),
+    ("six", '6'),
+    ("seven", '7'),
+    ("eight", '8'),
+    ("nine", '9'),
+    ("niner", '9'),
+    ("dash", '-'),
+    ("hyphen", '-'),
+    ("underscore", '_'),
+    ("dot", '.'),
+    ("slash", '/'),
+    ("space", ' '),
+];
+
+/// Words that make the character after them uppercase
+const CAPITALS: [&str; 2] = ["capital", "uppercase"];
+
+/// Punctuation whisper puts between spelled characters, ignored around words
+const SEPARATORS: [char; 8] = [',', '.', ';', ':', '!', '?', '"', '\''];
+
+/// The rest of `text` when it starts with the words of `prefix`, ignoring
+/// case and punctuation: "Spell: alpha 7" gives "alpha 7" for "spell".
+/// `None` for an empty `prefix`.
+pub fn strip_prefix<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut rest = text.trim_start();
+    let mut matched = false;
+    for expected in prefix.split_whitespace() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if rest[..end].trim_matches(SEPARATORS).to_lowercase() != expected.to_lowercase() {
+            return None;
+        }
+        rest = rest[end..].trim_start();
+        matched = true;
+    }
+    matched.then_some(rest)
+}
+
+/// The characters `text` spells out, lowercase unless after "capital".
+/// Characters run together, while words that stand for none are handled per
+/// `unknown_words`: kept, with a space either side, or returned as the error.
+pub fn spell(text: &str, unknown_words: UnknownWords) -> Result<String, String> {
+    let mut pieces: Vec<String> = Vec::new();
+    let mut run = String::new();
+    // A "capital" waiting for its character
+    let mut capital: Option<&str> = None;
+    let unknown = |word: &str, run: &mut String, pieces: &mut Vec<String>| match unknown_words {
+        UnknownWords::Keep => {
+            if !run.is_empty() {
+                pieces.push(std::mem::take(run));
+            }
+            pieces.push(word.to_string());
+            Ok(())
+        }
+        UnknownWords::Fail => Err(word.to_string()),
+    };
+
+    for word in text.split_whitespace() {
+        let bare = word.trim_matches(SEPARATORS);
+        if bare.is_empty() {
+            continue;
+        }
+        let lower = bare.to_lowercase();
+        if capital.is_none() && CAPITALS.contains(&lower.as_str()) {
+            capital = Some(bare);
+            continue;
+        }
+        match characters(&lower) {
+            Some(chars) if capital.take().is_some() => {
+                let mut chars = chars.chars();
+                run.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+                run.extend(chars);
+            }
+            Some(chars) => run.push_str(&chars),
+            None => {
+                if let Some(capital) = capital.take() {
+                    unknown(capital, &mut run, &mut pieces)?;
+                }
+                unknown(bare, &mut run, &mut pieces)?;
+            }
+        }
+    }
+    if let Some(capital) = capital {
+        unknown(capital, &mut run, &mut pieces)?;
+    }
+    if !run.is_empty() {
+        pieces.push(run);
+    }
+    Ok(pieces.join(" "))
+}
+
+/// What a lowercased word spells: a `WORDS` entry, a lone letter or digit,
+/// a number, or hyphenated parts that each spell something ("a-b-7")
+fn characters(word: &str) -> Option<String> {
+    if let Some(&(_, c)) = WORDS.iter().find(|(name, _)| *name == word) {
+        return Some(c.to_string());
+    }
+    let mut chars = word.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return c.is_alphanumeric().then(|| c.to_string());
+    }
+    if word.chars().all(|c| c.is_ascii_digit()) {
+        return Some(word.to_string());
+    }
+    if word.contains('-') {
+        return word.split('-').map(characters).collect();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keep(text: &str) -> String {
+        spell(text, UnknownWords::Keep).unwrap()
+    }
+
+    /// Every word in the table, alone and through its alternative spellings
+    #[test]
+    fn test_mapping_table() {
+        let alphabet = "alpha bravo charlie delta echo foxtrot golf hotel india juliet kilo lima mike \
+                        november oscar papa quebec romeo sierra tango uniform victor whiskey x-ray yankee zulu";
+        assert_eq!(keep(alphabet), "abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(keep("alfa juliett whisky xray niner"), "ajwx9");
+        assert_eq!(keep("zero one two three four five six seven eight nine"), "0123456789");
+        assert_eq!(keep("dash hyphen underscore dot slash"), "--_./");
+        assert_eq!(keep("alpha space bravo"), "a b");
+        for (word, c) in WORDS {
+            assert_eq!(keep(word), c.to_string(), "{}", word);
+            assert_eq!(characters(word), Some(c.to_string()), "{}", word);
+        }
+    }
+
+    #[test]
+    fn test_letters_and_digits() {
+        assert_eq!(keep("a b 7"), "ab7");
+        assert_eq!(keep("A B C 1 2 3"), "abc123");
+        assert_eq!(keep("Alpha, Bravo, Seven."), "ab7");
+        assert_eq!(keep("J 42"), "j42");
+        assert_eq!(keep("A-B-7"), "ab7");
+        assert_eq!(keep("X-ray"), "x");
+        assert_eq!(keep("alpha-please"), "alpha-please");
+    }
+
+    #[test]
+    fn test_capitals() {
+        assert_eq!(keep("capital alpha bravo"), "Ab");
+        assert_eq!(keep("Capital J, uppercase q, seven"), "JQ7");
+        assert_eq!(keep("capital seven"), "7");
+        // Nothing after it to capitalize, so "capital" is kept as said
+        assert_eq!(keep("alpha capital"), "a capital");
+        assert_eq!(keep("capital ticket alpha"), "capital ticket a");
+        assert_eq!(keep("capital capital alpha"), "capital capital a");
+    }
+
+    /// Words that spell nothing split the runs of characters around them
+    #[test]
+    fn test_mixed_input() {
+        assert_eq!(keep("ticket alpha bravo dash one two"), "ticket ab-12");
+        assert_eq!(keep("alpha Bravo please seven"), "ab please 7");
+        assert_eq!(keep("hello world"), "hello world");
+        assert_eq!(keep(""), "");
+        assert_eq!(keep(" , . "), "");
+        assert_eq!(keep("twenty"), "twenty");
+    }
+
+    #[test]
+    fn test_unknown_words_can_fail() {
+        assert_eq!(spell("alpha bravo seven", UnknownWords::Fail), Ok("ab7".to_string()));
+        assert_eq!(spell("alpha please seven", UnknownWords::Fail), Err("please".to_string()));
+        assert_eq!(spell("alpha capital", UnknownWords::Fail), Err("capital".to_string()));
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(strip_prefix("Spell alpha bravo", "spell"), Some("alpha bravo"));
+        assert_eq!(strip_prefix("  spell: a b 7", "spell"), Some("a b 7"));
+        assert_eq!(strip_prefix("Spell.", "spell"), Some(""));
+        assert_eq!(strip_prefix("Spell out, alpha", "spell out"), Some("alpha"));
+        assert_eq!(strip_prefix("Spelling is hard", "spell"), None);
+        assert_eq!(strip_prefix("Please spell alpha", "spell"), None);
+        assert_eq!(strip_prefix("Spell", "spell out"), None);
+        assert_eq!(strip_prefix("alpha", ""), None);
+    }
+}
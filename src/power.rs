@@ -0,0 +1,101 @@
+use crate::events::AppEvent;
+use block::ConcreteBlock;
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use tao::event_loop::EventLoopProxy;
+
+/// `NSActivityIdleSystemSleepDisabled`
+const IDLE_SYSTEM_SLEEP_DISABLED: u64 = 1 << 20;
+/// `NSActivityUserInitiated`, which includes `IDLE_SYSTEM_SLEEP_DISABLED`
+const USER_INITIATED: u64 = 0x00FF_FFFF | IDLE_SYSTEM_SLEEP_DISABLED;
+
+/// Something the system should hold off throttling or sleeping for, while
+/// an `ActivityGuard` from `begin` is alive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activity {
+    /// Keeps App Nap and idle system sleep away from the recording
+    Recording,
+    /// Keeps App Nap from throttling the audio callback of a warm stream
+    AudioStream,
+}
+
+impl Activity {
+    fn options(self) -> u64 {
+        match self {
+            Self::Recording => USER_INITIATED | IDLE_SYSTEM_SLEEP_DISABLED,
+            Self::AudioStream => USER_INITIATED & !IDLE_SYSTEM_SLEEP_DISABLED,
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Self::Recording => "Recording dictation",
+            Self::AudioStream => "Listening for dictation",
+        }
+    }
+
+    /// Take an NSProcessInfo activity assertion, held until the guard drops
+    pub fn begin(self) -> ActivityGuard {
+        let token: id = unsafe {
+            let process_info: id = msg_send![class!(NSProcessInfo), processInfo];
+            let reason = NSString::alloc(nil).init_str(self.reason());
+            let token: id = msg_send![process_info, beginActivityWithOptions: self.options() reason: reason];
+            let _: () = msg_send![reason, release];
+            // Autoreleased, and it has to outlive the current pool
+            msg_send![token, retain]
+        };
+        log::debug!("Took {:?} power assertion", self);
+        ActivityGuard { activity: self, token }
+    }
+}
+
+/// Ends its activity when dropped, so it can't outlive what it covers
+pub struct ActivityGuard {
+    activity: Activity,
+    token: id,
+}
+
+impl Drop for ActivityGuard {
+    fn drop(&mut self) {
+        if self.token != nil {
+            unsafe {
+                let process_info: id = msg_send![class!(NSProcessInfo), processInfo];
+                let _: () = msg_send![process_info, endActivity: self.token];
+                let _: () = msg_send![self.token, release];
+            }
+        }
+        log::debug!("Released {:?} power assertion", self.activity);
+    }
+}
+
+/// Forward NSWorkspace sleep and wake notifications to the main loop as
+/// `SystemWillSleep` / `SystemDidWake`. The observers live for the rest of
+/// the process.
+pub fn observe_sleep_wake(proxy: EventLoopProxy<AppEvent>) {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let center: id = msg_send![workspace, notificationCenter];
+
+        observe(center, "NSWorkspaceWillSleepNotification", proxy.clone(), || {
+            AppEvent::SystemWillSleep
+        });
+        observe(center, "NSWorkspaceDidWakeNotification", proxy, || {
+            AppEvent::SystemDidWake
+        });
+    }
+}
+
+/// Send `event()` through `proxy` every time `center` posts `name`
+unsafe fn observe(center: id, name: &str, proxy: EventLoopProxy<AppEvent>, event: fn() -> AppEvent) {
+    let handler = ConcreteBlock::new(move |_notification: id| {
+        let _ = proxy.send_event(event());
+    })
+    .copy();
+
+    let name = NSString::alloc(nil).init_str(name);
+    let _observer: id = msg_send![center,
+        addObserverForName: name
+        object: nil
+        queue: nil
+        usingBlock: &*handler];
+}
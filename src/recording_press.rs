@@ -0,0 +1,187 @@
+//! The dictation hotkey press behind the recording in progress, and whether
+//! that recording runs with no key held, which is what `toggle_max_secs` caps
+
+use std::time::{Duration, Instant};
+
+use crate::config::RecordingMode;
+
+/// What releasing the dictation hotkey does to the recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Release {
+    /// End it and transcribe it
+    Stop,
+    /// Throw it away, the key was only brushed
+    Discard,
+    /// Let it run
+    Keep,
+}
+
+#[derive(Debug, Default)]
+pub struct RecordingPress {
+    /// The dictation hotkey is down
+    held: bool,
+    /// When the press that started the current recording began; taken when
+    /// its release is handled
+    pressed_at: Option<Instant>,
+    /// When the current recording started
+    started_at: Option<Instant>,
+    /// The current recording runs on its own: a toggle recording, or a
+    /// hybrid one latched by a tap
+    latched: bool,
+}
+
+impl RecordingPress {
+    /// The dictation hotkey went down or came up
+    pub fn hotkey_down(&mut self, down: bool) {
+        self.held = down;
+    }
+
+    /// A press queued while the model loaded, made at `since`, is starting
+    /// a recording that lasts until it's released
+    pub fn queued(&mut self, since: Instant) {
+        self.pressed_at = Some(since);
+    }
+
+    /// A recording started at `now`, from `ToggleRecording` if `toggle`. In
+    /// push-to-talk and hybrid mode it only runs on its own when nothing
+    /// holds the hotkey, e.g. a tray click or a queued tap.
+    pub fn started(&mut self, mode: RecordingMode, toggle: bool, now: Instant) {
+        // A queued press keeps the time it was made
+        self.pressed_at.get_or_insert(now);
+        self.started_at = Some(now);
+        self.latched = toggle && (mode == RecordingMode::Toggle || !self.held);
+    }
+
+    /// The recording didn't start after all, so no press is waiting on a
+    /// release
+    pub fn start_failed(&mut self) {
+        self.stopped();
+    }
+
+    /// The dictation hotkey came up at `now`. A push-to-talk recording
+    /// stops, unless the key was held under `min_hold`; a hybrid one stops
+    /// if it was held for `hybrid_hold`, and a shorter tap latches it.
+    pub fn released(
+        &mut self,
+        mode: RecordingMode,
+        now: Instant,
+        min_hold: Duration,
+        hybrid_hold: Duration,
+    ) -> Release {
+        let held = self.take_press().map(|at| now.saturating_duration_since(at));
+        match mode {
+            // Unset if the press hasn't been handled yet, so it was
+            // certainly short
+            RecordingMode::PushToTalk if held.is_none_or(|held| held < min_hold) => Release::Discard,
+            RecordingMode::PushToTalk => Release::Stop,
+            RecordingMode::Toggle => Release::Keep,
+            RecordingMode::Hybrid if held.is_some_and(|held| held >= hybrid_hold) => Release::Stop,
+            RecordingMode::Hybrid => {
+                self.latch();
+                Release::Keep
+            }
+        }
+    }
+
+    /// When the press that started the recording began, taken once its
+    /// release is handled; `None` if the press hasn't been handled yet
+    fn take_press(&mut self) -> Option<Instant> {
+        self.pressed_at.take()
+    }
+
+    /// A hybrid tap: the recording it started keeps going on its own
+    fn latch(&mut self) {
+        self.latched = self.started_at.is_some();
+    }
+
+    /// The recording ended, or was never started
+    pub fn stopped(&mut self) {
+        self.pressed_at = None;
+        self.started_at = None;
+        self.latched = false;
+    }
+
+    /// The recording mode changed; a recording in progress still ends on
+    /// the next press
+    pub fn mode_changed(&mut self) {
+        self.pressed_at = None;
+        self.latched = self.started_at.is_some();
+    }
+
+    pub fn is_latched(&self) -> bool {
+        self.latched
+    }
+
+    /// Whether a recording running on its own has gone on for `max` by
+    /// `now`; never for a `max` of 0
+    pub fn over_limit(&self, max: Duration, now: Instant) -> bool {
+        match self.started_at {
+            Some(started) if self.latched && !max.is_zero() => now.saturating_duration_since(started) >= max,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: Duration = Duration::from_secs(60);
+    const HOLD: Duration = Duration::from_millis(300);
+
+    /// Whether a recording started with the hotkey `held` stops at the limit
+    fn capped(mode: RecordingMode, toggle: bool, held: bool) -> bool {
+        let start = Instant::now();
+        let mut press = RecordingPress::default();
+        press.hotkey_down(held);
+        press.started(mode, toggle, start);
+        press.over_limit(MAX, start + MAX)
+    }
+
+    /// Only recordings nothing holds down are capped
+    #[test]
+    fn test_only_toggle_recordings_are_capped() {
+        assert!(capped(RecordingMode::Toggle, true, true));
+        assert!(!capped(RecordingMode::PushToTalk, false, true));
+        // A double press in push-to-talk starts with `ToggleRecording`
+        assert!(!capped(RecordingMode::PushToTalk, true, true));
+        // Hybrid starts every press with `ToggleRecording`, held or not
+        assert!(!capped(RecordingMode::Hybrid, true, true));
+        // The tray or a queued tap, with nothing held
+        assert!(capped(RecordingMode::Hybrid, true, false));
+        assert!(capped(RecordingMode::PushToTalk, true, false));
+    }
+
+    #[test]
+    fn test_hybrid_tap_latches() {
+        let start = Instant::now();
+        let mut press = RecordingPress::default();
+        press.hotkey_down(true);
+        press.started(RecordingMode::Hybrid, true, start);
+        assert!(!press.over_limit(MAX, start + MAX));
+
+        press.hotkey_down(false);
+        assert_eq!(press.released(RecordingMode::Hybrid, start + HOLD / 2, Duration::ZERO, HOLD), Release::Keep);
+        assert!(press.over_limit(MAX, start + MAX));
+        assert!(!press.over_limit(MAX, start + MAX / 2));
+        assert!(!press.over_limit(Duration::ZERO, start + MAX), "0 never caps");
+
+        press.stopped();
+        assert_eq!(press.released(RecordingMode::Hybrid, start + MAX, Duration::ZERO, HOLD), Release::Keep);
+        assert!(!press.is_latched(), "a tap after the stop latches nothing");
+    }
+
+    /// A queued press whose recording fails to start leaves no press for
+    /// the next release to measure
+    #[test]
+    fn test_failed_start_clears_the_press() {
+        let since = Instant::now();
+        let mut press = RecordingPress::default();
+        press.queued(since);
+        press.start_failed();
+        assert_eq!(press.take_press(), None);
+
+        press.started(RecordingMode::PushToTalk, false, since + Duration::from_secs(1));
+        assert_eq!(press.take_press(), Some(since + Duration::from_secs(1)));
+    }
+}
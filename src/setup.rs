@@ -0,0 +1,333 @@
+//! First-run setup in the terminal: pick and download a model, then check
+//! that the microphone and the model work before the app starts
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use tao::event_loop::EventLoopProxy;
+use thiserror::Error;
+use ureq::tls::{TlsConfig, TlsProvider};
+use ureq::Agent;
+
+use crate::audio::{AudioCapture, AudioError, AudioSource};
+use crate::config::{Backend, Config, ConfigError, Overrides, WhisperModel};
+use crate::events::AppEvent;
+use crate::model;
+use crate::permissions::{self, PermissionKind, PermissionStatus};
+use crate::transcriber::{ModelError, TranscriptionOutcome};
+
+type Result<T> = std::result::Result<T, SetupError>;
+
+/// Why setup couldn't finish
+#[derive(Debug, Error)]
+pub enum SetupError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Model(#[from] ModelError),
+    /// Reading the answer from the terminal failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("No model chosen")]
+    NoModelChosen,
+    #[error("Couldn't download {model}: {source}")]
+    Download {
+        model: &'static str,
+        source: Box<SetupError>,
+    },
+    #[error("download request failed: {0}")]
+    Http(#[from] ureq::Error),
+    #[error("Download interrupted: {0}")]
+    Interrupted(io::Error),
+    #[error("Download ended early")]
+    Truncated,
+    /// Creating, writing or moving the downloaded file failed
+    #[error("Couldn't {action} {path:?}: {source}")]
+    File {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    #[error("Microphone access is {status}; grant it in {settings} and run with --setup again")]
+    MicrophoneDenied {
+        status: PermissionStatus,
+        settings: &'static str,
+    },
+    #[error("Couldn't open the microphone: {0}")]
+    OpenMicrophone(AudioError),
+    #[error("Couldn't start recording: {0}")]
+    StartRecording(AudioError),
+    /// The test recording was pure silence
+    #[error("{0}")]
+    Silent(&'static str),
+}
+
+/// Adds the file and what was being done with it to an I/O error
+fn file_error(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> SetupError {
+    let path = path.to_path_buf();
+    move |source| SetupError::File { action, path, source }
+}
+
+/// Length of the test recording
+const TEST_RECORDING: Duration = Duration::from_secs(2);
+/// Peaks below this mean the microphone is barely picking anything up
+const QUIET_DBFS: f32 = -40.0;
+/// How long to wait for an answer to the microphone prompt
+const PERMISSION_TIMEOUT: Duration = Duration::from_secs(120);
+/// Characters in the download progress bar
+const BAR_WIDTH: usize = 30;
+
+/// Walk through setup for the config file at `path`, which exists by now,
+/// and return the config it leaves there
+pub fn run(path: &Path, overrides: &Overrides, proxy: EventLoopProxy<AppEvent>) -> Result<Config> {
+    println!("Setting up dictation with {}\n", path.display());
+    // The model picked here should win over `--model`
+    let overrides = Overrides {
+        model: None,
+        ..overrides.clone()
+    };
+    let mut config = Config::load_with_overrides(path, &overrides)?;
+
+    if config.backend == Backend::Local {
+        let model = choose_model(config.model)?;
+        let model_path = Config::models_dir().join(model.filename());
+        if model_path.is_file() {
+            println!("{} is already downloaded", model.filename());
+        } else {
+            download(&model.download_url(), &model_path).map_err(|e| SetupError::Download {
+                model: model.filename(),
+                source: Box::new(e),
+            })?;
+        }
+        Config::set_model(path, model)?;
+        config = Config::load_with_overrides(path, &overrides)?;
+        println!("Saved model = \"{}\"", model_name(model));
+    } else {
+        println!("Transcribing on {}, so there's no model to download", config.remote.endpoint);
+    }
+
+    println!("\nChecking microphone access...");
+    check_microphone()?;
+
+    println!("\nRecording for {} seconds, say something...", TEST_RECORDING.as_secs());
+    let (samples, sample_rate) = record(&config, proxy)?;
+    let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+    match level_problem(peak) {
+        Some(problem) if peak == 0.0 => return Err(SetupError::Silent(problem)),
+        Some(problem) => println!("Peak level {:.0} dBFS: {}", dbfs(peak), problem),
+        None => println!("Peak level {:.0} dBFS", dbfs(peak)),
+    }
+
+    println!("\nLoading the model...");
+    let backend = model::open_backend(&config)?;
+    if config.warmup {
+        let (clip, clip_rate) = model::warmup_clip(&config);
+        backend.warmup(&clip, clip_rate)?;
+    }
+    match backend.transcribe(&samples, sample_rate, None, None, None)? {
+        TranscriptionOutcome::Text(result) => println!("Heard: {}", result.text),
+        TranscriptionOutcome::NoSpeech => println!("Heard nothing, which is fine if you stayed quiet"),
+    }
+
+    println!("\nSetup complete. Press {} to dictate.", config.hotkey);
+    Ok(config)
+}
+
+/// List the models and read the user's pick; Enter keeps `current`
+fn choose_model(current: WhisperModel) -> Result<WhisperModel> {
+    println!("Whisper models (larger ones are more accurate but slower):");
+    for (i, model) in WhisperModel::ALL.iter().enumerate() {
+        let downloaded = Config::models_dir().join(model.filename()).is_file();
+        println!(
+            "{:>4}. {:<20} {:>5} MB{}",
+            i + 1,
+            model_name(*model),
+            model.approx_size_mb(),
+            if downloaded { "  (downloaded)" } else { "" }
+        );
+    }
+    loop {
+        print!("Model [{}]: ", model_name(current));
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Err(SetupError::NoModelChosen);
+        }
+        match parse_choice(&line, current) {
+            Some(model) => return Ok(model),
+            None => println!("Enter a number from 1 to {} or a model name", WhisperModel::ALL.len()),
+        }
+    }
+}
+
+/// A number from the list, a model's config name, or nothing for `current`
+fn parse_choice(input: &str, current: WhisperModel) -> Option<WhisperModel> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Some(current);
+    }
+    if let Ok(number) = input.parse::<usize>() {
+        return number.checked_sub(1).and_then(|i| WhisperModel::ALL.get(i)).copied();
+    }
+    serde_json::from_value(serde_json::Value::String(input.to_lowercase())).ok()
+}
+
+/// How `model` is spelled in config.toml
+fn model_name(model: WhisperModel) -> String {
+    match serde_json::to_value(model) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", model),
+    }
+}
+
+/// Fetch `url` to `dest`, drawing progress on stderr. The file only appears
+/// at `dest` once complete, so an interrupted download is never loaded.
+fn download(url: &str, dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(file_error("create", parent))?;
+    }
+    let agent: Agent = Agent::config_builder()
+        .tls_config(TlsConfig::builder().provider(TlsProvider::NativeTls).build())
+        .build()
+        .into();
+    println!("Downloading {}", url);
+    let response = agent.get(url).call()?;
+    let total = response.body().content_length();
+    let mut reader = response.into_body().into_reader();
+
+    let partial = dest.with_extension("part");
+    let mut file = File::create(&partial).map_err(file_error("create", &partial))?;
+    let mut buf = vec![0; 256 * 1024];
+    let mut done = 0;
+    let mut drawn = Instant::now();
+    loop {
+        let read = reader.read(&mut buf).map_err(SetupError::Interrupted)?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buf[..read]).map_err(file_error("write", &partial))?;
+        done += read as u64;
+        if drawn.elapsed() >= Duration::from_millis(100) {
+            eprint!("\r{}", progress_bar(done, total));
+            drawn = Instant::now();
+        }
+    }
+    eprintln!("\r{}", progress_bar(done, total));
+    if total.is_some_and(|total| done < total) {
+        return Err(SetupError::Truncated);
+    }
+    file.sync_all().map_err(file_error("write", &partial))?;
+    fs::rename(&partial, dest).map_err(file_error("move the download to", dest))?;
+    Ok(())
+}
+
+/// e.g. `[#######.......]  50%  71/142 MB`; just the count when the size
+/// isn't known
+fn progress_bar(done: u64, total: Option<u64>) -> String {
+    const MB: u64 = 1024 * 1024;
+    match total {
+        Some(total) if total > 0 => {
+            let filled = (done.min(total) * BAR_WIDTH as u64 / total) as usize;
+            format!(
+                "[{}{}] {:>3}%  {}/{} MB",
+                "#".repeat(filled),
+                ".".repeat(BAR_WIDTH - filled),
+                done.min(total) * 100 / total,
+                done / MB,
+                total / MB
+            )
+        }
+        _ => format!("{} MB", done / MB),
+    }
+}
+
+/// Fail with where to grant access unless the microphone can be used,
+/// asking first if it hasn't been asked yet
+fn check_microphone() -> Result<()> {
+    let mut status = permissions::microphone_status();
+    if status == PermissionStatus::Undetermined {
+        println!("macOS will ask for microphone access; allow it to continue");
+        status = permissions::request_microphone_and_wait(PERMISSION_TIMEOUT);
+    }
+    match status {
+        PermissionStatus::Granted => {
+            println!("Microphone access granted");
+            Ok(())
+        }
+        _ => Err(SetupError::MicrophoneDenied {
+            status,
+            settings: PermissionKind::Microphone.settings_path(),
+        }),
+    }
+}
+
+/// Record `TEST_RECORDING` from the configured input
+fn record(config: &Config, proxy: EventLoopProxy<AppEvent>) -> Result<(Vec<f32>, u32)> {
+    let mut capture = AudioCapture::new(config, proxy).map_err(SetupError::OpenMicrophone)?;
+    capture.start_recording().map_err(SetupError::StartRecording)?;
+    thread::sleep(TEST_RECORDING);
+    let samples = capture.stop_recording();
+    Ok((samples, capture.sample_rate()))
+}
+
+fn dbfs(peak: f32) -> f32 {
+    20.0 * peak.max(1e-6).log10()
+}
+
+/// What's wrong with a recording peaking at `peak`, if anything
+fn level_problem(peak: f32) -> Option<&'static str> {
+    if peak == 0.0 {
+        Some("the recording was pure silence; check input_device and the microphone access")
+    } else if dbfs(peak) < QUIET_DBFS {
+        Some("very quiet; speak up, move closer, or raise the input volume in System Settings > Sound")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_choice() {
+        assert_eq!(parse_choice("\n", WhisperModel::SmallEn), Some(WhisperModel::SmallEn));
+        assert_eq!(parse_choice("1\n", WhisperModel::SmallEn), Some(WhisperModel::TinyEn));
+        assert_eq!(
+            parse_choice(" large_v3_turbo_q5_0 ", WhisperModel::BaseEn),
+            Some(WhisperModel::LargeV3TurboQ5)
+        );
+        assert_eq!(parse_choice("Medium_EN", WhisperModel::BaseEn), Some(WhisperModel::MediumEn));
+        assert_eq!(parse_choice("0", WhisperModel::BaseEn), None);
+        assert_eq!(parse_choice("99", WhisperModel::BaseEn), None);
+        assert_eq!(parse_choice("huge", WhisperModel::BaseEn), None);
+    }
+
+    #[test]
+    fn test_model_names_match_the_config() {
+        assert_eq!(model_name(WhisperModel::BaseEn), "base_en");
+        assert_eq!(model_name(WhisperModel::MediumEnQ8), "medium_en_q8_0");
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(
+            progress_bar(71 * MB, Some(142 * MB)),
+            format!("[{}{}]  50%  71/142 MB", "#".repeat(15), ".".repeat(15))
+        );
+        assert_eq!(progress_bar(0, Some(10)), format!("[{}]   0%  0/0 MB", ".".repeat(30)));
+        assert!(progress_bar(200, Some(100)).contains("100%"));
+        assert_eq!(progress_bar(3 * MB, None), "3 MB");
+    }
+
+    #[test]
+    fn test_level_problems() {
+        assert!(level_problem(0.0).unwrap().contains("silence"));
+        assert!(level_problem(0.005).unwrap().contains("quiet"));
+        assert_eq!(level_problem(0.3), None);
+        assert!((dbfs(0.5) + 6.0).abs() < 0.1);
+    }
+}
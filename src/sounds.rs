@@ -0,0 +1,61 @@
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use std::time::Duration;
+
+/// Audible feedback for recording start/stop
+#[derive(Debug, Clone, Copy)]
+pub enum Cue {
+    Start,
+    Stop,
+}
+
+impl Cue {
+    /// Built-in macOS system sound used for this cue
+    fn sound_name(&self) -> &'static str {
+        match self {
+            Cue::Start => "Tink",
+            Cue::Stop => "Pop",
+        }
+    }
+}
+
+/// Plays short system sounds via NSSound. Playback is asynchronous, so
+/// `play` returns immediately and never delays recording start.
+pub struct SoundPlayer {
+    enabled: bool,
+    volume: f32,
+}
+
+impl SoundPlayer {
+    pub fn new(enabled: bool, volume: f32) -> Self {
+        Self {
+            enabled,
+            volume: volume.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Start playing the cue and return how long it will be audible
+    /// (zero when sounds are disabled or unavailable).
+    pub fn play(&self, cue: Cue) -> Duration {
+        if !self.enabled {
+            return Duration::ZERO;
+        }
+
+        unsafe {
+            let name = NSString::alloc(nil).init_str(cue.sound_name());
+            let sound: id = msg_send![class!(NSSound), soundNamed: name];
+            if sound == nil {
+                log::warn!("System sound '{}' not found", cue.sound_name());
+                return Duration::ZERO;
+            }
+
+            // Stop a previous play of the same (shared) sound so it restarts
+            let _: () = msg_send![sound, stop];
+            let _: () = msg_send![sound, setVolume: self.volume];
+            let _: () = msg_send![sound, play];
+
+            let secs: f64 = msg_send![sound, duration];
+            Duration::from_secs_f64(secs.max(0.0))
+        }
+    }
+}
@@ -1,7 +1,15 @@
+use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+use serde::Serialize;
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
+use std::thread;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Transitions a subscriber can fall behind by before new ones are dropped
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum ReadinessState {
     Cold = 0,
@@ -26,15 +34,25 @@ impl From<u8> for ReadinessState {
     }
 }
 
+/// One call to `StateManager::set`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateChange {
+    pub from: ReadinessState,
+    pub to: ReadinessState,
+}
+
 #[derive(Clone)]
 pub struct StateManager {
     state: Arc<AtomicU8>,
+    /// Also held while setting, so every subscriber sees transitions in order
+    subscribers: Arc<Mutex<Vec<Sender<StateChange>>>>,
 }
 
 impl StateManager {
     pub fn new() -> Self {
         Self {
             state: Arc::new(AtomicU8::new(ReadinessState::Cold as u8)),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -43,28 +61,57 @@ impl StateManager {
     }
 
     pub fn set(&self, state: ReadinessState) {
-        self.state.store(state as u8, Ordering::SeqCst);
-        log::info!("State transition -> {:?}", state);
+        let mut subscribers = self.subscribers.lock();
+        let from = ReadinessState::from(self.state.swap(state as u8, Ordering::SeqCst));
+        notify(&mut subscribers, StateChange { from, to: state });
     }
 
-    pub fn transition_to_loading(&self) -> bool {
-        let current = self.get();
-        if current == ReadinessState::Cold {
-            self.set(ReadinessState::Loading);
-            true
-        } else {
-            false
+    /// Move to `to` only from one of the `from` states, atomically, so two
+    /// threads can't both make the same transition. Fails with the state
+    /// actually found.
+    pub fn transition_if(&self, from: &[ReadinessState], to: ReadinessState) -> Result<(), ReadinessState> {
+        let mut subscribers = self.subscribers.lock();
+        let mut current = self.get();
+        loop {
+            if !from.contains(&current) {
+                return Err(current);
+            }
+            match self
+                .state
+                .compare_exchange(current as u8, to as u8, Ordering::SeqCst, Ordering::SeqCst)
+            {
+                Ok(_) => break,
+                Err(actual) => current = ReadinessState::from(actual),
+            }
         }
+        notify(&mut subscribers, StateChange { from: current, to });
+        Ok(())
+    }
+
+    /// Every transition from now on, including ones that don't change the
+    /// state. Transitions are dropped while `SUBSCRIBER_CAPACITY` are unread.
+    pub fn subscribe(&self) -> Receiver<StateChange> {
+        let (tx, rx) = bounded(SUBSCRIBER_CAPACITY);
+        self.subscribers.lock().push(tx);
+        rx
+    }
+
+    /// Log each transition from a background thread
+    pub fn log_transitions(&self) {
+        let changes = self.subscribe();
+        thread::spawn(move || {
+            for change in changes {
+                log::info!("State transition {:?} -> {:?}", change.from, change.to);
+            }
+        });
+    }
+
+    pub fn transition_to_loading(&self) -> bool {
+        self.transition_if(&[ReadinessState::Cold], ReadinessState::Loading).is_ok()
     }
 
     pub fn transition_to_warm(&self) -> bool {
-        let current = self.get();
-        if current == ReadinessState::Loading {
-            self.set(ReadinessState::Warm);
-            true
-        } else {
-            false
-        }
+        self.transition_if(&[ReadinessState::Loading], ReadinessState::Warm).is_ok()
     }
 
     /// Transition to Hot (idle, ready) state after completing work.
@@ -73,9 +120,15 @@ impl StateManager {
         self.set(ReadinessState::Hot);
     }
 
+    /// Fails without a loaded model (Cold or Loading) or while already
+    /// recording. A transcription in progress doesn't block a new recording,
+    /// which is queued behind it.
     pub fn transition_to_recording(&self) -> bool {
-        self.set(ReadinessState::Recording);
-        true
+        self.transition_if(
+            &[ReadinessState::Warm, ReadinessState::Hot, ReadinessState::Transcribing],
+            ReadinessState::Recording,
+        )
+        .is_ok()
     }
 
     pub fn transition_to_transcribing(&self) -> bool {
@@ -95,6 +148,12 @@ impl StateManager {
     }
 }
 
+/// A subscriber that stopped reading misses transitions rather than holding
+/// up the rest of the app; one that was dropped is forgotten
+fn notify(subscribers: &mut Vec<Sender<StateChange>>, change: StateChange) {
+    subscribers.retain(|tx| !matches!(tx.try_send(change), Err(TrySendError::Disconnected(_))));
+}
+
 impl Default for StateManager {
     fn default() -> Self {
         Self::new()
@@ -155,22 +214,14 @@ mod tests {
         }
     }
 
-    /// Test Hypothesis #6: transition_to_recording always succeeds
-    /// This is important because recording should always be possible
+    /// Test Hypothesis #6: recording can start once a model is loaded,
+    /// including behind a transcription still running
     #[test]
-    fn test_transition_to_recording_always_succeeds() {
-        let states_to_test = [
-            ReadinessState::Cold,
-            ReadinessState::Loading,
-            ReadinessState::Warm,
-            ReadinessState::Hot,
-            ReadinessState::Transcribing,
-        ];
-
-        for initial_state in states_to_test {
+    fn test_transition_to_recording_needs_a_model() {
+        for initial_state in [ReadinessState::Warm, ReadinessState::Hot, ReadinessState::Transcribing] {
             let state = StateManager::new();
             state.set(initial_state);
-            
+
             assert!(
                 state.transition_to_recording(),
                 "transition_to_recording should succeed from {:?}",
@@ -178,6 +229,57 @@ mod tests {
             );
             assert_eq!(state.get(), ReadinessState::Recording);
         }
+
+        for initial_state in [ReadinessState::Cold, ReadinessState::Loading, ReadinessState::Recording] {
+            let state = StateManager::new();
+            state.set(initial_state);
+
+            assert!(
+                !state.transition_to_recording(),
+                "transition_to_recording should fail from {:?}",
+                initial_state
+            );
+            assert_eq!(state.get(), initial_state);
+        }
+    }
+
+    /// A refused transition reports the state it found and changes nothing
+    #[test]
+    fn test_transition_if_reports_actual_state() {
+        let state = StateManager::new();
+        state.set(ReadinessState::Hot);
+        let changes = state.subscribe();
+
+        assert_eq!(
+            state.transition_if(&[ReadinessState::Cold, ReadinessState::Loading], ReadinessState::Warm),
+            Err(ReadinessState::Hot)
+        );
+        assert_eq!(state.get(), ReadinessState::Hot);
+        assert!(changes.try_recv().is_err(), "Refused transitions aren't reported");
+
+        assert_eq!(state.transition_if(&[ReadinessState::Hot], ReadinessState::Cold), Ok(()));
+        assert_eq!(
+            changes.try_recv(),
+            Ok(StateChange { from: ReadinessState::Hot, to: ReadinessState::Cold })
+        );
+    }
+
+    /// Threads racing to start a load: exactly one wins
+    #[test]
+    fn test_concurrent_loading_has_one_winner() {
+        for _ in 0..100 {
+            let state = StateManager::new();
+            let winners: usize = (0..8)
+                .map(|_| {
+                    let state = state.clone();
+                    thread::spawn(move || state.transition_to_loading())
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| handle.join().unwrap() as usize)
+                .sum();
+            assert_eq!(winners, 1);
+        }
     }
 
     /// Test Hypothesis #6: transition_to_transcribing always succeeds
@@ -248,6 +350,61 @@ mod tests {
         assert_eq!(state.get(), ReadinessState::Hot);
     }
 
+    /// Subscribers see a whole recording flow's transitions in order
+    #[test]
+    fn test_subscriber_sees_recording_flow() {
+        let state = StateManager::new();
+        let changes = state.subscribe();
+
+        state.transition_to_loading();
+        state.transition_to_idle();
+        state.transition_to_recording();
+        state.transition_to_transcribing();
+        state.transition_to_idle();
+        // Refused transitions aren't reported
+        assert!(!state.transition_to_loading());
+
+        use ReadinessState::*;
+        let expected = [
+            (Cold, Loading),
+            (Loading, Hot),
+            (Hot, Recording),
+            (Recording, Transcribing),
+            (Transcribing, Hot),
+        ]
+        .map(|(from, to)| StateChange { from, to });
+        assert_eq!(changes.try_iter().collect::<Vec<_>>(), expected);
+    }
+
+    /// Clones share subscribers, and each subscriber gets every transition
+    #[test]
+    fn test_subscribers_on_clones() {
+        let state = StateManager::new();
+        let first = state.subscribe();
+        let second = state.clone().subscribe();
+
+        state.clone().set(ReadinessState::Hot);
+        let change = StateChange { from: ReadinessState::Cold, to: ReadinessState::Hot };
+        assert_eq!(first.try_recv(), Ok(change));
+        assert_eq!(second.try_recv(), Ok(change));
+    }
+
+    /// Dropped or stalled subscribers never block a transition
+    #[test]
+    fn test_slow_or_dropped_subscribers_dont_block() {
+        let state = StateManager::new();
+        drop(state.subscribe());
+        let stalled = state.subscribe();
+
+        for _ in 0..SUBSCRIBER_CAPACITY * 2 {
+            state.set(ReadinessState::Hot);
+        }
+        assert_eq!(state.subscribers.lock().len(), 1, "The dropped subscriber should be forgotten");
+        // The earliest transitions are kept, the overflow is dropped
+        assert_eq!(stalled.try_iter().count(), SUBSCRIBER_CAPACITY);
+        assert_eq!(stalled.try_recv().unwrap_err(), crossbeam_channel::TryRecvError::Empty);
+    }
+
     /// Test that transition_to_cold works from any state
     #[test]
     fn test_transition_to_cold_works_from_any_state() {
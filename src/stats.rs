@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, StatsError>;
+
+/// Why `stats.toml` couldn't be written
+#[derive(Debug, Error)]
+pub enum StatsError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("couldn't serialize the stats: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// How long changes sit in memory before `stats.toml` is rewritten, so a
+/// burst of dictations costs one write
+const SAVE_DELAY: Duration = Duration::from_secs(10);
+
+/// Usage counters for one run or for all time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Counters {
+    pub dictations: u64,
+    pub words: u64,
+    /// Length of the recordings that produced text
+    pub audio_secs: f64,
+    /// Sum over all dictations, for the average
+    pub latency_ms: u64,
+    /// Keyed by `FailureReason::label`
+    pub failures: BTreeMap<String, u64>,
+    /// Words output into each app, keyed by bundle id
+    pub words_by_app: BTreeMap<String, u64>,
+}
+
+impl Counters {
+    fn record_success(&mut self, text: &str, audio_secs: f32, latency: Duration, app: Option<&str>) {
+        let words = text.split_whitespace().count() as u64;
+        self.dictations += 1;
+        self.words += words;
+        self.audio_secs += f64::from(audio_secs);
+        self.latency_ms += latency.as_millis() as u64;
+        if let Some(app) = app {
+            *self.words_by_app.entry(app.to_string()).or_default() += words;
+        }
+    }
+
+    /// The `count` apps with the most words, most first
+    pub fn top_apps(&self, count: usize) -> Vec<(&str, u64)> {
+        let mut apps: Vec<(&str, u64)> = self
+            .words_by_app
+            .iter()
+            .map(|(app, &words)| (app.as_str(), words))
+            .collect();
+        apps.sort_by_key(|&(_, words)| std::cmp::Reverse(words));
+        apps.truncate(count);
+        apps
+    }
+
+    fn record_failure(&mut self, reason: &str) {
+        *self.failures.entry(reason.to_string()).or_default() += 1;
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.dictations > 0).then(|| Duration::from_millis(self.latency_ms / self.dictations))
+    }
+}
+
+impl fmt::Display for Counters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} dictations, {} words, {:.0}s of audio",
+            self.dictations, self.words, self.audio_secs
+        )?;
+        if let Some(latency) = self.average_latency() {
+            write!(f, ", {} ms average transcription time", latency.as_millis())?;
+        }
+        if !self.failures.is_empty() {
+            let failures: Vec<String> = self
+                .failures
+                .iter()
+                .map(|(reason, count)| format!("{} {}", count, reason))
+                .collect();
+            write!(f, "; failures: {}", failures.join(", "))?;
+        }
+        if !self.words_by_app.is_empty() {
+            let apps: Vec<String> = self
+                .top_apps(3)
+                .iter()
+                .map(|(app, words)| format!("{} {}", app, words))
+                .collect();
+            write!(f, "; most words in {}", apps.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Counters for this run plus the all-time totals kept in `stats.toml`
+pub struct Stats {
+    pub session: Counters,
+    pub total: Counters,
+    path: PathBuf,
+    /// When the first unsaved change was made
+    dirty_since: Option<Instant>,
+    /// Off with `save_stats = false`: only `session` is counted
+    saving: bool,
+}
+
+impl Stats {
+    /// Load the all-time totals from `path`, starting from zero if the file
+    /// is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        let total = match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable stats file {:?}: {}", path, e);
+                Counters::default()
+            }),
+            Err(_) => Counters::default(),
+        };
+        Self {
+            session: Counters::default(),
+            total,
+            path: path.to_path_buf(),
+            dirty_since: None,
+            saving: true,
+        }
+    }
+
+    /// Whether dictations count towards the all-time totals. Changes from
+    /// before it's turned off are saved first.
+    pub fn set_saving(&mut self, saving: bool) {
+        if !saving {
+            self.save();
+        }
+        self.saving = saving;
+    }
+
+    /// `app` is what the text went into, when known
+    pub fn record_success(&mut self, text: &str, audio_secs: f32, latency: Duration, app: Option<&str>) {
+        self.session.record_success(text, audio_secs, latency, app);
+        if self.saving {
+            self.total.record_success(text, audio_secs, latency, app);
+            self.mark_dirty();
+        }
+    }
+
+    pub fn record_failure(&mut self, reason: &str) {
+        self.session.record_failure(reason);
+        if self.saving {
+            self.total.record_failure(reason);
+            self.mark_dirty();
+        }
+    }
+
+    fn mark_dirty(&mut self) {
+        self.dirty_since.get_or_insert_with(Instant::now);
+    }
+
+    /// Save if changes have been waiting for longer than `SAVE_DELAY`
+    pub fn save_if_due(&mut self) {
+        if self.dirty_since.is_some_and(|since| since.elapsed() >= SAVE_DELAY) {
+            self.save();
+        }
+    }
+
+    /// Save any unsaved changes now
+    pub fn save(&mut self) {
+        if self.dirty_since.take().is_none() {
+            return;
+        }
+        if let Err(e) = self.write() {
+            log::warn!("Failed to save stats to {:?}: {}", self.path, e);
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, toml::to_string_pretty(&self.total)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("dictation-stats-{}-{}.toml", std::process::id(), name))
+    }
+
+    /// Successes add up words, audio, and latency; failures are counted by reason
+    #[test]
+    fn test_counters_accumulate() {
+        let mut counters = Counters::default();
+        assert_eq!(counters.average_latency(), None);
+
+        counters.record_success("hello there world", 2.0, Duration::from_millis(300), None);
+        counters.record_success(" one  ", 1.5, Duration::from_millis(500), None);
+        counters.record_failure("no_speech");
+        counters.record_failure("no_speech");
+        counters.record_failure("timeout");
+
+        assert_eq!(counters.dictations, 2);
+        assert_eq!(counters.words, 4);
+        assert!((counters.audio_secs - 3.5).abs() < 1e-9);
+        assert_eq!(counters.average_latency(), Some(Duration::from_millis(400)));
+        assert_eq!(counters.failures["no_speech"], 2);
+        assert_eq!(counters.failures["timeout"], 1);
+    }
+
+    /// Words are counted per app when the app is known
+    #[test]
+    fn test_words_by_app() {
+        let mut counters = Counters::default();
+        let latency = Duration::from_millis(100);
+        counters.record_success("one two", 1.0, latency, Some("com.apple.TextEdit"));
+        counters.record_success("three four five", 1.0, latency, Some("com.tinyspeck.slackmacgap"));
+        counters.record_success("six", 1.0, latency, Some("com.apple.TextEdit"));
+        counters.record_success("unknown app", 1.0, latency, None);
+        counters.record_success("seven", 1.0, latency, Some("Preview"));
+
+        assert_eq!(counters.words, 9);
+        assert_eq!(
+            counters.top_apps(2),
+            vec![("com.apple.TextEdit", 3), ("com.tinyspeck.slackmacgap", 3)]
+        );
+        assert_eq!(counters.top_apps(5).len(), 3);
+        assert!(counters
+            .to_string()
+            .ends_with("; most words in com.apple.TextEdit 3, com.tinyspeck.slackmacgap 3, Preview 1"));
+
+        // Stats files from before per-app counts still load
+        let old: Counters = toml::from_str("dictations = 2\nwords = 5\n").unwrap();
+        assert!(old.words_by_app.is_empty());
+    }
+
+    /// Totals survive a save and reload; session counters start over
+    #[test]
+    fn test_totals_persist_across_loads() {
+        let path = stats_path("persist");
+        let _ = fs::remove_file(&path);
+
+        let mut stats = Stats::load(&path);
+        stats.record_success("a b c", 1.0, Duration::from_millis(100), Some("com.apple.TextEdit"));
+        stats.record_failure("too_short");
+        stats.save_if_due();
+        assert!(!path.exists(), "Saving should wait for the delay");
+        stats.save();
+
+        let mut reloaded = Stats::load(&path);
+        assert_eq!(reloaded.total, stats.total);
+        assert_eq!(reloaded.session, Counters::default());
+
+        reloaded.record_success("d", 1.0, Duration::from_millis(100), None);
+        assert_eq!(reloaded.total.dictations, 2);
+        assert_eq!(reloaded.session.dictations, 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// With saving off (e.g. in privacy mode) the session still counts, but
+    /// the totals and the file are left alone
+    #[test]
+    fn test_nothing_saved_when_saving_is_off() {
+        let path = stats_path("private");
+        let _ = fs::remove_file(&path);
+
+        let mut stats = Stats::load(&path);
+        stats.record_success("before", 1.0, Duration::from_millis(100), None);
+        stats.set_saving(false);
+        assert!(path.exists(), "Earlier changes should be saved first");
+        fs::remove_file(&path).unwrap();
+
+        stats.record_success("secret words", 1.0, Duration::from_millis(100), Some("com.apple.Notes"));
+        stats.record_failure("no_speech");
+        stats.save();
+        assert!(!path.exists());
+        assert_eq!(stats.session.dictations, 2);
+        assert_eq!(stats.total.dictations, 1);
+        assert!(stats.total.failures.is_empty());
+        assert!(stats.total.words_by_app.is_empty());
+    }
+
+    /// A corrupt stats file is ignored rather than failing startup
+    #[test]
+    fn test_unreadable_file_starts_from_zero() {
+        let path = stats_path("corrupt");
+        fs::write(&path, "dictations = \"many\"").unwrap();
+        assert_eq!(Stats::load(&path).total, Counters::default());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+//! `http_status_port`: a tiny HTTP endpoint on localhost that status bars
+//! (sketchybar, Übersicht and the like) can poll. `GET /status` returns the
+//! app's state as JSON; everything else is refused.
+
+use crate::config::{Backend, Config};
+use crate::state::{ReadinessState, StateManager};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Longest request head read before giving up on the request
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// How long a client gets to send its request and take the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What the main loop knows and the state machine doesn't, updated as it
+/// changes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusSnapshot {
+    /// Whether the current recording is a toggle recording
+    pub toggle_recording: bool,
+    /// Length of the current recording, pauses excluded; `None` when not recording
+    pub recording_secs: Option<f64>,
+    /// Input level of the current recording, 0.0 to 1.0
+    pub level: f32,
+    /// When the last transcript was output, as an ISO 8601 local time
+    pub last_transcription_at: Option<String>,
+    /// The model transcribing, as named in config.toml
+    pub model: String,
+}
+
+/// `GET /status` response body
+#[derive(Debug, Serialize)]
+struct StatusJson<'a> {
+    state: ReadinessState,
+    toggle_recording: bool,
+    recording_secs: Option<f64>,
+    level: f32,
+    last_transcription_at: Option<&'a str>,
+    model: &'a str,
+}
+
+/// What a request gets back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    Status,
+    NotFound,
+    MethodNotAllowed,
+    /// Naming a host other than localhost, as a web page can through DNS rebinding
+    Forbidden,
+    BadRequest,
+}
+
+impl Route {
+    fn status_line(self) -> &'static str {
+        match self {
+            Self::Status => "200 OK",
+            Self::NotFound => "404 Not Found",
+            Self::MethodNotAllowed => "405 Method Not Allowed",
+            Self::Forbidden => "403 Forbidden",
+            Self::BadRequest => "400 Bad Request",
+        }
+    }
+}
+
+/// Where the request head `request` goes: only `GET /status` (a query string
+/// is ignored) from a client that names localhost, or no host at all
+pub fn route(request: &str) -> Route {
+    let mut lines = request.lines();
+    let mut parts = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Route::BadRequest;
+    };
+    if !version.starts_with("HTTP/") {
+        return Route::BadRequest;
+    }
+    let host = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+        .map(|(_, value)| value.trim());
+    if host.is_some_and(|host| !is_local(host)) {
+        return Route::Forbidden;
+    }
+    if method != "GET" {
+        return Route::MethodNotAllowed;
+    }
+    match target.split(['?', '#']).next() {
+        Some("/status") => Route::Status,
+        _ => Route::NotFound,
+    }
+}
+
+/// Whether a `Host` header names this machine's loopback address
+fn is_local(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(rest) => rest.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    name.eq_ignore_ascii_case("localhost") || name == "127.0.0.1" || name == "::1"
+}
+
+/// The full response for `route`, with `body` as the JSON for `Route::Status`
+pub fn response(route: Route, body: &str) -> String {
+    let (content_type, body) = match route {
+        Route::Status => ("application/json", body),
+        _ => ("text/plain; charset=utf-8", route.status_line()),
+    };
+    let allow = if route == Route::MethodNotAllowed { "Allow: GET\r\n" } else { "" };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+         Cache-Control: no-store\r\n{}Connection: close\r\n\r\n{}",
+        route.status_line(),
+        content_type,
+        body.len(),
+        allow,
+        body
+    )
+}
+
+/// The `GET /status` body for `state` and `snapshot`
+pub fn status_json(state: ReadinessState, snapshot: &StatusSnapshot) -> String {
+    let json = StatusJson {
+        state,
+        toggle_recording: snapshot.toggle_recording,
+        recording_secs: snapshot.recording_secs,
+        level: snapshot.level,
+        last_transcription_at: snapshot.last_transcription_at.as_deref(),
+        model: &snapshot.model,
+    };
+    serde_json::to_string(&json).unwrap_or_default()
+}
+
+/// The model `config` transcribes with: the `[remote]` model, the file name
+/// of `model_path`, or `model` as spelled in config.toml
+pub fn model_name(config: &Config) -> String {
+    if config.backend == Backend::Remote {
+        return config.remote.model.clone();
+    }
+    if let Some(name) = config.model_path.as_ref().and_then(|path| path.file_name()) {
+        return name.to_string_lossy().into_owned();
+    }
+    match serde_json::to_value(config.model) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{:?}", config.model),
+    }
+}
+
+/// The listener's thread, answering one request at a time until shut down
+pub struct StatusServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    /// Listen on 127.0.0.1:`port`, answering from `state` and `snapshot`.
+    /// Port 0 picks a free one.
+    pub fn start(port: u16, state: StateManager, snapshot: Arc<Mutex<StatusSnapshot>>) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_stop.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        if let Err(e) = serve(stream, &state, &snapshot) {
+                            log::debug!("Status request failed: {}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("Status endpoint failed to accept a connection: {}", e),
+                }
+            }
+            log::debug!("Status endpoint stopped");
+        });
+        log::info!("Status endpoint listening on http://{}/status", addr);
+        Ok(Self {
+            addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stop listening and wait for the request in progress, if any
+    pub fn shutdown(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        self.stop.store(true, Ordering::SeqCst);
+        // Wakes the blocked accept so the thread sees the flag
+        let _ = TcpStream::connect_timeout(&self.addr, CLIENT_TIMEOUT);
+        let _ = thread.join();
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Read one request head from `stream` and answer it
+fn serve(mut stream: TcpStream, state: &StateManager, snapshot: &Mutex<StatusSnapshot>) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let route = route(&String::from_utf8_lossy(&request));
+    let body = match route {
+        Route::Status => status_json(state.get(), &snapshot.lock()),
+        _ => String::new(),
+    };
+    stream.write_all(response(route, &body).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route("GET /status HTTP/1.1\r\nHost: 127.0.0.1:8765\r\n\r\n"), Route::Status);
+        assert_eq!(route("GET /status?t=1 HTTP/1.0\r\n\r\n"), Route::Status);
+        assert_eq!(route("GET / HTTP/1.1\r\n\r\n"), Route::NotFound);
+        assert_eq!(route("GET /status/x HTTP/1.1\r\n\r\n"), Route::NotFound);
+        assert_eq!(route("POST /status HTTP/1.1\r\n\r\n"), Route::MethodNotAllowed);
+        assert_eq!(route("HEAD /status HTTP/1.1\r\n\r\n"), Route::MethodNotAllowed);
+        assert_eq!(route(""), Route::BadRequest);
+        assert_eq!(route("GET /status\r\n\r\n"), Route::BadRequest);
+        assert_eq!(route("GET /status SPDY\r\n\r\n"), Route::BadRequest);
+    }
+
+    /// Only localhost names are accepted, so a web page can't reach the
+    /// endpoint by pointing its own domain at 127.0.0.1
+    #[test]
+    fn test_host_header() {
+        let with_host = |host: &str| route(&format!("GET /status HTTP/1.1\r\nHost: {}\r\n\r\n", host));
+        assert_eq!(with_host("localhost:8765"), Route::Status);
+        assert_eq!(with_host("LOCALHOST"), Route::Status);
+        assert_eq!(with_host("127.0.0.1"), Route::Status);
+        assert_eq!(with_host("[::1]:8765"), Route::Status);
+        assert_eq!(with_host("evil.example:8765"), Route::Forbidden);
+        assert_eq!(with_host("127.0.0.1.evil.example"), Route::Forbidden);
+        assert_eq!(route("GET /status HTTP/1.1\r\nhost:evil.example\r\n\r\n"), Route::Forbidden);
+    }
+
+    #[test]
+    fn test_response() {
+        let ok = response(Route::Status, "{}");
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"), "{}", ok);
+        assert!(ok.contains("Content-Type: application/json\r\n"), "{}", ok);
+        assert!(ok.contains("Content-Length: 2\r\n"), "{}", ok);
+        assert!(ok.ends_with("\r\n\r\n{}"), "{}", ok);
+
+        let refused = response(Route::MethodNotAllowed, "");
+        assert!(refused.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", refused);
+        assert!(refused.contains("Allow: GET\r\n"), "{}", refused);
+        assert!(!response(Route::NotFound, "").contains("Allow:"));
+    }
+
+    #[test]
+    fn test_status_json() {
+        let snapshot = StatusSnapshot {
+            toggle_recording: true,
+            recording_secs: Some(2.5),
+            level: 0.25,
+            last_transcription_at: Some("2026-10-15T14:03:22+02:00".to_string()),
+            model: "base_en".to_string(),
+        };
+        let json: serde_json::Value = serde_json::from_str(&status_json(ReadinessState::Recording, &snapshot)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "state": "recording",
+                "toggle_recording": true,
+                "recording_secs": 2.5,
+                "level": 0.25,
+                "last_transcription_at": "2026-10-15T14:03:22+02:00",
+                "model": "base_en",
+            })
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&status_json(ReadinessState::Hot, &StatusSnapshot::default())).unwrap();
+        assert_eq!(json["state"], "hot");
+        assert!(json["recording_secs"].is_null());
+        assert!(json["last_transcription_at"].is_null());
+    }
+
+    #[test]
+    fn test_model_name() {
+        let mut config = Config::default();
+        assert_eq!(model_name(&config), "base_en");
+        config.model_path = Some("~/models/ggml-custom.bin".into());
+        assert_eq!(model_name(&config), "ggml-custom.bin");
+        config.backend = Backend::Remote;
+        assert_eq!(model_name(&config), config.remote.model);
+    }
+
+    /// A real request over loopback, then a clean shutdown
+    #[test]
+    fn test_server() {
+        let snapshot = Arc::new(Mutex::new(StatusSnapshot {
+            model: "tiny_en".to_string(),
+            ..StatusSnapshot::default()
+        }));
+        let mut server = StatusServer::start(0, StateManager::new(), snapshot).unwrap();
+        assert!(server.addr.ip().is_loopback());
+
+        let get = |request: &str| {
+            let mut stream = TcpStream::connect(server.addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let ok = get("GET /status HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n");
+        assert!(ok.starts_with("HTTP/1.1 200 OK"), "{}", ok);
+        assert!(ok.contains("\"state\":\"cold\""), "{}", ok);
+        assert!(ok.contains("\"model\":\"tiny_en\""), "{}", ok);
+        assert!(get("DELETE /status HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405"));
+
+        let addr = server.addr;
+        server.shutdown();
+        assert!(TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_err());
+    }
+}
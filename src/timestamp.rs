@@ -0,0 +1,53 @@
+//! Local timestamps for the notes file and the log file
+
+/// Local time as e.g. `2026-10-15T14:03:22+02:00`
+pub fn now_iso8601() -> String {
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        iso8601(&tm)
+    }
+}
+
+fn iso8601(tm: &libc::tm) -> String {
+    let offset_minutes = tm.tm_gmtoff / 60;
+    let sign = if offset_minutes < 0 { '-' } else { '+' };
+    let offset_minutes = offset_minutes.abs();
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec,
+        sign,
+        offset_minutes / 60,
+        offset_minutes % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_format() {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        tm.tm_year = 126;
+        tm.tm_mon = 9;
+        tm.tm_mday = 5;
+        tm.tm_hour = 9;
+        tm.tm_min = 3;
+        tm.tm_sec = 7;
+        tm.tm_gmtoff = 2 * 3600;
+        assert_eq!(iso8601(&tm), "2026-10-05T09:03:07+02:00");
+
+        tm.tm_gmtoff = -(3 * 3600 + 30 * 60);
+        assert_eq!(iso8601(&tm), "2026-10-05T09:03:07-03:30");
+
+        let now = now_iso8601();
+        assert_eq!(now.len(), "2026-10-05T09:03:07+02:00".len(), "{}", now);
+    }
+}
@@ -0,0 +1,77 @@
+//! Where the time goes between releasing the hotkey and the text appearing
+
+use std::time::{Duration, Instant};
+
+/// Measurements for one dictation, carried from the worker to the main loop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timings {
+    /// When the recording was stopped and handed to the worker
+    pub stopped_at: Instant,
+    /// Length of the recording as captured
+    pub recorded_ms: u64,
+    /// Length left after VAD dropped the silence
+    pub speech_ms: u64,
+    /// Time spent in whisper
+    pub inference_ms: u64,
+}
+
+impl Timings {
+    /// Share of the recording VAD dropped, in percent
+    pub fn vad_trim_percent(&self) -> u64 {
+        if self.recorded_ms == 0 {
+            return 0;
+        }
+        self.recorded_ms.saturating_sub(self.speech_ms) * 100 / self.recorded_ms
+    }
+
+    /// One `key=value` line for dictation `id`, whose output took `output`
+    /// and finished `total` after the recording stopped. `other` is
+    /// whatever isn't inference or output: filtering, VAD, the queue.
+    pub fn summary(&self, id: u64, output: Duration, total: Duration) -> String {
+        let output_ms = output.as_millis() as u64;
+        let total_ms = total.as_millis() as u64;
+        let other_ms = total_ms.saturating_sub(self.inference_ms + output_ms);
+        format!(
+            "Timings for {}: recorded={}ms speech={}ms vad_trim={}% inference={}ms output={}ms other={}ms total={}ms",
+            id,
+            self.recorded_ms,
+            self.speech_ms,
+            self.vad_trim_percent(),
+            self.inference_ms,
+            output_ms,
+            other_ms,
+            total_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timings(recorded_ms: u64, speech_ms: u64) -> Timings {
+        Timings {
+            stopped_at: Instant::now(),
+            recorded_ms,
+            speech_ms,
+            inference_ms: 820,
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let line = timings(4000, 3000).summary(3, Duration::from_millis(60), Duration::from_millis(950));
+        assert_eq!(
+            line,
+            "Timings for 3: recorded=4000ms speech=3000ms vad_trim=25% inference=820ms output=60ms other=70ms total=950ms"
+        );
+    }
+
+    /// No VAD, or VAD keeping the padding, never shows a negative trim
+    #[test]
+    fn test_vad_trim_edges() {
+        assert_eq!(timings(4000, 4000).vad_trim_percent(), 0);
+        assert_eq!(timings(4000, 4100).vad_trim_percent(), 0);
+        assert_eq!(timings(0, 0).vad_trim_percent(), 0);
+    }
+}
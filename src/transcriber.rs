@@ -1,25 +1,143 @@
-use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use std::ffi::c_void;
 use std::path::PathBuf;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState, WhisperToken};
 use rubato::{Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction};
 
+mod error;
+mod outcome;
+mod remote;
+
+pub use error::ModelError;
+pub use outcome::TranscriptionOutcome;
+pub use remote::RemoteBackend;
+
+type Result<T> = std::result::Result<T, ModelError>;
+
+/// Language transcribed in unless a request names another
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Conditions under which a running transcription stops early
+pub struct Abort<'a> {
+    pub cancelled: &'a AtomicBool,
+    pub deadline: Option<Instant>,
+}
+
+impl Abort<'_> {
+    pub fn requested(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// One whisper segment, timed from the start of the audio it was given
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Mean probability whisper gave the segment's text tokens (0.0 - 1.0)
+    pub confidence: f32,
+}
+
+/// Everything a transcription produced, not just the text
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranscriptionResult {
+    pub text: String,
+    pub segments: Vec<Segment>,
+    /// Length of the audio transcribed
+    pub audio_ms: u64,
+    /// Wall time spent in whisper, excluding resampling
+    pub inference_ms: u64,
+}
+
+impl TranscriptionResult {
+    /// Add the result for the audio that follows, which starts `offset_ms`
+    /// into the audio of this one
+    pub fn append(&mut self, next: TranscriptionResult, offset_ms: u64) {
+        if !next.text.is_empty() {
+            if !self.text.is_empty() {
+                self.text.push(' ');
+            }
+            self.text.push_str(&next.text);
+        }
+        self.segments.extend(next.segments.into_iter().map(|segment| Segment {
+            start_ms: segment.start_ms + offset_ms,
+            end_ms: segment.end_ms + offset_ms,
+            ..segment
+        }));
+        self.audio_ms += next.audio_ms;
+        self.inference_ms += next.inference_ms;
+    }
+
+    /// Remove segments with a confidence under `min` and rebuild `text` from
+    /// the rest, returning how many were removed. `text` is untouched when
+    /// none are.
+    pub fn drop_low_confidence(&mut self, min: f32) -> usize {
+        let before = self.segments.len();
+        self.segments.retain(|segment| segment.confidence >= min);
+        let dropped = before - self.segments.len();
+        if dropped > 0 {
+            self.text = self
+                .segments
+                .iter()
+                .map(|segment| segment.text.as_str())
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+        }
+        dropped
+    }
+}
+
+/// Something that turns audio into text: whisper on this Mac, or a server.
+/// Shared by the worker threads, which may transcribe at the same time.
+pub trait TranscriptionBackend: Send + Sync {
+    /// Stops early once `abort` is requested. `prompt` is earlier text
+    /// treated as what came before this audio. `language` is a code like
+    /// "de" (unset = English).
+    fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome>;
+    /// Run `samples` through once, discarding the text, so the first real
+    /// transcription isn't the slow one
+    fn warmup(&self, samples: &[f32], sample_rate: u32) -> Result<()>;
+    /// Whether it can transcribe right now
+    fn is_loaded(&self) -> bool;
+}
+
 pub struct Transcriber {
     ctx: WhisperContext,
+    /// Worker threads for every inference run
+    threads: usize,
+    /// Kept between runs to skip reallocating the KV caches, one for each
+    /// run that has gone at once (`worker_parallelism`); a run takes one and
+    /// only hands it back if it succeeds
+    states: Mutex<Vec<WhisperState>>,
 }
 
 impl Transcriber {
-    pub fn new(model_path: PathBuf) -> Result<Self> {
+    /// `threads` overrides the default of half the logical cores
+    pub fn new(model_path: PathBuf, threads: Option<usize>) -> Result<Self> {
         log::info!("Loading Whisper model from {:?}", model_path);
 
         if !model_path.exists() {
-            return Err(anyhow!(
-                "Model not found at {:?}. Please download a model first.",
-                model_path
-            ));
+            return Err(ModelError::NotFound(model_path));
         }
 
-        let num_threads = (num_cpus::get() / 2).max(1);
-        log::info!("Using {} threads for Whisper", num_threads);
+        let threads = threads.unwrap_or_else(|| (num_cpus::get() / 2).max(1));
+        log::info!("Using {} threads for Whisper", threads);
 
         let mut params = WhisperContextParameters::default();
         params.use_gpu(false);
@@ -28,63 +146,224 @@ impl Transcriber {
             model_path.to_str().unwrap(),
             params,
         )
-        .map_err(|e| anyhow!("Failed to load model: {}", e))?;
+        .map_err(ModelError::Load)?;
 
         log::info!("Model loaded successfully");
-        Ok(Self { ctx })
+        Ok(Self {
+            ctx,
+            threads,
+            states: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Worker threads each run uses
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Inference stops early once `abort` is requested.
+    pub fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+    ) -> Result<TranscriptionOutcome> {
+        self.transcribe_detailed(samples, sample_rate, abort, None, None)
     }
 
-    pub fn transcribe(&self, samples: &[f32], sample_rate: u32) -> Result<String> {
+    /// Like `transcribe`, with per-segment timestamps and timing. `prompt` is
+    /// earlier text whisper treats as what came before this audio, and
+    /// `language` the code of the language it's in (unset = English).
+    pub fn transcribe_detailed(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome> {
+        let audio_ms = samples.len() as u64 * 1000 / u64::from(sample_rate.max(1));
         let samples = if sample_rate != 16000 {
-            resample_high_quality(samples, sample_rate, 16000)?
+            let started = Instant::now();
+            let resampled = resample_high_quality(samples, sample_rate, 16000)?;
+            log::info!("Resampled {} Hz audio in {:?}", sample_rate, started.elapsed());
+            resampled
         } else {
             samples.to_vec()
         };
 
-        let mut state = self.ctx.create_state()?;
+        let setup = Instant::now();
+        let (mut state, reused) = self.take_state()?;
+        log::debug!(
+            "Whisper state {} in {:?}",
+            if reused { "reused" } else { "created" },
+            setup.elapsed()
+        );
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
 
-        let num_threads = (num_cpus::get() / 2).max(1);
-        params.set_n_threads(num_threads as i32);
-        
-        params.set_language(Some("en"));
+        params.set_n_threads(self.threads as i32);
+
+        params.set_language(Some(language.unwrap_or(DEFAULT_LANGUAGE)));
         params.set_print_special(false);
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
         params.set_suppress_blank(true);
-        params.set_single_segment(true);
+        // Whisper's own segments are kept apart, each with its confidence
+        // for `min_segment_confidence`, rather than run into one
+        params.set_single_segment(false);
         params.set_no_context(true);
-        params.set_max_len(1);
+        if let Some(prompt) = prompt {
+            params.set_initial_prompt(&prompt.replace('\0', ""));
+        }
 
-        state.full(params, &samples)?;
+        if let Some(abort) = abort {
+            // Safety: `abort` outlives the `state.full` call below, the only
+            // place whisper invokes the callback
+            unsafe {
+                params.set_abort_callback(Some(abort_requested));
+                params.set_abort_callback_user_data(abort as *const Abort as *mut c_void);
+            }
+        }
 
-        let num_segments = state.full_n_segments()?;
-        let mut text = String::new();
+        let started = Instant::now();
+        let result = state.full(params, &samples);
+        let inference_ms = started.elapsed().as_millis() as u64;
+        if abort.is_some_and(Abort::requested) {
+            return Err(ModelError::Aborted);
+        }
+        result?;
 
-        for i in 0..num_segments {
-            if let Ok(segment) = state.full_get_segment_text(i) {
-                text.push_str(&segment);
+        let mut raw = Vec::new();
+        for i in 0..state.full_n_segments()? {
+            if let Ok(text) = state.full_get_segment_text(i) {
+                let mut tokens = Vec::new();
+                for token in 0..state.full_n_tokens(i)? {
+                    tokens.push((state.full_get_token_id(i, token)?, state.full_get_token_prob(i, token)?));
+                }
+                raw.push(RawSegment {
+                    text,
+                    t0: state.full_get_segment_t0(i)?,
+                    t1: state.full_get_segment_t1(i)?,
+                    tokens,
+                });
             }
         }
 
-        let mut result = text.trim().to_string();
-        if result == "[BLANK_AUDIO]" {
-            result.clear();
-        }
+        // Only a clean run hands the state back; each run overwrites the
+        // previous results, and `no_context` keeps old tokens out of it
+        self.states.lock().push(state);
 
-        Ok(result)
+        let mut result = assemble(raw, self.ctx.token_eot());
+        result.audio_ms = audio_ms;
+        result.inference_ms = inference_ms;
+        Ok(TranscriptionOutcome::from_result(result))
     }
 
-    pub fn warmup(&self) -> Result<()> {
+    /// A full transcription of `samples`, leaving the state kept for the
+    /// first real one
+    pub fn warmup(&self, samples: &[f32], sample_rate: u32) -> Result<()> {
         log::info!("Warming up model...");
-        let silent = vec![0.0f32; 16000];
-        let mut state = self.ctx.create_state()?;
-        let params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-        state.full(params, &silent)?;
-        log::info!("Warmup complete");
+        let started = Instant::now();
+        self.transcribe_detailed(samples, sample_rate, None, None, None)?;
+        log::info!("Warmup complete in {} ms", started.elapsed().as_millis());
         Ok(())
     }
+
+    /// A kept state if there is one (and whether it was), or a new one. A
+    /// concurrent caller finds them all taken and gets its own, which is
+    /// kept as well.
+    fn take_state(&self) -> Result<(WhisperState, bool)> {
+        if let Some(state) = self.states.lock().pop() {
+            return Ok((state, true));
+        }
+        let before = memory_footprint();
+        let state = self.ctx.create_state()?;
+        // What each `worker_parallelism` thread adds, as Activity Monitor
+        // counts it; its compute buffers only count once it first runs
+        if let (Some(before), Some(after)) = (before, memory_footprint()) {
+            log::info!("New whisper state takes {} MB", after.saturating_sub(before) / (1024 * 1024));
+        }
+        Ok((state, false))
+    }
+}
+
+impl TranscriptionBackend for Transcriber {
+    fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome> {
+        self.transcribe_detailed(samples, sample_rate, abort, prompt, language)
+    }
+
+    fn warmup(&self, samples: &[f32], sample_rate: u32) -> Result<()> {
+        Transcriber::warmup(self, samples, sample_rate)
+    }
+
+    /// Loaded as soon as it exists
+    fn is_loaded(&self) -> bool {
+        true
+    }
+}
+
+/// This process's physical footprint in bytes, the memory Activity Monitor
+/// shows for it
+fn memory_footprint() -> Option<u64> {
+    let mut info: libc::rusage_info_v2 = unsafe { std::mem::zeroed() };
+    let pid = std::process::id() as libc::c_int;
+    let buffer = (&mut info as *mut libc::rusage_info_v2).cast();
+    (unsafe { libc::proc_pid_rusage(pid, libc::RUSAGE_INFO_V2, buffer) } == 0).then_some(info.ri_phys_footprint)
+}
+
+/// One segment as whisper returned it, before it's made a `Segment`
+struct RawSegment {
+    text: String,
+    /// Start and end, in centiseconds
+    t0: i64,
+    t1: i64,
+    /// Id and probability of each token, special ones included
+    tokens: Vec<(WhisperToken, f32)>,
+}
+
+/// The text and segments of whisper's `raw` segments. A segment's
+/// confidence is the mean probability of its text tokens, those before
+/// `first_special` (timestamps and end of text come after all of them).
+fn assemble(raw: Vec<RawSegment>, first_special: WhisperToken) -> TranscriptionResult {
+    let mut text = String::new();
+    let mut segments = Vec::new();
+    for segment in raw {
+        text.push_str(&segment.text);
+        let probabilities: Vec<f32> = segment
+            .tokens
+            .iter()
+            .filter(|(id, _)| *id < first_special)
+            .map(|(_, probability)| *probability)
+            .collect();
+        segments.push(Segment {
+            text: segment.text.trim().to_string(),
+            start_ms: segment.t0.max(0) as u64 * 10,
+            end_ms: segment.t1.max(0) as u64 * 10,
+            confidence: if probabilities.is_empty() {
+                1.0
+            } else {
+                probabilities.iter().sum::<f32>() / probabilities.len() as f32
+            },
+        });
+    }
+    TranscriptionResult {
+        text: text.trim().to_string(),
+        segments,
+        ..Default::default()
+    }
+}
+
+/// Whisper abort callback; `data` points at the caller's `Abort`
+unsafe extern "C" fn abort_requested(data: *mut c_void) -> bool {
+    (*(data as *const Abort)).requested()
 }
 
 fn resample_high_quality(samples: &[f32], from_rate: u32, to_rate: u32) -> Result<Vec<f32>> {
@@ -102,11 +381,142 @@ fn resample_high_quality(samples: &[f32], from_rate: u32, to_rate: u32) -> Resul
         params,
         samples.len(),
         1,
-    ).map_err(|e| anyhow!("Resampler creation failed: {}", e))?;
+    ).map_err(|e| ModelError::Resample(e.to_string()))?;
 
     let input = vec![samples.to_vec()];
     let output = resampler.process(&input, None)
-        .map_err(|e| anyhow!("Resampling failed: {}", e))?;
+        .map_err(|e| ModelError::Resample(e.to_string()))?;
 
     Ok(output[0].clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(text: &str, confidence: f32) -> Segment {
+        Segment {
+            text: text.to_string(),
+            start_ms: 0,
+            end_ms: 0,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_chunk_results_are_joined() {
+        let mut result = TranscriptionResult::default();
+        let chunk = |text: &str, end_ms| TranscriptionResult {
+            text: text.to_string(),
+            segments: vec![Segment {
+                end_ms,
+                ..segment(text, 0.9)
+            }],
+            audio_ms: end_ms,
+            inference_ms: 500,
+        };
+        result.append(chunk("First part.", 28000), 0);
+        result.append(chunk("", 1000), 28000);
+        result.append(chunk("Second part.", 20000), 29000);
+
+        assert_eq!(result.text, "First part. Second part.");
+        assert_eq!(result.audio_ms, 49000);
+        assert_eq!(result.inference_ms, 1500);
+        let times: Vec<_> = result.segments.iter().map(|s| (s.start_ms, s.end_ms)).collect();
+        assert_eq!(times, vec![(0, 28000), (28000, 29000), (29000, 49000)]);
+    }
+
+    #[test]
+    fn test_low_confidence_segments_are_dropped() {
+        let mut result = TranscriptionResult {
+            text: "Send the report. Blorf an mip. By Friday.".to_string(),
+            segments: vec![
+                segment("Send the report.", 0.9),
+                segment("Blorf an mip.", 0.2),
+                segment("By Friday.", 0.7),
+            ],
+            audio_ms: 3000,
+            inference_ms: 100,
+        };
+        assert_eq!(result.drop_low_confidence(0.0), 0);
+        assert_eq!(result.text, "Send the report. Blorf an mip. By Friday.");
+
+        assert_eq!(result.drop_low_confidence(0.5), 1);
+        assert_eq!(result.text, "Send the report. By Friday.");
+
+        assert_eq!(result.drop_low_confidence(0.95), 2);
+        assert!(result.text.is_empty());
+        assert!(result.segments.is_empty());
+    }
+
+    /// Each of whisper's segments gets the confidence of its own text
+    /// tokens, so a gibberish one can be dropped from between good ones
+    #[test]
+    fn test_segments_are_assembled_and_filtered() {
+        let eot = 50257;
+        let raw = |text: &str, t0, t1, probabilities: &[f32]| RawSegment {
+            text: text.to_string(),
+            t0,
+            t1,
+            // A timestamp before the text and the end of text after, both certain
+            tokens: std::iter::once((eot + 100, 1.0))
+                .chain(probabilities.iter().map(|probability| (42, *probability)))
+                .chain(std::iter::once((eot, 1.0)))
+                .collect(),
+        };
+        let mut result = assemble(
+            vec![
+                raw(" Send the report.", 0, 120, &[0.9, 0.95, 0.85]),
+                raw(" Blorf an mip.", 120, 200, &[0.1, 0.3]),
+                raw(" By Friday.", 200, 300, &[0.7]),
+                raw("", 300, 300, &[]),
+            ],
+            eot,
+        );
+
+        assert_eq!(result.text, "Send the report. Blorf an mip. By Friday.");
+        let times: Vec<_> = result.segments.iter().map(|s| (s.start_ms, s.end_ms)).collect();
+        assert_eq!(times, vec![(0, 1200), (1200, 2000), (2000, 3000), (3000, 3000)]);
+        let confidences: Vec<_> = result.segments.iter().map(|s| s.confidence).collect();
+        assert!((confidences[0] - 0.9).abs() < 1e-6, "{:?}", confidences);
+        assert!((confidences[1] - 0.2).abs() < 1e-6, "{:?}", confidences);
+        assert_eq!(confidences[3], 1.0, "No text tokens means nothing to doubt");
+
+        assert_eq!(result.drop_low_confidence(0.5), 1);
+        assert_eq!(result.text, "Send the report. By Friday.");
+    }
+
+    /// Many back-to-back runs on one kept state give the same result each
+    /// time. Needs a real model: `DICTATION_TEST_MODEL=path cargo test -- --ignored`
+    #[test]
+    #[ignore = "needs a whisper model in DICTATION_TEST_MODEL"]
+    fn test_reused_state_survives_many_runs() {
+        let path = std::env::var("DICTATION_TEST_MODEL").expect("DICTATION_TEST_MODEL is not set");
+        let transcriber = Transcriber::new(PathBuf::from(path), None).unwrap();
+        transcriber.warmup(&crate::wav::synthetic_speech(2.0, 16000), 16000).unwrap();
+        assert_eq!(transcriber.states.lock().len(), 1, "Warmup should keep its state");
+
+        // A second of a 440 Hz tone, then silence
+        let mut samples: Vec<f32> = (0..16000)
+            .map(|i| (i as f32 * 440.0 * std::f32::consts::TAU / 16000.0).sin() * 0.3)
+            .collect();
+        samples.resize(32000, 0.0);
+        // Timings differ from run to run, the rest shouldn't
+        let heard = |outcome: Result<TranscriptionOutcome>| match outcome.unwrap() {
+            TranscriptionOutcome::Text(result) => Some((result.text, result.segments)),
+            TranscriptionOutcome::NoSpeech => None,
+        };
+        let first = heard(transcriber.transcribe_detailed(&samples, 16000, None, None, None));
+        for run in 0..50 {
+            let result = heard(transcriber.transcribe_detailed(&samples, 16000, None, None, None));
+            assert_eq!(result, first, "Run {} differs", run);
+        }
+
+        // An aborted run drops the state and the next one starts fresh
+        let cancelled = AtomicBool::new(true);
+        let abort = Abort { cancelled: &cancelled, deadline: None };
+        assert!(transcriber.transcribe(&samples, 16000, Some(&abort)).is_err());
+        assert!(transcriber.states.lock().is_empty());
+        assert_eq!(heard(transcriber.transcribe(&samples, 16000, None)), first);
+    }
+}
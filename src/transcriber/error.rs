@@ -0,0 +1,38 @@
+//! Why a model couldn't be loaded or a transcription failed
+
+use std::path::PathBuf;
+use thiserror::Error;
+use whisper_rs::WhisperError;
+
+#[derive(Debug, Error)]
+pub enum ModelError {
+    /// Nothing is loaded yet, or it was unloaded
+    #[error("model not loaded")]
+    NotLoaded,
+    #[error("model not found at {0:?}, please download a model first")]
+    NotFound(PathBuf),
+    #[error("failed to load model: {0}")]
+    Load(WhisperError),
+    #[error(transparent)]
+    Whisper(#[from] WhisperError),
+    /// Stopped by `Abort`: cancelled or past its deadline
+    #[error("transcription aborted")]
+    Aborted,
+    #[error("resampling failed: {0}")]
+    Resample(String),
+    #[error("couldn't encode the audio: {0}")]
+    Encode(String),
+    /// The server couldn't be reached or didn't answer in time
+    #[error("request to {endpoint} failed: {message}")]
+    Network { endpoint: String, message: String },
+    /// The server answered with an error status
+    #[error("{endpoint} answered {status}: {message}")]
+    Server {
+        endpoint: String,
+        status: u16,
+        message: String,
+    },
+    /// A success status with a body that isn't a transcript
+    #[error("unexpected response: {0}")]
+    Response(String),
+}
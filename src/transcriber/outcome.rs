@@ -0,0 +1,104 @@
+//! Telling text apart from whisper's ways of saying it heard nothing
+
+use super::TranscriptionResult;
+
+/// What a transcription came to
+#[derive(Debug, Clone, PartialEq)]
+pub enum TranscriptionOutcome {
+    Text(TranscriptionResult),
+    /// Silence, or only markers standing in for it
+    NoSpeech,
+}
+
+impl TranscriptionOutcome {
+    /// `NoSpeech` when `result` has nothing but `is_no_speech` text
+    pub fn from_result(result: TranscriptionResult) -> Self {
+        if is_no_speech(&result.text) {
+            Self::NoSpeech
+        } else {
+            Self::Text(result)
+        }
+    }
+}
+
+/// Whether `text` holds no words: blank, only bracketed markers like
+/// `[BLANK_AUDIO]`, "(clicking)" or "*sigh*", or only symbols and
+/// punctuation like "♪" or "."
+pub fn is_no_speech(text: &str) -> bool {
+    let mut closing = None;
+    for c in text.chars() {
+        match closing {
+            Some(close) if c == close => closing = None,
+            Some(_) => {}
+            None => match c {
+                '[' => closing = Some(']'),
+                '(' => closing = Some(')'),
+                '*' => closing = Some('*'),
+                c if c.is_alphanumeric() => return false,
+                _ => {}
+            },
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs reported for silence, noise and music
+    #[test]
+    fn test_junk_is_no_speech() {
+        for junk in [
+            "",
+            "   ",
+            "[BLANK_AUDIO]",
+            " [BLANK_AUDIO] ",
+            "(clicking)",
+            "[Music]",
+            "[MUSIC PLAYING]",
+            "(upbeat music)",
+            "*sigh*",
+            "[ Silence ]",
+            "(keyboard clacking) [BLANK_AUDIO]",
+            "♪",
+            "♪ ♪ ♪",
+            ".",
+            "...",
+            "-",
+            "?",
+            "[no speech",
+        ] {
+            assert!(is_no_speech(junk), "{:?} counts as speech", junk);
+        }
+    }
+
+    #[test]
+    fn test_words_are_speech() {
+        for text in [
+            "Hello.",
+            "OK",
+            "42",
+            "(laughs) That's fine.",
+            "Send it [tomorrow]",
+            "¿Qué?",
+            "♪ Happy birthday ♪",
+        ] {
+            assert!(!is_no_speech(text), "{:?} counts as no speech", text);
+        }
+    }
+
+    #[test]
+    fn test_outcome_from_result() {
+        let result = |text: &str| TranscriptionResult {
+            text: text.to_string(),
+            ..TranscriptionResult::default()
+        };
+        assert_eq!(TranscriptionOutcome::from_result(result("[BLANK_AUDIO]")), TranscriptionOutcome::NoSpeech);
+        assert_eq!(TranscriptionOutcome::from_result(result("")), TranscriptionOutcome::NoSpeech);
+        assert_eq!(
+            TranscriptionOutcome::from_result(result("Hi there.")),
+            TranscriptionOutcome::Text(result("Hi there."))
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Transcription on a server with an OpenAI-compatible
+//! `/v1/audio/transcriptions` endpoint
+
+use serde::Deserialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use ureq::tls::{TlsConfig, TlsProvider};
+use ureq::Agent;
+
+use super::{
+    Abort, ModelError, Result, Segment, TranscriptionBackend, TranscriptionOutcome, TranscriptionResult, DEFAULT_LANGUAGE,
+};
+use crate::config::RemoteConfig;
+use crate::wav;
+
+/// Longest server error message kept for the log
+const MAX_ERROR_CHARS: usize = 200;
+
+pub struct RemoteBackend {
+    endpoint: String,
+    model: String,
+    api_key: Option<String>,
+    timeout: Duration,
+    agent: Agent,
+}
+
+impl RemoteBackend {
+    pub fn new(config: &RemoteConfig) -> Self {
+        let api_key = config.api_key();
+        if api_key.is_none() {
+            log::warn!("No API key for {} (remote.api_key or DICTATION_API_KEY)", config.endpoint);
+        }
+        let agent = Agent::config_builder()
+            // Error responses carry the reason in their body
+            .http_status_as_error(false)
+            // ureq picks rustls unless told otherwise, and only native-tls is built in
+            .tls_config(TlsConfig::builder().provider(TlsProvider::NativeTls).build())
+            .build()
+            .into();
+        Self {
+            endpoint: config.endpoint.clone(),
+            model: config.model.clone(),
+            api_key,
+            timeout: Duration::from_secs(config.timeout_secs),
+            agent,
+        }
+    }
+}
+
+impl TranscriptionBackend for RemoteBackend {
+    /// The request can't be interrupted, so `abort` only shortens its timeout
+    /// to the deadline and turns a late answer into an error
+    fn transcribe(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        abort: Option<&Abort>,
+        prompt: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<TranscriptionOutcome> {
+        let audio_ms = samples.len() as u64 * 1000 / u64::from(sample_rate.max(1));
+        let audio = wav::encode_pcm16(samples, sample_rate).map_err(|e| ModelError::Encode(e.to_string()))?;
+        let mut fields = vec![
+            ("model", self.model.as_str()),
+            ("response_format", "verbose_json"),
+            ("language", language.unwrap_or(DEFAULT_LANGUAGE)),
+        ];
+        if let Some(prompt) = prompt {
+            fields.push(("prompt", prompt));
+        }
+        let boundary = boundary();
+        let body = multipart_body(&boundary, &fields, &audio);
+
+        let timeout = abort
+            .and_then(|abort| abort.deadline)
+            .map_or(self.timeout, |deadline| {
+                deadline.saturating_duration_since(Instant::now()).min(self.timeout)
+            });
+        let mut request = self
+            .agent
+            .post(&self.endpoint)
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary));
+        if let Some(key) = &self.api_key {
+            request = request.header("Authorization", format!("Bearer {}", key));
+        }
+
+        let started = Instant::now();
+        let response = request.config().timeout_global(Some(timeout)).build().send(&body[..]);
+        let inference_ms = started.elapsed().as_millis() as u64;
+        if abort.is_some_and(Abort::requested) {
+            return Err(ModelError::Aborted);
+        }
+        let network = |e: ureq::Error| ModelError::Network {
+            endpoint: self.endpoint.clone(),
+            message: e.to_string(),
+        };
+        let mut response = response.map_err(network)?;
+        let status = response.status();
+        let text = response.body_mut().read_to_string().map_err(network)?;
+        if !status.is_success() {
+            return Err(ModelError::Server {
+                endpoint: self.endpoint.clone(),
+                status: status.as_u16(),
+                message: error_message(&text),
+            });
+        }
+        log::debug!("Uploaded {} KB of audio, answered in {} ms", audio.len() / 1024, inference_ms);
+        parse_response(&text, audio_ms, inference_ms).map(TranscriptionOutcome::from_result)
+    }
+
+    /// Nothing to warm up; the server loads its own model
+    fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        true
+    }
+}
+
+/// Multipart separator, varied so it can't be predicted from the audio
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default();
+    format!("dictation-{:x}", nanos)
+}
+
+/// A `multipart/form-data` body with text `fields` and the audio as `file`
+fn multipart_body(boundary: &str, fields: &[(&str, &str)], audio: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(audio.len() + 512);
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                boundary, name, value
+            )
+            .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.wav\"\r\n\
+             Content-Type: audio/wav\r\n\r\n",
+            boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(audio);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    body
+}
+
+/// `verbose_json` response; servers answering plain `json` only send `text`
+#[derive(Deserialize)]
+struct Response {
+    text: String,
+    #[serde(default)]
+    segments: Vec<ResponseSegment>,
+}
+
+#[derive(Deserialize)]
+struct ResponseSegment {
+    text: String,
+    /// Seconds
+    start: f64,
+    end: f64,
+    avg_logprob: Option<f64>,
+}
+
+fn parse_response(body: &str, audio_ms: u64, inference_ms: u64) -> Result<TranscriptionResult> {
+    let response: Response = serde_json::from_str(body)
+        .map_err(|e| ModelError::Response(format!("{} ({})", error_message(body), e)))?;
+    let text = response.text.trim().to_string();
+    let mut segments: Vec<Segment> = response
+        .segments
+        .into_iter()
+        .map(|segment| Segment {
+            text: segment.text.trim().to_string(),
+            start_ms: (segment.start.max(0.0) * 1000.0) as u64,
+            end_ms: (segment.end.max(0.0) * 1000.0) as u64,
+            confidence: segment.avg_logprob.map_or(1.0, |logprob| logprob.exp().min(1.0) as f32),
+        })
+        .collect();
+    if segments.is_empty() && !text.is_empty() {
+        segments.push(Segment {
+            text: text.clone(),
+            start_ms: 0,
+            end_ms: audio_ms,
+            confidence: 1.0,
+        });
+    }
+    Ok(TranscriptionResult {
+        text,
+        segments,
+        audio_ms,
+        inference_ms,
+    })
+}
+
+/// The `error.message` of an OpenAI-style error body, or the start of the body
+fn error_message(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        error: ErrorDetail,
+    }
+    #[derive(Deserialize)]
+    struct ErrorDetail {
+        message: String,
+    }
+    match serde_json::from_str::<ErrorBody>(body) {
+        Ok(parsed) => parsed.error.message,
+        Err(_) => body.trim().chars().take(MAX_ERROR_CHARS).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multipart_body() {
+        let body = multipart_body("XYZ", &[("model", "whisper-1"), ("language", "en")], b"RIFF");
+        let body = String::from_utf8(body).unwrap();
+        assert_eq!(
+            body,
+            "--XYZ\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"language\"\r\n\r\nen\r\n\
+             --XYZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"audio.wav\"\r\n\
+             Content-Type: audio/wav\r\n\r\nRIFF\r\n--XYZ--\r\n"
+        );
+    }
+
+    #[test]
+    fn test_verbose_response() {
+        let body = r#"{
+            "task": "transcribe", "language": "english", "duration": 3.2,
+            "text": " Send the report. By Friday.",
+            "segments": [
+                {"id": 0, "start": 0.0, "end": 1.6, "text": " Send the report.", "avg_logprob": -0.1},
+                {"id": 1, "start": 1.6, "end": 3.2, "text": " By Friday.", "avg_logprob": -1.2}
+            ]
+        }"#;
+        let result = parse_response(body, 3200, 450).unwrap();
+        assert_eq!(result.text, "Send the report. By Friday.");
+        assert_eq!(result.segments.len(), 2);
+        assert_eq!(result.segments[1].text, "By Friday.");
+        assert_eq!((result.segments[1].start_ms, result.segments[1].end_ms), (1600, 3200));
+        assert!((result.segments[0].confidence - 0.905).abs() < 0.01);
+        assert!((result.segments[1].confidence - 0.301).abs() < 0.01);
+        assert_eq!((result.audio_ms, result.inference_ms), (3200, 450));
+    }
+
+    /// Plain `json` answers become one segment covering the audio
+    #[test]
+    fn test_text_only_response() {
+        let result = parse_response(r#"{"text": "Hello there."}"#, 1500, 300).unwrap();
+        assert_eq!(result.text, "Hello there.");
+        assert_eq!(result.segments.len(), 1);
+        assert_eq!(result.segments[0].end_ms, 1500);
+
+        let result = parse_response(r#"{"text": ""}"#, 1500, 300).unwrap();
+        assert!(result.segments.is_empty());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let body = r#"{"error": {"message": "Invalid API key", "type": "invalid_request_error"}}"#;
+        assert_eq!(error_message(body), "Invalid API key");
+        assert_eq!(error_message("  Bad Gateway\n"), "Bad Gateway");
+        assert_eq!(error_message(&"x".repeat(500)).len(), MAX_ERROR_CHARS);
+        assert!(matches!(
+            parse_response("<html>502</html>", 1000, 10),
+            Err(ModelError::Response(_))
+        ));
+    }
+}
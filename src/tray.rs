@@ -1,18 +1,107 @@
 use crate::events::AppEvent;
-use muda::{Menu, MenuItem, PredefinedMenuItem};
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use muda::{CheckMenuItem, ContextMenu, Menu, MenuId, MenuItem, PredefinedMenuItem, Submenu};
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel};
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tao::event_loop::EventLoopProxy;
+use thiserror::Error;
+
+/// Why the tray icon couldn't be set up
+#[derive(Debug, Error)]
+pub enum TrayError {
+    #[error("couldn't build the tray menu: {0}")]
+    Menu(#[from] muda::Error),
+}
+
+/// `NSVariableStatusItemLength`
+const VARIABLE_LENGTH: f64 = -1.0;
+/// `NSEventMaskLeftMouseUp | NSEventMaskRightMouseUp`
+const MOUSE_UP_MASK: u64 = (1 << 2) | (1 << 4);
+/// `NSEventTypeRightMouseUp`
+const RIGHT_MOUSE_UP: u64 = 4;
+/// `NSEventModifierFlagControl`
+const CONTROL_FLAG: u64 = 1 << 18;
+
+const IDLE_TITLE: &str = "🎙";
+const RECORDING_TITLE: &str = "🔴";
 
 pub struct TrayIcon {
     _menu: Menu,
+    privacy_item: CheckMenuItem,
+    redo_item: MenuItem,
+    status: &'static StatusItem,
+    recording: Cell<bool>,
+}
+
+/// The menu bar icon, shared with its click handler for the rest of the
+/// process
+struct StatusItem {
+    item: id,
+    menu: id,
+    proxy: EventLoopProxy<AppEvent>,
+    click_toggles: AtomicBool,
+}
+
+/// Whether a click opens the menu rather than toggling recording
+fn opens_menu(right_click: bool, control: bool, click_toggles: bool) -> bool {
+    !click_toggles || right_click || control
+}
+
+/// The Privacy Mode item's text, with a lock while it's on
+fn privacy_label(on: bool) -> &'static str {
+    if on {
+        "🔒 Privacy Mode"
+    } else {
+        "Privacy Mode"
+    }
 }
 
 impl TrayIcon {
-    pub fn new(proxy: EventLoopProxy<AppEvent>) -> anyhow::Result<Self> {
+    /// `profiles` fills the Profile submenu, which is left out when empty.
+    /// The Redo item is greyed out unless `redo` says there's a redo model.
+    pub fn new(
+        proxy: EventLoopProxy<AppEvent>,
+        profiles: &[&str],
+        privacy_mode: bool,
+        click_toggles: bool,
+        redo: bool,
+    ) -> Result<Self, TrayError> {
         let menu = Menu::new();
-        
+
+        let record_item = MenuItem::new("Start/Stop Recording", true, None);
+        let record_id = record_item.id().clone();
+        menu.append(&record_item)?;
+        let redo_item = MenuItem::new("Redo with Bigger Model", redo, None);
+        let redo_id = redo_item.id().clone();
+        menu.append(&redo_item)?;
+
+        let mut profile_ids: Vec<(MenuId, String)> = Vec::new();
+        if !profiles.is_empty() {
+            let submenu = Submenu::new("Profile", true);
+            for name in profiles {
+                let item = MenuItem::new(*name, true, None);
+                profile_ids.push((item.id().clone(), name.to_string()));
+                submenu.append(&item)?;
+            }
+            menu.append(&submenu)?;
+        }
+
+        let privacy_item = CheckMenuItem::new(privacy_label(privacy_mode), true, privacy_mode, None);
+        let privacy_id = privacy_item.id().clone();
+        let stats_item = MenuItem::new("Statistics…", true, None);
+        let stats_id = stats_item.id().clone();
+        let logs_item = MenuItem::new("Open Log Folder", true, None);
+        let logs_id = logs_item.id().clone();
         let quit_item = MenuItem::new("Quit Dictation", true, None);
         let quit_id = quit_item.id().clone();
         
+        menu.append(&privacy_item)?;
+        menu.append(&stats_item)?;
+        menu.append(&logs_item)?;
         menu.append(&PredefinedMenuItem::separator())?;
         menu.append(&quit_item)?;
 
@@ -20,11 +109,146 @@ impl TrayIcon {
         muda::MenuEvent::set_event_handler(Some(move |event: muda::MenuEvent| {
             if event.id == quit_id {
                 let _ = proxy_clone.send_event(AppEvent::Quit);
+            } else if event.id == record_id {
+                let _ = proxy_clone.send_event(AppEvent::ToggleRecording);
+            } else if event.id == redo_id {
+                let _ = proxy_clone.send_event(AppEvent::Retranscribe);
+            } else if event.id == stats_id {
+                let _ = proxy_clone.send_event(AppEvent::ShowStats);
+            } else if event.id == privacy_id {
+                let _ = proxy_clone.send_event(AppEvent::TogglePrivacyMode);
+            } else if event.id == logs_id {
+                let _ = proxy_clone.send_event(AppEvent::OpenLogFolder);
+            } else if let Some((_, name)) = profile_ids.iter().find(|(id, _)| *id == event.id) {
+                let _ = proxy_clone.send_event(AppEvent::SwitchProfile(name.clone()));
             }
         }));
 
+        let status = StatusItem::install(menu.ns_menu() as id, proxy, click_toggles);
         log::info!("Tray menu created");
 
-        Ok(Self { _menu: menu })
+        Ok(Self {
+            _menu: menu,
+            privacy_item,
+            redo_item,
+            status,
+            recording: Cell::new(false),
+        })
+    }
+
+    /// Show whether a recording is running; cheap to call when nothing changed
+    pub fn set_recording(&self, recording: bool) {
+        if self.recording.replace(recording) != recording {
+            self.status.set_title(if recording { RECORDING_TITLE } else { IDLE_TITLE });
+        }
+    }
+
+    pub fn set_click_toggles(&self, on: bool) {
+        self.status.click_toggles.store(on, Ordering::Relaxed);
+    }
+
+    /// Show whether privacy mode is on; clicking the item only asks for the
+    /// change, so this follows the config rather than the click
+    pub fn set_privacy_mode(&self, on: bool) {
+        self.privacy_item.set_text(privacy_label(on));
+        self.privacy_item.set_checked(on);
+    }
+
+    /// Grey out the Redo item while there's nothing it could do
+    pub fn set_redo_available(&self, available: bool) {
+        self.redo_item.set_enabled(available);
+    }
+}
+
+impl StatusItem {
+    /// Put the icon in the menu bar, with clicks going to `clicked`. Must run
+    /// on the main thread.
+    fn install(menu: id, proxy: EventLoopProxy<AppEvent>, click_toggles: bool) -> &'static Self {
+        let item: id = unsafe {
+            let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+            let item: id = msg_send![status_bar, statusItemWithLength: VARIABLE_LENGTH];
+            msg_send![item, retain]
+        };
+        let status: &'static Self = Box::leak(Box::new(Self {
+            item,
+            menu,
+            proxy,
+            click_toggles: AtomicBool::new(click_toggles),
+        }));
+        status.set_title(IDLE_TITLE);
+        unsafe {
+            let target: id = msg_send![target_class(), new];
+            (*target).set_ivar("status", status as *const Self as *mut c_void);
+            let button: id = msg_send![item, button];
+            let _: () = msg_send![button, setTarget: target];
+            let _: () = msg_send![button, setAction: sel!(clicked:)];
+            let _previous: i64 = msg_send![button, sendActionOn: MOUSE_UP_MASK];
+        }
+        status
+    }
+
+    fn set_title(&self, title: &str) {
+        unsafe {
+            let button: id = msg_send![self.item, button];
+            let title = NSString::alloc(nil).init_str(title);
+            let _: () = msg_send![button, setTitle: title];
+            let _: () = msg_send![title, release];
+        }
+    }
+
+    /// Open the menu under the icon, as if it were a plain menu item
+    fn show_menu(&self) {
+        unsafe {
+            let _: () = msg_send![self.item, setMenu: self.menu];
+            let button: id = msg_send![self.item, button];
+            let _: () = msg_send![button, performClick: nil];
+            let _: () = msg_send![self.item, setMenu: nil];
+        }
+    }
+}
+
+/// The button target, holding a pointer to its `StatusItem`
+fn target_class() -> &'static Class {
+    match ClassDecl::new("DictationStatusTarget", class!(NSObject)) {
+        Some(mut decl) => unsafe {
+            decl.add_ivar::<*mut c_void>("status");
+            decl.add_method(sel!(clicked:), clicked as extern "C" fn(&Object, Sel, id));
+            decl.register()
+        },
+        None => Class::get("DictationStatusTarget").expect("registered"),
+    }
+}
+
+extern "C" fn clicked(this: &Object, _cmd: Sel, _sender: id) {
+    unsafe {
+        let status = &*(*this.get_ivar::<*mut c_void>("status") as *const StatusItem);
+        let app: id = msg_send![class!(NSApplication), sharedApplication];
+        let event: id = msg_send![app, currentEvent];
+        let (event_type, flags): (u64, u64) = if event == nil {
+            (0, 0)
+        } else {
+            (msg_send![event, type], msg_send![event, modifierFlags])
+        };
+        let click_toggles = status.click_toggles.load(Ordering::Relaxed);
+        if opens_menu(event_type == RIGHT_MOUSE_UP, flags & CONTROL_FLAG != 0, click_toggles) {
+            status.show_menu();
+        } else {
+            let _ = status.proxy.send_event(AppEvent::ToggleRecording);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clicks() {
+        assert!(!opens_menu(false, false, true));
+        assert!(opens_menu(true, false, true));
+        assert!(opens_menu(false, true, true));
+        // With toggling off, every click opens the menu
+        assert!(opens_menu(false, false, false));
+        assert!(opens_menu(true, false, false));
     }
 }
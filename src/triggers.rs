@@ -2,9 +2,11 @@ use crossbeam_channel::Sender;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use core_graphics::event::{CGEventTap, CGEventTapLocation, CGEventTapPlacement, CGEventTapOptions, CGEventType};
+use core_graphics::event::{CGEventTap, CGEventTapLocation, CGEventTapPlacement, CGEventTapOptions, CGEventType, EventField};
 use parking_lot::Mutex;
 
+use crate::hotkey::HotkeyEvent;
+
 /// Debounce interval for activity events (milliseconds).
 /// Only one event is sent per this interval to prevent flooding.
 const DEBOUNCE_MS: u64 = 200;
@@ -14,6 +16,16 @@ pub enum TriggerEvent {
     Activity,
 }
 
+/// A mouse button that works like the dictation hotkey: pressing and
+/// releasing it sends `Pressed` / `Released` on the hotkey channel
+pub struct MouseTrigger {
+    /// CoreGraphics button number (2 = middle, 3 = the first side button)
+    pub button: i64,
+    /// Hide the button's clicks from other apps
+    pub swallow: bool,
+    pub tx: Sender<HotkeyEvent>,
+}
+
 pub struct TriggerMonitor {
     running: Arc<AtomicBool>,
 }
@@ -25,7 +37,7 @@ impl TriggerMonitor {
         }
     }
 
-    pub fn start(&self, tx: Sender<TriggerEvent>) {
+    pub fn start(&self, tx: Sender<TriggerEvent>, mouse: Option<MouseTrigger>) {
         self.running.store(true, Ordering::SeqCst);
 
         let tx_activity = tx.clone();
@@ -37,11 +49,15 @@ impl TriggerMonitor {
             let debounce_duration = Duration::from_millis(DEBOUNCE_MS);
             
             // Monitor mouse movement, mouse clicks, and key down
-            let events = vec![
+            let mut events = vec![
                 CGEventType::MouseMoved,
                 CGEventType::LeftMouseDown,
                 CGEventType::KeyDown,
             ];
+            if mouse.is_some() {
+                events.push(CGEventType::OtherMouseDown);
+                events.push(CGEventType::OtherMouseUp);
+            }
 
             let last_event_clone = last_event.clone();
             let tap = match CGEventTap::new(
@@ -49,7 +65,24 @@ impl TriggerMonitor {
                 CGEventTapPlacement::HeadInsertEventTap,
                 CGEventTapOptions::Default,
                 events,
-                move |_proxy, _etype, _event| {
+                move |_proxy, etype, event| {
+                    if let Some(mouse) = &mouse {
+                        let pressed = matches!(etype, CGEventType::OtherMouseDown);
+                        if (pressed || matches!(etype, CGEventType::OtherMouseUp))
+                            && event.get_integer_value_field(EventField::MOUSE_EVENT_BUTTON_NUMBER) == mouse.button
+                        {
+                            let _ = mouse.tx.send(if pressed {
+                                HotkeyEvent::Pressed
+                            } else {
+                                HotkeyEvent::Released
+                            });
+                            if mouse.swallow {
+                                // A null event is dropped instead of delivered
+                                event.set_type(CGEventType::Null);
+                            }
+                        }
+                    }
+
                     let now = Instant::now();
                     let mut last = last_event_clone.lock();
                     
@@ -0,0 +1,84 @@
+//! `dictation://` URLs, so Shortcuts, Stream Deck buttons and the like can
+//! drive the app. The scheme is declared in the bundle's Info.plist
+//! (`osx_url_schemes` in Cargo.toml), so this only works from the app bundle.
+//! tao's app delegate hands opened URLs to the event loop as `Event::Opened`.
+
+use crate::events::AppEvent;
+
+const SCHEME: &str = "dictation";
+
+/// What a `dictation://<action>` URL asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlAction {
+    Start,
+    Stop,
+    Toggle,
+    Repaste,
+}
+
+impl UrlAction {
+    /// The main loop event that carries the action out
+    pub fn event(self) -> AppEvent {
+        match self {
+            Self::Start => AppEvent::StartRecording,
+            Self::Stop => AppEvent::StopRecording,
+            Self::Toggle => AppEvent::ToggleRecording,
+            Self::Repaste => AppEvent::Repaste,
+        }
+    }
+}
+
+/// The action in `url`, which may be `dictation://toggle`, `dictation:toggle`,
+/// or either with a trailing slash, query or fragment. Scheme and action are
+/// case-insensitive. `None` for other schemes and unknown actions.
+pub fn parse(url: &str) -> Option<UrlAction> {
+    let (scheme, rest) = url.trim().split_once(':')?;
+    if !scheme.eq_ignore_ascii_case(SCHEME) {
+        return None;
+    }
+    let rest = rest.trim_start_matches('/');
+    let action = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    match action.to_ascii_lowercase().as_str() {
+        "start" => Some(UrlAction::Start),
+        "stop" => Some(UrlAction::Stop),
+        "toggle" => Some(UrlAction::Toggle),
+        "repaste" => Some(UrlAction::Repaste),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions() {
+        assert_eq!(parse("dictation://start"), Some(UrlAction::Start));
+        assert_eq!(parse("dictation://stop"), Some(UrlAction::Stop));
+        assert_eq!(parse("dictation://toggle"), Some(UrlAction::Toggle));
+        assert_eq!(parse("dictation://repaste"), Some(UrlAction::Repaste));
+    }
+
+    /// The variants Shortcuts and other launchers tend to produce
+    #[test]
+    fn test_url_forms() {
+        for url in [
+            "dictation:toggle",
+            "dictation:///toggle",
+            "dictation://toggle/",
+            "dictation://toggle?source=streamdeck",
+            "dictation://toggle#now",
+            "DICTATION://Toggle",
+            " dictation://toggle\n",
+        ] {
+            assert_eq!(parse(url), Some(UrlAction::Toggle), "{:?}", url);
+        }
+    }
+
+    #[test]
+    fn test_unknown_urls_are_rejected() {
+        for url in ["dictation://", "dictation://quit", "dictation://togglex", "other://toggle", "toggle", ""] {
+            assert_eq!(parse(url), None, "{:?}", url);
+        }
+    }
+}
@@ -1,80 +1,565 @@
-use anyhow::Result;
+use crate::audio::filters;
+use crate::config::{VadConfig, VadEngine};
+use std::ops::Range;
+use thiserror::Error;
 use voice_activity_detector::VoiceActivityDetector;
 
-pub struct VadProcessor {
-    threshold: f32,
-    min_speech_samples: usize,
-    padding_samples: usize,
+type Result<T> = std::result::Result<T, VadError>;
+
+/// Why audio couldn't be scored for speech
+#[derive(Debug, Error)]
+pub enum VadError {
+    #[error("VAD creation failed: {0}")]
+    Create(#[from] voice_activity_detector::Error),
 }
 
-impl VadProcessor {
-    pub fn new(threshold: f32, sample_rate: u32) -> Self {
-        Self {
-            threshold,
-            min_speech_samples: (sample_rate as f32 * 0.1) as usize,
-            padding_samples: (sample_rate as f32 * 0.05) as usize,
+/// Scores fixed-size chunks of audio by how likely they are to contain speech.
+/// Trimming, gap merging and padding are shared by `VadProcessor`.
+pub trait SpeechDetector {
+    /// Samples per scored chunk at `sample_rate`
+    fn chunk_size(&self, sample_rate: u32) -> usize;
+
+    /// Speech probability (0.0 - 1.0) for every full chunk of `samples`
+    fn probabilities(&mut self, samples: &[f32], sample_rate: u32) -> Result<Vec<f32>>;
+}
+
+/// Silero neural VAD. Only supports 8 kHz and 16 kHz input.
+///
+/// The model is built on first use and kept for later calls, reset between
+/// recordings; it is only rebuilt when the sample rate changes.
+#[derive(Default)]
+pub struct SileroDetector {
+    model: Option<(u32, VoiceActivityDetector)>,
+}
+
+impl SpeechDetector for SileroDetector {
+    fn chunk_size(&self, sample_rate: u32) -> usize {
+        if sample_rate == 8000 {
+            256
+        } else {
+            512
         }
     }
 
-    pub fn process(&self, samples: &[f32], sample_rate: u32) -> Result<Option<Vec<f32>>> {
-        let chunk_size = if sample_rate == 8000 { 256 } else { 512 };
-        
+    fn probabilities(&mut self, samples: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+        let chunk_size = self.chunk_size(sample_rate);
+
         let samples_i16: Vec<i16> = samples
             .iter()
             .map(|&s| (s * 32767.0).clamp(-32768.0, 32767.0) as i16)
             .collect();
 
-        let mut vad = VoiceActivityDetector::builder()
-            .sample_rate(sample_rate as i64)
-            .chunk_size(chunk_size)
-            .build()
-            .map_err(|e| anyhow::anyhow!("VAD creation failed: {}", e))?;
+        let vad = match &mut self.model {
+            Some((rate, vad)) if *rate == sample_rate => {
+                vad.reset();
+                vad
+            }
+            slot => {
+                let vad = VoiceActivityDetector::builder()
+                    .sample_rate(sample_rate as i64)
+                    .chunk_size(chunk_size)
+                    .build()?;
+                &mut slot.insert((sample_rate, vad)).1
+            }
+        };
 
-        let mut speech_start: Option<usize> = None;
-        let mut speech_end: Option<usize> = None;
-        let mut has_speech = false;
+        Ok(samples_i16
+            .chunks_exact(chunk_size)
+            .map(|chunk| vad.predict(chunk.iter().copied()))
+            .collect())
+    }
+}
 
-        for (i, chunk) in samples_i16.chunks(chunk_size).enumerate() {
-            if chunk.len() < chunk_size {
-                break;
-            }
-            
-            let probability = vad.predict(chunk.iter().copied());
-            let chunk_start = i * chunk_size;
-            
-            if probability > self.threshold {
-                has_speech = true;
-                if speech_start.is_none() {
-                    speech_start = Some(chunk_start);
+/// Frame length for the energy detector
+const ENERGY_FRAME_MS: u32 = 30;
+/// Frames at or below this level score 0.0
+const ENERGY_FLOOR_DB: f32 = -50.0;
+/// Level range over which the score rises from 0.0 to 1.0
+const ENERGY_RANGE_DB: f32 = 20.0;
+/// Zero crossings per sample above which a frame looks like hiss, not voice
+const MAX_SPEECH_ZCR: f32 = 0.35;
+
+/// Cheap detector based on frame loudness, discounting frames whose
+/// zero-crossing rate suggests broadband noise rather than voice.
+pub struct EnergyDetector;
+
+impl SpeechDetector for EnergyDetector {
+    fn chunk_size(&self, sample_rate: u32) -> usize {
+        ms_to_samples(ENERGY_FRAME_MS, sample_rate).max(1)
+    }
+
+    fn probabilities(&mut self, samples: &[f32], sample_rate: u32) -> Result<Vec<f32>> {
+        Ok(samples
+            .chunks_exact(self.chunk_size(sample_rate))
+            .map(|frame| {
+                let level = (filters::rms_db(frame) - ENERGY_FLOOR_DB) / ENERGY_RANGE_DB;
+                let score = level.clamp(0.0, 1.0);
+                if zero_crossing_rate(frame) > MAX_SPEECH_ZCR {
+                    score * 0.5
+                } else {
+                    score
                 }
-                speech_end = Some(chunk_start + chunk_size);
-            }
+            })
+            .collect())
+    }
+}
+
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
+
+/// Speech extracted from a recording by `VadProcessor::process`
+#[derive(Debug, PartialEq)]
+pub struct VadOutput {
+    /// Concatenated speech segments
+    pub samples: Vec<f32>,
+    /// Where each speech segment sits within `samples`
+    pub segments: Vec<Range<usize>>,
+}
+
+pub struct VadProcessor {
+    engine: VadEngine,
+    silero: SileroDetector,
+    threshold: f32,
+    min_speech_ms: u32,
+    padding_ms: u32,
+    max_gap_ms: u32,
+    join_silence_ms: u32,
+}
+
+impl VadProcessor {
+    pub fn new(config: &VadConfig) -> Self {
+        Self {
+            engine: config.engine,
+            silero: SileroDetector::default(),
+            threshold: config.threshold,
+            min_speech_ms: config.min_speech_ms,
+            padding_ms: config.padding_ms,
+            max_gap_ms: config.max_gap_ms,
+            join_silence_ms: config.join_silence_ms,
         }
+    }
+
+    pub fn min_speech_samples(&self, sample_rate: u32) -> usize {
+        ms_to_samples(self.min_speech_ms, sample_rate)
+    }
+
+    pub fn padding_samples(&self, sample_rate: u32) -> usize {
+        ms_to_samples(self.padding_ms, sample_rate)
+    }
+
+    /// Sample counts are derived per call so a device switch to a different
+    /// sample rate is picked up without rebuilding the processor.
+    pub fn process(&mut self, samples: &[f32], sample_rate: u32) -> Result<Option<VadOutput>> {
+        let (probabilities, chunk_size) = match self.engine {
+            VadEngine::Silero => match score(&mut self.silero, samples, sample_rate) {
+                Ok(scored) => scored,
+                Err(e) => {
+                    log::warn!("Silero VAD failed ({}), falling back to energy VAD", e);
+                    score(&mut EnergyDetector, samples, sample_rate)?
+                }
+            },
+            VadEngine::Energy => score(&mut EnergyDetector, samples, sample_rate)?,
+        };
+        Ok(self.extract_speech(samples, &probabilities, chunk_size, sample_rate))
+    }
+
+    /// Keep every run of chunks whose speech probability is above the
+    /// threshold, merging runs separated by short gaps, and concatenate the
+    /// padded runs with a little silence between them.
+    fn extract_speech(
+        &self,
+        samples: &[f32],
+        probabilities: &[f32],
+        chunk_size: usize,
+        sample_rate: u32,
+    ) -> Option<VadOutput> {
+        let is_speech: Vec<bool> = probabilities.iter().map(|&p| p > self.threshold).collect();
+        let ranges = speech_ranges(
+            &is_speech,
+            chunk_size,
+            samples.len(),
+            ms_to_samples(self.max_gap_ms, sample_rate),
+            self.padding_samples(sample_rate),
+        );
 
-        if !has_speech {
+        if ranges.is_empty() {
             log::info!("VAD: No speech detected");
-            return Ok(None);
+            return None;
         }
 
-        let start = speech_start.unwrap_or(0);
-        let end = speech_end.unwrap_or(samples.len());
-        
-        let padded_start = start.saturating_sub(self.padding_samples);
-        let padded_end = (end + self.padding_samples).min(samples.len());
-        
-        let trimmed_len = padded_end - padded_start;
-        if trimmed_len < self.min_speech_samples {
-            log::info!("VAD: Speech too short ({} samples)", trimmed_len);
-            return Ok(None);
+        let speech_len: usize = ranges.iter().map(|r| r.len()).sum();
+        if speech_len < self.min_speech_samples(sample_rate) {
+            log::info!("VAD: Speech too short ({} samples)", speech_len);
+            return None;
+        }
+
+        let join_silence = ms_to_samples(self.join_silence_ms, sample_rate);
+        let mut output = Vec::with_capacity(speech_len + join_silence * ranges.len());
+        let mut segments = Vec::with_capacity(ranges.len());
+        for (i, range) in ranges.into_iter().enumerate() {
+            if i > 0 {
+                output.resize(output.len() + join_silence, 0.0);
+            }
+            let start = output.len();
+            output.extend_from_slice(&samples[range]);
+            segments.push(start..output.len());
         }
 
         log::info!(
-            "VAD: Trimmed {} -> {} samples ({}ms)",
-            samples.len(),
-            trimmed_len,
-            trimmed_len * 1000 / sample_rate as usize
+            "VAD: {} speech segment{}, {:.1}s -> {:.1}s",
+            segments.len(),
+            if segments.len() == 1 { "" } else { "s" },
+            samples.len() as f32 / sample_rate as f32,
+            output.len() as f32 / sample_rate as f32
         );
 
-        Ok(Some(samples[padded_start..padded_end].to_vec()))
+        Some(VadOutput {
+            samples: output,
+            segments,
+        })
+    }
+}
+
+/// Per-chunk probabilities along with the chunk size they were scored at
+fn score(
+    detector: &mut dyn SpeechDetector,
+    samples: &[f32],
+    sample_rate: u32,
+) -> Result<(Vec<f32>, usize)> {
+    let probabilities = detector.probabilities(samples, sample_rate)?;
+    Ok((probabilities, detector.chunk_size(sample_rate)))
+}
+
+/// Turn per-chunk speech flags into sample ranges: consecutive speech chunks
+/// form a run, runs separated by less than `max_gap` samples are merged, and
+/// each run is padded on both sides (merging any that then overlap).
+fn speech_ranges(
+    is_speech: &[bool],
+    chunk_size: usize,
+    total_len: usize,
+    max_gap: usize,
+    padding: usize,
+) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (i, _) in is_speech.iter().enumerate().filter(|(_, &speech)| speech) {
+        let chunk = i * chunk_size..((i + 1) * chunk_size).min(total_len);
+        match runs.last_mut() {
+            Some(last) if chunk.start - last.end < max_gap.max(1) => last.end = chunk.end,
+            _ => runs.push(chunk),
+        }
+    }
+
+    let mut padded: Vec<Range<usize>> = Vec::with_capacity(runs.len());
+    for run in runs {
+        let range = run.start.saturating_sub(padding)..(run.end + padding).min(total_len);
+        match padded.last_mut() {
+            Some(last) if range.start <= last.end => last.end = range.end,
+            _ => padded.push(range),
+        }
+    }
+    padded
+}
+
+fn ms_to_samples(ms: u32, sample_rate: u32) -> usize {
+    (sample_rate as u64 * ms as u64 / 1000) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn processor(min_speech_ms: u32, padding_ms: u32) -> VadProcessor {
+        VadProcessor::new(&VadConfig {
+            min_speech_ms,
+            padding_ms,
+            ..VadConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_default_sample_counts() {
+        let vad = VadProcessor::new(&VadConfig::default());
+
+        assert_eq!(vad.min_speech_samples(16000), 1600);
+        assert_eq!(vad.padding_samples(16000), 800);
+    }
+
+    /// Custom durations convert to the right sample counts at common device rates
+    #[test]
+    fn test_custom_sample_counts_across_rates() {
+        let vad = processor(250, 300);
+
+        assert_eq!(vad.min_speech_samples(16000), 4000);
+        assert_eq!(vad.padding_samples(16000), 4800);
+
+        assert_eq!(vad.min_speech_samples(44100), 11025);
+        assert_eq!(vad.padding_samples(44100), 13230);
+
+        assert_eq!(vad.min_speech_samples(48000), 12000);
+        assert_eq!(vad.padding_samples(48000), 14400);
+    }
+
+    #[test]
+    fn test_zero_padding() {
+        let vad = processor(100, 0);
+        assert_eq!(vad.padding_samples(48000), 0);
+    }
+
+    const RATE: u32 = 16000;
+    const CHUNK: usize = 512;
+
+    /// Sine burst standing in for speech
+    fn burst(secs: f32) -> Vec<f32> {
+        (0..(RATE as f32 * secs) as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    fn silence(secs: f32) -> Vec<f32> {
+        vec![0.0; (RATE as f32 * secs) as usize]
+    }
+
+    /// Per-chunk probabilities from signal energy, in place of the neural model
+    fn energy_probabilities(samples: &[f32]) -> Vec<f32> {
+        samples
+            .chunks_exact(CHUNK)
+            .map(|c| {
+                if c.iter().any(|s| s.abs() > 0.1) {
+                    0.9
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    fn gap_processor(max_gap_ms: u32, join_silence_ms: u32) -> VadProcessor {
+        VadProcessor::new(&VadConfig {
+            max_gap_ms,
+            join_silence_ms,
+            padding_ms: 0,
+            ..VadConfig::default()
+        })
+    }
+
+    /// A long pause between two utterances is removed, both utterances kept
+    #[test]
+    fn test_long_internal_silence_is_removed() {
+        let samples = [burst(1.0), silence(10.0), burst(1.0)].concat();
+        let vad = gap_processor(500, 0);
+
+        let output = vad
+            .extract_speech(&samples, &energy_probabilities(&samples), CHUNK, RATE)
+            .expect("Should find speech");
+
+        assert_eq!(output.segments.len(), 2);
+        let secs = output.samples.len() as f32 / RATE as f32;
+        assert!(
+            secs > 1.9 && secs < 2.2,
+            "Expected ~2s of speech, got {}s",
+            secs
+        );
+    }
+
+    /// Pauses shorter than max_gap_ms stay inside a single segment
+    #[test]
+    fn test_short_gap_is_merged() {
+        let samples = [burst(1.0), silence(0.2), burst(1.0)].concat();
+        let vad = gap_processor(500, 0);
+
+        let output = vad
+            .extract_speech(&samples, &energy_probabilities(&samples), CHUNK, RATE)
+            .expect("Should find speech");
+
+        assert_eq!(output.segments.len(), 1);
+        assert!(
+            output.samples.len() > (RATE as f32 * 2.1) as usize,
+            "Gap should be kept"
+        );
+    }
+
+    #[test]
+    fn test_three_segments_with_join_silence() {
+        let samples = [
+            silence(0.5),
+            burst(0.5),
+            silence(2.0),
+            burst(0.5),
+            silence(2.0),
+            burst(0.5),
+            silence(0.5),
+        ]
+        .concat();
+        let vad = gap_processor(500, 100);
+
+        let output = vad
+            .extract_speech(&samples, &energy_probabilities(&samples), CHUNK, RATE)
+            .expect("Should find speech");
+
+        assert_eq!(output.segments.len(), 3);
+        // Segments are separated by exactly join_silence_ms of zeros
+        let join = ms_to_samples(100, RATE);
+        assert_eq!(output.segments[1].start - output.segments[0].end, join);
+        assert!(
+            output.samples[output.segments[0].end..output.segments[1].start]
+                .iter()
+                .all(|&s| s == 0.0)
+        );
+        assert_eq!(output.segments[2].end, output.samples.len());
+    }
+
+    #[test]
+    fn test_all_silence_returns_none() {
+        let samples = silence(2.0);
+        let vad = gap_processor(500, 0);
+
+        assert!(vad
+            .extract_speech(&samples, &energy_probabilities(&samples), CHUNK, RATE)
+            .is_none());
+    }
+
+    /// Both engines score every full chunk with a probability in range
+    #[test]
+    fn test_engines_through_trait() {
+        let samples = [silence(0.5), burst(1.0), silence(0.5)].concat();
+        let detectors: [Box<dyn SpeechDetector>; 2] = [
+            Box::new(SileroDetector::default()),
+            Box::new(EnergyDetector),
+        ];
+
+        for mut detector in detectors {
+            let chunk = detector.chunk_size(RATE);
+            let probabilities = detector
+                .probabilities(&samples, RATE)
+                .expect("Detector should run");
+
+            assert_eq!(probabilities.len(), samples.len() / chunk);
+            assert!(probabilities.iter().all(|p| (0.0..=1.0).contains(p)));
+        }
+    }
+
+    /// Energy VAD finds both utterances and drops the pause between them
+    #[test]
+    fn test_energy_engine_extracts_segments() {
+        let samples = [
+            silence(0.5),
+            burst(1.0),
+            silence(3.0),
+            burst(1.0),
+            silence(0.5),
+        ]
+        .concat();
+        let mut vad = VadProcessor::new(&VadConfig {
+            engine: VadEngine::Energy,
+            padding_ms: 0,
+            join_silence_ms: 0,
+            ..VadConfig::default()
+        });
+
+        let output = vad
+            .process(&samples, RATE)
+            .expect("Detector should run")
+            .expect("Should find speech");
+
+        assert_eq!(output.segments.len(), 2);
+        let secs = output.samples.len() as f32 / RATE as f32;
+        assert!(
+            secs > 1.9 && secs < 2.1,
+            "Expected ~2s of speech, got {}s",
+            secs
+        );
+    }
+
+    /// Silero cannot be built at 48 kHz, so the processor falls back to energy VAD
+    #[test]
+    fn test_silero_falls_back_to_energy() {
+        let rate = 48000;
+        let samples: Vec<f32> = (0..rate as usize)
+            .map(|i| 0.5 * (2.0 * std::f32::consts::PI * 440.0 * i as f32 / rate as f32).sin())
+            .collect();
+
+        assert!(SileroDetector::default()
+            .probabilities(&samples, rate)
+            .is_err());
+
+        let mut vad = VadProcessor::new(&VadConfig::default());
+        let output = vad.process(&samples, rate).expect("Fallback should run");
+        assert!(output.is_some());
+    }
+
+    /// A reused processor gives the same output as a fresh one for each recording
+    #[test]
+    fn test_reused_processor_matches_fresh() {
+        let first = [silence(0.5), burst(1.0), silence(2.0), burst(0.5)].concat();
+        let second = [burst(0.7), silence(1.0)].concat();
+        let config = VadConfig::default();
+
+        let mut reused = VadProcessor::new(&config);
+        let reused_first = reused.process(&first, RATE).unwrap();
+        let reused_second = reused.process(&second, RATE).unwrap();
+
+        assert_eq!(
+            reused_first,
+            VadProcessor::new(&config).process(&first, RATE).unwrap()
+        );
+        assert_eq!(
+            reused_second,
+            VadProcessor::new(&config).process(&second, RATE).unwrap()
+        );
+        assert!(reused_first.is_some() && reused_second.is_some());
+    }
+
+    #[test]
+    fn test_energy_detector_ignores_silence_and_hiss() {
+        let mut detector = EnergyDetector;
+        let frame = detector.chunk_size(RATE);
+
+        assert!(detector
+            .probabilities(&silence(0.3), RATE)
+            .unwrap()
+            .iter()
+            .all(|&p| p == 0.0));
+
+        // Quiet alternating-sign noise has a very high zero-crossing rate
+        let hiss: Vec<f32> = (0..frame * 4)
+            .map(|i| if i % 2 == 0 { 0.02 } else { -0.02 })
+            .collect();
+        assert!(detector
+            .probabilities(&hiss, RATE)
+            .unwrap()
+            .iter()
+            .all(|&p| p <= 0.5));
+
+        assert!(detector
+            .probabilities(&burst(0.3), RATE)
+            .unwrap()
+            .iter()
+            .all(|&p| p == 1.0));
+    }
+
+    #[test]
+    fn test_speech_ranges_padding_merges_overlaps() {
+        // Speech in chunks 0 and 3; a gap of 2 chunks (1024 samples)
+        let flags = [true, false, false, true];
+        let ranges = speech_ranges(&flags, CHUNK, 4 * CHUNK, 0, 600);
+
+        // Padding of 600 on each side closes the 1024-sample gap
+        assert_eq!(ranges, vec![0..4 * CHUNK]);
+    }
+
+    #[test]
+    fn test_speech_ranges_without_merging() {
+        let flags = [true, false, false, true];
+        let ranges = speech_ranges(&flags, CHUNK, 4 * CHUNK, 0, 0);
+
+        assert_eq!(ranges, vec![0..CHUNK, 3 * CHUNK..4 * CHUNK]);
     }
 }
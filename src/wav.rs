@@ -0,0 +1,199 @@
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+use thiserror::Error;
+
+type Result<T> = std::result::Result<T, WavError>;
+
+/// Why a WAV file couldn't be read or written
+#[derive(Debug, Error)]
+pub enum WavError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Hound(#[from] hound::Error),
+    #[error("unsupported bit depth {0}")]
+    BitDepth(u16),
+}
+
+/// Write mono f32 samples to a 32-bit float WAV file, creating parent
+/// directories as needed and overwriting any existing file.
+pub fn write_wav(path: &Path, samples: &[f32], sample_rate: u32) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for &sample in samples {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Mono f32 samples as the bytes of a 16-bit PCM WAV file, half the size of
+/// float, for sending over the network
+pub fn encode_pcm16(samples: &[f32], sample_rate: u32) -> Result<Vec<u8>> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+
+    let mut bytes = Cursor::new(Vec::new());
+    let mut writer = hound::WavWriter::new(&mut bytes, spec)?;
+    for &sample in samples {
+        writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?;
+    }
+    writer.finalize()?;
+    Ok(bytes.into_inner())
+}
+
+/// Read a WAV file as mono f32 samples plus its sample rate. Integer (8 to
+/// 32-bit) and float files are accepted; multi-channel audio is averaged.
+pub fn read_wav(path: &Path) -> Result<(Vec<f32>, u32)> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<std::result::Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            if !(1..=32).contains(&spec.bits_per_sample) {
+                return Err(WavError::BitDepth(spec.bits_per_sample));
+            }
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<std::result::Result<_, _>>()?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect();
+    Ok((samples, spec.sample_rate))
+}
+
+/// `secs` of a voice-like signal: a 140 Hz tone with harmonics, swelling
+/// and fading at syllable pace, over a little noise. Pure silence or a
+/// steady tone would let whisper finish early and flatter the numbers.
+pub fn synthetic_speech(secs: f32, sample_rate: u32) -> Vec<f32> {
+    let len = (secs * sample_rate as f32) as usize;
+    // Fixed seed so every run times the same audio
+    let mut seed: u32 = 0x2545_f491;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            let voice: f32 = (1..=4)
+                .map(|harmonic| (t * 140.0 * harmonic as f32 * std::f32::consts::TAU).sin() / harmonic as f32)
+                .sum();
+            let envelope = (t * 4.0 * std::f32::consts::PI).sin().abs();
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+            voice * envelope * 0.2 + noise * 0.01
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("dictation-wav-{}-{}", std::process::id(), name))
+    }
+
+    /// Float files written for debugging read back unchanged
+    #[test]
+    fn test_float_round_trip() {
+        let path = temp_path("float.wav");
+        let samples = vec![0.0, 0.5, -0.25, 1.0];
+        write_wav(&path, &samples, 16000).unwrap();
+
+        let (read, rate) = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rate, 16000);
+        assert_eq!(read, samples);
+    }
+
+    /// Encoded audio reads back within a 16-bit step, clipping included
+    #[test]
+    fn test_pcm16_encoding() {
+        let samples = vec![0.0, 0.5, -0.25, 1.0, -1.5];
+        let bytes = encode_pcm16(&samples, 16000).unwrap();
+        assert_eq!(bytes.len(), 44 + samples.len() * 2);
+
+        let path = temp_path("pcm16.wav");
+        fs::write(&path, &bytes).unwrap();
+        let (read, rate) = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rate, 16000);
+        for (read, expected) in read.iter().zip([0.0, 0.5, -0.25, 1.0, -1.0]) {
+            assert!((read - expected).abs() < 1.0 / 16000.0, "{} vs {}", read, expected);
+        }
+    }
+
+    /// 16-bit stereo is scaled to -1.0..1.0 and averaged down to mono
+    #[test]
+    fn test_int_stereo_is_downmixed() {
+        let path = temp_path("stereo.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for sample in [16384i16, 0, -32768, -32768] {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (read, rate) = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rate, 44100);
+        assert_eq!(read, vec![0.25, -1.0]);
+    }
+
+    /// 24-bit samples use the full 24-bit range
+    #[test]
+    fn test_24_bit_scaling() {
+        let path = temp_path("24bit.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        writer.write_sample(1i32 << 22).unwrap();
+        writer.finalize().unwrap();
+
+        let (read, _) = read_wav(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, vec![0.5]);
+    }
+
+    /// The made-up audio is the length asked for, in range, and the same
+    /// every time
+    #[test]
+    fn test_synthetic_speech() {
+        let samples = synthetic_speech(2.5, 16000);
+        assert_eq!(samples.len(), 40000);
+        assert!(samples.iter().all(|s| s.abs() <= 1.0));
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!(peak > 0.1, "Peak {}", peak);
+        assert_eq!(samples, synthetic_speech(2.5, 16000));
+    }
+}
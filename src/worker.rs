@@ -1,123 +1,1373 @@
-use crate::events::AppEvent;
+use std::cell::Cell;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{bounded, never, select, unbounded, Receiver, Sender, TrySendError};
+use parking_lot::Mutex;
+
+use crate::audio::filters;
+use crate::config::{Config, OutputMode, QueueOverflow, MAX_WORKER_PARALLELISM};
+use crate::context::ContextBuffer;
+use crate::event_sender::EventSender;
+use crate::events::{AppEvent, FailureReason};
+use crate::logging::Transcript;
 use crate::model::ModelManager;
+use crate::panic_hook;
+use crate::postprocess::commands::{self, VoiceCommand};
+use crate::postprocess::{self, case, hallucination, spell};
+use crate::timings::Timings;
+use crate::transcriber::{Abort, ModelError, TranscriptionOutcome, TranscriptionResult, DEFAULT_LANGUAGE};
 use crate::vad::VadProcessor;
-use crossbeam_channel::{bounded, Sender};
-use std::thread;
-use tao::event_loop::EventLoopProxy;
+use crate::wav;
+
+mod chunks;
+mod order;
+mod retry;
+mod stream;
+
+use order::ResultOrder;
+pub use retry::{RetrySlot, SubmitResult, Submitted};
+pub use stream::StreamChunk;
+use stream::StreamBuffer;
 
 /// Request to transcribe audio samples
 pub struct TranscriptionRequest {
+    /// Increases with every submission; echoed in `TranscriptionComplete`
+    pub id: u64,
     pub samples: Vec<f32>,
     pub sample_rate: u32,
+    /// Bundle id of the app the text is going to, for `use_context`
+    pub app: Option<String>,
+    /// Replaces `output_mode` for this transcript
+    pub output_override: Option<OutputMode>,
+    /// Press Return after outputting the text
+    pub send_return: bool,
+    /// Picked by the hotkey that started the recording
+    pub options: TranscriptionOptions,
+    /// When the request was made, just after the recording stopped
+    pub submitted_at: Instant,
+    /// Place in the queue, set by `submit`, for putting results back in order
+    seq: u64,
+}
+
+/// How to transcribe one recording, for hotkeys that do more than start it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TranscriptionOptions {
+    /// Code of the language spoken, from a `[[hotkeys]]` entry (unset = English)
+    pub language: Option<String>,
+    /// Spell the words out as characters, from `spell_hotkey`
+    pub spell: bool,
+    /// Transcribe the last recording again with `redo_model` instead of
+    /// the request's samples, which are left empty
+    pub redo: bool,
+}
+
+impl TranscriptionRequest {
+    pub fn duration_ms(&self) -> u64 {
+        duration_ms(self.samples.len(), self.sample_rate)
+    }
+}
+
+/// Length of `len` samples at `sample_rate`
+pub fn duration_ms(len: usize, sample_rate: u32) -> u64 {
+    len as u64 * 1000 / u64::from(sample_rate.max(1))
 }
 
 /// Background worker that handles transcription off the main thread.
 /// This keeps the UI responsive during VAD processing and inference.
 pub struct TranscriptionWorker {
-    request_tx: Sender<TranscriptionRequest>,
+    /// Dropped by `shutdown` so the worker threads exit
+    request_tx: Option<Sender<TranscriptionRequest>>,
+    /// Kept so `cancel` can discard a request that hasn't started yet
+    request_rx: Receiver<TranscriptionRequest>,
+    chunk_tx: Sender<StreamChunk>,
+    /// One per thread, so each of them gets every reload
+    config_txs: Vec<Sender<Config>>,
+    /// One per thread; `cancel` sets them all, and each thread clears its
+    /// own when it takes the next request
+    cancelled: Vec<Arc<AtomicBool>>,
+    overflow: QueueOverflow,
+    next_id: AtomicU64,
+    /// Place in the queue of the next request `submit` sends
+    next_seq: AtomicU64,
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+/// What the worker threads have in common besides the model
+struct Shared {
+    /// Earlier utterances, for `use_context`
+    context: Mutex<ContextBuffer>,
+    /// The last recording, for `redo_model`
+    retained: Mutex<Option<RetainedSpeech>>,
+    failures: Mutex<FailureStreak>,
+    /// Requests queued or in flight that haven't reported a result yet
+    pending: AtomicUsize,
+    /// Results waiting on earlier ones, and the main loop they go to. Sent
+    /// under the lock, so two threads can't swap them on the way.
+    results: Mutex<(ResultOrder<AppEvent>, EventSender)>,
+}
+
+impl Shared {
+    /// Report `event` as the one result of request `seq`, once every request
+    /// queued before it has reported. A request stops counting as pending
+    /// before the main loop can see its result.
+    fn finish(&self, seq: u64, event: AppEvent) {
+        let mut results = self.results.lock();
+        let due = results.0.finish(seq, event);
+        if due.is_empty() {
+            log::debug!("Holding a result until the requests before it finish ({} held)", results.0.held());
+        }
+        self.send(&results.1, due);
+    }
+
+    /// Note that request `seq` was taken off the queue without running
+    fn skip(&self, seq: u64) {
+        let mut results = self.results.lock();
+        let due = results.0.skip(seq);
+        self.send(&results.1, due);
+    }
+
+    fn send(&self, proxy: &EventSender, due: Vec<AppEvent>) {
+        for event in due {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            let _ = proxy.send_event(event);
+        }
+    }
 }
 
 impl TranscriptionWorker {
-    /// Create a new worker that owns the ModelManager and optional VadProcessor.
-    /// Results are sent back via the EventLoopProxy.
-    pub fn new(
-        model_manager: ModelManager,
-        vad_processor: Option<VadProcessor>,
-        proxy: EventLoopProxy<AppEvent>,
-    ) -> Self {
-        // Bounded channel with capacity 1 - if a new request comes in while
-        // processing, we can buffer exactly one. This prevents memory buildup
-        // from rapid requests.
-        let (request_tx, request_rx) = bounded::<TranscriptionRequest>(1);
-
-        thread::spawn(move || {
-            log::info!("Transcription worker started");
-            
-            loop {
-                // Block until we receive a request (no busy polling = lightweight)
-                match request_rx.recv() {
-                    Ok(request) => {
-                        Self::process_request(
-                            &request,
-                            &model_manager,
-                            &vad_processor,
-                            &proxy,
-                        );
+    /// Create a new worker that owns the ModelManager and a VadProcessor per
+    /// thread (`worker_parallelism` of them). Results are sent back via
+    /// `proxy`, in the order the requests were submitted.
+    pub fn new(model_manager: ModelManager, config: Config, proxy: EventSender) -> Self {
+        // Requests are processed in submission order. The bound prevents
+        // memory buildup from rapid requests; `queue_overflow` decides which
+        // request is dropped when it is reached.
+        let (request_tx, request_rx) = bounded::<TranscriptionRequest>(config.max_queue.max(1));
+        let overflow = config.queue_overflow;
+
+        // Streaming preview chunks are small and must not be dropped, or the
+        // accumulated audio would have holes in it
+        let (chunk_tx, chunk_rx) = unbounded::<StreamChunk>();
+
+        let shared = Arc::new(Shared {
+            context: Mutex::new(Self::context_buffer(&config)),
+            retained: Mutex::new(None),
+            failures: Mutex::new(FailureStreak::default()),
+            pending: AtomicUsize::new(0),
+            results: Mutex::new((ResultOrder::default(), proxy.clone())),
+        });
+
+        let parallelism = config.worker_parallelism.clamp(1, MAX_WORKER_PARALLELISM);
+        let mut config_txs = Vec::new();
+        let mut cancelled = Vec::new();
+        let mut threads = Vec::new();
+        for index in 0..parallelism {
+            let (config_tx, config_rx) = unbounded::<Config>();
+            let thread_cancelled = Arc::new(AtomicBool::new(false));
+            // Previews need every chunk of the recording, so one thread
+            // takes them all
+            let chunk_rx = if index == 0 { chunk_rx.clone() } else { never() };
+            let thread = WorkerThread {
+                index,
+                model_manager: model_manager.clone(),
+                config: config.clone(),
+                request_rx: request_rx.clone(),
+                chunk_rx,
+                config_rx,
+                cancelled: thread_cancelled.clone(),
+                shared: shared.clone(),
+                proxy: proxy.clone(),
+            };
+            let (thread_rx, thread_shared) = (request_rx.clone(), shared.clone());
+            threads.push(thread::spawn(move || {
+                // Ends the thread rather than the app; `submit` notices and
+                // the main loop starts a new worker
+                if let Err(message) = panic_hook::catch(|| thread.run()) {
+                    log::error!("Transcription worker {} died: {}", index, message);
+                    fail_queued(&thread_rx, &thread_shared);
+                }
+            }));
+            config_txs.push(config_tx);
+            cancelled.push(thread_cancelled);
+        }
+
+        Self {
+            request_tx: Some(request_tx),
+            request_rx,
+            chunk_tx,
+            config_txs,
+            cancelled,
+            overflow,
+            next_id: AtomicU64::new(1),
+            next_seq: AtomicU64::new(0),
+            shared,
+            threads,
+        }
+    }
+
+    /// A request for `samples` with the next id, for `submit`
+    pub fn request(
+        &self,
+        samples: Vec<f32>,
+        sample_rate: u32,
+        app: Option<String>,
+        output_override: Option<OutputMode>,
+        send_return: bool,
+        options: TranscriptionOptions,
+    ) -> TranscriptionRequest {
+        TranscriptionRequest {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            samples,
+            sample_rate,
+            app,
+            output_override,
+            send_return,
+            options,
+            submitted_at: Instant::now(),
+            seq: 0,
+        }
+    }
+
+    /// Queue `request` for transcription. A request that can't be queued
+    /// comes back in the result. Returns immediately - transcription happens
+    /// in background.
+    pub fn submit(&self, mut request: TranscriptionRequest) -> SubmitResult<TranscriptionRequest> {
+        let Some(request_tx) = &self.request_tx else {
+            log::error!("Transcription worker is shut down");
+            return SubmitResult::Disconnected(request);
+        };
+        if self.threads.iter().any(JoinHandle::is_finished) {
+            log::error!("A transcription worker thread is gone");
+            fail_queued(&self.request_rx, &self.shared);
+            return SubmitResult::Disconnected(request);
+        }
+        let id = request.id;
+
+        // Counted before sending so the worker can never finish it first
+        self.shared.pending.fetch_add(1, Ordering::SeqCst);
+        loop {
+            // Only taken once the request is in the queue, so every number
+            // reports; only the main thread submits
+            request.seq = self.next_seq.load(Ordering::SeqCst);
+            // Use try_send to avoid blocking the UI if the queue is full
+            match request_tx.try_send(request) {
+                Ok(_) => {
+                    self.next_seq.fetch_add(1, Ordering::SeqCst);
+                    log::debug!("Transcription request {} submitted", id);
+                    return SubmitResult::Accepted(id);
+                }
+                Err(TrySendError::Full(rejected)) => match self.overflow {
+                    QueueOverflow::DropNewest => {
+                        log::warn!("Transcription queue full, request {} turned away", id);
+                        self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+                        return SubmitResult::QueueFull(rejected);
                     }
-                    Err(_) => {
-                        // Channel closed, worker should exit
-                        log::info!("Transcription worker shutting down");
-                        break;
+                    QueueOverflow::DropOldest => {
+                        if let Ok(oldest) = self.request_rx.try_recv() {
+                            log::warn!("Transcription queue full, request {} dropped", oldest.id);
+                            self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+                            self.shared.skip(oldest.seq);
+                        }
+                        request = rejected;
                     }
+                },
+                Err(TrySendError::Disconnected(rejected)) => {
+                    log::error!("Transcription worker disconnected");
+                    self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+                    return SubmitResult::Disconnected(rejected);
                 }
             }
-        });
+        }
+    }
+
+    /// Number of submitted requests that haven't reported a result yet
+    pub fn pending(&self) -> usize {
+        self.shared.pending.load(Ordering::SeqCst)
+    }
 
-        Self { request_tx }
+    /// Abandon the transcriptions in progress and any request waiting behind
+    /// them. The worker answers with `FailureReason::Cancelled` instead of a
+    /// result.
+    pub fn cancel(&self) {
+        let mut dropped = 0;
+        while let Ok(request) = self.request_rx.try_recv() {
+            self.shared.pending.fetch_sub(1, Ordering::SeqCst);
+            self.shared.skip(request.seq);
+            dropped += 1;
+        }
+        for cancelled in &self.cancelled {
+            cancelled.store(true, Ordering::SeqCst);
+        }
+        log::info!("Transcription cancelled ({} queued request(s) dropped)", dropped);
     }
 
-    /// Submit a transcription request to the worker.
-    /// Returns immediately - transcription happens in background.
-    pub fn submit(&self, request: TranscriptionRequest) {
-        // Use try_send to avoid blocking if channel is full
-        // If channel is full, the old request gets dropped (which is fine -
-        // we only care about the latest audio)
-        match self.request_tx.try_send(request) {
-            Ok(_) => log::debug!("Transcription request submitted"),
-            Err(crossbeam_channel::TrySendError::Full(_)) => {
-                log::warn!("Transcription queue full, request dropped");
+    /// Stop accepting requests and wait for the worker threads to finish what
+    /// they are doing, so the process never exits with whisper mid-inference.
+    /// Call `cancel` first to abandon queued work instead of finishing it.
+    pub fn shutdown(&mut self) {
+        self.request_tx = None;
+        for thread in self.threads.drain(..) {
+            if thread.join().is_err() {
+                log::error!("Transcription worker panicked");
             }
-            Err(crossbeam_channel::TrySendError::Disconnected(_)) => {
+        }
+        log::info!("Transcription worker stopped");
+    }
+
+    /// Feed audio from the recording in progress to the worker, which sends
+    /// `AppEvent::PartialTranscription` as enough new speech accumulates.
+    pub fn stream(&self, chunk: StreamChunk) {
+        if self.chunk_tx.send(chunk).is_err() {
+            log::error!("Transcription worker disconnected");
+        }
+    }
+
+    /// Swap in reloaded VAD, preprocessing, timeout, and debug settings.
+    /// The queue size, overflow policy and thread count are fixed at startup.
+    pub fn reconfigure(&self, config: Config) {
+        *self.shared.context.lock() = Self::context_buffer(&config);
+        if !config.redo_available() && self.shared.retained.lock().take().is_some() {
+            log::debug!("Dropped the recording kept for redo_model");
+        }
+        for config_tx in &self.config_txs {
+            if config_tx.send(config.clone()).is_err() {
                 log::error!("Transcription worker disconnected");
             }
         }
     }
 
-    fn process_request(
-        request: &TranscriptionRequest,
+    fn process_stream(
+        stream: &mut StreamBuffer,
+        chunk_rx: &Receiver<StreamChunk>,
+        request_rx: &Receiver<TranscriptionRequest>,
         model_manager: &ModelManager,
-        vad_processor: &Option<VadProcessor>,
-        proxy: &EventLoopProxy<AppEvent>,
+        vad_processor: &mut Option<VadProcessor>,
+        proxy: &EventSender,
     ) {
-        // Step 1: VAD processing (trim silence)
-        let samples_to_transcribe = if let Some(vad) = vad_processor {
-            match vad.process(&request.samples, request.sample_rate) {
-                Ok(Some(trimmed)) => trimmed,
+        // Catch up on anything that arrived during the last preview
+        while let Ok(chunk) = chunk_rx.try_recv() {
+            stream.push(chunk);
+        }
+
+        // The final request takes priority over a preview that would be stale anyway
+        if !request_rx.is_empty() {
+            return;
+        }
+
+        // VAD only looks at what arrived since the last preview
+        let sample_rate = stream.sample_rate();
+        let pending = stream.take_pending();
+        match vad_processor {
+            Some(vad) => match vad.process(&pending, sample_rate) {
+                Ok(Some(speech)) => stream.push_speech(&speech.samples),
+                Ok(None) => {}
+                Err(e) => log::debug!("Preview VAD failed: {}", e),
+            },
+            None => stream.push_speech(&pending),
+        }
+
+        if !stream.is_due() {
+            return;
+        }
+        stream.mark_previewed();
+
+        match model_manager.transcribe(stream.speech(), sample_rate, None) {
+            Ok(TranscriptionOutcome::Text(result)) => {
+                let _ = proxy.send_event(AppEvent::PartialTranscription(result.text));
+            }
+            Ok(TranscriptionOutcome::NoSpeech) => {}
+            Err(e) => log::debug!("Preview transcription failed: {}", e),
+        }
+    }
+
+    fn context_buffer(config: &Config) -> ContextBuffer {
+        ContextBuffer::new(
+            config.context_max_chars,
+            Duration::from_secs(config.context_reset_secs),
+        )
+    }
+
+    /// Transcribe the chunks of `speech` in order and join the results,
+    /// `NoSpeech` if none had any. With `use_context`, the text so far is the
+    /// prompt for each next chunk. A redo runs on the `redo_model`.
+    fn transcribe_chunks(
+        model_manager: &ModelManager,
+        speech: Chunked,
+        config: &Config,
+    ) -> Result<TranscriptionOutcome, ModelError> {
+        let Chunked {
+            samples,
+            chunks,
+            sample_rate,
+            abort,
+            prompt,
+            options,
+        } = speech;
+        let language = options.language.as_deref();
+        let now = Instant::now();
+        // Never goes stale, the chunks are all one utterance
+        let mut context = ContextBuffer::new(config.context_max_chars, Duration::MAX);
+        if let Some(prompt) = &prompt {
+            context.push(prompt, now, None);
+        }
+        let mut combined = TranscriptionResult::default();
+        for (i, chunk) in chunks.iter().enumerate() {
+            let prompt = config.use_context.then(|| context.prompt(now, None)).flatten();
+            let samples = &samples[chunk.clone()];
+            let outcome = if options.redo {
+                model_manager.transcribe_secondary(samples, sample_rate, Some(abort), prompt, language)?
+            } else {
+                model_manager.transcribe_detailed(samples, sample_rate, Some(abort), prompt, language)?
+            };
+            let TranscriptionOutcome::Text(result) = outcome else {
+                log::debug!("No speech in part {}/{}", i + 1, chunks.len());
+                continue;
+            };
+            if chunks.len() > 1 {
+                log::info!(
+                    "Transcribed part {}/{} ({:.1}s of audio): {}",
+                    i + 1,
+                    chunks.len(),
+                    chunk.len() as f32 / sample_rate as f32,
+                    Transcript(&result.text)
+                );
+            }
+            context.push(&result.text, now, None);
+            combined.append(result, chunk.start as u64 * 1000 / sample_rate as u64);
+        }
+        Ok(TranscriptionOutcome::from_result(combined))
+    }
+
+    /// Steps 0 to 2b: the speech in `request` ready for whisper, with the
+    /// VAD's segments. `None` once it has reported that there's nothing to
+    /// transcribe.
+    fn prepare(
+        request: &TranscriptionRequest,
+        vad_processor: &mut Option<VadProcessor>,
+        config: &Config,
+        finish: &dyn Fn(AppEvent),
+    ) -> Option<(Vec<f32>, Vec<Range<usize>>)> {
+        // A muted mic records silence, which isn't worth running VAD over
+        if filters::is_silent(&request.samples, config.silence_floor_db) {
+            log::warn!(
+                "Recording is under silence_floor_db ({} dBFS) throughout, is the microphone muted?",
+                config.silence_floor_db
+            );
+            finish(AppEvent::TranscriptionFailed(FailureReason::MicSilent));
+            return None;
+        }
+
+        // Step 0: Optional filtering (high-pass, noise gate)
+        let mut samples = request.samples.clone();
+        filters::preprocess(&mut samples, &config.preprocess, request.sample_rate);
+
+        // Step 1: VAD processing (drop silence)
+        let mut speech_segments = Vec::new();
+        let mut samples_to_transcribe = if let Some(vad) = vad_processor {
+            match vad.process(&samples, request.sample_rate) {
+                Ok(Some(speech)) => {
+                    for segment in &speech.segments {
+                        log::debug!(
+                            "VAD segment: {:.2}s - {:.2}s",
+                            segment.start as f32 / request.sample_rate as f32,
+                            segment.end as f32 / request.sample_rate as f32
+                        );
+                    }
+                    speech_segments = speech.segments;
+                    speech.samples
+                }
                 Ok(None) => {
                     log::info!("No speech detected, skipping transcription");
-                    let _ = proxy.send_event(AppEvent::TranscriptionFailed);
-                    return;
+                    finish(AppEvent::TranscriptionFailed(FailureReason::NoSpeech));
+                    return None;
                 }
                 Err(e) => {
                     log::warn!("VAD failed: {}, using original samples", e);
-                    request.samples.clone()
+                    samples
                 }
             }
         } else {
-            request.samples.clone()
+            samples
         };
 
         // Step 2: Check minimum length
-        if samples_to_transcribe.len() <= 1600 {
-            log::warn!("Recording too short, ignoring");
-            let _ = proxy.send_event(AppEvent::TranscriptionFailed);
-            return;
+        if config.too_short(samples_to_transcribe.len(), request.sample_rate) {
+            log::warn!(
+                "Only {} ms of speech, under min_recording_ms ({} ms), ignoring",
+                duration_ms(samples_to_transcribe.len(), request.sample_rate),
+                config.min_recording_ms
+            );
+            finish(AppEvent::TranscriptionFailed(FailureReason::TooShort));
+            return None;
+        }
+
+        // Step 2b: Gain normalization (after VAD so its threshold is unaffected)
+        if config.normalize_audio {
+            let gain = filters::normalize(&mut samples_to_transcribe, config.normalize_target_db);
+            log::debug!("Normalized audio with gain {:.2}x", gain);
+        }
+        Some((samples_to_transcribe, speech_segments))
+    }
+
+    fn process_request(
+        request: &TranscriptionRequest,
+        model_manager: &ModelManager,
+        vad_processor: &mut Option<VadProcessor>,
+        config: &Config,
+        shared: &Shared,
+        cancelled: &AtomicBool,
+        finish: &dyn Fn(AppEvent),
+    ) -> Inference {
+        log::debug!("Processing request {} ({} ms of audio)", request.id, request.duration_ms());
+
+        // A redo picks up the last recording where its own request left off
+        let speech = if request.options.redo {
+            let Some(last) = shared.retained.lock().clone() else {
+                log::warn!("No recording kept to redo");
+                finish(AppEvent::TranscriptionFailed(FailureReason::NothingToRedo));
+                return Inference::Skipped;
+            };
+            log::info!(
+                "Redoing the last recording ({} ms of speech)",
+                duration_ms(last.samples.len(), last.sample_rate)
+            );
+            RetainedSpeech {
+                options: TranscriptionOptions {
+                    redo: true,
+                    ..last.options.clone()
+                },
+                ..last
+            }
+        } else {
+            let Some((samples, segments)) = Self::prepare(request, vad_processor, config, finish) else {
+                return Inference::Skipped;
+            };
+            let speech = RetainedSpeech {
+                samples,
+                segments,
+                sample_rate: request.sample_rate,
+                recorded_ms: request.duration_ms(),
+                app: request.app.clone(),
+                options: request.options.clone(),
+            };
+            // Kept whatever comes of this transcription, which may be what
+            // makes it worth redoing. Recordings stop at `max_recording_secs`,
+            // so what's kept is never longer than that.
+            *shared.retained.lock() = config.redo_available().then(|| speech.clone());
+            speech
+        };
+        let sample_rate = speech.sample_rate;
+
+        if cancelled.load(Ordering::SeqCst) {
+            log::info!("Cancelled before transcription");
+            finish(AppEvent::TranscriptionFailed(FailureReason::Cancelled));
+            return Inference::Skipped;
+        }
+
+        if speech.options.redo {
+            let loaded = match config.redo_model_path() {
+                Some(path) => model_manager.load_secondary(&path).map_err(|e| e.to_string()),
+                None => Err("redo_model isn't set".to_string()),
+            };
+            if let Err(e) = loaded {
+                log::error!("Can't load the redo model: {}", e);
+                finish(AppEvent::TranscriptionFailed(FailureReason::InferenceError(e)));
+                return Inference::Skipped;
+            }
+        } else if !model_manager.is_loaded() {
+            log::warn!("Model not loaded, skipping transcription");
+            finish(AppEvent::TranscriptionFailed(FailureReason::ModelNotLoaded));
+            return Inference::Skipped;
         }
 
         // Step 3: Transcription (includes resampling if needed)
-        match model_manager.transcribe(&samples_to_transcribe, request.sample_rate) {
-            Ok(text) => {
-                log::info!("Transcribed: {}", text);
-                if text.is_empty() {
-                    let _ = proxy.send_event(AppEvent::TranscriptionFailed);
+        let audio_secs = speech.samples.len() as f32 / sample_rate as f32;
+        let timeout = config.transcription_timeout(audio_secs);
+        let abort = Abort {
+            cancelled,
+            deadline: timeout.map(|timeout| Instant::now() + timeout),
+        };
+        let now = Instant::now();
+        let app = speech.app.as_deref();
+        // The context already ends with the transcript being redone, which
+        // would only steer the redo back towards it
+        let use_context = config.use_context && !speech.options.redo;
+        let prompt = use_context
+            .then(|| shared.context.lock().prompt(now, app).map(str::to_string))
+            .flatten();
+        let chunks = chunks::plan(
+            &speech.segments,
+            speech.samples.len(),
+            chunks::MAX_CHUNK_SECS * sample_rate as usize,
+        );
+        let chunked = Chunked {
+            samples: &speech.samples,
+            chunks: &chunks,
+            sample_rate,
+            abort: &abort,
+            prompt,
+            options: &speech.options,
+        };
+        let inference = match Self::transcribe_chunks(model_manager, chunked, config) {
+            _ if cancelled.load(Ordering::SeqCst) => {
+                log::info!("Transcription cancelled");
+                finish(AppEvent::TranscriptionFailed(FailureReason::Cancelled));
+                Inference::Skipped
+            }
+            Err(_) if abort.timed_out() => {
+                log::error!(
+                    "Transcription of {:.1}s of audio timed out after {:?}",
+                    audio_secs,
+                    timeout.unwrap_or_default()
+                );
+                finish(AppEvent::TranscriptionFailed(FailureReason::Timeout));
+                Inference::Skipped
+            }
+            // Unloaded since the check above, by a model switch
+            Err(ModelError::NotLoaded) => {
+                log::warn!("Model unloaded during transcription");
+                finish(AppEvent::TranscriptionFailed(FailureReason::ModelNotLoaded));
+                Inference::Skipped
+            }
+            Ok(TranscriptionOutcome::NoSpeech) => {
+                log::info!("Whisper heard no speech");
+                finish(AppEvent::TranscriptionFailed(FailureReason::NoSpeech));
+                Inference::Succeeded
+            }
+            Ok(TranscriptionOutcome::Text(mut result)) => {
+                log::info!(
+                    "Transcribed {} in {} ms: {}",
+                    speech.options.language.as_deref().unwrap_or(DEFAULT_LANGUAGE),
+                    result.inference_ms,
+                    Transcript(&result.text)
+                );
+                for segment in &result.segments {
+                    log::debug!("Segment confidence {:.2}: {}", segment.confidence, Transcript(&segment.text));
+                }
+                if config.min_segment_confidence > 0.0 {
+                    let dropped = result.drop_low_confidence(config.min_segment_confidence);
+                    if dropped > 0 {
+                        log::info!(
+                            "Dropped {} segment(s) under min_segment_confidence {}",
+                            dropped,
+                            config.min_segment_confidence
+                        );
+                    }
+                    if result.text.is_empty() {
+                        finish(AppEvent::TranscriptionFailed(FailureReason::NoSpeech));
+                        return Inference::Succeeded;
+                    }
+                }
+                if config.normalize_whitespace {
+                    result.text = postprocess::normalize_text(&result.text);
+                }
+                if let Some(reason) = hallucination::detect(&result.text, audio_secs, &config.hallucination) {
+                    // The reason can quote the transcript
+                    log::warn!("Dropping likely hallucination ({})", Transcript(&reason.to_string()));
+                    finish(AppEvent::TranscriptionFailed(FailureReason::NoSpeech));
+                    return Inference::Succeeded;
+                }
+                if config.enable_scratch_that
+                    && commands::detect(&postprocess::normalize(&result.text)) == Some(VoiceCommand::ScratchThat)
+                {
+                    log::info!("Heard \"scratch that\"");
+                    finish(AppEvent::ScratchThat { id: request.id });
+                    return Inference::Succeeded;
+                }
+                if use_context {
+                    shared.context.lock().push(&result.text, now, app);
+                }
+                // Spell mode, from the hotkey or the prefix, replaces the
+                // usual post-processing
+                let spelled = if speech.options.spell {
+                    Some(result.text.as_str())
+                } else {
+                    spell::strip_prefix(&result.text, &config.spell.prefix)
+                };
+                result.text = match spelled {
+                    Some(words) => match spell::spell(&postprocess::normalize(words), config.spell.unknown_words) {
+                        Ok(text) => text,
+                        Err(word) => {
+                            log::warn!("Spell mode has no character for \"{}\"", Transcript(&word));
+                            finish(AppEvent::TranscriptionFailed(FailureReason::Unspellable(word)));
+                            return Inference::Succeeded;
+                        }
+                    },
+                    None => {
+                        let (transform, text) = match case::strip_prefix(&result.text) {
+                            Some((transform, rest)) => (transform, rest.to_string()),
+                            None => (config.case_transform(app), result.text),
+                        };
+                        case::transform(&postprocess::apply(config, text), transform)
+                    }
+                };
+                if result.text.is_empty() {
+                    finish(AppEvent::TranscriptionFailed(FailureReason::NoSpeech));
                 } else {
-                    let _ = proxy.send_event(AppEvent::TranscriptionComplete(text));
+                    finish(AppEvent::TranscriptionComplete {
+                        id: request.id,
+                        output_override: request.output_override,
+                        send_return: request.send_return,
+                        timings: Timings {
+                            stopped_at: request.submitted_at,
+                            recorded_ms: speech.recorded_ms,
+                            speech_ms: result.audio_ms,
+                            inference_ms: result.inference_ms,
+                        },
+                        result,
+                    });
                 }
+                Inference::Succeeded
             }
             Err(e) => {
                 log::error!("Transcription failed: {}", e);
-                let _ = proxy.send_event(AppEvent::TranscriptionFailed);
+                finish(AppEvent::TranscriptionFailed(FailureReason::InferenceError(
+                    e.to_string(),
+                )));
+                Inference::Failed
+            }
+        };
+
+        // Written after the result is sent so it never delays the output
+        if let Some(path) = debug_audio_path(config) {
+            match wav::write_wav(&path, &speech.samples, sample_rate) {
+                Ok(()) => log::info!("Saved debug audio to {:?}", path),
+                Err(e) => log::warn!("Failed to save debug audio: {}", e),
             }
         }
+        inference
+    }
+}
+
+/// The speech of one request, split for `transcribe_chunks`
+struct Chunked<'a> {
+    samples: &'a [f32],
+    /// Ranges of `samples` transcribed one after another
+    chunks: &'a [Range<usize>],
+    sample_rate: u32,
+    abort: &'a Abort<'a>,
+    /// Earlier text, for the first chunk
+    prompt: Option<String>,
+    options: &'a TranscriptionOptions,
+}
+
+/// One of the `worker_parallelism` threads, each with its own VAD (and,
+/// through `Transcriber`, its own whisper state)
+struct WorkerThread {
+    index: usize,
+    model_manager: ModelManager,
+    config: Config,
+    request_rx: Receiver<TranscriptionRequest>,
+    /// Preview audio; `never` for all but the first thread
+    chunk_rx: Receiver<StreamChunk>,
+    config_rx: Receiver<Config>,
+    cancelled: Arc<AtomicBool>,
+    shared: Arc<Shared>,
+    /// For events other than results, which go through `shared`
+    proxy: EventSender,
+}
+
+impl WorkerThread {
+    fn run(mut self) {
+        log::info!("Transcription worker {} started", self.index);
+        let mut stream = StreamBuffer::default();
+        let mut vad_processor = self.vad_processor();
+
+        loop {
+            // Block until we receive a request or chunk (no busy polling = lightweight)
+            select! {
+                recv(self.request_rx) -> msg => match msg {
+                    Ok(request) => {
+                        // The final pass covers the whole recording
+                        stream.clear();
+                        // A cancel only applies to work that existed when it was made
+                        self.cancelled.store(false, Ordering::SeqCst);
+                        let finished = Cell::new(false);
+                        let finish_once = |event: AppEvent| {
+                            finished.set(true);
+                            self.shared.finish(request.seq, event);
+                        };
+                        let inference = match panic_hook::catch(|| {
+                            TranscriptionWorker::process_request(
+                                &request,
+                                &self.model_manager,
+                                &mut vad_processor,
+                                &self.config,
+                                &self.shared,
+                                &self.cancelled,
+                                &finish_once,
+                            )
+                        }) {
+                            Ok(inference) => inference,
+                            Err(message) => {
+                                if !finished.get() {
+                                    self.shared.finish(
+                                        request.seq,
+                                        AppEvent::TranscriptionFailed(FailureReason::InferenceError(format!(
+                                            "transcription worker crashed: {}",
+                                            message
+                                        ))),
+                                    );
+                                }
+                                vad_processor = self.recover(message);
+                                continue;
+                            }
+                        };
+                        // Requests that come in meanwhile find no model
+                        // and fail fast instead of waiting for the load
+                        if self.shared.failures.lock().record(inference, self.config.reload_after_failures) {
+                            log::warn!(
+                                "Transcription failed {} times in a row, reloading the model",
+                                self.config.reload_after_failures
+                            );
+                            let _ = self.proxy.send_event(AppEvent::ModelReloading);
+                            self.model_manager.reload();
+                        }
+                    }
+                    Err(_) => {
+                        // Channel closed, worker should exit
+                        log::info!("Transcription worker {} shutting down", self.index);
+                        break;
+                    }
+                },
+                recv(self.chunk_rx) -> msg => match msg {
+                    Ok(chunk) => {
+                        stream.push(chunk);
+                        let preview = panic_hook::catch(|| {
+                            TranscriptionWorker::process_stream(
+                                &mut stream,
+                                &self.chunk_rx,
+                                &self.request_rx,
+                                &self.model_manager,
+                                &mut vad_processor,
+                                &self.proxy,
+                            )
+                        });
+                        if let Err(message) = preview {
+                            stream.clear();
+                            vad_processor = self.recover(message);
+                        }
+                    }
+                    Err(_) => break,
+                },
+                recv(self.config_rx) -> msg => match msg {
+                    // Applies from the next request; the one in flight keeps its settings
+                    Ok(new_config) => {
+                        self.config = new_config;
+                        vad_processor = self.vad_processor();
+                        log::info!("Transcription worker {} reconfigured", self.index);
+                    }
+                    Err(_) => break,
+                },
+            }
+        }
+    }
+
+    fn vad_processor(&self) -> Option<VadProcessor> {
+        self.config.vad.enabled.then(|| VadProcessor::new(&self.config.vad))
+    }
+
+    /// Start over after a panic: report it, reload the model, which may be
+    /// what broke, and hand back a fresh VAD. The shared context and failure
+    /// count start over too.
+    fn recover(&self, message: String) -> Option<VadProcessor> {
+        log::error!("Transcription worker crashed, restarting it: {}", message);
+        let _ = self.proxy.send_event(AppEvent::WorkerCrashed(message));
+        self.model_manager.reload();
+        *self.shared.context.lock() = TranscriptionWorker::context_buffer(&self.config);
+        *self.shared.failures.lock() = FailureStreak::default();
+        self.vad_processor()
+    }
+}
+
+/// Fail every request still in the queue, which a worker missing a thread
+/// may never get to
+fn fail_queued(request_rx: &Receiver<TranscriptionRequest>, shared: &Shared) {
+    while let Ok(request) = request_rx.try_recv() {
+        let reason = FailureReason::InferenceError("transcription worker stopped".to_string());
+        shared.finish(request.seq, AppEvent::TranscriptionFailed(reason));
+    }
+}
+
+/// Whether the model ran on a request and how it went
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Inference {
+    /// Nothing to transcribe, or it was cancelled, timed out, or found no model
+    Skipped,
+    Succeeded,
+    Failed,
+}
+
+/// Transcription errors in a row, to reload a model that's stuck failing
+#[derive(Debug, Default)]
+struct FailureStreak {
+    count: u32,
+}
+
+impl FailureStreak {
+    /// Count `inference`; true when it makes `limit` failures in a row
+    /// (never for a `limit` of 0), which starts the count again
+    fn record(&mut self, inference: Inference, limit: u32) -> bool {
+        match inference {
+            Inference::Skipped => false,
+            Inference::Succeeded => {
+                self.count = 0;
+                false
+            }
+            Inference::Failed => {
+                self.count += 1;
+                if limit > 0 && self.count >= limit {
+                    self.count = 0;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// The speech of the last recording, ready for whisper, kept so
+/// `redo_model` can transcribe it again
+#[derive(Debug, Clone)]
+struct RetainedSpeech {
+    samples: Vec<f32>,
+    /// The VAD's speech ranges in `samples`, for `chunks::plan`
+    segments: Vec<Range<usize>>,
+    sample_rate: u32,
+    recorded_ms: u64,
+    app: Option<String>,
+    options: TranscriptionOptions,
+}
+
+/// Where to save the last recording, if anywhere; never in privacy mode,
+/// which `Config::effective` turns `debug_save_audio` off for
+fn debug_audio_path(config: &Config) -> Option<PathBuf> {
+    config
+        .debug_save_audio
+        .then(|| Config::debug_dir().join("last_recording.wav"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VadConfig;
+    use crate::events::ModelLoadStage;
+    use crate::model::Opener;
+    use crate::state::StateManager;
+    use crate::transcriber::TranscriptionBackend;
+
+    /// Fails its first `failures` transcriptions, then succeeds
+    struct FlakyBackend {
+        failures: AtomicUsize,
+    }
+
+    impl TranscriptionBackend for FlakyBackend {
+        fn transcribe(
+            &self,
+            _samples: &[f32],
+            _sample_rate: u32,
+            _abort: Option<&Abort>,
+            _prompt: Option<&str>,
+            _language: Option<&str>,
+        ) -> Result<TranscriptionOutcome, ModelError> {
+            match self.failures.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| left.checked_sub(1)) {
+                Ok(_) => Err(ModelError::Response("garbled state".to_string())),
+                Err(_) => Ok(TranscriptionOutcome::NoSpeech),
+            }
+        }
+
+        fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            true
+        }
+    }
+
+    /// Which of `runs` transcriptions by a backend failing `failures` times
+    /// would trigger a reload, counting from 1
+    fn reloads(failures: usize, runs: usize, limit: u32) -> Vec<usize> {
+        let backend = FlakyBackend {
+            failures: AtomicUsize::new(failures),
+        };
+        let mut streak = FailureStreak::default();
+        (1..=runs)
+            .filter(|_| {
+                let inference = match backend.transcribe(&[0.0; 160], 16000, None, None, None) {
+                    Ok(_) => Inference::Succeeded,
+                    Err(_) => Inference::Failed,
+                };
+                streak.record(inference, limit)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reload_after_failures_in_a_row() {
+        assert_eq!(reloads(2, 5, 3), Vec::<usize>::new());
+        assert_eq!(reloads(4, 6, 3), vec![3]);
+        assert_eq!(reloads(6, 8, 3), vec![3, 6]);
+        assert_eq!(reloads(10, 10, 0), Vec::<usize>::new(), "0 never reloads");
+    }
+
+    /// A success clears the count, while requests that never reached the
+    /// model leave it as it was
+    #[test]
+    fn test_failure_streak_resets_on_success() {
+        let mut streak = FailureStreak::default();
+        assert!(!streak.record(Inference::Failed, 2));
+        assert!(!streak.record(Inference::Succeeded, 2));
+        assert!(!streak.record(Inference::Failed, 2));
+        assert!(!streak.record(Inference::Skipped, 2));
+        assert!(streak.record(Inference::Failed, 2));
+    }
+
+    #[test]
+    fn test_no_debug_audio_in_privacy_mode() {
+        let mut config = Config {
+            debug_save_audio: true,
+            ..Config::default()
+        };
+        assert!(debug_audio_path(&config.effective()).is_some());
+        config.privacy_mode = true;
+        assert_eq!(debug_audio_path(&config.effective()), None);
+    }
+
+    /// Hears the same words in everything
+    struct EchoBackend;
+
+    impl TranscriptionBackend for EchoBackend {
+        fn transcribe(
+            &self,
+            _samples: &[f32],
+            _sample_rate: u32,
+            _abort: Option<&Abort>,
+            _prompt: Option<&str>,
+            _language: Option<&str>,
+        ) -> Result<TranscriptionOutcome, ModelError> {
+            Ok(TranscriptionOutcome::from_result(TranscriptionResult {
+                text: "testing one two three".to_string(),
+                ..TranscriptionResult::default()
+            }))
+        }
+
+        fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            true
+        }
+    }
+
+    /// Settings that send a recording straight to the model
+    fn test_config() -> Config {
+        Config {
+            vad: VadConfig {
+                enabled: false,
+                ..VadConfig::default()
+            },
+            warmup: false,
+            ..Config::default()
+        }
+    }
+
+    /// The first event `events` gets that `wanted` picks out
+    fn wait_for(events: &Receiver<AppEvent>, wanted: impl Fn(&AppEvent) -> bool) -> AppEvent {
+        loop {
+            let event = events.recv_timeout(Duration::from_secs(5)).expect("no event in time");
+            if wanted(&event) {
+                return event;
+            }
+        }
+    }
+
+    /// A model manager with `backend` loaded, and the channel it and the
+    /// workers made with it send events on
+    fn loaded_model(
+        backend: Arc<dyn TranscriptionBackend>,
+        config: &Config,
+    ) -> (ModelManager, EventSender, Receiver<AppEvent>) {
+        loaded_model_with(Arc::new(move |_: &Config| Ok(backend.clone())), config)
+    }
+
+    /// `loaded_model` with the backend `open` returns, every load
+    fn loaded_model_with(open: Opener, config: &Config) -> (ModelManager, EventSender, Receiver<AppEvent>) {
+        let (tx, events) = unbounded();
+        let sender = EventSender::Channel(tx);
+        let manager = ModelManager::with_opener(StateManager::new(), config.clone(), open, sender.clone());
+        manager.load_async();
+        wait_for(&events, |e| matches!(e, AppEvent::ModelLoadProgress(ModelLoadStage::Ready)));
+        (manager, sender, events)
+    }
+
+    /// Submit a second of speech to `worker`
+    fn submit_speech(worker: &TranscriptionWorker) -> SubmitResult<TranscriptionRequest> {
+        let samples = wav::synthetic_speech(1.0, 16000);
+        worker.submit(worker.request(samples, 16000, None, None, false, TranscriptionOptions::default()))
+    }
+
+    /// Once a worker thread is gone, the worker turns requests away, and a
+    /// new worker started in its place transcribes the one it turned away
+    #[test]
+    fn test_worker_respawned_after_thread_dies() {
+        let config = test_config();
+        let (manager, sender, events) = loaded_model(Arc::new(EchoBackend), &config);
+        let mut worker = TranscriptionWorker::new(manager.clone(), config.clone(), sender.clone());
+        // With its config channel closed, the one thread stops
+        worker.config_txs.clear();
+        while !worker.threads.iter().all(JoinHandle::is_finished) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let SubmitResult::Disconnected(request) = submit_speech(&worker) else {
+            panic!("a worker without threads took the request");
+        };
+        let respawned = TranscriptionWorker::new(manager, config, sender);
+        assert!(matches!(respawned.submit(request), SubmitResult::Accepted(_)));
+        let result = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionComplete { .. }));
+        assert!(matches!(result, AppEvent::TranscriptionComplete { id: 1, .. }));
+    }
+
+    /// Panics on its first transcription, then hears what `EchoBackend` does
+    struct PanickyBackend {
+        panicked: AtomicBool,
+    }
+
+    impl TranscriptionBackend for PanickyBackend {
+        fn transcribe(
+            &self,
+            samples: &[f32],
+            sample_rate: u32,
+            abort: Option<&Abort>,
+            prompt: Option<&str>,
+            language: Option<&str>,
+        ) -> Result<TranscriptionOutcome, ModelError> {
+            if !self.panicked.swap(true, Ordering::SeqCst) {
+                panic!("whisper state corrupted");
+            }
+            EchoBackend.transcribe(samples, sample_rate, abort, prompt, language)
+        }
+
+        fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            true
+        }
+    }
+
+    /// A panic in the backend fails its request and reports the crash, and
+    /// once the model is back the worker transcribes the next request
+    #[test]
+    fn test_worker_survives_a_panic() {
+        let config = test_config();
+        let backend = Arc::new(PanickyBackend {
+            panicked: AtomicBool::new(false),
+        });
+        let (manager, sender, events) = loaded_model(backend, &config);
+        let worker = TranscriptionWorker::new(manager, config, sender);
+
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(_)));
+        let failed = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionFailed(_)));
+        assert!(matches!(failed, AppEvent::TranscriptionFailed(FailureReason::InferenceError(_))));
+        let crashed = wait_for(&events, |e| matches!(e, AppEvent::WorkerCrashed(_)));
+        assert!(matches!(crashed, AppEvent::WorkerCrashed(message) if message == "whisper state corrupted"));
+        wait_for(&events, |e| matches!(e, AppEvent::ModelLoadProgress(ModelLoadStage::Ready)));
+
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(_)));
+        let result = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionComplete { .. }));
+        assert!(matches!(result, AppEvent::TranscriptionComplete { id: 2, .. }));
+    }
+
+    /// Blocks its first transcription until it's aborted, then hears what
+    /// `EchoBackend` does
+    struct BlockingBackend {
+        /// Told once the first transcription is running
+        started: Sender<()>,
+        blocked: AtomicBool,
+    }
+
+    impl TranscriptionBackend for BlockingBackend {
+        fn transcribe(
+            &self,
+            samples: &[f32],
+            sample_rate: u32,
+            abort: Option<&Abort>,
+            prompt: Option<&str>,
+            language: Option<&str>,
+        ) -> Result<TranscriptionOutcome, ModelError> {
+            if self.blocked.swap(true, Ordering::SeqCst) {
+                return EchoBackend.transcribe(samples, sample_rate, abort, prompt, language);
+            }
+            let _ = self.started.send(());
+            let abort = abort.expect("the worker always passes an abort");
+            let give_up = Instant::now() + Duration::from_secs(5);
+            while !abort.requested() && Instant::now() < give_up {
+                thread::sleep(Duration::from_millis(5));
+            }
+            Err(ModelError::Aborted)
+        }
+
+        fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            true
+        }
+    }
+
+    /// Whether `event` is the one result of a request
+    fn is_result(event: &AppEvent) -> bool {
+        matches!(event, AppEvent::TranscriptionComplete { .. } | AppEvent::TranscriptionFailed(_))
+    }
+
+    /// Cancel drops the requests still queued and aborts the one in flight,
+    /// which reports the cancel rather than a transcript; the queued ones
+    /// report nothing, and the next request goes through
+    #[test]
+    fn test_cancel_drops_queued_and_in_flight_requests() {
+        let config = test_config();
+        let (started_tx, started) = bounded(1);
+        let backend = Arc::new(BlockingBackend {
+            started: started_tx,
+            blocked: AtomicBool::new(false),
+        });
+        let (manager, sender, events) = loaded_model(backend, &config);
+        let worker = TranscriptionWorker::new(manager, config, sender);
+
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(1)));
+        started.recv_timeout(Duration::from_secs(5)).expect("transcription never started");
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(2)));
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(3)));
+        assert_eq!(worker.pending(), 3);
+
+        worker.cancel();
+        let cancelled = wait_for(&events, is_result);
+        assert!(
+            matches!(cancelled, AppEvent::TranscriptionFailed(FailureReason::Cancelled)),
+            "{:?}",
+            cancelled
+        );
+        assert_eq!(worker.pending(), 0);
+
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(4)));
+        let next = wait_for(&events, is_result);
+        assert!(matches!(next, AppEvent::TranscriptionComplete { id: 4, .. }), "{:?}", next);
+    }
+
+    /// Takes a tenth of the audio's length to hear what `EchoBackend` does
+    struct SlowBackend;
+
+    impl TranscriptionBackend for SlowBackend {
+        fn transcribe(
+            &self,
+            samples: &[f32],
+            sample_rate: u32,
+            abort: Option<&Abort>,
+            prompt: Option<&str>,
+            language: Option<&str>,
+        ) -> Result<TranscriptionOutcome, ModelError> {
+            thread::sleep(Duration::from_millis(duration_ms(samples.len(), sample_rate) / 10));
+            EchoBackend.transcribe(samples, sample_rate, abort, prompt, language)
+        }
+
+        fn warmup(&self, _samples: &[f32], _sample_rate: u32) -> Result<(), ModelError> {
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            true
+        }
+    }
+
+    /// Requests submitted together all complete, in submission order, even
+    /// when a later one finishes first on another thread
+    #[test]
+    fn test_queued_requests_complete_in_order() {
+        let config = Config {
+            worker_parallelism: MAX_WORKER_PARALLELISM,
+            ..test_config()
+        };
+        let (manager, sender, events) = loaded_model(Arc::new(SlowBackend), &config);
+        let worker = TranscriptionWorker::new(manager, config, sender);
+
+        let lengths = [3.0, 2.0, 1.0];
+        let ids: Vec<u64> = lengths
+            .iter()
+            .map(|&secs| {
+                let samples = wav::synthetic_speech(secs, 16000);
+                let request = worker.request(samples, 16000, None, None, false, TranscriptionOptions::default());
+                match worker.submit(request) {
+                    SubmitResult::Accepted(id) => id,
+                    _ => panic!("a request with room in the queue was turned away"),
+                }
+            })
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let results: Vec<(u64, u64)> = lengths
+            .iter()
+            .map(|_| match wait_for(&events, is_result) {
+                AppEvent::TranscriptionComplete { id, timings, .. } => (id, timings.recorded_ms),
+                other => panic!("expected a transcript, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(results, vec![(1, 3000), (2, 2000), (3, 1000)]);
+        assert_eq!(worker.pending(), 0);
+    }
+
+    /// Failures in a row reload the model from the worker thread. Requests
+    /// meanwhile fail with `ModelNotLoaded` rather than wait, and once it's
+    /// back they reach the model again.
+    #[test]
+    fn test_worker_reloads_a_failing_model() {
+        let config = Config {
+            reload_after_failures: 2,
+            ..test_config()
+        };
+        let backend = Arc::new(FlakyBackend {
+            failures: AtomicUsize::new(2),
+        });
+        let (release, released) = bounded::<()>(0);
+        let reloading = AtomicBool::new(false);
+        // The first load goes straight through, the reload waits to be let go
+        let open: Opener = Arc::new(move |_: &Config| {
+            if reloading.swap(true, Ordering::SeqCst) {
+                let _ = released.recv();
+            }
+            Ok(backend.clone())
+        });
+        let (manager, sender, events) = loaded_model_with(open, &config);
+        let worker = TranscriptionWorker::new(manager, config, sender);
+
+        for _ in 0..2 {
+            assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(_)));
+            let failed = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionFailed(_)));
+            assert!(matches!(failed, AppEvent::TranscriptionFailed(FailureReason::InferenceError(_))));
+        }
+        wait_for(&events, |e| matches!(e, AppEvent::ModelReloading));
+        // Sent once the old model is dropped
+        wait_for(&events, |e| matches!(e, AppEvent::ModelLoadProgress(ModelLoadStage::Starting)));
+
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(_)));
+        let failed = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionFailed(_)));
+        assert!(matches!(failed, AppEvent::TranscriptionFailed(FailureReason::ModelNotLoaded)));
+
+        release.send(()).unwrap();
+        wait_for(&events, |e| matches!(e, AppEvent::ModelLoadProgress(ModelLoadStage::Ready)));
+        assert!(matches!(submit_speech(&worker), SubmitResult::Accepted(_)));
+        let failed = wait_for(&events, |e| matches!(e, AppEvent::TranscriptionFailed(_)));
+        assert!(matches!(failed, AppEvent::TranscriptionFailed(FailureReason::NoSpeech)));
     }
 }
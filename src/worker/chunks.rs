@@ -0,0 +1,96 @@
+//! Splitting long recordings into pieces whisper handles in one window
+
+use std::ops::Range;
+
+/// Longest piece handed to whisper in one go
+pub const MAX_CHUNK_SECS: usize = 30;
+
+/// Split `len` samples into consecutive ranges of at most `max_len`,
+/// cutting in the middle of the pauses between `segments` (speech ranges, as
+/// in `VadOutput::segments`). Each cut is the latest pause that fits; speech
+/// running longer than `max_len` without one is cut wherever it has to be.
+pub fn plan(segments: &[Range<usize>], len: usize, max_len: usize) -> Vec<Range<usize>> {
+    let pauses: Vec<usize> = segments
+        .windows(2)
+        .map(|pair| (pair[0].end + pair[1].start) / 2)
+        .collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while len - start > max_len {
+        let limit = start + max_len;
+        let end = pauses
+            .iter()
+            .copied()
+            .rfind(|&pause| pause > start && pause <= limit)
+            .unwrap_or(limit);
+        chunks.push(start..end);
+        start = end;
+    }
+    chunks.push(start..len);
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chunks cover everything, in order, without overlap or going over
+    fn assert_valid(chunks: &[Range<usize>], len: usize, max_len: usize) {
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, len);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start, "{:?}", chunks);
+        }
+        assert!(chunks.iter().all(|c| !c.is_empty() && c.len() <= max_len), "{:?}", chunks);
+    }
+
+    #[test]
+    fn test_short_audio_is_one_chunk() {
+        let speech = 0..20;
+        assert_eq!(plan(&[speech], 25, 30), vec![0..25]);
+        assert_eq!(plan(&[], 30, 30), vec![0..30]);
+    }
+
+    /// Cuts fall mid-pause, as late as the limit allows
+    #[test]
+    fn test_cuts_at_latest_pause() {
+        let segments = [0..10, 12..24, 26..40, 42..50, 52..75];
+        let chunks = plan(&segments, 75, 30);
+        assert_eq!(chunks, vec![0..25, 25..51, 51..75]);
+        assert_valid(&chunks, 75, 30);
+    }
+
+    /// Speech without a usable pause is cut at the limit
+    #[test]
+    fn test_long_speech_is_cut_hard() {
+        let speech = 0..70;
+        assert_eq!(plan(&[speech], 70, 30), vec![0..30, 30..60, 60..70]);
+        // Without VAD there are no segments at all
+        assert_eq!(plan(&[], 65, 30), vec![0..30, 30..60, 60..65]);
+        // A pause only in the second window
+        let chunks = plan(&[0..40, 44..70], 70, 30);
+        assert_eq!(chunks, vec![0..30, 30..42, 42..70]);
+        assert_valid(&chunks, 70, 30);
+    }
+
+    /// Three minutes of speech at 16 kHz in sentences of 4 - 9 s
+    #[test]
+    fn test_realistic_recording() {
+        let rate = 16000;
+        let mut segments = Vec::new();
+        let mut at = 0;
+        for i in 0..30 {
+            let speech = (4 + i % 6) * rate;
+            segments.push(at..at + speech);
+            at += speech + rate / 10;
+        }
+        let max_len = MAX_CHUNK_SECS * rate;
+        let chunks = plan(&segments, at, max_len);
+        assert_valid(&chunks, at, max_len);
+        assert!(chunks.len() >= 7, "{} chunks", chunks.len());
+        // Every cut lands in a pause, never mid-sentence
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(!segments.iter().any(|s| s.contains(&chunk.end)), "Cut at {}", chunk.end);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Putting results back in the order their requests were queued, when
+//! several worker threads finish them in whatever order
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Results held until every request queued before theirs has reported.
+/// Requests are numbered from 0 in the order they were queued.
+#[derive(Debug)]
+pub struct ResultOrder<T> {
+    /// Number of the next result to hand out
+    next: u64,
+    /// Finished ahead of their turn
+    ready: BTreeMap<u64, T>,
+    /// Taken off the queue without running, so they never report
+    skipped: BTreeSet<u64>,
+}
+
+impl<T> Default for ResultOrder<T> {
+    fn default() -> Self {
+        Self {
+            next: 0,
+            ready: BTreeMap::new(),
+            skipped: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T> ResultOrder<T> {
+    /// Take `result` for request `seq`, and return every result now due, in
+    /// order: none while an earlier request is still running
+    pub fn finish(&mut self, seq: u64, result: T) -> Vec<T> {
+        self.ready.insert(seq, result);
+        self.due()
+    }
+
+    /// Note that request `seq` won't report, and return the results that
+    /// were only waiting on it
+    pub fn skip(&mut self, seq: u64) -> Vec<T> {
+        self.skipped.insert(seq);
+        self.due()
+    }
+
+    /// Results finished ahead of their turn
+    pub fn held(&self) -> usize {
+        self.ready.len()
+    }
+
+    fn due(&mut self) -> Vec<T> {
+        let mut due = Vec::new();
+        loop {
+            if let Some(result) = self.ready.remove(&self.next) {
+                due.push(result);
+            } else if !self.skipped.remove(&self.next) {
+                return due;
+            }
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_order_results_go_straight_out() {
+        let mut order = ResultOrder::default();
+        assert_eq!(order.finish(0, "a"), vec!["a"]);
+        assert_eq!(order.finish(1, "b"), vec!["b"]);
+        assert_eq!(order.held(), 0);
+    }
+
+    /// A result that overtakes an earlier one waits for it
+    #[test]
+    fn test_early_results_wait() {
+        let mut order = ResultOrder::default();
+        assert_eq!(order.finish(1, "b"), Vec::<&str>::new());
+        assert_eq!(order.finish(2, "c"), Vec::<&str>::new());
+        assert_eq!(order.held(), 2);
+        assert_eq!(order.finish(0, "a"), vec!["a", "b", "c"]);
+        assert_eq!(order.held(), 0);
+        assert_eq!(order.finish(3, "d"), vec!["d"]);
+    }
+
+    /// Requests dropped from the queue don't hold up the ones after them
+    #[test]
+    fn test_skipped_requests() {
+        let mut order = ResultOrder::default();
+        assert_eq!(order.skip(0), Vec::<&str>::new());
+        assert_eq!(order.finish(2, "c"), Vec::<&str>::new());
+        assert_eq!(order.skip(1), vec!["c"]);
+        assert_eq!(order.skip(4), Vec::<&str>::new());
+        assert_eq!(order.finish(3, "d"), vec!["d"]);
+        assert_eq!(order.finish(5, "f"), vec!["f"]);
+    }
+}
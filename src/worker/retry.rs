@@ -0,0 +1,168 @@
+//! A request the worker couldn't take, held to resubmit ahead of anything
+//! made after it
+
+/// What came of handing a request to the worker
+#[derive(Debug, PartialEq)]
+pub enum SubmitResult<T> {
+    /// Queued under this id
+    Accepted(u64),
+    /// The queue was full; the request comes back unsent
+    QueueFull(T),
+    /// The worker thread is gone; the request comes back unsent
+    Disconnected(T),
+}
+
+/// What `RetrySlot` did with a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Submitted {
+    Accepted(u64),
+    /// Held until `resubmit`
+    Held,
+    /// The slot already held an earlier request, which keeps its place
+    Dropped,
+    /// Held, but the worker has to be restarted before it can go
+    Disconnected,
+}
+
+/// At most one request turned away by the worker, so output stays in the
+/// order things were said
+#[derive(Debug)]
+pub struct RetrySlot<T> {
+    held: Option<T>,
+}
+
+impl<T> Default for RetrySlot<T> {
+    fn default() -> Self {
+        Self { held: None }
+    }
+}
+
+impl<T> RetrySlot<T> {
+    /// Send `request` through `send`, after the held request if there is
+    /// one. A request that can't go is held, unless the slot is still taken.
+    pub fn submit(&mut self, request: T, mut send: impl FnMut(T) -> SubmitResult<T>) -> Submitted {
+        match self.resubmit(&mut send) {
+            Some(Submitted::Held | Submitted::Disconnected) => Submitted::Dropped,
+            _ => self.hold_unsent(send(request)),
+        }
+    }
+
+    /// Send the held request through `send`; `None` if nothing was held
+    pub fn resubmit(&mut self, send: impl FnOnce(T) -> SubmitResult<T>) -> Option<Submitted> {
+        let held = self.held.take()?;
+        Some(self.hold_unsent(send(held)))
+    }
+
+    /// Drop the held request, e.g. when transcription is cancelled
+    pub fn clear(&mut self) -> bool {
+        self.held.take().is_some()
+    }
+
+    fn hold_unsent(&mut self, result: SubmitResult<T>) -> Submitted {
+        match result {
+            SubmitResult::Accepted(id) => Submitted::Accepted(id),
+            SubmitResult::QueueFull(request) => {
+                self.held = Some(request);
+                Submitted::Held
+            }
+            SubmitResult::Disconnected(request) => {
+                self.held = Some(request);
+                Submitted::Disconnected
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A queue of `room` requests recording what it was sent, in order
+    struct MockQueue {
+        room: usize,
+        sent: Vec<&'static str>,
+        connected: bool,
+    }
+
+    impl MockQueue {
+        fn new(room: usize) -> Self {
+            Self {
+                room,
+                sent: Vec::new(),
+                connected: true,
+            }
+        }
+
+        fn send(&mut self, request: &'static str) -> SubmitResult<&'static str> {
+            if !self.connected {
+                return SubmitResult::Disconnected(request);
+            }
+            if self.sent.len() == self.room {
+                return SubmitResult::QueueFull(request);
+            }
+            self.sent.push(request);
+            SubmitResult::Accepted(self.sent.len() as u64)
+        }
+
+        /// The worker finishing the oldest request
+        fn finish(&mut self) {
+            self.sent.remove(0);
+        }
+    }
+
+    #[test]
+    fn test_full_queue_holds_the_request() {
+        let mut queue = MockQueue::new(1);
+        let mut slot = RetrySlot::default();
+        assert_eq!(slot.submit("first", |r| queue.send(r)), Submitted::Accepted(1));
+        assert_eq!(slot.submit("second", |r| queue.send(r)), Submitted::Held);
+
+        // Still full, so it stays held
+        assert_eq!(slot.resubmit(|r| queue.send(r)), Some(Submitted::Held));
+        queue.finish();
+        assert_eq!(slot.resubmit(|r| queue.send(r)), Some(Submitted::Accepted(1)));
+        assert_eq!(queue.sent, vec!["second"]);
+        assert_eq!(slot.resubmit(|r| queue.send(r)), None);
+    }
+
+    /// A later request never overtakes the held one
+    #[test]
+    fn test_held_request_goes_first() {
+        let mut queue = MockQueue::new(2);
+        let mut slot = RetrySlot::default();
+        for request in ["one", "two"] {
+            slot.submit(request, |r| queue.send(r));
+        }
+        assert_eq!(slot.submit("three", |r| queue.send(r)), Submitted::Held);
+
+        // Room for one more, which the held request takes
+        queue.finish();
+        assert_eq!(slot.submit("four", |r| queue.send(r)), Submitted::Held);
+        assert_eq!(queue.sent, vec!["two", "three"]);
+
+        // The slot is taken and the queue full, so the newest is dropped
+        assert_eq!(slot.submit("five", |r| queue.send(r)), Submitted::Dropped);
+        queue.finish();
+        queue.finish();
+        assert_eq!(slot.submit("six", |r| queue.send(r)), Submitted::Accepted(2));
+        assert_eq!(queue.sent, vec!["four", "six"]);
+    }
+
+    /// A request the worker was gone for is kept for its replacement
+    #[test]
+    fn test_disconnected_request_is_kept() {
+        let mut queue = MockQueue::new(1);
+        queue.connected = false;
+        let mut slot = RetrySlot::default();
+        assert_eq!(slot.submit("lost", |r| queue.send(r)), Submitted::Disconnected);
+        assert_eq!(slot.submit("later", |r| queue.send(r)), Submitted::Dropped);
+
+        queue.connected = true;
+        assert_eq!(slot.resubmit(|r| queue.send(r)), Some(Submitted::Accepted(1)));
+        assert_eq!(queue.sent, vec!["lost"]);
+
+        slot.submit("held", |r| queue.send(r));
+        assert!(slot.clear());
+        assert!(!slot.clear());
+    }
+}
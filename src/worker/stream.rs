@@ -0,0 +1,150 @@
+/// Transcribe a new preview once this much more speech has accumulated
+const PARTIAL_INTERVAL_SECS: u32 = 3;
+
+/// New audio from a recording that is still in progress
+pub struct StreamChunk {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    /// Position of `samples` within the recording; 0 starts a new recording
+    pub offset: usize,
+}
+
+/// Audio accumulated for streaming previews of the current recording. Only
+/// audio that arrived since the last preview still needs VAD; the speech
+/// found in what came before is kept rather than searched for again.
+#[derive(Default)]
+pub struct StreamBuffer {
+    /// Audio not run through VAD yet
+    pending: Vec<f32>,
+    /// Speech found in the recording so far
+    speech: Vec<f32>,
+    /// Samples of the recording received so far
+    received: usize,
+    sample_rate: u32,
+    /// Speech length (in samples) at the last emitted preview
+    previewed: usize,
+}
+
+impl StreamBuffer {
+    /// Append a chunk. Chunks that don't continue the current recording
+    /// (left over from one that already finished) are ignored.
+    pub fn push(&mut self, chunk: StreamChunk) -> bool {
+        if chunk.offset == 0 {
+            self.clear();
+            self.sample_rate = chunk.sample_rate;
+        }
+        if chunk.offset != self.received || chunk.sample_rate != self.sample_rate {
+            return false;
+        }
+        self.received += chunk.samples.len();
+        self.pending.extend_from_slice(&chunk.samples);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.speech.clear();
+        self.received = 0;
+        self.previewed = 0;
+    }
+
+    /// Audio that arrived since the last call, to look for speech in
+    pub fn take_pending(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Add the speech found in audio from `take_pending`
+    pub fn push_speech(&mut self, speech: &[f32]) {
+        self.speech.extend_from_slice(speech);
+    }
+
+    pub fn speech(&self) -> &[f32] {
+        &self.speech
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Whether enough speech has accumulated for a new preview
+    pub fn is_due(&self) -> bool {
+        let interval = (self.sample_rate * PARTIAL_INTERVAL_SECS) as usize;
+        self.speech.len() >= self.previewed + interval
+    }
+
+    pub fn mark_previewed(&mut self) {
+        self.previewed = self.speech.len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(len: usize, offset: usize) -> StreamChunk {
+        StreamChunk {
+            samples: vec![0.1; len],
+            sample_rate: 16000,
+            offset,
+        }
+    }
+
+    /// Only audio that arrived since the last `take_pending` is returned
+    #[test]
+    fn test_contiguous_chunks_accumulate() {
+        let mut buffer = StreamBuffer::default();
+
+        assert!(buffer.push(chunk(16000, 0)));
+        assert!(buffer.push(chunk(8000, 16000)));
+        assert_eq!(buffer.take_pending().len(), 24000);
+        assert_eq!(buffer.sample_rate(), 16000);
+
+        assert!(buffer.push(chunk(8000, 24000)));
+        assert_eq!(buffer.take_pending().len(), 8000);
+        assert!(buffer.take_pending().is_empty());
+    }
+
+    /// A chunk still in flight when the final request arrives is dropped
+    #[test]
+    fn test_stale_chunk_after_clear_is_ignored() {
+        let mut buffer = StreamBuffer::default();
+        buffer.push(chunk(16000, 0));
+        buffer.clear();
+
+        assert!(!buffer.push(chunk(16000, 16000)));
+        assert!(buffer.take_pending().is_empty());
+    }
+
+    #[test]
+    fn test_offset_zero_starts_new_recording() {
+        let mut buffer = StreamBuffer::default();
+        buffer.push(chunk(48000, 0));
+        let pending = buffer.take_pending();
+        buffer.push_speech(&pending);
+        buffer.mark_previewed();
+
+        assert!(buffer.push(chunk(1000, 0)));
+        assert!(buffer.speech().is_empty());
+        assert_eq!(buffer.take_pending().len(), 1000);
+        buffer.push_speech(&[0.1; 48000]);
+        assert!(buffer.is_due());
+    }
+
+    /// Previews are spaced by PARTIAL_INTERVAL_SECS of new speech
+    #[test]
+    fn test_preview_interval() {
+        let mut buffer = StreamBuffer::default();
+        buffer.push(chunk(16000, 0));
+
+        buffer.push_speech(&[0.1; 47999]);
+        assert!(!buffer.is_due());
+        buffer.push_speech(&[0.1]);
+        assert!(buffer.is_due());
+
+        buffer.mark_previewed();
+        buffer.push_speech(&[0.1; 12000]);
+        assert!(!buffer.is_due());
+        buffer.push_speech(&[0.1; 36000]);
+        assert!(buffer.is_due());
+    }
+}
@@ -7,6 +7,45 @@ use crossbeam_channel::{unbounded, TryRecvError};
 use std::time::{Duration, Instant};
 use std::thread;
 
+#[allow(dead_code)]
+#[path = "../src/audio/error.rs"]
+mod audio_error;
+#[allow(dead_code)]
+#[path = "../src/audio/source.rs"]
+mod source;
+
+#[allow(dead_code)]
+#[path = "../src/worker/retry.rs"]
+mod retry;
+#[path = "../src/pipeline.rs"]
+mod pipeline;
+#[allow(dead_code)]
+#[path = "../src/recording_press.rs"]
+mod recording_press;
+
+use audio_error::AudioError;
+use recording_press::{RecordingPress, Release};
+use retry::{RetrySlot, SubmitResult, Submitted};
+use source::{sine, AudioSource, FakeAudioSource};
+
+/// Where `pipeline` finds these in the app
+mod audio {
+    pub use crate::audio_error::AudioError;
+    pub use crate::source::AudioSource;
+}
+mod worker {
+    pub use crate::retry::{RetrySlot, SubmitResult, Submitted};
+}
+/// Where `recording_press` finds it in the app
+mod config {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum RecordingMode {
+        PushToTalk,
+        Toggle,
+        Hybrid,
+    }
+}
+
 /// Simulated hotkey event for testing
 #[derive(Debug, Clone, PartialEq)]
 enum MockHotkeyEvent {
@@ -212,6 +251,210 @@ fn test_toggle_mode_logic() {
     assert!(is_toggle_recording, "Should be recording at the end");
 }
 
+/// Result of feeding timed hotkey events through PushToTalk with `min_hold_ms`
+#[derive(Debug, Default, PartialEq)]
+struct PushToTalkOutcome {
+    submitted: u32,
+    discarded: u32,
+}
+
+/// PushToTalk as main.rs runs it: a press starts a recording, and a release
+/// submits or discards it as `RecordingPress::released` says.
+/// Events are (event, milliseconds since the start of the sequence).
+fn run_push_to_talk(events: &[(MockHotkeyEvent, u64)], min_hold_ms: u64) -> PushToTalkOutcome {
+    let mut outcome = PushToTalkOutcome::default();
+    let mut is_recording = false;
+    let mut press = RecordingPress::default();
+    let start = Instant::now();
+
+    for (evt, at) in events {
+        let now = start + Duration::from_millis(*at);
+        match evt {
+            MockHotkeyEvent::Pressed => {
+                press.hotkey_down(true);
+                is_recording = true;
+                press.started(config::RecordingMode::PushToTalk, false, now);
+            }
+            MockHotkeyEvent::Released => {
+                press.hotkey_down(false);
+                let release = press.released(
+                    config::RecordingMode::PushToTalk,
+                    now,
+                    Duration::from_millis(min_hold_ms),
+                    Duration::ZERO,
+                );
+                if is_recording {
+                    match release {
+                        Release::Stop => outcome.submitted += 1,
+                        Release::Discard => outcome.discarded += 1,
+                        Release::Keep => continue,
+                    }
+                    is_recording = false;
+                    press.stopped();
+                }
+            }
+        }
+    }
+    outcome
+}
+
+/// Test PushToTalk: a brushed hotkey is discarded, a short deliberate press is not
+#[test]
+fn test_push_to_talk_ignores_accidental_taps() {
+    let events = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 40), // Brushed: discarded
+        (MockHotkeyEvent::Pressed, 1000),
+        (MockHotkeyEvent::Released, 1150), // Exactly min_hold_ms: counts
+        (MockHotkeyEvent::Pressed, 2000),
+        (MockHotkeyEvent::Released, 3500),
+    ];
+    assert_eq!(
+        run_push_to_talk(&events, 150),
+        PushToTalkOutcome {
+            submitted: 2,
+            discarded: 1
+        }
+    );
+}
+
+/// Test PushToTalk: min_hold_ms = 0 submits every press
+#[test]
+fn test_push_to_talk_min_hold_disabled() {
+    let events = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 1),
+    ];
+    assert_eq!(
+        run_push_to_talk(&events, 0),
+        PushToTalkOutcome {
+            submitted: 1,
+            discarded: 0
+        }
+    );
+}
+
+/// Result of feeding timed hotkey events through the Hybrid mode logic
+#[derive(Debug, PartialEq)]
+struct HybridOutcome {
+    started: u32,
+    stopped: u32,
+    recording: bool,
+}
+
+/// Hybrid mode as main.rs runs it: a press starts a recording or stops the
+/// one running, and a release does what `RecordingPress::released` says.
+/// Events are (event, milliseconds since the start of the sequence).
+fn run_hybrid(events: &[(MockHotkeyEvent, u64)], hold_ms: u64) -> HybridOutcome {
+    let mut outcome = HybridOutcome {
+        started: 0,
+        stopped: 0,
+        recording: false,
+    };
+    let mut press = RecordingPress::default();
+    let start = Instant::now();
+
+    for (evt, at) in events {
+        let now = start + Duration::from_millis(*at);
+        match evt {
+            MockHotkeyEvent::Pressed => {
+                press.hotkey_down(true);
+                if outcome.recording {
+                    outcome.recording = false;
+                    outcome.stopped += 1;
+                    press.stopped();
+                } else {
+                    outcome.recording = true;
+                    outcome.started += 1;
+                    press.started(config::RecordingMode::Hybrid, true, now);
+                }
+            }
+            MockHotkeyEvent::Released => {
+                press.hotkey_down(false);
+                let release = press.released(
+                    config::RecordingMode::Hybrid,
+                    now,
+                    Duration::ZERO,
+                    Duration::from_millis(hold_ms),
+                );
+                if release == Release::Stop && outcome.recording {
+                    outcome.recording = false;
+                    outcome.stopped += 1;
+                    press.stopped();
+                }
+            }
+        }
+    }
+    outcome
+}
+
+/// Test Hybrid mode: two quick taps start and then stop one recording
+#[test]
+fn test_hybrid_mode_tap_tap() {
+    let events = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 100), // Tap: keeps recording
+        (MockHotkeyEvent::Pressed, 2000), // Stops
+        (MockHotkeyEvent::Released, 2100), // Ignored
+    ];
+    let outcome = run_hybrid(&events, 300);
+    assert_eq!(
+        outcome,
+        HybridOutcome {
+            started: 1,
+            stopped: 1,
+            recording: false
+        }
+    );
+}
+
+/// Test Hybrid mode: a long hold records only until release
+#[test]
+fn test_hybrid_mode_hold_release() {
+    let events = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 1500),
+    ];
+    let outcome = run_hybrid(&events, 300);
+    assert_eq!(
+        outcome,
+        HybridOutcome {
+            started: 1,
+            stopped: 1,
+            recording: false
+        }
+    );
+
+    // Exactly at the threshold counts as a hold
+    let at_threshold = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 300),
+    ];
+    assert!(!run_hybrid(&at_threshold, 300).recording);
+}
+
+/// Test Hybrid mode: after a tap latches recording, the next press stops it
+/// even if held, and its release doesn't start anything
+#[test]
+fn test_hybrid_mode_tap_then_hold() {
+    let events = [
+        (MockHotkeyEvent::Pressed, 0),
+        (MockHotkeyEvent::Released, 120), // Tap: latched
+        (MockHotkeyEvent::Pressed, 3000), // Stops
+        (MockHotkeyEvent::Released, 4500), // Long hold, still ignored
+        (MockHotkeyEvent::Pressed, 6000), // New recording
+    ];
+    let outcome = run_hybrid(&events, 300);
+    assert_eq!(
+        outcome,
+        HybridOutcome {
+            started: 2,
+            stopped: 1,
+            recording: true
+        }
+    );
+}
+
 // =============================================================================
 // Test for potential timing issues
 // =============================================================================
@@ -260,3 +503,134 @@ fn test_cloned_sender_survives_original_drop() {
     
     assert_eq!(rx.try_recv().unwrap(), MockHotkeyEvent::Pressed);
 }
+
+// =============================================================================
+// Tests for the Recording Pipeline (hotkey -> audio -> worker -> event)
+// =============================================================================
+
+/// What the "worker" reports back, like `AppEvent`
+#[derive(Debug, PartialEq)]
+enum PipelineEvent {
+    /// The loudest frequency found, standing in for the transcript
+    Transcribed { id: u64, hz: u32 },
+    TooShort,
+}
+
+/// Frequency of a clean sine, from its zero crossings after any muted start
+fn dominant_hz(samples: &[f32], sample_rate: u32) -> u32 {
+    let start = samples.iter().position(|&s| s != 0.0).unwrap_or(0);
+    let samples = &samples[start..];
+    let crossings = samples
+        .windows(2)
+        .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+        .count();
+    let secs = samples.len() as f32 / sample_rate as f32;
+    (crossings as f32 / 2.0 / secs).round() as u32
+}
+
+/// Drive push-to-talk presses through `pipeline` the way main.rs does:
+/// start on press, stop on release, drop recordings under
+/// `min_recording_ms`, and hand the rest to a worker thread
+fn run_pipeline(
+    source: &mut dyn AudioSource,
+    events: &[MockHotkeyEvent],
+    min_recording_ms: u64,
+) -> Vec<PipelineEvent> {
+    let (request_tx, request_rx) = unbounded::<(u64, Vec<f32>, u32)>();
+    let (event_tx, event_rx) = unbounded::<PipelineEvent>();
+
+    let worker_events = event_tx.clone();
+    let worker = thread::spawn(move || {
+        while let Ok((id, samples, sample_rate)) = request_rx.recv() {
+            let hz = dominant_hz(&samples, sample_rate);
+            worker_events.send(PipelineEvent::Transcribed { id, hz }).unwrap();
+        }
+    });
+
+    let mut retry = RetrySlot::default();
+    let mut next_id = 1;
+    for event in events {
+        match event {
+            MockHotkeyEvent::Pressed if !source.is_recording() => {
+                pipeline::start(source, Duration::from_millis(10)).unwrap();
+            }
+            MockHotkeyEvent::Released if source.is_recording() => {
+                let stopped = pipeline::stop_and_submit(
+                    source,
+                    min_recording_ms,
+                    &mut retry,
+                    |samples, sample_rate| (samples, sample_rate),
+                    |(samples, sample_rate)| {
+                        let id = next_id;
+                        next_id += 1;
+                        match request_tx.send((id, samples, sample_rate)) {
+                            Ok(()) => SubmitResult::Accepted(id),
+                            Err(e) => SubmitResult::Disconnected((e.0 .1, e.0 .2)),
+                        }
+                    },
+                );
+                match stopped.submitted {
+                    Some(submitted) => assert!(matches!(submitted, Submitted::Accepted(_)), "{:?}", submitted),
+                    None => event_tx.send(PipelineEvent::TooShort).unwrap(),
+                }
+            }
+            _ => {}
+        }
+    }
+    drop(request_tx);
+    worker.join().unwrap();
+    drop(event_tx);
+    event_rx.iter().collect()
+}
+
+/// Deterministic audio from the fake source makes it through to results,
+/// in order, with the short recording rejected before the worker
+#[test]
+fn test_pipeline_with_fake_audio() {
+    let rate = 16000;
+    let mut source = FakeAudioSource::new(rate);
+    source
+        .push(sine(440.0, 1.0, rate))
+        .push(sine(300.0, 0.1, rate))
+        .push(sine(1000.0, 2.0, rate));
+
+    let press_release = [MockHotkeyEvent::Pressed, MockHotkeyEvent::Released];
+    let events: Vec<_> = press_release.iter().cycle().take(6).cloned().collect();
+    let results = run_pipeline(&mut source, &events, 200);
+
+    // The too-short result is reported straight away; transcriptions arrive
+    // whenever the worker finishes, so compare them apart
+    assert!(results.contains(&PipelineEvent::TooShort));
+    let transcribed: Vec<_> = results
+        .into_iter()
+        .filter(|event| *event != PipelineEvent::TooShort)
+        .collect();
+    assert_eq!(
+        transcribed,
+        vec![
+            PipelineEvent::Transcribed { id: 1, hz: 440 },
+            PipelineEvent::Transcribed { id: 2, hz: 1000 },
+        ]
+    );
+    assert_eq!(source.starts, 3);
+    assert!(!source.is_recording());
+}
+
+/// Repeated presses while recording and releases while idle do nothing
+#[test]
+fn test_pipeline_ignores_stray_hotkey_events() {
+    let rate = 16000;
+    let mut source = FakeAudioSource::new(rate);
+    source.push(sine(440.0, 1.0, rate));
+
+    let events = [
+        MockHotkeyEvent::Released,
+        MockHotkeyEvent::Pressed,
+        MockHotkeyEvent::Pressed,
+        MockHotkeyEvent::Released,
+        MockHotkeyEvent::Released,
+    ];
+    let results = run_pipeline(&mut source, &events, 200);
+    assert_eq!(results, vec![PipelineEvent::Transcribed { id: 1, hz: 440 }]);
+    assert_eq!(source.starts, 1);
+}